@@ -0,0 +1,379 @@
+//! Rate the physical devices against some requirements, and pick the best one.
+//!
+//! A suitable discrete GPU is preferred to an integrated one, then to a virtual one and to a CPU.
+//! Among the same type, the device supporting more of the preferred features wins, then the one
+//! with more device local memory.
+//! Set `VK_TUTORIAL_DEVICE` to the index or to a part of the name of a device to force its use.
+//!
+//! Shared by the tutorial and by the crate at the root of the repository, so it only depends on `ash`.
+
+use ash::version::InstanceV1_0;
+use ash::vk;
+
+use std::cmp::Reverse;
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::c_char;
+
+/// The environment variable to choose the physical device by index or by name.
+pub const DEVICE_OVERRIDE_VAR: &str = "VK_TUTORIAL_DEVICE";
+
+/// The optional features of `vk::PhysicalDeviceFeatures` used in the tutorial.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceFeature {
+    SamplerAnisotropy,
+    SampleRateShading,
+    FillModeNonSolid,
+    WideLines,
+    GeometryShader,
+    TessellationShader,
+}
+
+impl DeviceFeature {
+    /// The name of the feature in the Vulkan specification.
+    pub fn name(self) -> &'static str {
+        match self {
+            DeviceFeature::SamplerAnisotropy => "samplerAnisotropy",
+            DeviceFeature::SampleRateShading => "sampleRateShading",
+            DeviceFeature::FillModeNonSolid => "fillModeNonSolid",
+            DeviceFeature::WideLines => "wideLines",
+            DeviceFeature::GeometryShader => "geometryShader",
+            DeviceFeature::TessellationShader => "tessellationShader",
+        }
+    }
+
+    pub fn is_supported(self, features: &vk::PhysicalDeviceFeatures) -> bool {
+        let is_supported = match self {
+            DeviceFeature::SamplerAnisotropy => features.sampler_anisotropy,
+            DeviceFeature::SampleRateShading => features.sample_rate_shading,
+            DeviceFeature::FillModeNonSolid => features.fill_mode_non_solid,
+            DeviceFeature::WideLines => features.wide_lines,
+            DeviceFeature::GeometryShader => features.geometry_shader,
+            DeviceFeature::TessellationShader => features.tessellation_shader,
+        };
+
+        is_supported == vk::TRUE
+    }
+}
+
+/// What a physical device must support, and what it had better support.
+pub struct DeviceRequirements<'a> {
+    pub extensions: &'a [&'a str],
+    pub features: &'a [DeviceFeature],
+    /// The features the app can do without, the devices supporting them get a better score.
+    pub preferred_features: &'a [DeviceFeature],
+}
+
+/// The device type ranks first, then the count of preferred features, then the size of the device local memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DeviceScore {
+    pub type_rank: u32,
+    pub preferred_feature_count: u32,
+    pub device_local_memory: vk::DeviceSize,
+}
+
+impl DeviceScore {
+    fn new(
+        properties: &vk::PhysicalDeviceProperties,
+        memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        preferred_feature_count: u32,
+    ) -> DeviceScore {
+        let type_rank = match properties.device_type {
+            vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+            vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+            vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+            vk::PhysicalDeviceType::CPU => 1,
+            _ => 0,
+        };
+        let device_local_memory = memory_properties.memory_heaps
+            [..memory_properties.memory_heap_count as usize]
+            .iter()
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .sum();
+
+        DeviceScore {
+            type_rank,
+            preferred_feature_count,
+            device_local_memory,
+        }
+    }
+}
+
+/// A physical device rated by `rate_physical_devices`.
+pub struct DeviceCandidate {
+    pub physical_device: vk::PhysicalDevice,
+    /// The index of the device in `vkEnumeratePhysicalDevices`.
+    pub index: usize,
+    pub name: String,
+    pub device_type: vk::PhysicalDeviceType,
+    pub score: DeviceScore,
+    /// Why the device can not be used, empty if it is suitable.
+    pub rejection_reasons: Vec<String>,
+}
+
+impl DeviceCandidate {
+    pub fn is_suitable(&self) -> bool {
+        self.rejection_reasons.is_empty()
+    }
+}
+
+impl fmt::Display for DeviceCandidate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "#{} {} ({:?}, {} MiB)",
+            self.index,
+            self.name,
+            self.device_type,
+            self.score.device_local_memory / (1024 * 1024)
+        )?;
+
+        if !self.is_suitable() {
+            write!(f, ": {}", self.rejection_reasons.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Rate all the physical devices, the rejected ones are kept with the reasons of the rejection.
+///
+/// `check_device` returns the reasons to reject a device which only the app can check,
+/// e.g. the queue families it needs or the support of its surface.
+pub fn rate_physical_devices<E, F>(
+    instance: &ash::Instance,
+    requirements: &DeviceRequirements,
+    mut check_device: F,
+) -> Result<Vec<DeviceCandidate>, E>
+where
+    E: From<vk::Result>,
+    F: FnMut(vk::PhysicalDevice) -> Result<Vec<String>, E>,
+{
+    let physical_devices = unsafe { instance.enumerate_physical_devices()? };
+    let device_override = std::env::var(DEVICE_OVERRIDE_VAR).ok();
+
+    let mut candidates = Vec::with_capacity(physical_devices.len());
+    for (index, &physical_device) in physical_devices.iter().enumerate() {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let features = unsafe { instance.get_physical_device_features(physical_device) };
+        let name = vk_to_string(&properties.device_name);
+
+        let mut rejection_reasons = check_device(physical_device)?;
+        rejection_reasons.extend(check_requirements(instance, physical_device, requirements)?);
+        if let Some(device_override) = device_override.as_ref() {
+            if !is_device_chosen(device_override, index, &name) {
+                rejection_reasons.push(format!(
+                    "not chosen by {}={}",
+                    DEVICE_OVERRIDE_VAR, device_override
+                ));
+            }
+        }
+
+        let preferred_feature_count = requirements
+            .preferred_features
+            .iter()
+            .filter(|feature| feature.is_supported(&features))
+            .count() as u32;
+
+        candidates.push(DeviceCandidate {
+            physical_device,
+            index,
+            name,
+            device_type: properties.device_type,
+            score: DeviceScore::new(&properties, &memory_properties, preferred_feature_count),
+            rejection_reasons,
+        });
+    }
+
+    Ok(candidates)
+}
+
+/// The suitable candidate with the best score, the first enumerated one wins a tie.
+pub fn select_physical_device(candidates: &[DeviceCandidate]) -> Option<&DeviceCandidate> {
+    candidates
+        .iter()
+        .filter(|candidate| candidate.is_suitable())
+        .min_by_key(|candidate| Reverse(candidate.score))
+}
+
+/// Return the extensions and the features of `requirements` which are not supported by
+/// `physical_device`, in a readable form.
+pub fn check_requirements(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    requirements: &DeviceRequirements,
+) -> Result<Vec<String>, vk::Result> {
+    let mut rejection_reasons = vec![];
+
+    let available_extension_names = available_extension_names(instance, physical_device)?;
+    let missing_extensions: Vec<&str> = requirements
+        .extensions
+        .iter()
+        .filter(|&&extension| {
+            !available_extension_names
+                .iter()
+                .any(|available| available == extension)
+        })
+        .copied()
+        .collect();
+    if !missing_extensions.is_empty() {
+        rejection_reasons.push(format!(
+            "missing extensions {}",
+            missing_extensions.join(", ")
+        ));
+    }
+
+    let features = unsafe { instance.get_physical_device_features(physical_device) };
+    let missing_features: Vec<&str> = requirements
+        .features
+        .iter()
+        .filter(|feature| !feature.is_supported(&features))
+        .map(|feature| feature.name())
+        .collect();
+    if !missing_features.is_empty() {
+        rejection_reasons.push(format!("missing features {}", missing_features.join(", ")));
+    }
+
+    Ok(rejection_reasons)
+}
+
+/// The names of the extensions supported by `physical_device`.
+pub fn available_extension_names(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+) -> Result<Vec<String>, vk::Result> {
+    let available_extensions =
+        unsafe { instance.enumerate_device_extension_properties(physical_device)? };
+
+    Ok(available_extensions
+        .iter()
+        .map(|extension| vk_to_string(&extension.extension_name))
+        .collect())
+}
+
+fn is_device_chosen(device_override: &str, index: usize, name: &str) -> bool {
+    match device_override.trim().parse::<usize>() {
+        Ok(chosen_index) => chosen_index == index,
+        Err(_) => name
+            .to_lowercase()
+            .contains(&device_override.trim().to_lowercase()),
+    }
+}
+
+fn vk_to_string(raw_string_array: &[c_char]) -> String {
+    let raw_string = unsafe { CStr::from_ptr(raw_string_array.as_ptr()) };
+
+    raw_string.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(
+        index: usize,
+        device_type: vk::PhysicalDeviceType,
+        preferred_feature_count: u32,
+        device_local_memory: vk::DeviceSize,
+        rejection_reasons: &[&str],
+    ) -> DeviceCandidate {
+        DeviceCandidate {
+            physical_device: vk::PhysicalDevice::null(),
+            index,
+            name: format!("GPU {}", index),
+            device_type,
+            score: DeviceScore::new(
+                &vk::PhysicalDeviceProperties {
+                    device_type,
+                    ..Default::default()
+                },
+                &vk::PhysicalDeviceMemoryProperties {
+                    memory_heap_count: 1,
+                    memory_heaps: [vk::MemoryHeap {
+                        size: device_local_memory,
+                        flags: vk::MemoryHeapFlags::DEVICE_LOCAL,
+                    }; vk::MAX_MEMORY_HEAPS],
+                    ..Default::default()
+                },
+                preferred_feature_count,
+            ),
+            rejection_reasons: rejection_reasons.iter().map(|&r| String::from(r)).collect(),
+        }
+    }
+
+    #[test]
+    fn device_type_ranks_before_the_memory() {
+        let candidates = [
+            candidate(0, vk::PhysicalDeviceType::INTEGRATED_GPU, 0, 8 << 30, &[]),
+            candidate(1, vk::PhysicalDeviceType::DISCRETE_GPU, 0, 2 << 30, &[]),
+            candidate(2, vk::PhysicalDeviceType::DISCRETE_GPU, 0, 4 << 30, &[]),
+            candidate(3, vk::PhysicalDeviceType::CPU, 0, 16 << 30, &[]),
+        ];
+
+        assert_eq!(select_physical_device(&candidates).unwrap().index, 2);
+    }
+
+    #[test]
+    fn preferred_features_rank_between_the_type_and_the_memory() {
+        let candidates = [
+            candidate(0, vk::PhysicalDeviceType::DISCRETE_GPU, 0, 8 << 30, &[]),
+            candidate(1, vk::PhysicalDeviceType::DISCRETE_GPU, 1, 2 << 30, &[]),
+            candidate(2, vk::PhysicalDeviceType::INTEGRATED_GPU, 2, 4 << 30, &[]),
+        ];
+
+        assert_eq!(select_physical_device(&candidates).unwrap().index, 1);
+        // a device without the preferred features is still suitable.
+        assert_eq!(select_physical_device(&candidates[..1]).unwrap().index, 0);
+    }
+
+    #[test]
+    fn rejected_devices_are_never_selected() {
+        let candidates = [
+            candidate(
+                0,
+                vk::PhysicalDeviceType::DISCRETE_GPU,
+                0,
+                8 << 30,
+                &["no graphics queue family"],
+            ),
+            candidate(1, vk::PhysicalDeviceType::INTEGRATED_GPU, 0, 1 << 30, &[]),
+            candidate(2, vk::PhysicalDeviceType::INTEGRATED_GPU, 0, 1 << 30, &[]),
+        ];
+
+        // the first enumerated device wins a tie.
+        assert_eq!(select_physical_device(&candidates).unwrap().index, 1);
+        assert!(select_physical_device(&candidates[..1]).is_none());
+    }
+
+    #[test]
+    fn rejection_reasons_are_displayed() {
+        let suitable = candidate(0, vk::PhysicalDeviceType::DISCRETE_GPU, 0, 2 << 30, &[]);
+        assert_eq!(suitable.to_string(), "#0 GPU 0 (DISCRETE_GPU, 2048 MiB)");
+
+        let rejected = candidate(
+            1,
+            vk::PhysicalDeviceType::CPU,
+            0,
+            0,
+            &[
+                "missing extensions VK_KHR_swapchain",
+                "missing features wideLines",
+            ],
+        );
+        assert_eq!(
+            rejected.to_string(),
+            "#1 GPU 1 (CPU, 0 MiB): missing extensions VK_KHR_swapchain, missing features wideLines"
+        );
+    }
+
+    #[test]
+    fn override_chooses_by_index_or_by_name() {
+        assert!(is_device_chosen("1", 1, "NVIDIA GeForce"));
+        assert!(!is_device_chosen(" 0 ", 1, "NVIDIA GeForce"));
+        assert!(is_device_chosen("geforce", 0, "NVIDIA GeForce"));
+        assert!(!is_device_chosen("radeon", 0, "NVIDIA GeForce"));
+    }
+}
@@ -11,6 +11,7 @@ use validation::Validation;
 
 pub mod constant;
 pub mod device_extensions;
+pub mod physical_device;
 pub mod prelude;
 pub mod tool;
 pub mod validation;
@@ -79,16 +80,9 @@ impl VulkanApp {
     }
 
     fn pick_physical_device(instance: &Instance) -> vk::PhysicalDevice {
-        unsafe { instance.enumerate_physical_devices() }
-            .unwrap()
-            .into_iter()
-            .filter(|&device| Self::is_device_suitable(instance, device))
-            .next()
-            .expect("suitable physical device")
-    }
+        let candidates = physical_device::rate_devices(instance);
 
-    fn is_device_suitable(instance: &Instance, device: vk::PhysicalDevice) -> bool {
-        QueueFamilyIndicies::for_device(instance, device).is_complete()
+        physical_device::pick_device(&candidates)
     }
 
     fn create_logical_device(
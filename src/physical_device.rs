@@ -0,0 +1,50 @@
+use ash::{
+    vk,
+    Instance,
+};
+use device_selection::DeviceRequirements;
+
+use crate::QueueFamilyIndicies;
+
+pub use device_selection::{
+    DeviceCandidate,
+    DEVICE_OVERRIDE_VAR,
+};
+
+/// The device extensions the app can not run without: none, since it presents nothing.
+/// `DeviceExtensions` still enables `VK_KHR_portability_subset` on the devices which have it.
+const REQUIRED_EXTENSIONS: &[&str] = &[];
+
+/// Rate every physical device with the scoring of the tutorial.
+///
+/// The app has no surface yet, so a device only needs a graphics queue family.
+pub fn rate_devices(instance: &Instance) -> Vec<DeviceCandidate> {
+    let requirements = DeviceRequirements {
+        extensions: REQUIRED_EXTENSIONS,
+        features: &[],
+        preferred_features: &[],
+    };
+
+    device_selection::rate_physical_devices(instance, &requirements, |device| {
+        let mut rejection_reasons = vec![];
+        if !QueueFamilyIndicies::for_device(instance, device).is_complete() {
+            rejection_reasons.push(String::from("no graphics queue family"));
+        }
+        Ok::<_, vk::Result>(rejection_reasons)
+    })
+    .expect("physical devices")
+}
+
+/// The suitable device with the best score, panics with the list of rejected devices if none.
+pub fn pick_device(candidates: &[DeviceCandidate]) -> vk::PhysicalDevice {
+    match device_selection::select_physical_device(candidates) {
+        Some(candidate) => candidate.physical_device,
+        None => {
+            let candidates: Vec<String> = candidates.iter().map(|c| c.to_string()).collect();
+            panic!(
+                "Failed to find a suitable GPU\n    {}",
+                candidates.join("\n    ")
+            )
+        }
+    }
+}
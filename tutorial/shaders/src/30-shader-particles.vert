@@ -0,0 +1,21 @@
+
+#version 450
+
+#extension GL_ARB_separate_shader_objects : enable
+
+layout (location = 0) in vec2 inPosition;
+layout (location = 1) in vec4 inColor;
+
+layout (location = 0) out vec4 fragColor;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+    float gl_PointSize;
+};
+
+void main() {
+
+    gl_Position = vec4(inPosition, 0.0, 1.0);
+    gl_PointSize = 2.0;
+    fragColor = inColor;
+}
@@ -2,6 +2,7 @@ use vulkan_tutorial_rust::{
     utility, // the mod define some fixed functions that have been learned before.
     utility::constants::*,
     utility::debug::ValidationInfo,
+    utility::error::VkTutorialResult,
     utility::share,
 };
 
@@ -48,7 +49,7 @@ struct VulkanApp {
 }
 
 impl VulkanApp {
-    pub fn new() -> VulkanApp {
+    pub fn new() -> VkTutorialResult<VulkanApp> {
         // init vulkan stuff
         let entry = ash::Entry::new().unwrap();
         let instance = share::create_instance(
@@ -56,7 +57,8 @@ impl VulkanApp {
             WINDOW_TITLE,
             VALIDATION.is_enable,
             &VALIDATION.required_validation_layers.to_vec(),
-        );
+            None,
+        )?;
         let (debug_utils_loader, debug_merssager) =
             utility::debug::setup_debug_utils(VALIDATION.is_enable, &entry, &instance);
         let physical_device = VulkanApp::pick_physical_device(&instance);
@@ -64,7 +66,7 @@ impl VulkanApp {
             VulkanApp::create_logical_device(&instance, physical_device, &VALIDATION);
 
         // cleanup(); the 'drop' function will take care of it.
-        VulkanApp {
+        Ok(VulkanApp {
             _entry: entry,
             instance,
             debug_utils_loader,
@@ -72,7 +74,7 @@ impl VulkanApp {
             _physical_device: physical_device,
             device: logical_device,
             _graphics_queue: graphics_queue,
-        }
+        })
     }
 
     fn pick_physical_device(instance: &ash::Instance) -> vk::PhysicalDevice {
@@ -253,7 +255,7 @@ fn main() {
     let window =
         utility::window::init_window(&event_loop, WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
 
-    let vulkan_app = VulkanApp::new();
+    let vulkan_app = VulkanApp::new().expect("Failed to initialize the app!");
     vulkan_app.main_loop(event_loop, window);
 }
 // -------------------------------------------------------------------------------------------
@@ -2,6 +2,7 @@ use vulkan_tutorial_rust::{
     utility, // the mod define some fixed functions that have been learned before.
     utility::constants::*,
     utility::debug::ValidationInfo,
+    utility::error::VkTutorialResult,
     utility::share,
 };
 
@@ -64,14 +65,15 @@ struct VulkanApp {
 }
 
 impl VulkanApp {
-    pub fn new(window: &winit::window::Window) -> VulkanApp {
+    pub fn new(window: &winit::window::Window) -> VkTutorialResult<VulkanApp> {
         let entry = ash::Entry::new().unwrap();
         let instance = share::create_instance(
             &entry,
             WINDOW_TITLE,
             VALIDATION.is_enable,
             &VALIDATION.required_validation_layers.to_vec(),
-        );
+            Some(&window),
+        )?;
         let (debug_utils_loader, debug_merssager) =
             utility::debug::setup_debug_utils(VALIDATION.is_enable, &entry, &instance);
         let surface_stuff = VulkanApp::create_surface(&entry, &instance, &window);
@@ -88,7 +90,7 @@ impl VulkanApp {
             unsafe { device.get_device_queue(family_indices.present_family.unwrap(), 0) };
 
         // cleanup(); the 'drop' function will take care of it.
-        VulkanApp {
+        Ok(VulkanApp {
             _entry: entry,
             instance,
             surface: surface_stuff.surface,
@@ -99,7 +101,7 @@ impl VulkanApp {
             device,
             _graphics_queue: graphics_queue,
             _present_queue: present_queue,
-        }
+        })
     }
 
     fn create_surface(
@@ -322,7 +324,7 @@ fn main() {
     let window =
         utility::window::init_window(&event_loop, WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
 
-    let vulkan_app = VulkanApp::new(&window);
+    let vulkan_app = VulkanApp::new(&window).expect("Failed to initialize the app!");
     vulkan_app.main_loop(event_loop, window);
 }
 // -------------------------------------------------------------------------------------------
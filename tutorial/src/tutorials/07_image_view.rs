@@ -2,6 +2,7 @@ use vulkan_tutorial_rust::{
     utility, // the mod define some fixed functions that have been learned before.
     utility::constants::*,
     utility::debug::*,
+    utility::error::VkTutorialResult,
     utility::share,
 };
 
@@ -48,7 +49,7 @@ struct VulkanApp {
 }
 
 impl VulkanApp {
-    pub fn new(window: &winit::window::Window) -> VulkanApp {
+    pub fn new(window: &winit::window::Window) -> VkTutorialResult<VulkanApp> {
         // init vulkan stuff
         let entry = ash::Entry::new().unwrap();
         let instance = share::create_instance(
@@ -56,20 +57,26 @@ impl VulkanApp {
             WINDOW_TITLE,
             VALIDATION.is_enable,
             &VALIDATION.required_validation_layers.to_vec(),
-        );
-        let surface_stuff =
-            share::create_surface(&entry, &instance, &window, WINDOW_WIDTH, WINDOW_HEIGHT);
+            Some(&window),
+        )?;
+        let surface_stuff = share::create_surface(
+            &entry,
+            &instance,
+            Some(&window),
+            WINDOW_WIDTH,
+            WINDOW_HEIGHT,
+        )?;
         let (debug_utils_loader, debug_merssager) =
             setup_debug_utils(VALIDATION.is_enable, &entry, &instance);
         let physical_device =
-            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS);
+            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS)?;
         let (device, family_indices) = share::create_logical_device(
             &instance,
             physical_device,
             &VALIDATION,
             &DEVICE_EXTENSIONS,
             &surface_stuff,
-        );
+        )?;
         let graphics_queue =
             unsafe { device.get_device_queue(family_indices.graphics_family.unwrap(), 0) };
         let present_queue =
@@ -81,7 +88,7 @@ impl VulkanApp {
             &window,
             &surface_stuff,
             &family_indices,
-        );
+        )?;
         let swapchain_imageviews = VulkanApp::create_image_views(
             &device,
             swapchain_stuff.swapchain_format,
@@ -89,7 +96,7 @@ impl VulkanApp {
         );
 
         // cleanup(); the 'drop' function will take care of it.
-        VulkanApp {
+        Ok(VulkanApp {
             _entry: entry,
             instance,
             surface: surface_stuff.surface,
@@ -109,7 +116,7 @@ impl VulkanApp {
             _swapchain_images: swapchain_stuff.swapchain_images,
             _swapchain_extent: swapchain_stuff.swapchain_extent,
             swapchain_imageviews,
-        }
+        })
     }
 
     fn create_image_views(
@@ -215,7 +222,7 @@ fn main() {
     let window =
         utility::window::init_window(&event_loop, WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
 
-    let vulkan_app = VulkanApp::new(&window);
+    let vulkan_app = VulkanApp::new(&window).expect("Failed to initialize the app!");
     vulkan_app.main_loop(event_loop, window);
 }
 // -------------------------------------------------------------------------------------------
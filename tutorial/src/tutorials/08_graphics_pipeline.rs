@@ -2,6 +2,7 @@ use vulkan_tutorial_rust::{
     utility, // the mod define some fixed functions that have been learned before.
     utility::constants::*,
     utility::debug::*,
+    utility::error::VkTutorialResult,
     utility::share,
 };
 
@@ -46,27 +47,33 @@ struct VulkanApp {
 }
 
 impl VulkanApp {
-    pub fn new(window: &winit::window::Window) -> VulkanApp {
+    pub fn new(window: &winit::window::Window) -> VkTutorialResult<VulkanApp> {
         let entry = ash::Entry::new().unwrap();
         let instance = share::create_instance(
             &entry,
             WINDOW_TITLE,
             VALIDATION.is_enable,
             &VALIDATION.required_validation_layers.to_vec(),
-        );
-        let surface_stuff =
-            share::create_surface(&entry, &instance, &window, WINDOW_WIDTH, WINDOW_HEIGHT);
+            Some(&window),
+        )?;
+        let surface_stuff = share::create_surface(
+            &entry,
+            &instance,
+            Some(&window),
+            WINDOW_WIDTH,
+            WINDOW_HEIGHT,
+        )?;
         let (debug_utils_loader, debug_merssager) =
             setup_debug_utils(VALIDATION.is_enable, &entry, &instance);
         let physical_device =
-            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS);
+            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS)?;
         let (device, family_indices) = share::create_logical_device(
             &instance,
             physical_device,
             &VALIDATION,
             &DEVICE_EXTENSIONS,
             &surface_stuff,
-        );
+        )?;
         let graphics_queue =
             unsafe { device.get_device_queue(family_indices.graphics_family.unwrap(), 0) };
         let present_queue =
@@ -78,16 +85,16 @@ impl VulkanApp {
             &window,
             &surface_stuff,
             &family_indices,
-        );
+        )?;
         let swapchain_imageviews = share::v1::create_image_views(
             &device,
             swapchain_stuff.swapchain_format,
             &swapchain_stuff.swapchain_images,
-        );
+        )?;
         let _graphics_pipeline = VulkanApp::create_graphics_pipeline();
 
         // cleanup(); the 'drop' function will take care of it.
-        VulkanApp {
+        Ok(VulkanApp {
             _entry: entry,
             instance,
             surface: surface_stuff.surface,
@@ -107,7 +114,7 @@ impl VulkanApp {
             _swapchain_images: swapchain_stuff.swapchain_images,
             _swapchain_extent: swapchain_stuff.swapchain_extent,
             swapchain_imageviews,
-        }
+        })
     }
 
     fn create_graphics_pipeline() {
@@ -176,7 +183,7 @@ fn main() {
     let window =
         utility::window::init_window(&event_loop, WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
 
-    let vulkan_app = VulkanApp::new(&window);
+    let vulkan_app = VulkanApp::new(&window).expect("Failed to initialize the app!");
     vulkan_app.main_loop(event_loop, window);
 }
 // -------------------------------------------------------------------------------------------
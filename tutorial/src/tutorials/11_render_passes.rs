@@ -2,6 +2,7 @@ use vulkan_tutorial_rust::{
     utility, // the mod define some fixed functions that have been learned before.
     utility::constants::*,
     utility::debug::*,
+    utility::error::VkTutorialResult,
     utility::share,
 };
 
@@ -52,27 +53,33 @@ struct VulkanApp {
 }
 
 impl VulkanApp {
-    pub fn new(window: &winit::window::Window) -> VulkanApp {
+    pub fn new(window: &winit::window::Window) -> VkTutorialResult<VulkanApp> {
         let entry = ash::Entry::new().unwrap();
         let instance = share::create_instance(
             &entry,
             WINDOW_TITLE,
             VALIDATION.is_enable,
             &VALIDATION.required_validation_layers.to_vec(),
-        );
-        let surface_stuff =
-            share::create_surface(&entry, &instance, &window, WINDOW_WIDTH, WINDOW_HEIGHT);
+            Some(&window),
+        )?;
+        let surface_stuff = share::create_surface(
+            &entry,
+            &instance,
+            Some(&window),
+            WINDOW_WIDTH,
+            WINDOW_HEIGHT,
+        )?;
         let (debug_utils_loader, debug_merssager) =
             setup_debug_utils(VALIDATION.is_enable, &entry, &instance);
         let physical_device =
-            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS);
+            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS)?;
         let (device, family_indices) = share::create_logical_device(
             &instance,
             physical_device,
             &VALIDATION,
             &DEVICE_EXTENSIONS,
             &surface_stuff,
-        );
+        )?;
         let graphics_queue =
             unsafe { device.get_device_queue(family_indices.graphics_family.unwrap(), 0) };
         let present_queue =
@@ -84,18 +91,18 @@ impl VulkanApp {
             &window,
             &surface_stuff,
             &family_indices,
-        );
+        )?;
         let swapchain_imageviews = share::v1::create_image_views(
             &device,
             swapchain_stuff.swapchain_format,
             &swapchain_stuff.swapchain_images,
-        );
+        )?;
         let render_pass = VulkanApp::create_render_pass(&device, swapchain_stuff.swapchain_format);
         let pipeline_layout =
-            VulkanApp::create_graphics_pipeline(&device, swapchain_stuff.swapchain_extent);
+            VulkanApp::create_graphics_pipeline(&device, swapchain_stuff.swapchain_extent)?;
 
         // cleanup(); the 'drop' function will take care of it.
-        VulkanApp {
+        Ok(VulkanApp {
             _entry: entry,
             instance,
             surface: surface_stuff.surface,
@@ -118,20 +125,20 @@ impl VulkanApp {
 
             pipeline_layout,
             render_pass,
-        }
+        })
     }
 
     fn create_graphics_pipeline(
         device: &ash::Device,
         swapchain_extent: vk::Extent2D,
-    ) -> vk::PipelineLayout {
+    ) -> VkTutorialResult<vk::PipelineLayout> {
         let vert_shader_code =
-            utility::tools::read_shader_code(Path::new("shaders/spv/09-shader-base.vert.spv"));
+            utility::tools::read_shader_code(Path::new("shaders/spv/09-shader-base.vert.spv"))?;
         let frag_shader_code =
-            utility::tools::read_shader_code(Path::new("shaders/spv/09-shader-base.frag.spv"));
+            utility::tools::read_shader_code(Path::new("shaders/spv/09-shader-base.frag.spv"))?;
 
-        let vert_shader_module = share::create_shader_module(device, vert_shader_code);
-        let frag_shader_module = share::create_shader_module(device, frag_shader_code);
+        let vert_shader_module = share::create_shader_module(device, vert_shader_code)?;
+        let frag_shader_module = share::create_shader_module(device, frag_shader_code)?;
 
         let main_function_name = CString::new("main").unwrap(); // the beginning function name in shader code.
 
@@ -304,7 +311,7 @@ impl VulkanApp {
             device.destroy_shader_module(frag_shader_module, None);
         }
 
-        pipeline_layout
+        Ok(pipeline_layout)
     }
 
     fn create_render_pass(device: &ash::Device, surface_format: vk::Format) -> vk::RenderPass {
@@ -425,7 +432,7 @@ fn main() {
     let window =
         utility::window::init_window(&event_loop, WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
 
-    let vulkan_app = VulkanApp::new(&window);
+    let vulkan_app = VulkanApp::new(&window).expect("Failed to initialize the app!");
     vulkan_app.main_loop(event_loop, window);
 }
 // -------------------------------------------------------------------------------------------
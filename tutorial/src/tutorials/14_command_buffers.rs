@@ -2,6 +2,8 @@ use vulkan_tutorial_rust::{
     utility, // the mod define some fixed functions that have been learned before.
     utility::constants::*,
     utility::debug::*,
+    utility::error::VkTutorialResult,
+    utility::pipeline_cache::PipelineCache,
     utility::share,
     utility::structures::*,
 };
@@ -51,33 +53,40 @@ struct VulkanApp {
     render_pass: vk::RenderPass,
     pipeline_layout: vk::PipelineLayout,
     graphics_pipeline: vk::Pipeline,
+    pipeline_cache: PipelineCache,
 
     command_pool: vk::CommandPool,
     _command_buffers: Vec<vk::CommandBuffer>,
 }
 
 impl VulkanApp {
-    pub fn new(window: &winit::window::Window) -> VulkanApp {
+    pub fn new(window: &winit::window::Window) -> VkTutorialResult<VulkanApp> {
         let entry = ash::Entry::new().unwrap();
         let instance = share::create_instance(
             &entry,
             WINDOW_TITLE,
             VALIDATION.is_enable,
             &VALIDATION.required_validation_layers.to_vec(),
-        );
-        let surface_stuff =
-            share::create_surface(&entry, &instance, &window, WINDOW_WIDTH, WINDOW_HEIGHT);
+            Some(&window),
+        )?;
+        let surface_stuff = share::create_surface(
+            &entry,
+            &instance,
+            Some(&window),
+            WINDOW_WIDTH,
+            WINDOW_HEIGHT,
+        )?;
         let (debug_utils_loader, debug_merssager) =
             setup_debug_utils(VALIDATION.is_enable, &entry, &instance);
         let physical_device =
-            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS);
+            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS)?;
         let (device, family_indices) = share::create_logical_device(
             &instance,
             physical_device,
             &VALIDATION,
             &DEVICE_EXTENSIONS,
             &surface_stuff,
-        );
+        )?;
         let graphics_queue =
             unsafe { device.get_device_queue(family_indices.graphics_family.unwrap(), 0) };
         let present_queue =
@@ -89,24 +98,26 @@ impl VulkanApp {
             &window,
             &surface_stuff,
             &family_indices,
-        );
+        )?;
         let swapchain_imageviews = share::v1::create_image_views(
             &device,
             swapchain_stuff.swapchain_format,
             &swapchain_stuff.swapchain_images,
-        );
-        let render_pass = share::v1::create_render_pass(&device, swapchain_stuff.swapchain_format);
+        )?;
+        let render_pass = share::v1::create_render_pass(&device, swapchain_stuff.swapchain_format)?;
+        let pipeline_cache = PipelineCache::new(&instance, physical_device, &device)?;
         let (graphics_pipeline, pipeline_layout) = share::v1::create_graphics_pipeline(
             &device,
             render_pass,
             swapchain_stuff.swapchain_extent,
-        );
+            pipeline_cache.cache,
+        )?;
         let swapchain_framebuffers = share::v1::create_framebuffers(
             &device,
             render_pass,
             &swapchain_imageviews,
             swapchain_stuff.swapchain_extent,
-        );
+        )?;
         let command_pool = VulkanApp::create_command_pool(&device, &family_indices);
         let command_buffers = VulkanApp::create_command_buffers(
             &device,
@@ -118,7 +129,7 @@ impl VulkanApp {
         );
 
         // cleanup(); the 'drop' function will take care of it.
-        VulkanApp {
+        Ok(VulkanApp {
             _entry: entry,
             instance,
             surface: surface_stuff.surface,
@@ -143,10 +154,11 @@ impl VulkanApp {
             pipeline_layout,
             render_pass,
             graphics_pipeline,
+            pipeline_cache,
 
             command_pool,
             _command_buffers: command_buffers,
-        }
+        })
     }
 
     fn create_command_pool(
@@ -271,6 +283,8 @@ impl Drop for VulkanApp {
 
             self.swapchain_loader
                 .destroy_swapchain(self.swapchain, None);
+            self.pipeline_cache.destroy();
+
             self.device.destroy_device(None);
             self.surface_loader.destroy_surface(self.surface, None);
 
@@ -319,7 +333,7 @@ fn main() {
     let window =
         utility::window::init_window(&event_loop, WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
 
-    let vulkan_app = VulkanApp::new(&window);
+    let vulkan_app = VulkanApp::new(&window).expect("Failed to initialize the app!");
     vulkan_app.main_loop(event_loop, window);
 }
 // -------------------------------------------------------------------------------------------
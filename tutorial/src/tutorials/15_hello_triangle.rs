@@ -2,6 +2,8 @@ use vulkan_tutorial_rust::{
     utility, // the mod define some fixed functions that have been learned before.
     utility::constants::*,
     utility::debug::*,
+    utility::error::VkTutorialResult,
+    utility::pipeline_cache::PipelineCache,
     utility::share,
 };
 
@@ -59,6 +61,7 @@ struct VulkanApp {
     render_pass: vk::RenderPass,
     pipeline_layout: vk::PipelineLayout,
     graphics_pipeline: vk::Pipeline,
+    pipeline_cache: PipelineCache,
 
     command_pool: vk::CommandPool,
     command_buffers: Vec<vk::CommandBuffer>,
@@ -70,7 +73,7 @@ struct VulkanApp {
 }
 
 impl VulkanApp {
-    pub fn new(event_loop: &winit::event_loop::EventLoop<()>) -> VulkanApp {
+    pub fn new(event_loop: &winit::event_loop::EventLoop<()>) -> VkTutorialResult<VulkanApp> {
         let window =
             utility::window::init_window(event_loop, WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
 
@@ -81,20 +84,26 @@ impl VulkanApp {
             WINDOW_TITLE,
             VALIDATION.is_enable,
             &VALIDATION.required_validation_layers.to_vec(),
-        );
-        let surface_stuff =
-            share::create_surface(&entry, &instance, &window, WINDOW_WIDTH, WINDOW_HEIGHT);
+            Some(&window),
+        )?;
+        let surface_stuff = share::create_surface(
+            &entry,
+            &instance,
+            Some(&window),
+            WINDOW_WIDTH,
+            WINDOW_HEIGHT,
+        )?;
         let (debug_utils_loader, debug_merssager) =
             setup_debug_utils(VALIDATION.is_enable, &entry, &instance);
         let physical_device =
-            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS);
+            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS)?;
         let (device, family_indices) = share::create_logical_device(
             &instance,
             physical_device,
             &VALIDATION,
             &DEVICE_EXTENSIONS,
             &surface_stuff,
-        );
+        )?;
         let graphics_queue =
             unsafe { device.get_device_queue(family_indices.graphics_family.unwrap(), 0) };
         let present_queue =
@@ -106,25 +115,27 @@ impl VulkanApp {
             &window,
             &surface_stuff,
             &family_indices,
-        );
+        )?;
         let swapchain_imageviews = share::v1::create_image_views(
             &device,
             swapchain_stuff.swapchain_format,
             &swapchain_stuff.swapchain_images,
-        );
+        )?;
         let render_pass = VulkanApp::create_render_pass(&device, swapchain_stuff.swapchain_format);
+        let pipeline_cache = PipelineCache::new(&instance, physical_device, &device)?;
         let (graphics_pipeline, pipeline_layout) = share::v1::create_graphics_pipeline(
             &device,
             render_pass,
             swapchain_stuff.swapchain_extent,
-        );
+            pipeline_cache.cache,
+        )?;
         let swapchain_framebuffers = share::v1::create_framebuffers(
             &device,
             render_pass,
             &swapchain_imageviews,
             swapchain_stuff.swapchain_extent,
-        );
-        let command_pool = share::v1::create_command_pool(&device, &family_indices);
+        )?;
+        let command_pool = share::v1::create_command_pool(&device, &family_indices)?;
         let command_buffers = share::v1::create_command_buffers(
             &device,
             command_pool,
@@ -132,11 +143,11 @@ impl VulkanApp {
             &swapchain_framebuffers,
             render_pass,
             swapchain_stuff.swapchain_extent,
-        );
+        )?;
         let sync_ojbects = VulkanApp::create_sync_objects(&device);
 
         // cleanup(); the 'drop' function will take care of it.
-        VulkanApp {
+        Ok(VulkanApp {
             window,
             // vulkan stuff
             _entry: entry,
@@ -163,6 +174,7 @@ impl VulkanApp {
             pipeline_layout,
             render_pass,
             graphics_pipeline,
+            pipeline_cache,
 
             command_pool,
             command_buffers,
@@ -171,7 +183,7 @@ impl VulkanApp {
             render_finished_semaphores: sync_ojbects.render_finished_semaphores,
             in_flight_fences: sync_ojbects.inflight_fences,
             current_frame: 0,
-        }
+        })
     }
 
     fn draw_frame(&mut self) {
@@ -379,6 +391,8 @@ impl Drop for VulkanApp {
 
             self.swapchain_loader
                 .destroy_swapchain(self.swapchain, None);
+            self.pipeline_cache.destroy();
+
             self.device.destroy_device(None);
             self.surface_loader.destroy_surface(self.surface, None);
 
@@ -432,7 +446,7 @@ impl VulkanApp {
 fn main() {
     let event_loop = EventLoop::new();
 
-    let vulkan_app = VulkanApp::new(&event_loop);
+    let vulkan_app = VulkanApp::new(&event_loop).expect("Failed to initialize the app!");
     vulkan_app.main_loop(event_loop);
 }
 // -------------------------------------------------------------------------------------------
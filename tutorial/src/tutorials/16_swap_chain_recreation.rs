@@ -1,139 +1,184 @@
 use vulkan_tutorial_rust::{
-    utility, // the mod define some fixed functions that have been learned before.
     utility::constants::*,
     utility::debug::*,
+    utility::error::VkTutorialResult,
+    utility::handles::{
+        self,
+        CommandPool,
+        Device,
+        Fence,
+        Framebuffer,
+        ImageView,
+        Instance,
+        Pipeline,
+        PipelineLayout,
+        RenderPass,
+        Semaphore,
+    },
+    utility::hot_reload::{
+        self,
+        ShaderWatcher,
+    },
+    utility::pipeline_cache::PipelineCache,
+    utility::render_target::RenderTarget,
     utility::share,
     utility::structures::*,
+    utility::window::{
+        ProgramProc,
+        VulkanApp,
+    },
 };
 
 use ash::version::DeviceV1_0;
-use ash::version::InstanceV1_0;
 use ash::vk;
-use winit::event::{
-    ElementState,
-    Event,
-    KeyboardInput,
-    VirtualKeyCode,
-    WindowEvent,
-};
-use winit::event_loop::{
-    ControlFlow,
-    EventLoop,
-};
 
+use std::path::Path;
 use std::ptr;
+use std::sync::Arc;
 
 // Constants
 const WINDOW_TITLE: &'static str = "16.Swap Chain Recreation";
+const VERT_SHADER_SOURCE_PATH: &'static str = "shaders/src/09-shader-base.vert";
+const FRAG_SHADER_SOURCE_PATH: &'static str = "shaders/src/09-shader-base.frag";
 
-struct VulkanApp {
-    window: winit::window::Window,
+/// Built with the owned handles of `share::owned`, which destroy themselves when the app is dropped.
+/// Only the swapchain, the surface and the debug messenger are destroyed by hand.
+struct VulkanApp16 {
+    window: Option<winit::window::Window>,
 
     // vulkan stuff
     _entry: ash::Entry,
-    instance: ash::Instance,
+    instance: Arc<Instance>,
     surface_loader: ash::extensions::khr::Surface,
     surface: vk::SurfaceKHR,
     debug_utils_loader: ash::extensions::ext::DebugUtils,
     debug_merssager: vk::DebugUtilsMessengerEXT,
 
     physical_device: vk::PhysicalDevice,
-    device: ash::Device,
+    device: Arc<Device>,
 
     queue_family: QueueFamilyIndices,
     graphics_queue: vk::Queue,
     present_queue: vk::Queue,
 
-    swapchain_loader: ash::extensions::khr::Swapchain,
-    swapchain: vk::SwapchainKHR,
+    render_target: RenderTarget,
     swapchain_images: Vec<vk::Image>,
     swapchain_format: vk::Format,
     swapchain_extent: vk::Extent2D,
-    swapchain_imageviews: Vec<vk::ImageView>,
-    swapchain_framebuffers: Vec<vk::Framebuffer>,
+    swapchain_imageviews: Vec<ImageView>,
+    swapchain_framebuffers: Vec<Framebuffer>,
 
-    render_pass: vk::RenderPass,
-    pipeline_layout: vk::PipelineLayout,
-    graphics_pipeline: vk::Pipeline,
+    render_pass: RenderPass,
+    pipeline_layout: PipelineLayout,
+    graphics_pipeline: Pipeline,
+    /// Not an owned handle, it is saved and destroyed by `drop`.
+    pipeline_cache: PipelineCache,
 
-    command_pool: vk::CommandPool,
+    shader_watcher: Option<ShaderWatcher>,
+    vert_shader_code: Vec<u8>,
+    frag_shader_code: Vec<u8>,
+
+    command_pool: CommandPool,
     command_buffers: Vec<vk::CommandBuffer>,
 
-    image_available_semaphores: Vec<vk::Semaphore>,
-    render_finished_semaphores: Vec<vk::Semaphore>,
-    in_flight_fences: Vec<vk::Fence>,
+    image_available_semaphores: Vec<Semaphore>,
+    render_finished_semaphores: Vec<Semaphore>,
+    in_flight_fences: Vec<Fence>,
     current_frame: usize,
 
     is_framebuffer_resized: bool,
 }
 
-impl VulkanApp {
-    pub fn new(event_loop: &winit::event_loop::EventLoop<()>) -> VulkanApp {
-        let window =
-            utility::window::init_window(event_loop, WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
+impl VulkanApp16 {
+    pub fn new(program_proc: &ProgramProc) -> VkTutorialResult<VulkanApp16> {
+        let window = program_proc.init_window(WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
 
         let entry = ash::Entry::new().unwrap();
-        let instance = share::create_instance(
+        let instance = share::owned::create_instance(
             &entry,
             WINDOW_TITLE,
             VALIDATION.is_enable,
             &VALIDATION.required_validation_layers.to_vec(),
-        );
-        let surface_stuff =
-            share::create_surface(&entry, &instance, &window, WINDOW_WIDTH, WINDOW_HEIGHT);
+            window.as_ref(),
+        )?;
+        let surface_stuff = share::create_surface(
+            &entry,
+            &instance,
+            window.as_ref(),
+            WINDOW_WIDTH,
+            WINDOW_HEIGHT,
+        )?;
         let (debug_utils_loader, debug_merssager) =
             setup_debug_utils(VALIDATION.is_enable, &entry, &instance);
         let physical_device =
-            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS);
-        let (device, queue_family) = share::create_logical_device(
+            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS)?;
+        let (device, queue_family) = share::owned::create_logical_device(
             &instance,
             physical_device,
             &VALIDATION,
             &DEVICE_EXTENSIONS,
             &surface_stuff,
-        );
+        )?;
         let graphics_queue =
             unsafe { device.get_device_queue(queue_family.graphics_family.unwrap(), 0) };
         let present_queue =
             unsafe { device.get_device_queue(queue_family.present_family.unwrap(), 0) };
-        let swapchain_stuff = share::create_swapchain(
+        let swapchain_stuff = share::create_render_target(
             &instance,
             &device,
             physical_device,
-            &window,
+            window.as_ref(),
             &surface_stuff,
             &queue_family,
-        );
-        let swapchain_imageviews = share::v1::create_image_views(
+            graphics_queue,
+        )?;
+        let swapchain_imageviews = share::owned::create_image_views(
             &device,
             swapchain_stuff.swapchain_format,
             &swapchain_stuff.swapchain_images,
-        );
-        let render_pass = share::v1::create_render_pass(&device, swapchain_stuff.swapchain_format);
-        let (graphics_pipeline, pipeline_layout) = share::v1::create_graphics_pipeline(
-            &device,
-            render_pass,
-            swapchain_stuff.swapchain_extent,
-        );
-        let swapchain_framebuffers = share::v1::create_framebuffers(
+        )?;
+        let render_pass =
+            share::owned::create_render_pass(&device, swapchain_stuff.swapchain_format)?;
+        let vert_shader_code = include_bytes!("../../shaders/spv/09-shader-base.vert.spv").to_vec();
+        let frag_shader_code = include_bytes!("../../shaders/spv/09-shader-base.frag.spv").to_vec();
+        let pipeline_cache = PipelineCache::new(&instance, physical_device, &device)?;
+        let (graphics_pipeline, pipeline_layout) =
+            share::owned::create_graphics_pipeline_from_code(
+                &device,
+                &render_pass,
+                swapchain_stuff.swapchain_extent,
+                &vert_shader_code,
+                &frag_shader_code,
+                pipeline_cache.cache,
+            )?;
+        // only watch the shader sources when they can be compiled.
+        let shader_watcher = if hot_reload::is_supported() {
+            Some(ShaderWatcher::new(&[
+                Path::new(VERT_SHADER_SOURCE_PATH),
+                Path::new(FRAG_SHADER_SOURCE_PATH),
+            ]))
+        } else {
+            None
+        };
+        let swapchain_framebuffers = share::owned::create_framebuffers(
             &device,
-            render_pass,
+            &render_pass,
             &swapchain_imageviews,
             swapchain_stuff.swapchain_extent,
-        );
-        let command_pool = share::v1::create_command_pool(&device, &queue_family);
+        )?;
+        let command_pool = share::owned::create_command_pool(&device, &queue_family)?;
         let command_buffers = share::v1::create_command_buffers(
             &device,
-            command_pool,
-            graphics_pipeline,
-            &swapchain_framebuffers,
-            render_pass,
+            command_pool.raw(),
+            graphics_pipeline.raw(),
+            &handles::raw_handles(&swapchain_framebuffers),
+            render_pass.raw(),
             swapchain_stuff.swapchain_extent,
-        );
-        let sync_ojbects = share::v1::create_sync_objects(&device, MAX_FRAMES_IN_FLIGHT);
+        )?;
+        let sync_ojbects = share::owned::create_sync_objects(&device, MAX_FRAMES_IN_FLIGHT)?;
 
-        // cleanup(); the 'drop' function will take care of it.
-        VulkanApp {
+        // cleanup(); the handles are destroyed when they are dropped.
+        Ok(VulkanApp16 {
             // winit stuff
             window,
 
@@ -152,8 +197,7 @@ impl VulkanApp {
             graphics_queue,
             present_queue,
 
-            swapchain_loader: swapchain_stuff.swapchain_loader,
-            swapchain: swapchain_stuff.swapchain,
+            render_target: swapchain_stuff.render_target,
             swapchain_format: swapchain_stuff.swapchain_format,
             swapchain_images: swapchain_stuff.swapchain_images,
             swapchain_extent: swapchain_stuff.swapchain_extent,
@@ -163,6 +207,11 @@ impl VulkanApp {
             pipeline_layout,
             render_pass,
             graphics_pipeline,
+            pipeline_cache,
+
+            shader_watcher,
+            vert_shader_code,
+            frag_shader_code,
 
             command_pool,
             command_buffers,
@@ -173,11 +222,110 @@ impl VulkanApp {
             current_frame: 0,
 
             is_framebuffer_resized: false,
+        })
+    }
+
+    /// Rebuild the graphics pipeline if the shader sources have been edited.
+    /// A shader that fails to compile is reported, and the previous pipeline is kept.
+    fn reload_changed_shaders(&mut self) -> VkTutorialResult<()> {
+        let modified_paths = match self.shader_watcher.as_mut() {
+            Some(shader_watcher) => shader_watcher.poll(),
+            None => return Ok(()),
+        };
+        if modified_paths.is_empty() {
+            return Ok(());
+        }
+
+        let compiled_codes = hot_reload::compile_glsl(Path::new(VERT_SHADER_SOURCE_PATH)).and_then(
+            |vert_shader_code| {
+                hot_reload::compile_glsl(Path::new(FRAG_SHADER_SOURCE_PATH))
+                    .map(|frag_shader_code| (vert_shader_code, frag_shader_code))
+            },
+        );
+        let (vert_shader_code, frag_shader_code) = match compiled_codes {
+            Ok(codes) => codes,
+            Err(error) => {
+                eprintln!("{}, keep the previous pipeline.", error);
+                return Ok(());
+            }
+        };
+
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .expect("Failed to wait device idle!")
+        };
+
+        let (graphics_pipeline, pipeline_layout) =
+            match share::owned::create_graphics_pipeline_from_code(
+                &self.device,
+                &self.render_pass,
+                self.swapchain_extent,
+                &vert_shader_code,
+                &frag_shader_code,
+                self.pipeline_cache.cache,
+            ) {
+                Ok(pipeline) => pipeline,
+                Err(error) => {
+                    eprintln!("{}, keep the previous pipeline.", error);
+                    return Ok(());
+                }
+            };
+
+        unsafe {
+            self.device
+                .free_command_buffers(self.command_pool.raw(), &self.command_buffers);
+        }
+        // the previous pipeline is destroyed when it is replaced.
+        self.graphics_pipeline = graphics_pipeline;
+        self.pipeline_layout = pipeline_layout;
+        self.vert_shader_code = vert_shader_code;
+        self.frag_shader_code = frag_shader_code;
+
+        self.command_buffers = share::v1::create_command_buffers(
+            &self.device,
+            self.command_pool.raw(),
+            self.graphics_pipeline.raw(),
+            &handles::raw_handles(&self.swapchain_framebuffers),
+            self.render_pass.raw(),
+            self.swapchain_extent,
+        )?;
+
+        for path in modified_paths.iter() {
+            println!("Reloaded {:?}", path);
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for VulkanApp16 {
+    fn drop(&mut self) {
+        self.wait_device_idle();
+        self.cleanup_swapchain();
+
+        // the other handles are destroyed with the fields, the device and the instance last.
+        unsafe {
+            self.pipeline_cache.destroy();
+
+            if self.window.is_some() {
+                self.surface_loader.destroy_surface(self.surface, None);
+            }
+
+            if VALIDATION.is_enable {
+                self.debug_utils_loader
+                    .destroy_debug_utils_messenger(self.debug_merssager, None);
+            }
         }
     }
+}
 
-    fn draw_frame(&mut self) {
-        let wait_fences = [self.in_flight_fences[self.current_frame]];
+// Fix content -------------------------------------------------------------------------------
+impl VulkanApp for VulkanApp16 {
+    fn draw_frame(&mut self, _delta_time: f32) -> VkTutorialResult<()> {
+        self.reload_changed_shaders()?;
+
+        let wait_fences = [self.in_flight_fences[self.current_frame].raw()];
 
         unsafe {
             self.device
@@ -186,27 +334,31 @@ impl VulkanApp {
         }
 
         let (image_index, _is_sub_optimal) = unsafe {
-            let result = self.swapchain_loader.acquire_next_image(
-                self.swapchain,
+            let result = self.render_target.acquire_next_image(
                 std::u64::MAX,
-                self.image_available_semaphores[self.current_frame],
-                vk::Fence::null(),
+                self.image_available_semaphores[self.current_frame].raw(),
             );
             match result {
                 Ok(image_index) => image_index,
                 Err(vk_result) => match vk_result {
                     vk::Result::ERROR_OUT_OF_DATE_KHR => {
-                        self.recreate_swapchain();
-                        return;
+                        return self.recreate_swapchain();
                     }
                     _ => panic!("Failed to acquire Swap Chain Image!"),
                 },
             }
         };
 
-        let wait_semaphores = [self.image_available_semaphores[self.current_frame]];
+        let mut command_buffers = vec![self.command_buffers[image_index as usize]];
+        command_buffers.extend(self.render_target.record_capture(
+            self.command_pool.raw(),
+            image_index,
+            self.in_flight_fences[self.current_frame].raw(),
+        )?);
+
+        let wait_semaphores = [self.image_available_semaphores[self.current_frame].raw()];
         let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-        let signal_semaphores = [self.render_finished_semaphores[self.current_frame]];
+        let signal_semaphores = [self.render_finished_semaphores[self.current_frame].raw()];
 
         let submit_infos = [vk::SubmitInfo {
             s_type: vk::StructureType::SUBMIT_INFO,
@@ -214,8 +366,8 @@ impl VulkanApp {
             wait_semaphore_count: wait_semaphores.len() as u32,
             p_wait_semaphores: wait_semaphores.as_ptr(),
             p_wait_dst_stage_mask: wait_stages.as_ptr(),
-            command_buffer_count: 1,
-            p_command_buffers: &self.command_buffers[image_index as usize],
+            command_buffer_count: command_buffers.len() as u32,
+            p_command_buffers: command_buffers.as_ptr(),
             signal_semaphore_count: signal_semaphores.len() as u32,
             p_signal_semaphores: signal_semaphores.as_ptr(),
         }];
@@ -229,27 +381,14 @@ impl VulkanApp {
                 .queue_submit(
                     self.graphics_queue,
                     &submit_infos,
-                    self.in_flight_fences[self.current_frame],
+                    self.in_flight_fences[self.current_frame].raw(),
                 )
                 .expect("Failed to execute queue submit.");
         }
 
-        let swapchains = [self.swapchain];
-
-        let present_info = vk::PresentInfoKHR {
-            s_type: vk::StructureType::PRESENT_INFO_KHR,
-            p_next: ptr::null(),
-            wait_semaphore_count: 1,
-            p_wait_semaphores: signal_semaphores.as_ptr(),
-            swapchain_count: 1,
-            p_swapchains: swapchains.as_ptr(),
-            p_image_indices: &image_index,
-            p_results: ptr::null_mut(),
-        };
-
         let result = unsafe {
-            self.swapchain_loader
-                .queue_present(self.present_queue, &present_info)
+            self.render_target
+                .queue_present(self.present_queue, &signal_semaphores, image_index)
         };
         let is_resized = match result {
             Ok(_) => self.is_framebuffer_resized,
@@ -260,13 +399,15 @@ impl VulkanApp {
         };
         if is_resized {
             self.is_framebuffer_resized = false;
-            self.recreate_swapchain();
+            self.recreate_swapchain()?;
         }
 
         self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+
+        Ok(())
     }
 
-    fn recreate_swapchain(&mut self) {
+    fn recreate_swapchain(&mut self) -> VkTutorialResult<()> {
         // parameters -------------
         let surface_suff = SurfaceStuff {
             surface_loader: self.surface_loader.clone(),
@@ -283,139 +424,96 @@ impl VulkanApp {
         };
         self.cleanup_swapchain();
 
-        let swapchain_stuff = share::create_swapchain(
+        let swapchain_stuff = share::create_render_target(
             &self.instance,
             &self.device,
             self.physical_device,
-            &self.window,
+            self.window.as_ref(),
             &surface_suff,
             &self.queue_family,
-        );
-        self.swapchain_loader = swapchain_stuff.swapchain_loader;
-        self.swapchain = swapchain_stuff.swapchain;
+            self.graphics_queue,
+        )?;
+        self.render_target = swapchain_stuff.render_target;
         self.swapchain_images = swapchain_stuff.swapchain_images;
         self.swapchain_format = swapchain_stuff.swapchain_format;
         self.swapchain_extent = swapchain_stuff.swapchain_extent;
 
-        self.swapchain_imageviews = share::v1::create_image_views(
+        self.swapchain_imageviews = share::owned::create_image_views(
             &self.device,
             self.swapchain_format,
             &self.swapchain_images,
-        );
-        self.render_pass = share::v1::create_render_pass(&self.device, self.swapchain_format);
-        let (graphics_pipeline, pipeline_layout) = share::v1::create_graphics_pipeline(
-            &self.device,
-            self.render_pass,
-            swapchain_stuff.swapchain_extent,
-        );
+        )?;
+        self.render_pass = share::owned::create_render_pass(&self.device, self.swapchain_format)?;
+        let (graphics_pipeline, pipeline_layout) =
+            share::owned::create_graphics_pipeline_from_code(
+                &self.device,
+                &self.render_pass,
+                swapchain_stuff.swapchain_extent,
+                &self.vert_shader_code,
+                &self.frag_shader_code,
+                self.pipeline_cache.cache,
+            )?;
         self.graphics_pipeline = graphics_pipeline;
         self.pipeline_layout = pipeline_layout;
 
-        self.swapchain_framebuffers = share::v1::create_framebuffers(
+        self.swapchain_framebuffers = share::owned::create_framebuffers(
             &self.device,
-            self.render_pass,
+            &self.render_pass,
             &self.swapchain_imageviews,
             self.swapchain_extent,
-        );
+        )?;
         self.command_buffers = share::v1::create_command_buffers(
             &self.device,
-            self.command_pool,
-            self.graphics_pipeline,
-            &self.swapchain_framebuffers,
-            self.render_pass,
+            self.command_pool.raw(),
+            self.graphics_pipeline.raw(),
+            &handles::raw_handles(&self.swapchain_framebuffers),
+            self.render_pass.raw(),
             self.swapchain_extent,
-        );
+        )?;
+
+        Ok(())
     }
 
-    fn cleanup_swapchain(&self) {
+    /// The render pass and the pipeline are destroyed when they are replaced.
+    fn cleanup_swapchain(&mut self) {
         unsafe {
             self.device
-                .free_command_buffers(self.command_pool, &self.command_buffers);
-            for &framebuffer in self.swapchain_framebuffers.iter() {
-                self.device.destroy_framebuffer(framebuffer, None);
-            }
-            self.device.destroy_pipeline(self.graphics_pipeline, None);
-            self.device
-                .destroy_pipeline_layout(self.pipeline_layout, None);
-            self.device.destroy_render_pass(self.render_pass, None);
-            for &image_view in self.swapchain_imageviews.iter() {
-                self.device.destroy_image_view(image_view, None);
-            }
-            self.swapchain_loader
-                .destroy_swapchain(self.swapchain, None);
+                .free_command_buffers(self.command_pool.raw(), &self.command_buffers);
+        }
+        self.command_buffers.clear();
+        // the views of the swapchain images go before the swapchain.
+        self.swapchain_framebuffers.clear();
+        self.swapchain_imageviews.clear();
+        unsafe {
+            self.render_target.destroy(&self.device);
         }
     }
-}
 
-impl Drop for VulkanApp {
-    fn drop(&mut self) {
+    fn wait_device_idle(&self) {
         unsafe {
-            for i in 0..MAX_FRAMES_IN_FLIGHT {
-                self.device
-                    .destroy_semaphore(self.image_available_semaphores[i], None);
-                self.device
-                    .destroy_semaphore(self.render_finished_semaphores[i], None);
-                self.device.destroy_fence(self.in_flight_fences[i], None);
-            }
-
-            self.cleanup_swapchain();
-
-            self.device.destroy_command_pool(self.command_pool, None);
+            self.device
+                .device_wait_idle()
+                .expect("Failed to wait device idle!")
+        };
+    }
 
-            self.device.destroy_device(None);
-            self.surface_loader.destroy_surface(self.surface, None);
+    fn resize_framebuffer(&mut self) {
+        self.is_framebuffer_resized = true;
+    }
 
-            if VALIDATION.is_enable {
-                self.debug_utils_loader
-                    .destroy_debug_utils_messenger(self.debug_merssager, None);
-            }
-            self.instance.destroy_instance(None);
-        }
+    fn window_ref(&self) -> Option<&winit::window::Window> {
+        self.window.as_ref()
     }
-}
 
-// Fix content -------------------------------------------------------------------------------
-impl VulkanApp {
-    pub fn main_loop(mut self, event_loop: EventLoop<()>) {
-        event_loop.run(move |event, _, control_flow| match event {
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                WindowEvent::KeyboardInput { input, .. } => match input {
-                    KeyboardInput {
-                        virtual_keycode,
-                        state,
-                        ..
-                    } => match (virtual_keycode, state) {
-                        (Some(VirtualKeyCode::Escape), ElementState::Pressed) => {
-                            *control_flow = ControlFlow::Exit
-                        }
-                        _ => {}
-                    },
-                },
-                _ => {}
-            },
-            Event::MainEventsCleared => {
-                self.window.request_redraw();
-            }
-            Event::RedrawRequested(_window_id) => {
-                self.draw_frame();
-            }
-            Event::LoopDestroyed => {
-                unsafe {
-                    self.device
-                        .device_wait_idle()
-                        .expect("Failed to wait device idle!")
-                };
-            }
-            _ => (),
-        })
+    fn render_target_mut(&mut self) -> &mut RenderTarget {
+        &mut self.render_target
     }
 }
 
 fn main() {
-    let event_loop = EventLoop::new();
+    let program_proc = ProgramProc::new();
+    let vulkan_app = VulkanApp16::new(&program_proc).expect("Failed to initialize the app!");
 
-    let vulkan_app = VulkanApp::new(&event_loop);
-    vulkan_app.main_loop(event_loop);
+    program_proc.main_loop(vulkan_app);
 }
 // -------------------------------------------------------------------------------------------
@@ -1,26 +1,21 @@
 use vulkan_tutorial_rust::{
-    utility, // the mod define some fixed functions that have been learned before.
     utility::constants::*,
     utility::debug::*,
+    utility::error::VkTutorialResult,
+    utility::pipeline_cache::PipelineCache,
+    utility::render_target::RenderTarget,
     utility::share,
     utility::structures::*,
+    utility::window::{
+        ProgramProc,
+        VulkanApp,
+    },
 };
 
 use ash::version::DeviceV1_0;
 use ash::version::InstanceV1_0;
 use ash::vk;
 use memoffset::offset_of;
-use winit::event::{
-    ElementState,
-    Event,
-    KeyboardInput,
-    VirtualKeyCode,
-    WindowEvent,
-};
-use winit::event_loop::{
-    ControlFlow,
-    EventLoop,
-};
 
 use std::ffi::CString;
 use std::ptr;
@@ -76,8 +71,8 @@ const VERTICES_DATA: [Vertex; 3] = [
     },
 ];
 
-struct VulkanApp {
-    window: winit::window::Window,
+struct VulkanApp18 {
+    window: Option<winit::window::Window>,
 
     // vulkan stuff
     _entry: ash::Entry,
@@ -94,8 +89,7 @@ struct VulkanApp {
     graphics_queue: vk::Queue,
     present_queue: vk::Queue,
 
-    swapchain_loader: ash::extensions::khr::Swapchain,
-    swapchain: vk::SwapchainKHR,
+    render_target: RenderTarget,
     swapchain_images: Vec<vk::Image>,
     swapchain_format: vk::Format,
     swapchain_extent: vk::Extent2D,
@@ -105,6 +99,7 @@ struct VulkanApp {
     render_pass: vk::RenderPass,
     pipeline_layout: vk::PipelineLayout,
     graphics_pipeline: vk::Pipeline,
+    pipeline_cache: PipelineCache,
 
     vertex_buffer: vk::Buffer,
     vertex_buffer_memory: vk::DeviceMemory,
@@ -120,10 +115,9 @@ struct VulkanApp {
     is_framebuffer_resized: bool,
 }
 
-impl VulkanApp {
-    pub fn new(event_loop: &winit::event_loop::EventLoop<()>) -> VulkanApp {
-        let window =
-            utility::window::init_window(event_loop, WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
+impl VulkanApp18 {
+    pub fn new(program_proc: &ProgramProc) -> VkTutorialResult<VulkanApp18> {
+        let window = program_proc.init_window(WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
 
         // init vulkan stuff
         let entry = ash::Entry::new().unwrap();
@@ -132,53 +126,62 @@ impl VulkanApp {
             WINDOW_TITLE,
             VALIDATION.is_enable,
             &VALIDATION.required_validation_layers.to_vec(),
-        );
-        let surface_stuff =
-            share::create_surface(&entry, &instance, &window, WINDOW_WIDTH, WINDOW_HEIGHT);
+            window.as_ref(),
+        )?;
+        let surface_stuff = share::create_surface(
+            &entry,
+            &instance,
+            window.as_ref(),
+            WINDOW_WIDTH,
+            WINDOW_HEIGHT,
+        )?;
         let (debug_utils_loader, debug_merssager) =
             setup_debug_utils(VALIDATION.is_enable, &entry, &instance);
         let physical_device =
-            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS);
+            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS)?;
         let (device, queue_family) = share::create_logical_device(
             &instance,
             physical_device,
             &VALIDATION,
             &DEVICE_EXTENSIONS,
             &surface_stuff,
-        );
+        )?;
         let graphics_queue =
             unsafe { device.get_device_queue(queue_family.graphics_family.unwrap(), 0) };
         let present_queue =
             unsafe { device.get_device_queue(queue_family.present_family.unwrap(), 0) };
-        let swapchain_stuff = share::create_swapchain(
+        let swapchain_stuff = share::create_render_target(
             &instance,
             &device,
             physical_device,
-            &window,
+            window.as_ref(),
             &surface_stuff,
             &queue_family,
-        );
+            graphics_queue,
+        )?;
         let swapchain_imageviews = share::v1::create_image_views(
             &device,
             swapchain_stuff.swapchain_format,
             &swapchain_stuff.swapchain_images,
-        );
-        let render_pass = share::v1::create_render_pass(&device, swapchain_stuff.swapchain_format);
-        let (graphics_pipeline, pipeline_layout) = VulkanApp::create_graphics_pipeline(
+        )?;
+        let render_pass = share::v1::create_render_pass(&device, swapchain_stuff.swapchain_format)?;
+        let pipeline_cache = PipelineCache::new(&instance, physical_device, &device)?;
+        let (graphics_pipeline, pipeline_layout) = VulkanApp18::create_graphics_pipeline(
             &device,
+            pipeline_cache.cache,
             render_pass,
             swapchain_stuff.swapchain_extent,
-        );
+        )?;
         let swapchain_framebuffers = share::v1::create_framebuffers(
             &device,
             render_pass,
             &swapchain_imageviews,
             swapchain_stuff.swapchain_extent,
-        );
-        let command_pool = share::v1::create_command_pool(&device, &queue_family);
+        )?;
+        let command_pool = share::v1::create_command_pool(&device, &queue_family)?;
         let (vertex_buffer, vertex_buffer_memory) =
-            VulkanApp::create_vertex_buffer(&instance, &device, physical_device);
-        let command_buffers = VulkanApp::create_command_buffers(
+            VulkanApp18::create_vertex_buffer(&instance, &device, physical_device);
+        let command_buffers = VulkanApp18::create_command_buffers(
             &device,
             command_pool,
             graphics_pipeline,
@@ -187,10 +190,10 @@ impl VulkanApp {
             swapchain_stuff.swapchain_extent,
             vertex_buffer,
         );
-        let sync_ojbects = share::v1::create_sync_objects(&device, MAX_FRAMES_IN_FLIGHT);
+        let sync_ojbects = share::v1::create_sync_objects(&device, MAX_FRAMES_IN_FLIGHT)?;
 
         // cleanup(); the 'drop' function will take care of it.
-        VulkanApp {
+        Ok(VulkanApp18 {
             // winit stuff
             window,
 
@@ -209,8 +212,7 @@ impl VulkanApp {
             graphics_queue,
             present_queue,
 
-            swapchain_loader: swapchain_stuff.swapchain_loader,
-            swapchain: swapchain_stuff.swapchain,
+            render_target: swapchain_stuff.render_target,
             swapchain_format: swapchain_stuff.swapchain_format,
             swapchain_images: swapchain_stuff.swapchain_images,
             swapchain_extent: swapchain_stuff.swapchain_extent,
@@ -220,6 +222,7 @@ impl VulkanApp {
             pipeline_layout,
             render_pass,
             graphics_pipeline,
+            pipeline_cache,
 
             vertex_buffer,
             vertex_buffer_memory,
@@ -233,7 +236,7 @@ impl VulkanApp {
             current_frame: 0,
 
             is_framebuffer_resized: false,
-        }
+        })
     }
 
     fn create_vertex_buffer(
@@ -263,7 +266,7 @@ impl VulkanApp {
             unsafe { instance.get_physical_device_memory_properties(physical_device) };
         let required_memory_flags: vk::MemoryPropertyFlags =
             vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
-        let memory_type = VulkanApp::find_memory_type(
+        let memory_type = VulkanApp18::find_memory_type(
             mem_requirements.memory_type_bits,
             required_memory_flags,
             mem_properties,
@@ -413,20 +416,21 @@ impl VulkanApp {
 }
 
 // Fix content -------------------------------------------------------------------------------
-impl VulkanApp {
+impl VulkanApp18 {
     fn create_graphics_pipeline(
         device: &ash::Device,
+        pipeline_cache: vk::PipelineCache,
         render_pass: vk::RenderPass,
         swapchain_extent: vk::Extent2D,
-    ) -> (vk::Pipeline, vk::PipelineLayout) {
+    ) -> VkTutorialResult<(vk::Pipeline, vk::PipelineLayout)> {
         let vert_shader_module = share::create_shader_module(
             device,
             include_bytes!("../../shaders/spv/17-shader-vertexbuffer.vert.spv").to_vec(),
-        );
+        )?;
         let frag_shader_module = share::create_shader_module(
             device,
             include_bytes!("../../shaders/spv/17-shader-vertexbuffer.frag.spv").to_vec(),
-        );
+        )?;
 
         let main_function_name = CString::new("main").unwrap(); // the beginning function name in shader code.
 
@@ -612,11 +616,7 @@ impl VulkanApp {
 
         let graphics_pipelines = unsafe {
             device
-                .create_graphics_pipelines(
-                    vk::PipelineCache::null(),
-                    &graphic_pipeline_create_infos,
-                    None,
-                )
+                .create_graphics_pipelines(pipeline_cache, &graphic_pipeline_create_infos, None)
                 .expect("Failed to create Graphics Pipeline!.")
         };
 
@@ -625,10 +625,46 @@ impl VulkanApp {
             device.destroy_shader_module(frag_shader_module, None);
         }
 
-        (graphics_pipelines[0], pipeline_layout)
+        Ok((graphics_pipelines[0], pipeline_layout))
     }
+}
 
-    fn draw_frame(&mut self) {
+impl Drop for VulkanApp18 {
+    fn drop(&mut self) {
+        unsafe {
+            for i in 0..MAX_FRAMES_IN_FLIGHT {
+                self.device
+                    .destroy_semaphore(self.image_available_semaphores[i], None);
+                self.device
+                    .destroy_semaphore(self.render_finished_semaphores[i], None);
+                self.device.destroy_fence(self.in_flight_fences[i], None);
+            }
+
+            self.cleanup_swapchain();
+
+            self.device.destroy_buffer(self.vertex_buffer, None);
+            self.device.free_memory(self.vertex_buffer_memory, None);
+
+            self.device.destroy_command_pool(self.command_pool, None);
+
+            self.pipeline_cache.destroy();
+
+            self.device.destroy_device(None);
+            if self.window.is_some() {
+                self.surface_loader.destroy_surface(self.surface, None);
+            }
+
+            if VALIDATION.is_enable {
+                self.debug_utils_loader
+                    .destroy_debug_utils_messenger(self.debug_merssager, None);
+            }
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+impl VulkanApp for VulkanApp18 {
+    fn draw_frame(&mut self, _delta_time: f32) -> VkTutorialResult<()> {
         let wait_fences = [self.in_flight_fences[self.current_frame]];
 
         unsafe {
@@ -638,24 +674,28 @@ impl VulkanApp {
         }
 
         let (image_index, _is_sub_optimal) = unsafe {
-            let result = self.swapchain_loader.acquire_next_image(
-                self.swapchain,
+            let result = self.render_target.acquire_next_image(
                 std::u64::MAX,
                 self.image_available_semaphores[self.current_frame],
-                vk::Fence::null(),
             );
             match result {
                 Ok(image_index) => image_index,
                 Err(vk_result) => match vk_result {
                     vk::Result::ERROR_OUT_OF_DATE_KHR => {
-                        self.recreate_swapchain();
-                        return;
+                        return self.recreate_swapchain();
                     }
                     _ => panic!("Failed to acquire Swap Chain Image!"),
                 },
             }
         };
 
+        let mut command_buffers = vec![self.command_buffers[image_index as usize]];
+        command_buffers.extend(self.render_target.record_capture(
+            self.command_pool,
+            image_index,
+            self.in_flight_fences[self.current_frame],
+        )?);
+
         let wait_semaphores = [self.image_available_semaphores[self.current_frame]];
         let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
         let signal_semaphores = [self.render_finished_semaphores[self.current_frame]];
@@ -666,8 +706,8 @@ impl VulkanApp {
             wait_semaphore_count: wait_semaphores.len() as u32,
             p_wait_semaphores: wait_semaphores.as_ptr(),
             p_wait_dst_stage_mask: wait_stages.as_ptr(),
-            command_buffer_count: 1,
-            p_command_buffers: &self.command_buffers[image_index as usize],
+            command_buffer_count: command_buffers.len() as u32,
+            p_command_buffers: command_buffers.as_ptr(),
             signal_semaphore_count: signal_semaphores.len() as u32,
             p_signal_semaphores: signal_semaphores.as_ptr(),
         }];
@@ -686,22 +726,9 @@ impl VulkanApp {
                 .expect("Failed to execute queue submit.");
         }
 
-        let swapchains = [self.swapchain];
-
-        let present_info = vk::PresentInfoKHR {
-            s_type: vk::StructureType::PRESENT_INFO_KHR,
-            p_next: ptr::null(),
-            wait_semaphore_count: 1,
-            p_wait_semaphores: signal_semaphores.as_ptr(),
-            swapchain_count: 1,
-            p_swapchains: swapchains.as_ptr(),
-            p_image_indices: &image_index,
-            p_results: ptr::null_mut(),
-        };
-
         let result = unsafe {
-            self.swapchain_loader
-                .queue_present(self.present_queue, &present_info)
+            self.render_target
+                .queue_present(self.present_queue, &signal_semaphores, image_index)
         };
 
         let is_resized = match result {
@@ -713,13 +740,15 @@ impl VulkanApp {
         };
         if is_resized {
             self.is_framebuffer_resized = false;
-            self.recreate_swapchain();
+            self.recreate_swapchain()?;
         }
 
         self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+
+        Ok(())
     }
 
-    fn recreate_swapchain(&mut self) {
+    fn recreate_swapchain(&mut self) -> VkTutorialResult<()> {
         // parameters -------------
         let surface_suff = SurfaceStuff {
             surface_loader: self.surface_loader.clone(),
@@ -736,16 +765,16 @@ impl VulkanApp {
         };
         self.cleanup_swapchain();
 
-        let swapchain_stuff = share::create_swapchain(
+        let swapchain_stuff = share::create_render_target(
             &self.instance,
             &self.device,
             self.physical_device,
-            &self.window,
+            self.window.as_ref(),
             &surface_suff,
             &self.queue_family,
-        );
-        self.swapchain_loader = swapchain_stuff.swapchain_loader;
-        self.swapchain = swapchain_stuff.swapchain;
+            self.graphics_queue,
+        )?;
+        self.render_target = swapchain_stuff.render_target;
         self.swapchain_images = swapchain_stuff.swapchain_images;
         self.swapchain_format = swapchain_stuff.swapchain_format;
         self.swapchain_extent = swapchain_stuff.swapchain_extent;
@@ -754,13 +783,14 @@ impl VulkanApp {
             &self.device,
             self.swapchain_format,
             &self.swapchain_images,
-        );
-        self.render_pass = share::v1::create_render_pass(&self.device, self.swapchain_format);
-        let (graphics_pipeline, pipeline_layout) = VulkanApp::create_graphics_pipeline(
+        )?;
+        self.render_pass = share::v1::create_render_pass(&self.device, self.swapchain_format)?;
+        let (graphics_pipeline, pipeline_layout) = VulkanApp18::create_graphics_pipeline(
             &self.device,
+            self.pipeline_cache.cache,
             self.render_pass,
             swapchain_stuff.swapchain_extent,
-        );
+        )?;
         self.graphics_pipeline = graphics_pipeline;
         self.pipeline_layout = pipeline_layout;
 
@@ -769,8 +799,8 @@ impl VulkanApp {
             self.render_pass,
             &self.swapchain_imageviews,
             self.swapchain_extent,
-        );
-        self.command_buffers = VulkanApp::create_command_buffers(
+        )?;
+        self.command_buffers = VulkanApp18::create_command_buffers(
             &self.device,
             self.command_pool,
             self.graphics_pipeline,
@@ -779,9 +809,11 @@ impl VulkanApp {
             self.swapchain_extent,
             self.vertex_buffer,
         );
+
+        Ok(())
     }
 
-    fn cleanup_swapchain(&self) {
+    fn cleanup_swapchain(&mut self) {
         unsafe {
             self.device
                 .free_command_buffers(self.command_pool, &self.command_buffers);
@@ -795,90 +827,35 @@ impl VulkanApp {
             for &image_view in self.swapchain_imageviews.iter() {
                 self.device.destroy_image_view(image_view, None);
             }
-            self.swapchain_loader
-                .destroy_swapchain(self.swapchain, None);
+            self.render_target.destroy(&self.device);
         }
     }
-}
 
-impl Drop for VulkanApp {
-    fn drop(&mut self) {
+    fn wait_device_idle(&self) {
         unsafe {
-            for i in 0..MAX_FRAMES_IN_FLIGHT {
-                self.device
-                    .destroy_semaphore(self.image_available_semaphores[i], None);
-                self.device
-                    .destroy_semaphore(self.render_finished_semaphores[i], None);
-                self.device.destroy_fence(self.in_flight_fences[i], None);
-            }
-
-            self.cleanup_swapchain();
-
-            self.device.destroy_buffer(self.vertex_buffer, None);
-            self.device.free_memory(self.vertex_buffer_memory, None);
-
-            self.device.destroy_command_pool(self.command_pool, None);
-
-            self.device.destroy_device(None);
-            self.surface_loader.destroy_surface(self.surface, None);
+            self.device
+                .device_wait_idle()
+                .expect("Failed to wait device idle!")
+        };
+    }
 
-            if VALIDATION.is_enable {
-                self.debug_utils_loader
-                    .destroy_debug_utils_messenger(self.debug_merssager, None);
-            }
-            self.instance.destroy_instance(None);
-        }
+    fn resize_framebuffer(&mut self) {
+        self.is_framebuffer_resized = true;
     }
-}
 
-impl VulkanApp {
-    pub fn main_loop(mut self, event_loop: EventLoop<()>) {
-        let mut tick_counter = utility::fps_limiter::FPSLimiter::new();
-
-        event_loop.run(move |event, _, control_flow| match event {
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                WindowEvent::KeyboardInput { input, .. } => match input {
-                    KeyboardInput {
-                        virtual_keycode,
-                        state,
-                        ..
-                    } => match (virtual_keycode, state) {
-                        (Some(VirtualKeyCode::Escape), ElementState::Pressed) => {
-                            *control_flow = ControlFlow::Exit
-                        }
-                        _ => {}
-                    },
-                },
-                _ => {}
-            },
-            Event::MainEventsCleared => {
-                self.window.request_redraw();
-            }
-            Event::RedrawRequested(_window_id) => {
-                self.draw_frame();
+    fn window_ref(&self) -> Option<&winit::window::Window> {
+        self.window.as_ref()
+    }
 
-                tick_counter.tick_frame();
-                if IS_PAINT_FPS_COUNTER {
-                    print!("FPS: {}\r", tick_counter.fps());
-                }
-            }
-            Event::LoopDestroyed => {
-                unsafe {
-                    self.device
-                        .device_wait_idle()
-                        .expect("Failed to wait device idle!")
-                };
-            }
-            _ => (),
-        })
+    fn render_target_mut(&mut self) -> &mut RenderTarget {
+        &mut self.render_target
     }
 }
 
 fn main() {
-    let event_loop = EventLoop::new();
+    let program_proc = ProgramProc::new();
+    let vulkan_app = VulkanApp18::new(&program_proc).expect("Failed to initialize the app!");
 
-    let vulkan_app = VulkanApp::new(&event_loop);
-    vulkan_app.main_loop(event_loop);
+    program_proc.main_loop(vulkan_app);
 }
 // -------------------------------------------------------------------------------------------
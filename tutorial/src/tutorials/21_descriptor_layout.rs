@@ -1,7 +1,10 @@
 use vulkan_tutorial_rust::{
-    utility, // the mod define some fixed functions that have been learned before.
     utility::constants::*,
     utility::debug::*,
+    utility::error::VkTutorialResult,
+    utility::pipeline_cache::PipelineCache,
+    utility::reflection::ShaderReflection,
+    utility::render_target::RenderTarget,
     utility::share,
     utility::structures::*,
     utility::window::{
@@ -35,7 +38,7 @@ struct UniformBufferObject {
 }
 
 struct VulkanApp21 {
-    window: winit::window::Window,
+    window: Option<winit::window::Window>,
 
     // vulkan stuff
     _entry: ash::Entry,
@@ -52,8 +55,7 @@ struct VulkanApp21 {
     graphics_queue: vk::Queue,
     present_queue: vk::Queue,
 
-    swapchain_loader: ash::extensions::khr::Swapchain,
-    swapchain: vk::SwapchainKHR,
+    render_target: RenderTarget,
     swapchain_images: Vec<vk::Image>,
     swapchain_format: vk::Format,
     swapchain_extent: vk::Extent2D,
@@ -64,6 +66,7 @@ struct VulkanApp21 {
     ubo_layout: vk::DescriptorSetLayout,
     pipeline_layout: vk::PipelineLayout,
     graphics_pipeline: vk::Pipeline,
+    pipeline_cache: PipelineCache,
 
     vertex_buffer: vk::Buffer,
     vertex_buffer_memory: vk::DeviceMemory,
@@ -85,9 +88,8 @@ struct VulkanApp21 {
 }
 
 impl VulkanApp21 {
-    pub fn new(event_loop: &winit::event_loop::EventLoop<()>) -> VulkanApp21 {
-        let window =
-            utility::window::init_window(event_loop, WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
+    pub fn new(program_proc: &ProgramProc) -> VkTutorialResult<VulkanApp21> {
+        let window = program_proc.init_window(WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
 
         // init vulkan stuff
         let entry = ash::Entry::new().unwrap();
@@ -96,13 +98,19 @@ impl VulkanApp21 {
             WINDOW_TITLE,
             VALIDATION.is_enable,
             &VALIDATION.required_validation_layers.to_vec(),
-        );
-        let surface_stuff =
-            share::create_surface(&entry, &instance, &window, WINDOW_WIDTH, WINDOW_HEIGHT);
+            window.as_ref(),
+        )?;
+        let surface_stuff = share::create_surface(
+            &entry,
+            &instance,
+            window.as_ref(),
+            WINDOW_WIDTH,
+            WINDOW_HEIGHT,
+        )?;
         let (debug_utils_loader, debug_merssager) =
             setup_debug_utils(VALIDATION.is_enable, &entry, &instance);
         let physical_device =
-            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS);
+            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS)?;
         let physical_device_memory_properties =
             unsafe { instance.get_physical_device_memory_properties(physical_device) };
         let (device, queue_family) = share::create_logical_device(
@@ -111,58 +119,61 @@ impl VulkanApp21 {
             &VALIDATION,
             &DEVICE_EXTENSIONS,
             &surface_stuff,
-        );
+        )?;
         let graphics_queue =
             unsafe { device.get_device_queue(queue_family.graphics_family.unwrap(), 0) };
         let present_queue =
             unsafe { device.get_device_queue(queue_family.present_family.unwrap(), 0) };
-        let swapchain_stuff = share::create_swapchain(
+        let swapchain_stuff = share::create_render_target(
             &instance,
             &device,
             physical_device,
-            &window,
+            window.as_ref(),
             &surface_stuff,
             &queue_family,
-        );
+            graphics_queue,
+        )?;
         let swapchain_imageviews = share::v1::create_image_views(
             &device,
             swapchain_stuff.swapchain_format,
             &swapchain_stuff.swapchain_images,
-        );
-        let render_pass = share::v1::create_render_pass(&device, swapchain_stuff.swapchain_format);
+        )?;
+        let render_pass = share::v1::create_render_pass(&device, swapchain_stuff.swapchain_format)?;
         let ubo_layout = VulkanApp21::create_descriptor_set_layout(&device);
+        let pipeline_cache = PipelineCache::new(&instance, physical_device, &device)?;
         let (graphics_pipeline, pipeline_layout) = VulkanApp21::create_graphics_pipeline(
             &device,
+            pipeline_cache.cache,
             render_pass,
             swapchain_stuff.swapchain_extent,
             ubo_layout,
-        );
+        )?;
         let swapchain_framebuffers = share::v1::create_framebuffers(
             &device,
             render_pass,
             &swapchain_imageviews,
             swapchain_stuff.swapchain_extent,
-        );
-        let command_pool = share::v1::create_command_pool(&device, &queue_family);
+        )?;
+        let command_pool = share::v1::create_command_pool(&device, &queue_family)?;
         let (vertex_buffer, vertex_buffer_memory) = share::v1::create_vertex_buffer(
             &device,
             &physical_device_memory_properties,
             command_pool,
             graphics_queue,
             &RECT_VERTICES_DATA,
-        );
+        )?;
         let (index_buffer, index_buffer_memory) = share::v1::create_index_buffer(
             &device,
             &physical_device_memory_properties,
             command_pool,
             graphics_queue,
             &RECT_INDICES_DATA,
-        );
+        )?;
         let (uniform_buffers, uniform_buffers_memory) = VulkanApp21::create_uniform_buffers(
             &device,
             &physical_device_memory_properties,
             swapchain_stuff.swapchain_images.len(),
-        );
+        )?;
         let command_buffers = VulkanApp21::create_command_buffers(
             &device,
             command_pool,
@@ -173,10 +184,10 @@ impl VulkanApp21 {
             vertex_buffer,
             index_buffer,
         );
-        let sync_ojbects = share::v1::create_sync_objects(&device, MAX_FRAMES_IN_FLIGHT);
+        let sync_ojbects = share::v1::create_sync_objects(&device, MAX_FRAMES_IN_FLIGHT)?;
 
         // cleanup(); the 'drop' function will take care of it.
-        VulkanApp21 {
+        Ok(VulkanApp21 {
             // winit stuff
             window,
 
@@ -195,8 +206,7 @@ impl VulkanApp21 {
             graphics_queue,
             present_queue,
 
-            swapchain_loader: swapchain_stuff.swapchain_loader,
-            swapchain: swapchain_stuff.swapchain,
+            render_target: swapchain_stuff.render_target,
             swapchain_format: swapchain_stuff.swapchain_format,
             swapchain_images: swapchain_stuff.swapchain_images,
             swapchain_extent: swapchain_stuff.swapchain_extent,
@@ -207,6 +217,7 @@ impl VulkanApp21 {
             ubo_layout,
             render_pass,
             graphics_pipeline,
+            pipeline_cache,
 
             vertex_buffer,
             vertex_buffer_memory,
@@ -225,7 +236,7 @@ impl VulkanApp21 {
             current_frame: 0,
 
             is_framebuffer_resized: false,
-        }
+        })
     }
 
     fn create_descriptor_set_layout(device: &ash::Device) -> vk::DescriptorSetLayout {
@@ -256,7 +267,7 @@ impl VulkanApp21 {
         device: &ash::Device,
         device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
         swapchain_image_count: usize,
-    ) -> (Vec<vk::Buffer>, Vec<vk::DeviceMemory>) {
+    ) -> VkTutorialResult<(Vec<vk::Buffer>, Vec<vk::DeviceMemory>)> {
         let buffer_size = std::mem::size_of::<UniformBufferObject>();
 
         let mut uniform_buffers = vec![];
@@ -269,12 +280,12 @@ impl VulkanApp21 {
                 vk::BufferUsageFlags::UNIFORM_BUFFER,
                 vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
                 device_memory_properties,
-            );
+            )?;
             uniform_buffers.push(uniform_buffer);
             uniform_buffers_memory.push(uniform_buffer_memory);
         }
 
-        (uniform_buffers, uniform_buffers_memory)
+        Ok((uniform_buffers, uniform_buffers_memory))
     }
 
     fn update_uniform_buffer(&self, current_image: usize, delta_time: f32) {
@@ -315,18 +326,15 @@ impl VulkanApp21 {
 
     fn create_graphics_pipeline(
         device: &ash::Device,
+        pipeline_cache: vk::PipelineCache,
         render_pass: vk::RenderPass,
         swapchain_extent: vk::Extent2D,
         ubo_set_layout: vk::DescriptorSetLayout,
-    ) -> (vk::Pipeline, vk::PipelineLayout) {
-        let vert_shader_module = share::create_shader_module(
-            device,
-            include_bytes!("../../shaders/spv/21-shader-ubo.vert.spv").to_vec(),
-        );
-        let frag_shader_module = share::create_shader_module(
-            device,
-            include_bytes!("../../shaders/spv/21-shader-ubo.frag.spv").to_vec(),
-        );
+    ) -> VkTutorialResult<(vk::Pipeline, vk::PipelineLayout)> {
+        let vert_shader_code = include_bytes!("../../shaders/spv/21-shader-ubo.vert.spv");
+        let vert_shader_module = share::create_shader_module(device, vert_shader_code.to_vec())?;
+        let frag_shader_code = include_bytes!("../../shaders/spv/21-shader-ubo.frag.spv");
+        let frag_shader_module = share::create_shader_module(device, frag_shader_code.to_vec())?;
 
         let main_function_name = CString::new("main").unwrap(); // the beginning function name in shader code.
 
@@ -355,6 +363,8 @@ impl VulkanApp21 {
 
         let binding_description = VertexV1::get_binding_description();
         let attribute_description = VertexV1::get_attribute_descriptions();
+        // the vertex type must provide all the inputs declared by the vertex shader.
+        ShaderReflection::new(vert_shader_code)?.validate_vertex_input(&attribute_description)?;
 
         let vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo {
             s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
@@ -514,11 +524,7 @@ impl VulkanApp21 {
 
         let graphics_pipelines = unsafe {
             device
-                .create_graphics_pipelines(
-                    vk::PipelineCache::null(),
-                    &graphic_pipeline_create_infos,
-                    None,
-                )
+                .create_graphics_pipelines(pipeline_cache, &graphic_pipeline_create_infos, None)
                 .expect("Failed to create Graphics Pipeline!.")
         };
 
@@ -527,7 +533,7 @@ impl VulkanApp21 {
             device.destroy_shader_module(frag_shader_module, None);
         }
 
-        (graphics_pipelines[0], pipeline_layout)
+        Ok((graphics_pipelines[0], pipeline_layout))
     }
 }
 
@@ -657,8 +663,12 @@ impl Drop for VulkanApp21 {
 
             self.device.destroy_command_pool(self.command_pool, None);
 
+            self.pipeline_cache.destroy();
+
             self.device.destroy_device(None);
-            self.surface_loader.destroy_surface(self.surface, None);
+            if self.window.is_some() {
+                self.surface_loader.destroy_surface(self.surface, None);
+            }
 
             if VALIDATION.is_enable {
                 self.debug_utils_loader
@@ -670,7 +680,7 @@ impl Drop for VulkanApp21 {
 }
 
 impl VulkanApp for VulkanApp21 {
-    fn draw_frame(&mut self, delta_time: f32) {
+    fn draw_frame(&mut self, delta_time: f32) -> VkTutorialResult<()> {
         let wait_fences = [self.in_flight_fences[self.current_frame]];
 
         unsafe {
@@ -680,18 +690,15 @@ impl VulkanApp for VulkanApp21 {
         }
 
         let (image_index, _is_sub_optimal) = unsafe {
-            let result = self.swapchain_loader.acquire_next_image(
-                self.swapchain,
+            let result = self.render_target.acquire_next_image(
                 std::u64::MAX,
                 self.image_available_semaphores[self.current_frame],
-                vk::Fence::null(),
             );
             match result {
                 Ok(image_index) => image_index,
                 Err(vk_result) => match vk_result {
                     vk::Result::ERROR_OUT_OF_DATE_KHR => {
-                        self.recreate_swapchain();
-                        return;
+                        return self.recreate_swapchain();
                     }
                     _ => panic!("Failed to acquire Swap Chain Image!"),
                 },
@@ -700,6 +707,13 @@ impl VulkanApp for VulkanApp21 {
 
         self.update_uniform_buffer(image_index as usize, delta_time);
 
+        let mut command_buffers = vec![self.command_buffers[image_index as usize]];
+        command_buffers.extend(self.render_target.record_capture(
+            self.command_pool,
+            image_index,
+            self.in_flight_fences[self.current_frame],
+        )?);
+
         let wait_semaphores = [self.image_available_semaphores[self.current_frame]];
         let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
         let signal_semaphores = [self.render_finished_semaphores[self.current_frame]];
@@ -710,8 +724,8 @@ impl VulkanApp for VulkanApp21 {
             wait_semaphore_count: wait_semaphores.len() as u32,
             p_wait_semaphores: wait_semaphores.as_ptr(),
             p_wait_dst_stage_mask: wait_stages.as_ptr(),
-            command_buffer_count: 1,
-            p_command_buffers: &self.command_buffers[image_index as usize],
+            command_buffer_count: command_buffers.len() as u32,
+            p_command_buffers: command_buffers.as_ptr(),
             signal_semaphore_count: signal_semaphores.len() as u32,
             p_signal_semaphores: signal_semaphores.as_ptr(),
         }];
@@ -730,22 +744,9 @@ impl VulkanApp for VulkanApp21 {
                 .expect("Failed to execute queue submit.");
         }
 
-        let swapchains = [self.swapchain];
-
-        let present_info = vk::PresentInfoKHR {
-            s_type: vk::StructureType::PRESENT_INFO_KHR,
-            p_next: ptr::null(),
-            wait_semaphore_count: 1,
-            p_wait_semaphores: signal_semaphores.as_ptr(),
-            swapchain_count: 1,
-            p_swapchains: swapchains.as_ptr(),
-            p_image_indices: &image_index,
-            p_results: ptr::null_mut(),
-        };
-
         let result = unsafe {
-            self.swapchain_loader
-                .queue_present(self.present_queue, &present_info)
+            self.render_target
+                .queue_present(self.present_queue, &signal_semaphores, image_index)
         };
 
         let is_resized = match result {
@@ -757,13 +758,15 @@ impl VulkanApp for VulkanApp21 {
         };
         if is_resized {
             self.is_framebuffer_resized = false;
-            self.recreate_swapchain();
+            self.recreate_swapchain()?;
         }
 
         self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+
+        Ok(())
     }
 
-    fn recreate_swapchain(&mut self) {
+    fn recreate_swapchain(&mut self) -> VkTutorialResult<()> {
         // parameters -------------
         let surface_suff = SurfaceStuff {
             surface_loader: self.surface_loader.clone(),
@@ -780,16 +783,16 @@ impl VulkanApp for VulkanApp21 {
         };
         self.cleanup_swapchain();
 
-        let swapchain_stuff = share::create_swapchain(
+        let swapchain_stuff = share::create_render_target(
             &self.instance,
             &self.device,
             self.physical_device,
-            &self.window,
+            self.window.as_ref(),
             &surface_suff,
             &self.queue_family,
-        );
-        self.swapchain_loader = swapchain_stuff.swapchain_loader;
-        self.swapchain = swapchain_stuff.swapchain;
+            self.graphics_queue,
+        )?;
+        self.render_target = swapchain_stuff.render_target;
         self.swapchain_images = swapchain_stuff.swapchain_images;
         self.swapchain_format = swapchain_stuff.swapchain_format;
         self.swapchain_extent = swapchain_stuff.swapchain_extent;
@@ -798,14 +801,15 @@ impl VulkanApp for VulkanApp21 {
             &self.device,
             self.swapchain_format,
             &self.swapchain_images,
-        );
-        self.render_pass = share::v1::create_render_pass(&self.device, self.swapchain_format);
+        )?;
+        self.render_pass = share::v1::create_render_pass(&self.device, self.swapchain_format)?;
         let (graphics_pipeline, pipeline_layout) = VulkanApp21::create_graphics_pipeline(
             &self.device,
+            self.pipeline_cache.cache,
             self.render_pass,
             swapchain_stuff.swapchain_extent,
             self.ubo_layout,
-        );
+        )?;
         self.graphics_pipeline = graphics_pipeline;
         self.pipeline_layout = pipeline_layout;
 
@@ -814,7 +818,7 @@ impl VulkanApp for VulkanApp21 {
             self.render_pass,
             &self.swapchain_imageviews,
             self.swapchain_extent,
-        );
+        )?;
         self.command_buffers = VulkanApp21::create_command_buffers(
             &self.device,
             self.command_pool,
@@ -825,9 +829,11 @@ impl VulkanApp for VulkanApp21 {
             self.vertex_buffer,
             self.index_buffer,
         );
+
+        Ok(())
     }
 
-    fn cleanup_swapchain(&self) {
+    fn cleanup_swapchain(&mut self) {
         unsafe {
             self.device
                 .free_command_buffers(self.command_pool, &self.command_buffers);
@@ -841,8 +847,7 @@ impl VulkanApp for VulkanApp21 {
             for &image_view in self.swapchain_imageviews.iter() {
                 self.device.destroy_image_view(image_view, None);
             }
-            self.swapchain_loader
-                .destroy_swapchain(self.swapchain, None);
+            self.render_target.destroy(&self.device);
         }
     }
 
@@ -858,14 +863,18 @@ impl VulkanApp for VulkanApp21 {
         self.is_framebuffer_resized = true;
     }
 
-    fn window_ref(&self) -> &winit::window::Window {
-        &self.window
+    fn window_ref(&self) -> Option<&winit::window::Window> {
+        self.window.as_ref()
+    }
+
+    fn render_target_mut(&mut self) -> &mut RenderTarget {
+        &mut self.render_target
     }
 }
 
 fn main() {
     let program_proc = ProgramProc::new();
-    let vulkan_app = VulkanApp21::new(&program_proc.event_loop);
+    let vulkan_app = VulkanApp21::new(&program_proc).expect("Failed to initialize the app!");
 
     program_proc.main_loop(vulkan_app);
 }
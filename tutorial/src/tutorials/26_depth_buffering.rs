@@ -1,7 +1,10 @@
 use vulkan_tutorial_rust::{
-    utility, // the mod define some fixed functions that have been learned before.
     utility::constants::*,
     utility::debug::*,
+    utility::error::VkTutorialResult,
+    utility::pipeline_cache::PipelineCache,
+    utility::reflection::ShaderReflection,
+    utility::render_target::RenderTarget,
     utility::share,
     utility::structures::*,
     utility::window::{
@@ -114,7 +117,7 @@ pub const RECT_TEX_COORD_VERTICES_DATA: [VertexV3; 8] = [
 pub const RECT_TEX_COORD_INDICES_DATA: [u32; 12] = [0, 1, 2, 2, 3, 0, 4, 5, 6, 6, 7, 4];
 
 struct VulkanApp26 {
-    window: winit::window::Window,
+    window: Option<winit::window::Window>,
 
     // vulkan stuff
     _entry: ash::Entry,
@@ -132,8 +135,7 @@ struct VulkanApp26 {
     graphics_queue: vk::Queue,
     present_queue: vk::Queue,
 
-    swapchain_loader: ash::extensions::khr::Swapchain,
-    swapchain: vk::SwapchainKHR,
+    render_target: RenderTarget,
     swapchain_images: Vec<vk::Image>,
     swapchain_format: vk::Format,
     swapchain_extent: vk::Extent2D,
@@ -144,6 +146,7 @@ struct VulkanApp26 {
     ubo_layout: vk::DescriptorSetLayout,
     pipeline_layout: vk::PipelineLayout,
     graphics_pipeline: vk::Pipeline,
+    pipeline_cache: PipelineCache,
 
     depth_image: vk::Image,
     depth_image_view: vk::ImageView,
@@ -178,9 +181,8 @@ struct VulkanApp26 {
 }
 
 impl VulkanApp26 {
-    pub fn new(event_loop: &winit::event_loop::EventLoop<()>) -> VulkanApp26 {
-        let window =
-            utility::window::init_window(&event_loop, WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
+    pub fn new(program_proc: &ProgramProc) -> VkTutorialResult<VulkanApp26> {
+        let window = program_proc.init_window(WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
 
         // init vulkan stuff
         let entry = ash::Entry::new().unwrap();
@@ -189,13 +191,19 @@ impl VulkanApp26 {
             WINDOW_TITLE,
             VALIDATION.is_enable,
             &VALIDATION.required_validation_layers.to_vec(),
-        );
-        let surface_stuff =
-            share::create_surface(&entry, &instance, &window, WINDOW_WIDTH, WINDOW_HEIGHT);
+            window.as_ref(),
+        )?;
+        let surface_stuff = share::create_surface(
+            &entry,
+            &instance,
+            window.as_ref(),
+            WINDOW_WIDTH,
+            WINDOW_HEIGHT,
+        )?;
         let (debug_utils_loader, debug_merssager) =
             setup_debug_utils(VALIDATION.is_enable, &entry, &instance);
         let physical_device =
-            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS);
+            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS)?;
         let physical_device_memory_properties =
             unsafe { instance.get_physical_device_memory_properties(physical_device) };
         let (device, queue_family) = share::create_logical_device(
@@ -204,38 +212,41 @@ impl VulkanApp26 {
             &VALIDATION,
             &DEVICE_EXTENSIONS,
             &surface_stuff,
-        );
+        )?;
         let graphics_queue =
             unsafe { device.get_device_queue(queue_family.graphics_family.unwrap(), 0) };
         let present_queue =
             unsafe { device.get_device_queue(queue_family.present_family.unwrap(), 0) };
-        let swapchain_stuff = share::create_swapchain(
+        let swapchain_stuff = share::create_render_target(
             &instance,
             &device,
             physical_device,
-            &window,
+            window.as_ref(),
             &surface_stuff,
             &queue_family,
-        );
+            graphics_queue,
+        )?;
         let swapchain_imageviews = share::v1::create_image_views(
             &device,
             swapchain_stuff.swapchain_format,
             &swapchain_stuff.swapchain_images,
-        );
+        )?;
         let render_pass = VulkanApp26::create_render_pass(
             &instance,
             &device,
             physical_device,
             swapchain_stuff.swapchain_format,
         );
-        let ubo_layout = share::v2::create_descriptor_set_layout(&device);
+        let ubo_layout = share::v2::create_descriptor_set_layout(&device, false)?;
+        let pipeline_cache = PipelineCache::new(&instance, physical_device, &device)?;
         let (graphics_pipeline, pipeline_layout) = VulkanApp26::create_graphics_pipeline(
             &device,
+            pipeline_cache.cache,
             render_pass,
             swapchain_stuff.swapchain_extent,
             ubo_layout,
-        );
-        let command_pool = share::v1::create_command_pool(&device, &queue_family);
+        )?;
+        let command_pool = share::v1::create_command_pool(&device, &queue_family)?;
         let (depth_image, depth_image_view, depth_image_memory) =
             VulkanApp26::create_depth_resources(
                 &instance,
@@ -245,7 +256,7 @@ impl VulkanApp26 {
                 graphics_queue,
                 swapchain_stuff.swapchain_extent,
                 &physical_device_memory_properties,
-            );
+            )?;
         let swapchain_framebuffers = VulkanApp26::create_framebuffers(
             &device,
             render_pass,
@@ -259,30 +270,34 @@ impl VulkanApp26 {
             graphics_queue,
             &physical_device_memory_properties,
             &Path::new(TEXTURE_PATH),
-        );
-        let texture_image_view = share::v1::create_texture_image_view(&device, texture_image, 1);
-        let texture_sampler = share::v1::create_texture_sampler(&device);
+        )?;
+        let texture_image_view = share::v1::create_texture_image_view(&device, texture_image, 1)?;
+        let texture_sampler =
+            share::v1::create_texture_sampler(&instance, physical_device, &device)?;
         let (vertex_buffer, vertex_buffer_memory) = share::v1::create_vertex_buffer(
             &device,
             &physical_device_memory_properties,
             command_pool,
             graphics_queue,
             &RECT_TEX_COORD_VERTICES_DATA,
-        );
+        )?;
         let (index_buffer, index_buffer_memory) = share::v1::create_index_buffer(
             &device,
             &physical_device_memory_properties,
             command_pool,
             graphics_queue,
             &RECT_TEX_COORD_INDICES_DATA,
-        );
+        )?;
         let (uniform_buffers, uniform_buffers_memory) = share::v1::create_uniform_buffers(
             &device,
             &physical_device_memory_properties,
             swapchain_stuff.swapchain_images.len(),
-        );
-        let descriptor_pool =
-            share::v2::create_descriptor_pool(&device, swapchain_stuff.swapchain_images.len());
+        )?;
+        let descriptor_pool = share::v2::create_descriptor_pool(
+            &device,
+            swapchain_stuff.swapchain_images.len(),
+            false,
+        )?;
         let descriptor_sets = share::v2::create_descriptor_sets(
             &device,
             descriptor_pool,
@@ -291,7 +306,8 @@ impl VulkanApp26 {
             texture_image_view,
             texture_sampler,
             swapchain_stuff.swapchain_images.len(),
-        );
+            false,
+        )?;
         let command_buffers = VulkanApp26::create_command_buffers(
             &device,
             command_pool,
@@ -304,10 +320,10 @@ impl VulkanApp26 {
             pipeline_layout,
             &descriptor_sets,
         );
-        let sync_ojbects = share::v1::create_sync_objects(&device, MAX_FRAMES_IN_FLIGHT);
+        let sync_ojbects = share::v1::create_sync_objects(&device, MAX_FRAMES_IN_FLIGHT)?;
 
         // cleanup(); the 'drop' function will take care of it.
-        VulkanApp26 {
+        Ok(VulkanApp26 {
             // winit stuff
             window,
 
@@ -327,8 +343,7 @@ impl VulkanApp26 {
             graphics_queue,
             present_queue,
 
-            swapchain_loader: swapchain_stuff.swapchain_loader,
-            swapchain: swapchain_stuff.swapchain,
+            render_target: swapchain_stuff.render_target,
             swapchain_format: swapchain_stuff.swapchain_format,
             swapchain_images: swapchain_stuff.swapchain_images,
             swapchain_extent: swapchain_stuff.swapchain_extent,
@@ -339,6 +354,7 @@ impl VulkanApp26 {
             ubo_layout,
             render_pass,
             graphics_pipeline,
+            pipeline_cache,
 
             depth_image,
             depth_image_view,
@@ -388,7 +404,7 @@ impl VulkanApp26 {
             current_frame: 0,
 
             is_framebuffer_resized: false,
-        }
+        })
     }
 
     fn create_depth_resources(
@@ -399,7 +415,7 @@ impl VulkanApp26 {
         _submit_queue: vk::Queue,
         swapchain_extent: vk::Extent2D,
         device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
-    ) -> (vk::Image, vk::ImageView, vk::DeviceMemory) {
+    ) -> VkTutorialResult<(vk::Image, vk::ImageView, vk::DeviceMemory)> {
         let depth_format = VulkanApp26::find_depth_format(instance, physical_device);
         let (depth_image, depth_image_memory) = share::v1::create_image(
             device,
@@ -412,16 +428,16 @@ impl VulkanApp26 {
             vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
             device_memory_properties,
-        );
+        )?;
         let depth_image_view = share::v1::create_image_view(
             device,
             depth_image,
             depth_format,
             vk::ImageAspectFlags::DEPTH,
             1,
-        );
+        )?;
 
-        (depth_image, depth_image_view, depth_image_memory)
+        Ok((depth_image, depth_image_view, depth_image_memory))
     }
 
     fn find_depth_format(
@@ -593,18 +609,15 @@ impl VulkanApp26 {
 
     fn create_graphics_pipeline(
         device: &ash::Device,
+        pipeline_cache: vk::PipelineCache,
         render_pass: vk::RenderPass,
         swapchain_extent: vk::Extent2D,
         ubo_set_layout: vk::DescriptorSetLayout,
-    ) -> (vk::Pipeline, vk::PipelineLayout) {
-        let vert_shader_module = share::create_shader_module(
-            device,
-            include_bytes!("../../shaders/spv/26-shader-depth.vert.spv").to_vec(),
-        );
-        let frag_shader_module = share::create_shader_module(
-            device,
-            include_bytes!("../../shaders/spv/26-shader-depth.frag.spv").to_vec(),
-        );
+    ) -> VkTutorialResult<(vk::Pipeline, vk::PipelineLayout)> {
+        let vert_shader_code = include_bytes!("../../shaders/spv/26-shader-depth.vert.spv");
+        let vert_shader_module = share::create_shader_module(device, vert_shader_code.to_vec())?;
+        let frag_shader_code = include_bytes!("../../shaders/spv/26-shader-depth.frag.spv");
+        let frag_shader_module = share::create_shader_module(device, frag_shader_code.to_vec())?;
 
         let main_function_name = CString::new("main").unwrap(); // the beginning function name in shader code.
 
@@ -633,6 +646,7 @@ impl VulkanApp26 {
 
         let binding_description = VertexV3::get_binding_descriptions();
         let attribute_description = VertexV3::get_attribute_descriptions();
+        ShaderReflection::new(vert_shader_code)?.validate_vertex_input(&attribute_description)?;
 
         let vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo {
             s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
@@ -792,11 +806,7 @@ impl VulkanApp26 {
 
         let graphics_pipelines = unsafe {
             device
-                .create_graphics_pipelines(
-                    vk::PipelineCache::null(),
-                    &graphic_pipeline_create_infos,
-                    None,
-                )
+                .create_graphics_pipelines(pipeline_cache, &graphic_pipeline_create_infos, None)
                 .expect("Failed to create Graphics Pipeline!.")
         };
 
@@ -805,7 +815,7 @@ impl VulkanApp26 {
             device.destroy_shader_module(frag_shader_module, None);
         }
 
-        (graphics_pipelines[0], pipeline_layout)
+        Ok((graphics_pipelines[0], pipeline_layout))
     }
 }
 
@@ -996,8 +1006,12 @@ impl Drop for VulkanApp26 {
 
             self.device.destroy_command_pool(self.command_pool, None);
 
+            self.pipeline_cache.destroy();
+
             self.device.destroy_device(None);
-            self.surface_loader.destroy_surface(self.surface, None);
+            if self.window.is_some() {
+                self.surface_loader.destroy_surface(self.surface, None);
+            }
 
             if VALIDATION.is_enable {
                 self.debug_utils_loader
@@ -1009,7 +1023,7 @@ impl Drop for VulkanApp26 {
 }
 
 impl VulkanApp for VulkanApp26 {
-    fn draw_frame(&mut self, delta_time: f32) {
+    fn draw_frame(&mut self, delta_time: f32) -> VkTutorialResult<()> {
         let wait_fences = [self.in_flight_fences[self.current_frame]];
 
         unsafe {
@@ -1019,18 +1033,15 @@ impl VulkanApp for VulkanApp26 {
         }
 
         let (image_index, _is_sub_optimal) = unsafe {
-            let result = self.swapchain_loader.acquire_next_image(
-                self.swapchain,
+            let result = self.render_target.acquire_next_image(
                 std::u64::MAX,
                 self.image_available_semaphores[self.current_frame],
-                vk::Fence::null(),
             );
             match result {
                 Ok(image_index) => image_index,
                 Err(vk_result) => match vk_result {
                     vk::Result::ERROR_OUT_OF_DATE_KHR => {
-                        self.recreate_swapchain();
-                        return;
+                        return self.recreate_swapchain();
                     }
                     _ => panic!("Failed to acquire Swap Chain Image!"),
                 },
@@ -1039,6 +1050,13 @@ impl VulkanApp for VulkanApp26 {
 
         self.update_uniform_buffer(image_index as usize, delta_time);
 
+        let mut command_buffers = vec![self.command_buffers[image_index as usize]];
+        command_buffers.extend(self.render_target.record_capture(
+            self.command_pool,
+            image_index,
+            self.in_flight_fences[self.current_frame],
+        )?);
+
         let wait_semaphores = [self.image_available_semaphores[self.current_frame]];
         let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
         let signal_semaphores = [self.render_finished_semaphores[self.current_frame]];
@@ -1049,8 +1067,8 @@ impl VulkanApp for VulkanApp26 {
             wait_semaphore_count: wait_semaphores.len() as u32,
             p_wait_semaphores: wait_semaphores.as_ptr(),
             p_wait_dst_stage_mask: wait_stages.as_ptr(),
-            command_buffer_count: 1,
-            p_command_buffers: &self.command_buffers[image_index as usize],
+            command_buffer_count: command_buffers.len() as u32,
+            p_command_buffers: command_buffers.as_ptr(),
             signal_semaphore_count: signal_semaphores.len() as u32,
             p_signal_semaphores: signal_semaphores.as_ptr(),
         }];
@@ -1069,22 +1087,9 @@ impl VulkanApp for VulkanApp26 {
                 .expect("Failed to execute queue submit.");
         }
 
-        let swapchains = [self.swapchain];
-
-        let present_info = vk::PresentInfoKHR {
-            s_type: vk::StructureType::PRESENT_INFO_KHR,
-            p_next: ptr::null(),
-            wait_semaphore_count: 1,
-            p_wait_semaphores: signal_semaphores.as_ptr(),
-            swapchain_count: 1,
-            p_swapchains: swapchains.as_ptr(),
-            p_image_indices: &image_index,
-            p_results: ptr::null_mut(),
-        };
-
         let result = unsafe {
-            self.swapchain_loader
-                .queue_present(self.present_queue, &present_info)
+            self.render_target
+                .queue_present(self.present_queue, &signal_semaphores, image_index)
         };
 
         let is_resized = match result {
@@ -1096,13 +1101,15 @@ impl VulkanApp for VulkanApp26 {
         };
         if is_resized {
             self.is_framebuffer_resized = false;
-            self.recreate_swapchain();
+            self.recreate_swapchain()?;
         }
 
         self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+
+        Ok(())
     }
 
-    fn recreate_swapchain(&mut self) {
+    fn recreate_swapchain(&mut self) -> VkTutorialResult<()> {
         // parameters -------------
         let surface_suff = SurfaceStuff {
             surface_loader: self.surface_loader.clone(),
@@ -1119,16 +1126,16 @@ impl VulkanApp for VulkanApp26 {
         };
         self.cleanup_swapchain();
 
-        let swapchain_stuff = share::create_swapchain(
+        let swapchain_stuff = share::create_render_target(
             &self.instance,
             &self.device,
             self.physical_device,
-            &self.window,
+            self.window.as_ref(),
             &surface_suff,
             &self.queue_family,
-        );
-        self.swapchain_loader = swapchain_stuff.swapchain_loader;
-        self.swapchain = swapchain_stuff.swapchain;
+            self.graphics_queue,
+        )?;
+        self.render_target = swapchain_stuff.render_target;
         self.swapchain_images = swapchain_stuff.swapchain_images;
         self.swapchain_format = swapchain_stuff.swapchain_format;
         self.swapchain_extent = swapchain_stuff.swapchain_extent;
@@ -1137,7 +1144,7 @@ impl VulkanApp for VulkanApp26 {
             &self.device,
             self.swapchain_format,
             &self.swapchain_images,
-        );
+        )?;
         self.render_pass = VulkanApp26::create_render_pass(
             &self.instance,
             &self.device,
@@ -1146,10 +1153,11 @@ impl VulkanApp for VulkanApp26 {
         );
         let (graphics_pipeline, pipeline_layout) = VulkanApp26::create_graphics_pipeline(
             &self.device,
+            self.pipeline_cache.cache,
             self.render_pass,
             swapchain_stuff.swapchain_extent,
             self.ubo_layout,
-        );
+        )?;
         self.graphics_pipeline = graphics_pipeline;
         self.pipeline_layout = pipeline_layout;
 
@@ -1161,7 +1169,7 @@ impl VulkanApp for VulkanApp26 {
             self.graphics_queue,
             self.swapchain_extent,
             &self.memory_properties,
-        );
+        )?;
         self.depth_image = depth_resources.0;
         self.depth_image_view = depth_resources.1;
         self.depth_image_memory = depth_resources.2;
@@ -1185,9 +1193,11 @@ impl VulkanApp for VulkanApp26 {
             self.pipeline_layout,
             &self.descriptor_sets,
         );
+
+        Ok(())
     }
 
-    fn cleanup_swapchain(&self) {
+    fn cleanup_swapchain(&mut self) {
         unsafe {
             self.device.destroy_image_view(self.depth_image_view, None);
             self.device.destroy_image(self.depth_image, None);
@@ -1205,8 +1215,7 @@ impl VulkanApp for VulkanApp26 {
             for &image_view in self.swapchain_imageviews.iter() {
                 self.device.destroy_image_view(image_view, None);
             }
-            self.swapchain_loader
-                .destroy_swapchain(self.swapchain, None);
+            self.render_target.destroy(&self.device);
         }
     }
 
@@ -1222,14 +1231,18 @@ impl VulkanApp for VulkanApp26 {
         self.is_framebuffer_resized = true;
     }
 
-    fn window_ref(&self) -> &winit::window::Window {
-        &self.window
+    fn window_ref(&self) -> Option<&winit::window::Window> {
+        self.window.as_ref()
+    }
+
+    fn render_target_mut(&mut self) -> &mut RenderTarget {
+        &mut self.render_target
     }
 }
 
 fn main() {
     let program_proc = ProgramProc::new();
-    let vulkan_app = VulkanApp26::new(&program_proc.event_loop);
+    let vulkan_app = VulkanApp26::new(&program_proc).expect("Failed to initialize the app!");
 
     program_proc.main_loop(vulkan_app);
 }
@@ -1,7 +1,10 @@
 use vulkan_tutorial_rust::{
-    utility, // the mod define some fixed functions that have been learned before.
     utility::constants::*,
     utility::debug::*,
+    utility::error::VkTutorialResult,
+    utility::pipeline_cache::PipelineCache,
+    utility::reflection::ShaderReflection,
+    utility::render_target::RenderTarget,
     utility::share,
     utility::structures::*,
     utility::window::{
@@ -71,7 +74,7 @@ impl VertexV3 {
 }
 
 struct VulkanApp27 {
-    window: winit::window::Window,
+    window: Option<winit::window::Window>,
 
     // vulkan stuff
     _entry: ash::Entry,
@@ -89,8 +92,7 @@ struct VulkanApp27 {
     graphics_queue: vk::Queue,
     present_queue: vk::Queue,
 
-    swapchain_loader: ash::extensions::khr::Swapchain,
-    swapchain: vk::SwapchainKHR,
+    render_target: RenderTarget,
     swapchain_images: Vec<vk::Image>,
     swapchain_format: vk::Format,
     swapchain_extent: vk::Extent2D,
@@ -101,6 +103,7 @@ struct VulkanApp27 {
     ubo_layout: vk::DescriptorSetLayout,
     pipeline_layout: vk::PipelineLayout,
     graphics_pipeline: vk::Pipeline,
+    pipeline_cache: PipelineCache,
 
     depth_image: vk::Image,
     depth_image_view: vk::ImageView,
@@ -138,9 +141,8 @@ struct VulkanApp27 {
 }
 
 impl VulkanApp27 {
-    pub fn new(event_loop: &winit::event_loop::EventLoop<()>) -> VulkanApp27 {
-        let window =
-            utility::window::init_window(&event_loop, WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
+    pub fn new(program_proc: &ProgramProc) -> VkTutorialResult<VulkanApp27> {
+        let window = program_proc.init_window(WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
 
         // init vulkan stuff
         let entry = ash::Entry::new().unwrap();
@@ -149,13 +151,19 @@ impl VulkanApp27 {
             WINDOW_TITLE,
             VALIDATION.is_enable,
             &VALIDATION.required_validation_layers.to_vec(),
-        );
-        let surface_stuff =
-            share::create_surface(&entry, &instance, &window, WINDOW_WIDTH, WINDOW_HEIGHT);
+            window.as_ref(),
+        )?;
+        let surface_stuff = share::create_surface(
+            &entry,
+            &instance,
+            window.as_ref(),
+            WINDOW_WIDTH,
+            WINDOW_HEIGHT,
+        )?;
         let (debug_utils_loader, debug_merssager) =
             setup_debug_utils(VALIDATION.is_enable, &entry, &instance);
         let physical_device =
-            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS);
+            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS)?;
         let physical_device_memory_properties =
             unsafe { instance.get_physical_device_memory_properties(physical_device) };
         let (device, queue_family) = share::create_logical_device(
@@ -164,48 +172,52 @@ impl VulkanApp27 {
             &VALIDATION,
             &DEVICE_EXTENSIONS,
             &surface_stuff,
-        );
+        )?;
         let graphics_queue =
             unsafe { device.get_device_queue(queue_family.graphics_family.unwrap(), 0) };
         let present_queue =
             unsafe { device.get_device_queue(queue_family.present_family.unwrap(), 0) };
-        let swapchain_stuff = share::create_swapchain(
+        let swapchain_stuff = share::create_render_target(
             &instance,
             &device,
             physical_device,
-            &window,
+            window.as_ref(),
             &surface_stuff,
             &queue_family,
-        );
+            graphics_queue,
+        )?;
         let swapchain_imageviews = share::v1::create_image_views(
             &device,
             swapchain_stuff.swapchain_format,
             &swapchain_stuff.swapchain_images,
-        );
+        )?;
         let render_pass = VulkanApp27::create_render_pass(
             &instance,
             &device,
             physical_device,
             swapchain_stuff.swapchain_format,
-        );
-        let ubo_layout = share::v2::create_descriptor_set_layout(&device);
+        )?;
+        let ubo_layout = share::v2::create_descriptor_set_layout(&device, false)?;
+        let pipeline_cache = PipelineCache::new(&instance, physical_device, &device)?;
         let (graphics_pipeline, pipeline_layout) = VulkanApp27::create_graphics_pipeline(
             &device,
+            pipeline_cache.cache,
             render_pass,
             swapchain_stuff.swapchain_extent,
             ubo_layout,
-        );
-        let command_pool = share::v1::create_command_pool(&device, &queue_family);
-        let (depth_image, depth_image_view, depth_image_memory) = share::v1::create_depth_resources(
-            &instance,
-            &device,
-            physical_device,
-            command_pool,
-            graphics_queue,
-            swapchain_stuff.swapchain_extent,
-            &physical_device_memory_properties,
-            vk::SampleCountFlags::TYPE_1,
-        );
+        )?;
+        let command_pool = share::v1::create_command_pool(&device, &queue_family)?;
+        let (depth_image, depth_image_view, depth_image_memory) =
+            share::v1::create_depth_resources(
+                &instance,
+                &device,
+                physical_device,
+                command_pool,
+                graphics_queue,
+                swapchain_stuff.swapchain_extent,
+                &physical_device_memory_properties,
+                vk::SampleCountFlags::TYPE_1,
+            )?;
         let swapchain_framebuffers = VulkanApp27::create_framebuffers(
             &device,
             render_pass,
@@ -220,30 +232,34 @@ impl VulkanApp27 {
             graphics_queue,
             &physical_device_memory_properties,
             &Path::new(TEXTURE_PATH),
-        );
-        let texture_image_view = share::v1::create_texture_image_view(&device, texture_image, 1);
-        let texture_sampler = share::v1::create_texture_sampler(&device);
+        )?;
+        let texture_image_view = share::v1::create_texture_image_view(&device, texture_image, 1)?;
+        let texture_sampler =
+            share::v1::create_texture_sampler(&instance, physical_device, &device)?;
         let (vertex_buffer, vertex_buffer_memory) = share::v1::create_vertex_buffer(
             &device,
             &physical_device_memory_properties,
             command_pool,
             graphics_queue,
             &vertices,
-        );
+        )?;
         let (index_buffer, index_buffer_memory) = share::v1::create_index_buffer(
             &device,
             &physical_device_memory_properties,
             command_pool,
             graphics_queue,
             &indices,
-        );
+        )?;
         let (uniform_buffers, uniform_buffers_memory) = share::v1::create_uniform_buffers(
             &device,
             &physical_device_memory_properties,
             swapchain_stuff.swapchain_images.len(),
-        );
-        let descriptor_pool =
-            share::v2::create_descriptor_pool(&device, swapchain_stuff.swapchain_images.len());
+        )?;
+        let descriptor_pool = share::v2::create_descriptor_pool(
+            &device,
+            swapchain_stuff.swapchain_images.len(),
+            false,
+        )?;
         let descriptor_sets = share::v2::create_descriptor_sets(
             &device,
             descriptor_pool,
@@ -252,7 +268,8 @@ impl VulkanApp27 {
             texture_image_view,
             texture_sampler,
             swapchain_stuff.swapchain_images.len(),
-        );
+            false,
+        )?;
         let command_buffers = VulkanApp27::create_command_buffers(
             &device,
             command_pool,
@@ -266,10 +283,10 @@ impl VulkanApp27 {
             &descriptor_sets,
             indices.len() as u32,
         );
-        let sync_ojbects = share::v1::create_sync_objects(&device, MAX_FRAMES_IN_FLIGHT);
+        let sync_ojbects = share::v1::create_sync_objects(&device, MAX_FRAMES_IN_FLIGHT)?;
 
         // cleanup(); the 'drop' function will take care of it.
-        VulkanApp27 {
+        Ok(VulkanApp27 {
             // winit stuff
             window,
 
@@ -289,8 +306,7 @@ impl VulkanApp27 {
             graphics_queue,
             present_queue,
 
-            swapchain_loader: swapchain_stuff.swapchain_loader,
-            swapchain: swapchain_stuff.swapchain,
+            render_target: swapchain_stuff.render_target,
             swapchain_format: swapchain_stuff.swapchain_format,
             swapchain_images: swapchain_stuff.swapchain_images,
             swapchain_extent: swapchain_stuff.swapchain_extent,
@@ -301,6 +317,7 @@ impl VulkanApp27 {
             ubo_layout,
             render_pass,
             graphics_pipeline,
+            pipeline_cache,
 
             depth_image,
             depth_image_view,
@@ -353,7 +370,7 @@ impl VulkanApp27 {
             current_frame: 0,
 
             is_framebuffer_resized: false,
-        }
+        })
     }
 
     fn load_model(model_path: &Path) -> (Vec<VertexV3>, Vec<u32>) {
@@ -399,7 +416,7 @@ impl VulkanApp27 {
         device: &ash::Device,
         physcial_device: vk::PhysicalDevice,
         surface_format: vk::Format,
-    ) -> vk::RenderPass {
+    ) -> VkTutorialResult<vk::RenderPass> {
         let color_attachment = vk::AttachmentDescription {
             flags: vk::AttachmentDescriptionFlags::empty(),
             format: surface_format,
@@ -414,7 +431,7 @@ impl VulkanApp27 {
 
         let depth_attachment = vk::AttachmentDescription {
             flags: vk::AttachmentDescriptionFlags::empty(),
-            format: share::find_depth_format(instance, physcial_device),
+            format: share::find_depth_format(instance, physcial_device)?,
             samples: vk::SampleCountFlags::TYPE_1,
             load_op: vk::AttachmentLoadOp::CLEAR,
             store_op: vk::AttachmentStoreOp::DONT_CARE,
@@ -472,11 +489,11 @@ impl VulkanApp27 {
             p_dependencies: subpass_dependencies.as_ptr(),
         };
 
-        unsafe {
+        Ok(unsafe {
             device
                 .create_render_pass(&renderpass_create_info, None)
                 .expect("Failed to create render pass!")
-        }
+        })
     }
 
     fn create_framebuffers(
@@ -517,18 +534,15 @@ impl VulkanApp27 {
 
     fn create_graphics_pipeline(
         device: &ash::Device,
+        pipeline_cache: vk::PipelineCache,
         render_pass: vk::RenderPass,
         swapchain_extent: vk::Extent2D,
         ubo_set_layout: vk::DescriptorSetLayout,
-    ) -> (vk::Pipeline, vk::PipelineLayout) {
-        let vert_shader_module = share::create_shader_module(
-            device,
-            include_bytes!("../../shaders/spv/26-shader-depth.vert.spv").to_vec(),
-        );
-        let frag_shader_module = share::create_shader_module(
-            device,
-            include_bytes!("../../shaders/spv/26-shader-depth.frag.spv").to_vec(),
-        );
+    ) -> VkTutorialResult<(vk::Pipeline, vk::PipelineLayout)> {
+        let vert_shader_code = include_bytes!("../../shaders/spv/26-shader-depth.vert.spv");
+        let vert_shader_module = share::create_shader_module(device, vert_shader_code.to_vec())?;
+        let frag_shader_code = include_bytes!("../../shaders/spv/26-shader-depth.frag.spv");
+        let frag_shader_module = share::create_shader_module(device, frag_shader_code.to_vec())?;
 
         let main_function_name = CString::new("main").unwrap(); // the beginning function name in shader code.
 
@@ -557,6 +571,7 @@ impl VulkanApp27 {
 
         let binding_description = VertexV3::get_binding_descriptions();
         let attribute_description = VertexV3::get_attribute_descriptions();
+        ShaderReflection::new(vert_shader_code)?.validate_vertex_input(&attribute_description)?;
 
         let vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo {
             s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
@@ -716,11 +731,7 @@ impl VulkanApp27 {
 
         let graphics_pipelines = unsafe {
             device
-                .create_graphics_pipelines(
-                    vk::PipelineCache::null(),
-                    &graphic_pipeline_create_infos,
-                    None,
-                )
+                .create_graphics_pipelines(pipeline_cache, &graphic_pipeline_create_infos, None)
                 .expect("Failed to create Graphics Pipeline!.")
         };
 
@@ -729,7 +740,7 @@ impl VulkanApp27 {
             device.destroy_shader_module(frag_shader_module, None);
         }
 
-        (graphics_pipelines[0], pipeline_layout)
+        Ok((graphics_pipelines[0], pipeline_layout))
     }
 
     fn create_command_buffers(
@@ -915,8 +926,12 @@ impl Drop for VulkanApp27 {
 
             self.device.destroy_command_pool(self.command_pool, None);
 
+            self.pipeline_cache.destroy();
+
             self.device.destroy_device(None);
-            self.surface_loader.destroy_surface(self.surface, None);
+            if self.window.is_some() {
+                self.surface_loader.destroy_surface(self.surface, None);
+            }
 
             if VALIDATION.is_enable {
                 self.debug_utils_loader
@@ -928,7 +943,7 @@ impl Drop for VulkanApp27 {
 }
 
 impl VulkanApp for VulkanApp27 {
-    fn draw_frame(&mut self, delta_time: f32) {
+    fn draw_frame(&mut self, delta_time: f32) -> VkTutorialResult<()> {
         let wait_fences = [self.in_flight_fences[self.current_frame]];
 
         unsafe {
@@ -938,18 +953,15 @@ impl VulkanApp for VulkanApp27 {
         }
 
         let (image_index, _is_sub_optimal) = unsafe {
-            let result = self.swapchain_loader.acquire_next_image(
-                self.swapchain,
+            let result = self.render_target.acquire_next_image(
                 std::u64::MAX,
                 self.image_available_semaphores[self.current_frame],
-                vk::Fence::null(),
             );
             match result {
                 Ok(image_index) => image_index,
                 Err(vk_result) => match vk_result {
                     vk::Result::ERROR_OUT_OF_DATE_KHR => {
-                        self.recreate_swapchain();
-                        return;
+                        return self.recreate_swapchain();
                     }
                     _ => panic!("Failed to acquire Swap Chain Image!"),
                 },
@@ -958,6 +970,13 @@ impl VulkanApp for VulkanApp27 {
 
         self.update_uniform_buffer(image_index as usize, delta_time);
 
+        let mut command_buffers = vec![self.command_buffers[image_index as usize]];
+        command_buffers.extend(self.render_target.record_capture(
+            self.command_pool,
+            image_index,
+            self.in_flight_fences[self.current_frame],
+        )?);
+
         let wait_semaphores = [self.image_available_semaphores[self.current_frame]];
         let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
         let signal_semaphores = [self.render_finished_semaphores[self.current_frame]];
@@ -968,8 +987,8 @@ impl VulkanApp for VulkanApp27 {
             wait_semaphore_count: wait_semaphores.len() as u32,
             p_wait_semaphores: wait_semaphores.as_ptr(),
             p_wait_dst_stage_mask: wait_stages.as_ptr(),
-            command_buffer_count: 1,
-            p_command_buffers: &self.command_buffers[image_index as usize],
+            command_buffer_count: command_buffers.len() as u32,
+            p_command_buffers: command_buffers.as_ptr(),
             signal_semaphore_count: signal_semaphores.len() as u32,
             p_signal_semaphores: signal_semaphores.as_ptr(),
         }];
@@ -988,22 +1007,9 @@ impl VulkanApp for VulkanApp27 {
                 .expect("Failed to execute queue submit.");
         }
 
-        let swapchains = [self.swapchain];
-
-        let present_info = vk::PresentInfoKHR {
-            s_type: vk::StructureType::PRESENT_INFO_KHR,
-            p_next: ptr::null(),
-            wait_semaphore_count: 1,
-            p_wait_semaphores: signal_semaphores.as_ptr(),
-            swapchain_count: 1,
-            p_swapchains: swapchains.as_ptr(),
-            p_image_indices: &image_index,
-            p_results: ptr::null_mut(),
-        };
-
         let result = unsafe {
-            self.swapchain_loader
-                .queue_present(self.present_queue, &present_info)
+            self.render_target
+                .queue_present(self.present_queue, &signal_semaphores, image_index)
         };
 
         let is_resized = match result {
@@ -1015,13 +1021,15 @@ impl VulkanApp for VulkanApp27 {
         };
         if is_resized {
             self.is_framebuffer_resized = false;
-            self.recreate_swapchain();
+            self.recreate_swapchain()?;
         }
 
         self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+
+        Ok(())
     }
 
-    fn recreate_swapchain(&mut self) {
+    fn recreate_swapchain(&mut self) -> VkTutorialResult<()> {
         // parameters -------------
         let surface_suff = SurfaceStuff {
             surface_loader: self.surface_loader.clone(),
@@ -1038,16 +1046,16 @@ impl VulkanApp for VulkanApp27 {
         };
         self.cleanup_swapchain();
 
-        let swapchain_stuff = share::create_swapchain(
+        let swapchain_stuff = share::create_render_target(
             &self.instance,
             &self.device,
             self.physical_device,
-            &self.window,
+            self.window.as_ref(),
             &surface_suff,
             &self.queue_family,
-        );
-        self.swapchain_loader = swapchain_stuff.swapchain_loader;
-        self.swapchain = swapchain_stuff.swapchain;
+            self.graphics_queue,
+        )?;
+        self.render_target = swapchain_stuff.render_target;
         self.swapchain_images = swapchain_stuff.swapchain_images;
         self.swapchain_format = swapchain_stuff.swapchain_format;
         self.swapchain_extent = swapchain_stuff.swapchain_extent;
@@ -1056,19 +1064,20 @@ impl VulkanApp for VulkanApp27 {
             &self.device,
             self.swapchain_format,
             &self.swapchain_images,
-        );
+        )?;
         self.render_pass = VulkanApp27::create_render_pass(
             &self.instance,
             &self.device,
             self.physical_device,
             self.swapchain_format,
-        );
+        )?;
         let (graphics_pipeline, pipeline_layout) = VulkanApp27::create_graphics_pipeline(
             &self.device,
+            self.pipeline_cache.cache,
             self.render_pass,
             swapchain_stuff.swapchain_extent,
             self.ubo_layout,
-        );
+        )?;
         self.graphics_pipeline = graphics_pipeline;
         self.pipeline_layout = pipeline_layout;
 
@@ -1081,7 +1090,7 @@ impl VulkanApp for VulkanApp27 {
             self.swapchain_extent,
             &self.memory_properties,
             vk::SampleCountFlags::TYPE_1,
-        );
+        )?;
         self.depth_image = depth_resources.0;
         self.depth_image_view = depth_resources.1;
         self.depth_image_memory = depth_resources.2;
@@ -1106,9 +1115,11 @@ impl VulkanApp for VulkanApp27 {
             &self.descriptor_sets,
             self.indices.len() as u32,
         );
+
+        Ok(())
     }
 
-    fn cleanup_swapchain(&self) {
+    fn cleanup_swapchain(&mut self) {
         unsafe {
             self.device.destroy_image_view(self.depth_image_view, None);
             self.device.destroy_image(self.depth_image, None);
@@ -1126,8 +1137,7 @@ impl VulkanApp for VulkanApp27 {
             for &image_view in self.swapchain_imageviews.iter() {
                 self.device.destroy_image_view(image_view, None);
             }
-            self.swapchain_loader
-                .destroy_swapchain(self.swapchain, None);
+            self.render_target.destroy(&self.device);
         }
     }
 
@@ -1143,14 +1153,18 @@ impl VulkanApp for VulkanApp27 {
         self.is_framebuffer_resized = true;
     }
 
-    fn window_ref(&self) -> &winit::window::Window {
-        &self.window
+    fn window_ref(&self) -> Option<&winit::window::Window> {
+        self.window.as_ref()
+    }
+
+    fn render_target_mut(&mut self) -> &mut RenderTarget {
+        &mut self.render_target
     }
 }
 
 fn main() {
     let program_proc = ProgramProc::new();
-    let vulkan_app = VulkanApp27::new(&program_proc.event_loop);
+    let vulkan_app = VulkanApp27::new(&program_proc).expect("Failed to initialize the app!");
 
     program_proc.main_loop(vulkan_app);
 }
@@ -1,8 +1,15 @@
 use vulkan_tutorial_rust::{
-    utility, // the mod define some fixed functions that have been learned before.
     utility::constants::*,
     utility::debug::*,
-    utility::share,
+    utility::error::VkTutorialResult,
+    utility::pipeline_cache::PipelineCache,
+    utility::reflection::ShaderReflection,
+    utility::render_target::RenderTarget,
+    utility::sampler,
+    utility::share::{
+        self,
+        mipmap,
+    },
     utility::structures::*,
     utility::window::{
         ProgramProc,
@@ -19,7 +26,6 @@ use cgmath::{
     Point3,
     Vector3,
 };
-use image::GenericImageView;
 
 use std::cmp::max;
 use std::ffi::CString;
@@ -30,9 +36,11 @@ use std::ptr;
 const WINDOW_TITLE: &'static str = "28.Mipmapping";
 const MODEL_PATH: &'static str = "assets/chalet.obj";
 const TEXTURE_PATH: &'static str = "assets/chalet.jpg";
+// a KTX2 or DDS export of the texture, stored bottom row first as the flipped jpg, is used when it exists.
+const COMPRESSED_TEXTURE_PATH: &'static str = "assets/chalet.ktx2";
 
 struct VulkanApp28 {
-    window: winit::window::Window,
+    window: Option<winit::window::Window>,
 
     // vulkan stuff
     _entry: ash::Entry,
@@ -50,8 +58,7 @@ struct VulkanApp28 {
     graphics_queue: vk::Queue,
     present_queue: vk::Queue,
 
-    swapchain_loader: ash::extensions::khr::Swapchain,
-    swapchain: vk::SwapchainKHR,
+    render_target: RenderTarget,
     swapchain_images: Vec<vk::Image>,
     swapchain_format: vk::Format,
     swapchain_extent: vk::Extent2D,
@@ -62,6 +69,7 @@ struct VulkanApp28 {
     ubo_layout: vk::DescriptorSetLayout,
     pipeline_layout: vk::PipelineLayout,
     graphics_pipeline: vk::Pipeline,
+    pipeline_cache: PipelineCache,
 
     depth_image: vk::Image,
     depth_image_view: vk::ImageView,
@@ -100,9 +108,8 @@ struct VulkanApp28 {
 }
 
 impl VulkanApp28 {
-    pub fn new(event_loop: &winit::event_loop::EventLoop<()>) -> VulkanApp28 {
-        let window =
-            utility::window::init_window(&event_loop, WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
+    pub fn new(program_proc: &ProgramProc) -> VkTutorialResult<VulkanApp28> {
+        let window = program_proc.init_window(WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
 
         // init vulkan stuff
         let entry = ash::Entry::new().unwrap();
@@ -111,13 +118,19 @@ impl VulkanApp28 {
             WINDOW_TITLE,
             VALIDATION.is_enable,
             &VALIDATION.required_validation_layers.to_vec(),
-        );
-        let surface_stuff =
-            share::create_surface(&entry, &instance, &window, WINDOW_WIDTH, WINDOW_HEIGHT);
+            window.as_ref(),
+        )?;
+        let surface_stuff = share::create_surface(
+            &entry,
+            &instance,
+            window.as_ref(),
+            WINDOW_WIDTH,
+            WINDOW_HEIGHT,
+        )?;
         let (debug_utils_loader, debug_merssager) =
             setup_debug_utils(VALIDATION.is_enable, &entry, &instance);
         let physical_device =
-            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS);
+            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS)?;
         let physical_device_memory_properties =
             unsafe { instance.get_physical_device_memory_properties(physical_device) };
         let (device, queue_family) = share::create_logical_device(
@@ -126,48 +139,52 @@ impl VulkanApp28 {
             &VALIDATION,
             &DEVICE_EXTENSIONS,
             &surface_stuff,
-        );
+        )?;
         let graphics_queue =
             unsafe { device.get_device_queue(queue_family.graphics_family.unwrap(), 0) };
         let present_queue =
             unsafe { device.get_device_queue(queue_family.present_family.unwrap(), 0) };
-        let swapchain_stuff = share::create_swapchain(
+        let swapchain_stuff = share::create_render_target(
             &instance,
             &device,
             physical_device,
-            &window,
+            window.as_ref(),
             &surface_stuff,
             &queue_family,
-        );
+            graphics_queue,
+        )?;
         let swapchain_imageviews = share::v1::create_image_views(
             &device,
             swapchain_stuff.swapchain_format,
             &swapchain_stuff.swapchain_images,
-        );
+        )?;
         let render_pass = VulkanApp28::create_render_pass(
             &instance,
             &device,
             physical_device,
             swapchain_stuff.swapchain_format,
-        );
-        let ubo_layout = share::v2::create_descriptor_set_layout(&device);
+        )?;
+        let ubo_layout = share::v2::create_descriptor_set_layout(&device, false)?;
+        let pipeline_cache = PipelineCache::new(&instance, physical_device, &device)?;
         let (graphics_pipeline, pipeline_layout) = VulkanApp28::create_graphics_pipeline(
             &device,
+            pipeline_cache.cache,
             render_pass,
             swapchain_stuff.swapchain_extent,
             ubo_layout,
-        );
-        let command_pool = share::v1::create_command_pool(&device, &queue_family);
-        let (depth_image, depth_image_view, depth_image_memory) = share::v1::create_depth_resources(
-            &instance,
-            &device,
-            physical_device,
-            command_pool,
-            graphics_queue,
-            swapchain_stuff.swapchain_extent,
-            &physical_device_memory_properties,
-            vk::SampleCountFlags::TYPE_1,
-        );
+        )?;
+        let command_pool = share::v1::create_command_pool(&device, &queue_family)?;
+        let (depth_image, depth_image_view, depth_image_memory) =
+            share::v1::create_depth_resources(
+                &instance,
+                &device,
+                physical_device,
+                command_pool,
+                graphics_queue,
+                swapchain_stuff.swapchain_extent,
+                &physical_device_memory_properties,
+                vk::SampleCountFlags::TYPE_1,
+            )?;
         let swapchain_framebuffers = VulkanApp28::create_framebuffers(
             &device,
             render_pass,
@@ -175,39 +192,72 @@ impl VulkanApp28 {
             depth_image_view,
             swapchain_stuff.swapchain_extent,
         );
-        let (vertices, indices) = share::load_model(&Path::new(MODEL_PATH));
-        VulkanApp28::check_mipmap_support(&instance, physical_device, vk::Format::R8G8B8A8_UNORM);
-        let (texture_image, texture_image_memory, mip_levels) = VulkanApp28::create_texture_image(
+        let (vertices, indices) = share::load_model(&Path::new(MODEL_PATH))?;
+        let (texture_image, texture_image_memory, texture_format, mip_levels) =
+            if Path::new(COMPRESSED_TEXTURE_PATH).is_file() {
+                share::texture::create_compressed_texture_image(
+                    &instance,
+                    &device,
+                    physical_device,
+                    command_pool,
+                    graphics_queue,
+                    &physical_device_memory_properties,
+                    &Path::new(COMPRESSED_TEXTURE_PATH),
+                )?
+            } else {
+                let (texture_image, texture_image_memory, mip_levels) =
+                    VulkanApp28::create_texture_image(
+                        &instance,
+                        &device,
+                        physical_device,
+                        command_pool,
+                        graphics_queue,
+                        &physical_device_memory_properties,
+                        &Path::new(TEXTURE_PATH),
+                    )?;
+                (
+                    texture_image,
+                    texture_image_memory,
+                    vk::Format::R8G8B8A8_SRGB,
+                    mip_levels,
+                )
+            };
+        let texture_image_view = share::v1::create_image_view(
             &device,
-            command_pool,
-            graphics_queue,
-            &physical_device_memory_properties,
-            &Path::new(TEXTURE_PATH),
+            texture_image,
+            texture_format,
+            vk::ImageAspectFlags::COLOR,
+            mip_levels,
+        )?;
+        let texture_sampler = VulkanApp28::create_texture_sampler(
+            &device,
+            mip_levels,
+            sampler::device_max_anisotropy(&instance, physical_device),
         );
-        let texture_image_view =
-            share::v1::create_texture_image_view(&device, texture_image, mip_levels);
-        let texture_sampler = VulkanApp28::create_texture_sampler(&device, mip_levels);
         let (vertex_buffer, vertex_buffer_memory) = share::v1::create_vertex_buffer(
             &device,
             &physical_device_memory_properties,
             command_pool,
             graphics_queue,
             &vertices,
-        );
+        )?;
         let (index_buffer, index_buffer_memory) = share::v1::create_index_buffer(
             &device,
             &physical_device_memory_properties,
             command_pool,
             graphics_queue,
             &indices,
-        );
+        )?;
         let (uniform_buffers, uniform_buffers_memory) = share::v1::create_uniform_buffers(
             &device,
             &physical_device_memory_properties,
             swapchain_stuff.swapchain_images.len(),
-        );
-        let descriptor_pool =
-            share::v2::create_descriptor_pool(&device, swapchain_stuff.swapchain_images.len());
+        )?;
+        let descriptor_pool = share::v2::create_descriptor_pool(
+            &device,
+            swapchain_stuff.swapchain_images.len(),
+            false,
+        )?;
         let descriptor_sets = share::v2::create_descriptor_sets(
             &device,
             descriptor_pool,
@@ -216,7 +266,8 @@ impl VulkanApp28 {
             texture_image_view,
             texture_sampler,
             swapchain_stuff.swapchain_images.len(),
-        );
+            false,
+        )?;
         let command_buffers = VulkanApp28::create_command_buffers(
             &device,
             command_pool,
@@ -230,10 +281,10 @@ impl VulkanApp28 {
             &descriptor_sets,
             indices.len() as u32,
         );
-        let sync_ojbects = share::v1::create_sync_objects(&device, MAX_FRAMES_IN_FLIGHT);
+        let sync_ojbects = share::v1::create_sync_objects(&device, MAX_FRAMES_IN_FLIGHT)?;
 
         // cleanup(); the 'drop' function will take care of it.
-        VulkanApp28 {
+        Ok(VulkanApp28 {
             // winit stuff
             window,
 
@@ -253,8 +304,7 @@ impl VulkanApp28 {
             graphics_queue,
             present_queue,
 
-            swapchain_loader: swapchain_stuff.swapchain_loader,
-            swapchain: swapchain_stuff.swapchain,
+            render_target: swapchain_stuff.render_target,
             swapchain_format: swapchain_stuff.swapchain_format,
             swapchain_images: swapchain_stuff.swapchain_images,
             swapchain_extent: swapchain_stuff.swapchain_extent,
@@ -265,6 +315,7 @@ impl VulkanApp28 {
             ubo_layout,
             render_pass,
             graphics_pipeline,
+            pipeline_cache,
 
             depth_image,
             depth_image_view,
@@ -318,59 +369,38 @@ impl VulkanApp28 {
             current_frame: 0,
 
             is_framebuffer_resized: false,
-        }
+        })
     }
 
+    /// The mip levels are blitted on the GPU when the format supports linear blits, and generated
+    /// on the CPU otherwise (e.g. on some software rasterizers).
+    /// The precomputed levels found next to the texture are used as they are, see `mipmap::load_mip_chain`.
     fn create_texture_image(
+        instance: &ash::Instance,
         device: &ash::Device,
+        physical_device: vk::PhysicalDevice,
         command_pool: vk::CommandPool,
         submit_queue: vk::Queue,
         device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
         image_path: &Path,
-    ) -> (vk::Image, vk::DeviceMemory, u32) {
-        let mut image_object = image::open(image_path).unwrap(); // this function is slow in debug mode.
-        image_object = image_object.flipv();
-        let (image_width, image_height) = (image_object.width(), image_object.height());
-        let image_data = match &image_object {
-            image::DynamicImage::ImageBgr8(_)
-            | image::DynamicImage::ImageLuma8(_)
-            | image::DynamicImage::ImageRgb8(_) => image_object.to_rgba().into_raw(),
-            image::DynamicImage::ImageBgra8(_)
-            | image::DynamicImage::ImageLumaA8(_)
-            | image::DynamicImage::ImageRgba8(_) => image_object.raw_pixels(),
-        };
-        let image_size =
-            (::std::mem::size_of::<u8>() as u32 * image_width * image_height * 4) as vk::DeviceSize;
-        let mip_levels = ((::std::cmp::max(image_width, image_height) as f32)
-            .log2()
-            .floor() as u32)
-            + 1;
-
-        if image_size <= 0 {
-            panic!("Failed to load texture image!")
-        }
-
-        let (staging_buffer, staging_buffer_memory) = share::create_buffer(
-            device,
-            image_size,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-            device_memory_properties,
-        );
-
-        unsafe {
-            let data_ptr = device
-                .map_memory(
-                    staging_buffer_memory,
-                    0,
-                    image_size,
-                    vk::MemoryMapFlags::empty(),
-                )
-                .expect("Failed to Map Memory") as *mut u8;
-
-            data_ptr.copy_from_nonoverlapping(image_data.as_ptr(), image_data.len());
-
-            device.unmap_memory(staging_buffer_memory);
+    ) -> VkTutorialResult<(vk::Image, vk::DeviceMemory, u32)> {
+        let image_format = vk::Format::R8G8B8A8_SRGB;
+        let mut mip_chain = mipmap::load_mip_chain(image_path)?; // this function is slow in debug mode.
+        let (image_width, image_height) = (mip_chain[0].width(), mip_chain[0].height());
+        let mip_levels = mipmap::mip_level_count(image_width, image_height);
+
+        let is_blitted = mip_chain.len() < mip_levels as usize
+            && mipmap::is_linear_blit_supported(instance, physical_device, image_format);
+        if is_blitted {
+            mip_chain.truncate(1);
+        } else if mip_chain.len() < mip_levels as usize {
+            let missing_levels = mip_levels - mip_chain.len() as u32;
+            let generated_chain = mipmap::generate_mip_chain(
+                mip_chain.last().unwrap(),
+                missing_levels + 1,
+                mipmap::CPU_MIPMAP_FILTER,
+            );
+            mip_chain.extend(generated_chain.into_iter().skip(1));
         }
 
         let (texture_image, texture_image_memory) = share::v1::create_image(
@@ -379,52 +409,70 @@ impl VulkanApp28 {
             image_height,
             mip_levels,
             vk::SampleCountFlags::TYPE_1,
-            vk::Format::R8G8B8A8_UNORM,
+            image_format,
             vk::ImageTiling::OPTIMAL,
             vk::ImageUsageFlags::TRANSFER_SRC
                 | vk::ImageUsageFlags::TRANSFER_DST
                 | vk::ImageUsageFlags::SAMPLED,
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
             device_memory_properties,
-        );
+        )?;
 
-        share::v1::transition_image_layout(
+        let levels: Vec<(u32, u32, &[u8])> = mip_chain
+            .iter()
+            .map(|mip| (mip.width(), mip.height(), &**mip))
+            .collect();
+        let uploaded = share::v1::transition_image_layout(
             device,
             command_pool,
             submit_queue,
             texture_image,
-            vk::Format::R8G8B8A8_UNORM,
+            image_format,
             vk::ImageLayout::UNDEFINED,
             vk::ImageLayout::TRANSFER_DST_OPTIMAL,
             mip_levels,
-        );
-
-        share::copy_buffer_to_image(
-            device,
-            command_pool,
-            submit_queue,
-            staging_buffer,
-            texture_image,
-            image_width,
-            image_height,
-        );
-
-        VulkanApp28::generate_mipmaps(
-            device,
-            command_pool,
-            submit_queue,
-            texture_image,
-            image_width,
-            image_height,
-            mip_levels,
-        );
+        )
+        .and_then(|()| {
+            share::texture::copy_levels_to_image(
+                device,
+                command_pool,
+                submit_queue,
+                device_memory_properties,
+                texture_image,
+                &levels,
+            )
+        })
+        .and_then(|()| {
+            if is_blitted {
+                VulkanApp28::generate_mipmaps(
+                    device,
+                    command_pool,
+                    submit_queue,
+                    texture_image,
+                    image_width,
+                    image_height,
+                    mip_levels,
+                )
+            } else {
+                share::v1::transition_image_layout(
+                    device,
+                    command_pool,
+                    submit_queue,
+                    texture_image,
+                    image_format,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    mip_levels,
+                )
+            }
+        });
 
-        unsafe {
-            device.destroy_buffer(staging_buffer, None);
-            device.free_memory(staging_buffer_memory, None);
+        if let Err(error) = uploaded {
+            share::destroy_image(device, texture_image, texture_image_memory);
+            return Err(error);
         }
 
-        (texture_image, texture_image_memory, mip_levels)
+        Ok((texture_image, texture_image_memory, mip_levels))
     }
 
     fn generate_mipmaps(
@@ -435,8 +483,8 @@ impl VulkanApp28 {
         tex_width: u32,
         tex_height: u32,
         mip_levels: u32,
-    ) {
-        let command_buffer = share::begin_single_time_command(device, command_pool);
+    ) -> VkTutorialResult<()> {
+        let command_buffer = share::begin_single_time_command(device, command_pool)?;
 
         let mut image_barrier = vk::ImageMemoryBarrier {
             s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
@@ -561,28 +609,17 @@ impl VulkanApp28 {
             );
         }
 
-        share::end_single_time_command(device, command_pool, submit_queue, command_buffer);
-    }
+        share::end_single_time_command(device, command_pool, submit_queue, command_buffer)?;
 
-    fn check_mipmap_support(
-        instance: &ash::Instance,
-        physcial_device: vk::PhysicalDevice,
-        image_format: vk::Format,
-    ) {
-        let format_properties = unsafe {
-            instance.get_physical_device_format_properties(physcial_device, image_format)
-        };
-
-        let is_sample_image_filter_linear_support = format_properties
-            .optimal_tiling_features
-            .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR);
-
-        if is_sample_image_filter_linear_support == false {
-            panic!("Texture Image format does not support linear blitting!")
-        }
+        Ok(())
     }
 
-    fn create_texture_sampler(device: &ash::Device, mip_levels: u32) -> vk::Sampler {
+    fn create_texture_sampler(
+        device: &ash::Device,
+        mip_levels: u32,
+        device_max_anisotropy: Option<f32>,
+    ) -> vk::Sampler {
+        // the anisotropic filtering is an optional feature, `share::create_logical_device` enables it when supported.
         let sampler_create_info = vk::SamplerCreateInfo {
             s_type: vk::StructureType::SAMPLER_CREATE_INFO,
             p_next: ptr::null(),
@@ -592,8 +629,13 @@ impl VulkanApp28 {
             address_mode_u: vk::SamplerAddressMode::REPEAT,
             address_mode_v: vk::SamplerAddressMode::REPEAT,
             address_mode_w: vk::SamplerAddressMode::REPEAT,
-            anisotropy_enable: vk::TRUE,
-            max_anisotropy: 16.0,
+            anisotropy_enable: if device_max_anisotropy.is_some() {
+                vk::TRUE
+            } else {
+                vk::FALSE
+            },
+            max_anisotropy: device_max_anisotropy
+                .map_or(1.0, |max_anisotropy| max_anisotropy.min(16.0)),
             compare_enable: vk::FALSE,
             compare_op: vk::CompareOp::ALWAYS,
             mipmap_mode: vk::SamplerMipmapMode::LINEAR,
@@ -619,7 +661,7 @@ impl VulkanApp28 {
         device: &ash::Device,
         physcial_device: vk::PhysicalDevice,
         surface_format: vk::Format,
-    ) -> vk::RenderPass {
+    ) -> VkTutorialResult<vk::RenderPass> {
         let color_attachment = vk::AttachmentDescription {
             flags: vk::AttachmentDescriptionFlags::empty(),
             format: surface_format,
@@ -634,7 +676,7 @@ impl VulkanApp28 {
 
         let depth_attachment = vk::AttachmentDescription {
             flags: vk::AttachmentDescriptionFlags::empty(),
-            format: share::find_depth_format(instance, physcial_device),
+            format: share::find_depth_format(instance, physcial_device)?,
             samples: vk::SampleCountFlags::TYPE_1,
             load_op: vk::AttachmentLoadOp::CLEAR,
             store_op: vk::AttachmentStoreOp::DONT_CARE,
@@ -692,11 +734,11 @@ impl VulkanApp28 {
             p_dependencies: subpass_dependencies.as_ptr(),
         };
 
-        unsafe {
+        Ok(unsafe {
             device
                 .create_render_pass(&renderpass_create_info, None)
                 .expect("Failed to create render pass!")
-        }
+        })
     }
 
     fn create_framebuffers(
@@ -737,18 +779,15 @@ impl VulkanApp28 {
 
     fn create_graphics_pipeline(
         device: &ash::Device,
+        pipeline_cache: vk::PipelineCache,
         render_pass: vk::RenderPass,
         swapchain_extent: vk::Extent2D,
         ubo_set_layout: vk::DescriptorSetLayout,
-    ) -> (vk::Pipeline, vk::PipelineLayout) {
-        let vert_shader_module = share::create_shader_module(
-            device,
-            include_bytes!("../../shaders/spv/26-shader-depth.vert.spv").to_vec(),
-        );
-        let frag_shader_module = share::create_shader_module(
-            device,
-            include_bytes!("../../shaders/spv/26-shader-depth.frag.spv").to_vec(),
-        );
+    ) -> VkTutorialResult<(vk::Pipeline, vk::PipelineLayout)> {
+        let vert_shader_code = include_bytes!("../../shaders/spv/26-shader-depth.vert.spv");
+        let vert_shader_module = share::create_shader_module(device, vert_shader_code.to_vec())?;
+        let frag_shader_code = include_bytes!("../../shaders/spv/26-shader-depth.frag.spv");
+        let frag_shader_module = share::create_shader_module(device, frag_shader_code.to_vec())?;
 
         let main_function_name = CString::new("main").unwrap(); // the beginning function name in shader code.
 
@@ -777,6 +816,7 @@ impl VulkanApp28 {
 
         let binding_description = VertexV3::get_binding_descriptions();
         let attribute_description = VertexV3::get_attribute_descriptions();
+        ShaderReflection::new(vert_shader_code)?.validate_vertex_input(&attribute_description)?;
 
         let vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo {
             s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
@@ -936,11 +976,7 @@ impl VulkanApp28 {
 
         let graphics_pipelines = unsafe {
             device
-                .create_graphics_pipelines(
-                    vk::PipelineCache::null(),
-                    &graphic_pipeline_create_infos,
-                    None,
-                )
+                .create_graphics_pipelines(pipeline_cache, &graphic_pipeline_create_infos, None)
                 .expect("Failed to create Graphics Pipeline!.")
         };
 
@@ -949,7 +985,7 @@ impl VulkanApp28 {
             device.destroy_shader_module(frag_shader_module, None);
         }
 
-        (graphics_pipelines[0], pipeline_layout)
+        Ok((graphics_pipelines[0], pipeline_layout))
     }
 
     fn create_command_buffers(
@@ -1135,8 +1171,12 @@ impl Drop for VulkanApp28 {
 
             self.device.destroy_command_pool(self.command_pool, None);
 
+            self.pipeline_cache.destroy();
+
             self.device.destroy_device(None);
-            self.surface_loader.destroy_surface(self.surface, None);
+            if self.window.is_some() {
+                self.surface_loader.destroy_surface(self.surface, None);
+            }
 
             if VALIDATION.is_enable {
                 self.debug_utils_loader
@@ -1148,7 +1188,7 @@ impl Drop for VulkanApp28 {
 }
 
 impl VulkanApp for VulkanApp28 {
-    fn draw_frame(&mut self, delta_time: f32) {
+    fn draw_frame(&mut self, delta_time: f32) -> VkTutorialResult<()> {
         let wait_fences = [self.in_flight_fences[self.current_frame]];
 
         unsafe {
@@ -1158,18 +1198,15 @@ impl VulkanApp for VulkanApp28 {
         }
 
         let (image_index, _is_sub_optimal) = unsafe {
-            let result = self.swapchain_loader.acquire_next_image(
-                self.swapchain,
+            let result = self.render_target.acquire_next_image(
                 std::u64::MAX,
                 self.image_available_semaphores[self.current_frame],
-                vk::Fence::null(),
             );
             match result {
                 Ok(image_index) => image_index,
                 Err(vk_result) => match vk_result {
                     vk::Result::ERROR_OUT_OF_DATE_KHR => {
-                        self.recreate_swapchain();
-                        return;
+                        return self.recreate_swapchain();
                     }
                     _ => panic!("Failed to acquire Swap Chain Image!"),
                 },
@@ -1178,6 +1215,13 @@ impl VulkanApp for VulkanApp28 {
 
         self.update_uniform_buffer(image_index as usize, delta_time);
 
+        let mut command_buffers = vec![self.command_buffers[image_index as usize]];
+        command_buffers.extend(self.render_target.record_capture(
+            self.command_pool,
+            image_index,
+            self.in_flight_fences[self.current_frame],
+        )?);
+
         let wait_semaphores = [self.image_available_semaphores[self.current_frame]];
         let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
         let signal_semaphores = [self.render_finished_semaphores[self.current_frame]];
@@ -1188,8 +1232,8 @@ impl VulkanApp for VulkanApp28 {
             wait_semaphore_count: wait_semaphores.len() as u32,
             p_wait_semaphores: wait_semaphores.as_ptr(),
             p_wait_dst_stage_mask: wait_stages.as_ptr(),
-            command_buffer_count: 1,
-            p_command_buffers: &self.command_buffers[image_index as usize],
+            command_buffer_count: command_buffers.len() as u32,
+            p_command_buffers: command_buffers.as_ptr(),
             signal_semaphore_count: signal_semaphores.len() as u32,
             p_signal_semaphores: signal_semaphores.as_ptr(),
         }];
@@ -1208,22 +1252,9 @@ impl VulkanApp for VulkanApp28 {
                 .expect("Failed to execute queue submit.");
         }
 
-        let swapchains = [self.swapchain];
-
-        let present_info = vk::PresentInfoKHR {
-            s_type: vk::StructureType::PRESENT_INFO_KHR,
-            p_next: ptr::null(),
-            wait_semaphore_count: 1,
-            p_wait_semaphores: signal_semaphores.as_ptr(),
-            swapchain_count: 1,
-            p_swapchains: swapchains.as_ptr(),
-            p_image_indices: &image_index,
-            p_results: ptr::null_mut(),
-        };
-
         let result = unsafe {
-            self.swapchain_loader
-                .queue_present(self.present_queue, &present_info)
+            self.render_target
+                .queue_present(self.present_queue, &signal_semaphores, image_index)
         };
 
         let is_resized = match result {
@@ -1235,13 +1266,15 @@ impl VulkanApp for VulkanApp28 {
         };
         if is_resized {
             self.is_framebuffer_resized = false;
-            self.recreate_swapchain();
+            self.recreate_swapchain()?;
         }
 
         self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+
+        Ok(())
     }
 
-    fn recreate_swapchain(&mut self) {
+    fn recreate_swapchain(&mut self) -> VkTutorialResult<()> {
         // parameters -------------
         let surface_suff = SurfaceStuff {
             surface_loader: self.surface_loader.clone(),
@@ -1258,16 +1291,16 @@ impl VulkanApp for VulkanApp28 {
         };
         self.cleanup_swapchain();
 
-        let swapchain_stuff = share::create_swapchain(
+        let swapchain_stuff = share::create_render_target(
             &self.instance,
             &self.device,
             self.physical_device,
-            &self.window,
+            self.window.as_ref(),
             &surface_suff,
             &self.queue_family,
-        );
-        self.swapchain_loader = swapchain_stuff.swapchain_loader;
-        self.swapchain = swapchain_stuff.swapchain;
+            self.graphics_queue,
+        )?;
+        self.render_target = swapchain_stuff.render_target;
         self.swapchain_images = swapchain_stuff.swapchain_images;
         self.swapchain_format = swapchain_stuff.swapchain_format;
         self.swapchain_extent = swapchain_stuff.swapchain_extent;
@@ -1276,19 +1309,20 @@ impl VulkanApp for VulkanApp28 {
             &self.device,
             self.swapchain_format,
             &self.swapchain_images,
-        );
+        )?;
         self.render_pass = VulkanApp28::create_render_pass(
             &self.instance,
             &self.device,
             self.physical_device,
             self.swapchain_format,
-        );
+        )?;
         let (graphics_pipeline, pipeline_layout) = VulkanApp28::create_graphics_pipeline(
             &self.device,
+            self.pipeline_cache.cache,
             self.render_pass,
             swapchain_stuff.swapchain_extent,
             self.ubo_layout,
-        );
+        )?;
         self.graphics_pipeline = graphics_pipeline;
         self.pipeline_layout = pipeline_layout;
 
@@ -1301,7 +1335,7 @@ impl VulkanApp for VulkanApp28 {
             self.swapchain_extent,
             &self.memory_properties,
             vk::SampleCountFlags::TYPE_1,
-        );
+        )?;
         self.depth_image = depth_resources.0;
         self.depth_image_view = depth_resources.1;
         self.depth_image_memory = depth_resources.2;
@@ -1326,9 +1360,11 @@ impl VulkanApp for VulkanApp28 {
             &self.descriptor_sets,
             self.indices.len() as u32,
         );
+
+        Ok(())
     }
 
-    fn cleanup_swapchain(&self) {
+    fn cleanup_swapchain(&mut self) {
         unsafe {
             self.device.destroy_image_view(self.depth_image_view, None);
             self.device.destroy_image(self.depth_image, None);
@@ -1346,8 +1382,7 @@ impl VulkanApp for VulkanApp28 {
             for &image_view in self.swapchain_imageviews.iter() {
                 self.device.destroy_image_view(image_view, None);
             }
-            self.swapchain_loader
-                .destroy_swapchain(self.swapchain, None);
+            self.render_target.destroy(&self.device);
         }
     }
 
@@ -1363,14 +1398,18 @@ impl VulkanApp for VulkanApp28 {
         self.is_framebuffer_resized = true;
     }
 
-    fn window_ref(&self) -> &winit::window::Window {
-        &self.window
+    fn window_ref(&self) -> Option<&winit::window::Window> {
+        self.window.as_ref()
+    }
+
+    fn render_target_mut(&mut self) -> &mut RenderTarget {
+        &mut self.render_target
     }
 }
 
 fn main() {
     let program_proc = ProgramProc::new();
-    let vulkan_app = VulkanApp28::new(&program_proc.event_loop);
+    let vulkan_app = VulkanApp28::new(&program_proc).expect("Failed to initialize the app!");
 
     program_proc.main_loop(vulkan_app);
 }
@@ -1,8 +1,55 @@
 use vulkan_tutorial_rust::{
-    utility, // the mod define some fixed functions that have been learned before.
+    utility::allocator::{
+        self,
+        Allocation,
+        MemoryAllocator,
+    },
     utility::constants::*,
     utility::debug::*,
+    utility::descriptor::{
+        DescriptorAllocator,
+        DescriptorLayoutCache,
+    },
+    utility::error::{
+        VkTutorialError,
+        VkTutorialResult,
+    },
+    utility::handles::{
+        self,
+        Device,
+        Fence,
+        Framebuffer,
+        ImageView,
+        Instance,
+        Pipeline,
+        PipelineLayout,
+        RenderPass,
+        Semaphore,
+        ShaderModule,
+    },
+    utility::pipeline_cache::PipelineCache,
+    utility::reflection::ShaderReflection,
+    utility::render_target::RenderTarget,
+    utility::sampler::{
+        SamplerCache,
+        SamplerDescription,
+    },
     utility::share,
+    utility::share::frame::{
+        DrawSubmission,
+        FrameContexts,
+    },
+    utility::share::mipmap,
+    utility::share::msaa::{
+        self,
+        DepthResolve,
+        MsaaAttachments,
+    },
+    utility::share::uniform::UniformRing,
+    utility::share::upload::{
+        UploadHandle,
+        UploadManager,
+    },
     utility::structures::*,
     utility::window::{
         ProgramProc,
@@ -19,238 +66,272 @@ use cgmath::{
     Point3,
     Vector3,
 };
-use image::GenericImageView;
 
 use std::ffi::CString;
 use std::path::Path;
 use std::ptr;
+use std::sync::Arc;
 
 // Constants
 const WINDOW_TITLE: &'static str = "29.Multi-Sampling";
 const MODEL_PATH: &'static str = "assets/chalet.obj";
 const TEXTURE_PATH: &'static str = "assets/chalet.jpg";
+/// The sample count at startup, clamped to the counts supported by the device.
+const MSAA_SAMPLES: u32 = 8;
+/// The size of the ring staging buffer of the uploads.
+const UPLOAD_STAGING_SIZE: vk::DeviceSize = 16 * 1024 * 1024;
 
+/// Built with the owned handles of `utility::handles`, which destroy themselves when the app is dropped.
+/// The allocations, the caches, the swapchain, the surface and the debug messenger are destroyed by hand.
 struct VulkanApp29 {
-    window: winit::window::Window,
+    window: Option<winit::window::Window>,
 
     // vulkan stuff
     _entry: ash::Entry,
-    instance: ash::Instance,
+    instance: Arc<Instance>,
     surface_loader: ash::extensions::khr::Surface,
     surface: vk::SurfaceKHR,
     debug_utils_loader: ash::extensions::ext::DebugUtils,
     debug_merssager: vk::DebugUtilsMessengerEXT,
 
     physical_device: vk::PhysicalDevice,
-    memory_properties: vk::PhysicalDeviceMemoryProperties,
-    device: ash::Device,
+    device: Arc<Device>,
+    memory_allocator: MemoryAllocator,
 
     queue_family: QueueFamilyIndices,
     graphics_queue: vk::Queue,
     present_queue: vk::Queue,
 
-    swapchain_loader: ash::extensions::khr::Swapchain,
-    swapchain: vk::SwapchainKHR,
+    render_target: RenderTarget,
     swapchain_images: Vec<vk::Image>,
     swapchain_format: vk::Format,
     swapchain_extent: vk::Extent2D,
-    swapchain_imageviews: Vec<vk::ImageView>,
-    swapchain_framebuffers: Vec<vk::Framebuffer>,
+    swapchain_imageviews: Vec<ImageView>,
+    swapchain_framebuffers: Vec<Framebuffer>,
 
-    render_pass: vk::RenderPass,
+    render_pass: RenderPass,
+    descriptor_layout_cache: DescriptorLayoutCache,
     ubo_layout: vk::DescriptorSetLayout,
-    pipeline_layout: vk::PipelineLayout,
-    graphics_pipeline: vk::Pipeline,
-
-    color_image: vk::Image,
-    color_image_view: vk::ImageView,
-    color_image_memory: vk::DeviceMemory,
-
-    depth_image: vk::Image,
-    depth_image_view: vk::ImageView,
-    depth_image_memory: vk::DeviceMemory,
+    ubo_layout_bindings: Vec<vk::DescriptorSetLayoutBinding>,
+    pipeline_cache: PipelineCache,
+    pipeline_layout: PipelineLayout,
+    graphics_pipeline: Pipeline,
 
+    msaa_attachments: MsaaAttachments,
     msaa_samples: vk::SampleCountFlags,
+    max_msaa_samples: vk::SampleCountFlags,
+    /// `None` when the device can not resolve the depth samples, they are dropped then.
+    depth_resolve: Option<DepthResolve>,
 
     _mip_levels: u32,
     texture_image: vk::Image,
-    texture_image_view: vk::ImageView,
+    texture_image_view: ImageView,
+    /// Owned by `sampler_cache`.
     texture_sampler: vk::Sampler,
-    texture_image_memory: vk::DeviceMemory,
+    sampler_cache: SamplerCache,
+    texture_image_allocation: Allocation,
 
     _vertices: Vec<VertexV3>,
     indices: Vec<u32>,
 
     vertex_buffer: vk::Buffer,
-    vertex_buffer_memory: vk::DeviceMemory,
+    vertex_buffer_allocation: Allocation,
     index_buffer: vk::Buffer,
-    index_buffer_memory: vk::DeviceMemory,
+    index_buffer_allocation: Allocation,
 
     uniform_transform: UniformBufferObject,
-    uniform_buffers: Vec<vk::Buffer>,
-    uniform_buffers_memory: Vec<vk::DeviceMemory>,
+    uniform_ring: UniformRing<UniformBufferObject>,
 
-    descriptor_pool: vk::DescriptorPool,
-    descriptor_sets: Vec<vk::DescriptorSet>,
+    /// One per frame in flight, reset when the frame begins to allocate the set of the frame again.
+    descriptor_allocators: Vec<DescriptorAllocator>,
+    /// The set of the current frame, which binds its uniform slot with a dynamic offset.
+    descriptor_set: vk::DescriptorSet,
 
-    command_pool: vk::CommandPool,
-    command_buffers: Vec<vk::CommandBuffer>,
+    upload_manager: UploadManager,
+    /// The index buffer is uploaded after the texture and the vertex buffer, so all are resident with it.
+    mesh_upload: UploadHandle,
+    frame_contexts: FrameContexts,
 
-    image_available_semaphores: Vec<vk::Semaphore>,
-    render_finished_semaphores: Vec<vk::Semaphore>,
-    in_flight_fences: Vec<vk::Fence>,
+    image_available_semaphores: Vec<Semaphore>,
+    render_finished_semaphores: Vec<Semaphore>,
+    in_flight_fences: Vec<Fence>,
     current_frame: usize,
 
     is_framebuffer_resized: bool,
 }
 
 impl VulkanApp29 {
-    pub fn new(event_loop: &winit::event_loop::EventLoop<()>) -> VulkanApp29 {
-        let window =
-            utility::window::init_window(&event_loop, WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
+    pub fn new(program_proc: &ProgramProc) -> VkTutorialResult<VulkanApp29> {
+        let window = program_proc.init_window(WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
 
         // init vulkan stuff
         let entry = ash::Entry::new().unwrap();
-        let instance = share::create_instance(
+        let instance = share::owned::create_instance(
             &entry,
             WINDOW_TITLE,
             VALIDATION.is_enable,
             &VALIDATION.required_validation_layers.to_vec(),
-        );
-        let surface_stuff =
-            share::create_surface(&entry, &instance, &window, WINDOW_WIDTH, WINDOW_HEIGHT);
+            window.as_ref(),
+        )?;
+        let surface_stuff = share::create_surface(
+            &entry,
+            &instance,
+            window.as_ref(),
+            WINDOW_WIDTH,
+            WINDOW_HEIGHT,
+        )?;
         let (debug_utils_loader, debug_merssager) =
             setup_debug_utils(VALIDATION.is_enable, &entry, &instance);
         let physical_device =
-            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS);
-        let msaa_samples = VulkanApp29::get_max_usable_sample_count(&instance, physical_device);
+            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS)?;
+        let max_msaa_samples = msaa::get_max_usable_sample_count(&instance, physical_device);
+        let msaa_samples = msaa::clamp_sample_count(MSAA_SAMPLES, max_msaa_samples);
+        VulkanApp29::show_msaa_samples(window.as_ref(), msaa_samples);
         let physical_device_memory_properties =
             unsafe { instance.get_physical_device_memory_properties(physical_device) };
-        let (device, queue_family) = share::create_logical_device(
+        let is_depth_resolve_supported = DepthResolve::is_supported(&instance, physical_device)?;
+        let (device, queue_family) = share::owned::create_logical_device_with_extensions(
             &instance,
             physical_device,
             &VALIDATION,
             &DEVICE_EXTENSIONS,
+            if is_depth_resolve_supported {
+                &msaa::DEPTH_RESOLVE_EXTENSIONS
+            } else {
+                &[]
+            },
             &surface_stuff,
+        )?;
+        let depth_resolve = if is_depth_resolve_supported {
+            Some(DepthResolve::new(&instance, &device))
+        } else {
+            None
+        };
+        let mut memory_allocator = MemoryAllocator::new(
+            &instance,
+            &device,
+            physical_device,
+            allocator::DEFAULT_BLOCK_SIZE,
         );
         let graphics_queue =
             unsafe { device.get_device_queue(queue_family.graphics_family.unwrap(), 0) };
         let present_queue =
             unsafe { device.get_device_queue(queue_family.present_family.unwrap(), 0) };
-        let swapchain_stuff = share::create_swapchain(
+        let swapchain_stuff = share::create_render_target(
             &instance,
             &device,
             physical_device,
-            &window,
+            window.as_ref(),
             &surface_stuff,
             &queue_family,
-        );
-        let swapchain_imageviews = share::v1::create_image_views(
+            graphics_queue,
+        )?;
+        let swapchain_imageviews = share::owned::create_image_views(
             &device,
             swapchain_stuff.swapchain_format,
             &swapchain_stuff.swapchain_images,
-        );
-        let render_pass = VulkanApp29::create_render_pass(
-            &instance,
+        )?;
+        let render_pass = RenderPass::new(
             &device,
-            physical_device,
-            swapchain_stuff.swapchain_format,
-            msaa_samples,
+            msaa::create_render_pass(
+                &instance,
+                &device,
+                physical_device,
+                swapchain_stuff.swapchain_format,
+                msaa_samples,
+                depth_resolve.as_ref(),
+            )?,
         );
-        let ubo_layout = share::v2::create_descriptor_set_layout(&device);
+        let mut descriptor_layout_cache = DescriptorLayoutCache::new(&device);
+        let ubo_layout_bindings = share::v2::descriptor_set_layout_bindings(true)?;
+        let ubo_layout = descriptor_layout_cache.get(&ubo_layout_bindings)?;
+        let pipeline_cache = PipelineCache::new(&instance, physical_device, &device)?;
         let (graphics_pipeline, pipeline_layout) = VulkanApp29::create_graphics_pipeline(
             &device,
-            render_pass,
+            pipeline_cache.cache,
+            &render_pass,
             swapchain_stuff.swapchain_extent,
             ubo_layout,
             msaa_samples,
-        );
-        let command_pool = share::v1::create_command_pool(&device, &queue_family);
-        let (color_image, color_image_view, color_image_memory) =
-            VulkanApp29::create_color_resources(
-                &device,
-                swapchain_stuff.swapchain_format,
-                swapchain_stuff.swapchain_extent,
-                &physical_device_memory_properties,
-                msaa_samples,
-            );
-        let (depth_image, depth_image_view, depth_image_memory) = share::v1::create_depth_resources(
+        )?;
+        let mut upload_manager = UploadManager::new(
+            &device,
+            &physical_device_memory_properties,
+            &queue_family,
+            graphics_queue,
+            UPLOAD_STAGING_SIZE,
+        )?;
+        let msaa_attachments = MsaaAttachments::new(
             &instance,
             &device,
             physical_device,
-            command_pool,
-            graphics_queue,
+            &mut memory_allocator,
+            swapchain_stuff.swapchain_format,
             swapchain_stuff.swapchain_extent,
-            &physical_device_memory_properties,
             msaa_samples,
-        );
-        let swapchain_framebuffers = VulkanApp29::create_framebuffers(
-            &device,
-            render_pass,
-            &swapchain_imageviews,
-            depth_image_view,
-            color_image_view,
-            swapchain_stuff.swapchain_extent,
-        );
-        let (vertices, indices) = share::load_model(&Path::new(MODEL_PATH));
-        share::check_mipmap_support(&instance, physical_device, vk::Format::R8G8B8A8_UNORM);
-        let (texture_image, texture_image_memory, mip_levels) = VulkanApp29::create_texture_image(
-            &device,
-            command_pool,
-            graphics_queue,
-            &physical_device_memory_properties,
-            &Path::new(TEXTURE_PATH),
-        );
-        let texture_image_view =
-            share::v1::create_texture_image_view(&device, texture_image, mip_levels);
-        let texture_sampler = VulkanApp29::create_texture_sampler(&device, mip_levels);
-        let (vertex_buffer, vertex_buffer_memory) = share::v1::create_vertex_buffer(
-            &device,
-            &physical_device_memory_properties,
-            command_pool,
-            graphics_queue,
-            &vertices,
-        );
-        let (index_buffer, index_buffer_memory) = share::v1::create_index_buffer(
+            depth_resolve.is_some(),
+        )?;
+        let swapchain_framebuffers = Framebuffer::from_raws(
             &device,
-            &physical_device_memory_properties,
-            command_pool,
-            graphics_queue,
-            &indices,
-        );
-        let (uniform_buffers, uniform_buffers_memory) = share::v1::create_uniform_buffers(
-            &device,
-            &physical_device_memory_properties,
-            swapchain_stuff.swapchain_images.len(),
+            msaa::create_framebuffers(
+                &device,
+                render_pass.raw(),
+                &handles::raw_handles(&swapchain_imageviews),
+                &msaa_attachments,
+                swapchain_stuff.swapchain_extent,
+            )?,
         );
-        let descriptor_pool =
-            share::v2::create_descriptor_pool(&device, swapchain_stuff.swapchain_images.len());
-        let descriptor_sets = share::v2::create_descriptor_sets(
+        let (vertices, indices) = share::load_model(&Path::new(MODEL_PATH))?;
+        let (texture_image, texture_image_allocation, mip_levels) =
+            VulkanApp29::create_texture_image(
+                &mut memory_allocator,
+                &mut upload_manager,
+                &Path::new(TEXTURE_PATH),
+            )?;
+        let texture_image_view = ImageView::new(
             &device,
-            descriptor_pool,
-            ubo_layout,
-            &uniform_buffers,
-            texture_image_view,
-            texture_sampler,
-            swapchain_stuff.swapchain_images.len(),
+            share::v1::create_texture_image_view(&device, texture_image, mip_levels)?,
         );
-        let command_buffers = VulkanApp29::create_command_buffers(
+        let mut sampler_cache = SamplerCache::new(&instance, physical_device, &device);
+        let texture_sampler =
+            sampler_cache.get(&SamplerDescription::default().lod_range(0.0, mip_levels as f32))?;
+        let (vertex_buffer, vertex_buffer_allocation, _) = upload_manager
+            .create_device_local_buffer(
+                &mut memory_allocator,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                vk::PipelineStageFlags::VERTEX_INPUT,
+                vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
+                &vertices,
+            )?;
+        let (index_buffer, index_buffer_allocation, mesh_upload) = upload_manager
+            .create_device_local_buffer(
+                &mut memory_allocator,
+                vk::BufferUsageFlags::INDEX_BUFFER,
+                vk::PipelineStageFlags::VERTEX_INPUT,
+                vk::AccessFlags::INDEX_READ,
+                &indices,
+            )?;
+        upload_manager.flush()?;
+        let uniform_ring = UniformRing::new(
+            &instance,
+            physical_device,
+            &mut memory_allocator,
+            MAX_FRAMES_IN_FLIGHT,
+            1,
+        )?;
+        // the sets are allocated by `allocate_frame_descriptor_set` when each frame begins.
+        let descriptor_allocators = (0..MAX_FRAMES_IN_FLIGHT)
+            .map(|_| DescriptorAllocator::new(&device))
+            .collect();
+        let frame_contexts = FrameContexts::new(
             &device,
-            command_pool,
-            graphics_pipeline,
-            &swapchain_framebuffers,
-            render_pass,
-            swapchain_stuff.swapchain_extent,
-            vertex_buffer,
-            index_buffer,
-            pipeline_layout,
-            &descriptor_sets,
-            indices.len() as u32,
-        );
-        let sync_ojbects = share::v1::create_sync_objects(&device, MAX_FRAMES_IN_FLIGHT);
+            queue_family.graphics_family.unwrap(),
+            MAX_FRAMES_IN_FLIGHT,
+        )?;
+        let sync_ojbects = share::owned::create_sync_objects(&device, MAX_FRAMES_IN_FLIGHT)?;
 
         // cleanup(); the 'drop' function will take care of it.
-        VulkanApp29 {
+        Ok(VulkanApp29 {
             // winit stuff
             window,
 
@@ -263,49 +344,47 @@ impl VulkanApp29 {
             debug_merssager,
 
             physical_device,
-            memory_properties: physical_device_memory_properties,
             device,
+            memory_allocator,
 
             queue_family,
             graphics_queue,
             present_queue,
 
-            swapchain_loader: swapchain_stuff.swapchain_loader,
-            swapchain: swapchain_stuff.swapchain,
+            render_target: swapchain_stuff.render_target,
             swapchain_format: swapchain_stuff.swapchain_format,
             swapchain_images: swapchain_stuff.swapchain_images,
             swapchain_extent: swapchain_stuff.swapchain_extent,
             swapchain_imageviews,
             swapchain_framebuffers,
 
+            pipeline_cache,
             pipeline_layout,
+            descriptor_layout_cache,
             ubo_layout,
+            ubo_layout_bindings,
             render_pass,
             graphics_pipeline,
 
-            color_image,
-            color_image_view,
-            color_image_memory,
-
-            depth_image,
-            depth_image_view,
-            depth_image_memory,
-
+            msaa_attachments,
             msaa_samples,
+            max_msaa_samples,
+            depth_resolve,
 
             _mip_levels: mip_levels,
             texture_image,
             texture_image_view,
             texture_sampler,
-            texture_image_memory,
+            sampler_cache,
+            texture_image_allocation,
 
             _vertices: vertices,
             indices,
 
             vertex_buffer,
-            vertex_buffer_memory,
+            vertex_buffer_allocation,
             index_buffer,
-            index_buffer_memory,
+            index_buffer_allocation,
 
             uniform_transform: UniformBufferObject {
                 model: Matrix4::from_angle_z(Deg(90.0)),
@@ -326,14 +405,14 @@ impl VulkanApp29 {
                     proj
                 },
             },
-            uniform_buffers,
-            uniform_buffers_memory,
+            uniform_ring,
 
-            descriptor_pool,
-            descriptor_sets,
+            descriptor_allocators,
+            descriptor_set: vk::DescriptorSet::null(),
 
-            command_pool,
-            command_buffers,
+            upload_manager,
+            mesh_upload,
+            frame_contexts,
 
             image_available_semaphores: sync_ojbects.image_available_semaphores,
             render_finished_semaphores: sync_ojbects.render_finished_semaphores,
@@ -341,373 +420,31 @@ impl VulkanApp29 {
             current_frame: 0,
 
             is_framebuffer_resized: false,
-        }
-    }
-
-    fn get_max_usable_sample_count(
-        instance: &ash::Instance,
-        physical_device: vk::PhysicalDevice,
-    ) -> vk::SampleCountFlags {
-        let physical_device_properties =
-            unsafe { instance.get_physical_device_properties(physical_device) };
-
-        let count = std::cmp::min(
-            physical_device_properties
-                .limits
-                .framebuffer_color_sample_counts,
-            physical_device_properties
-                .limits
-                .framebuffer_depth_sample_counts,
-        );
-
-        if count.contains(vk::SampleCountFlags::TYPE_64) {
-            return vk::SampleCountFlags::TYPE_64;
-        }
-        if count.contains(vk::SampleCountFlags::TYPE_32) {
-            return vk::SampleCountFlags::TYPE_32;
-        }
-        if count.contains(vk::SampleCountFlags::TYPE_16) {
-            return vk::SampleCountFlags::TYPE_16;
-        }
-        if count.contains(vk::SampleCountFlags::TYPE_8) {
-            return vk::SampleCountFlags::TYPE_8;
-        }
-        if count.contains(vk::SampleCountFlags::TYPE_4) {
-            return vk::SampleCountFlags::TYPE_4;
-        }
-        if count.contains(vk::SampleCountFlags::TYPE_2) {
-            return vk::SampleCountFlags::TYPE_2;
-        }
-
-        vk::SampleCountFlags::TYPE_1
-    }
-
-    fn create_color_resources(
-        device: &ash::Device,
-        swapchain_format: vk::Format,
-        swapchain_extent: vk::Extent2D,
-        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
-        msaa_samples: vk::SampleCountFlags,
-    ) -> (vk::Image, vk::ImageView, vk::DeviceMemory) {
-        let color_format = swapchain_format;
-
-        let (color_image, color_image_memory) = share::v1::create_image(
-            device,
-            swapchain_extent.width,
-            swapchain_extent.height,
-            1,
-            msaa_samples,
-            color_format,
-            vk::ImageTiling::OPTIMAL,
-            vk::ImageUsageFlags::TRANSIENT_ATTACHMENT | vk::ImageUsageFlags::COLOR_ATTACHMENT,
-            vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            device_memory_properties,
-        );
-
-        let color_image_view = share::v1::create_image_view(
-            device,
-            color_image,
-            color_format,
-            vk::ImageAspectFlags::COLOR,
-            1,
-        );
-
-        (color_image, color_image_view, color_image_memory)
-    }
-
-    fn create_texture_image(
-        device: &ash::Device,
-        command_pool: vk::CommandPool,
-        submit_queue: vk::Queue,
-        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
-        image_path: &Path,
-    ) -> (vk::Image, vk::DeviceMemory, u32) {
-        let mut image_object = image::open(image_path).unwrap(); // this function is slow in debug mode.
-        image_object = image_object.flipv();
-        let (image_width, image_height) = (image_object.width(), image_object.height());
-        let image_data = match &image_object {
-            image::DynamicImage::ImageBgr8(_)
-            | image::DynamicImage::ImageLuma8(_)
-            | image::DynamicImage::ImageRgb8(_) => image_object.to_rgba().into_raw(),
-            image::DynamicImage::ImageBgra8(_)
-            | image::DynamicImage::ImageLumaA8(_)
-            | image::DynamicImage::ImageRgba8(_) => image_object.raw_pixels(),
-        };
-        let image_size =
-            (::std::mem::size_of::<u8>() as u32 * image_width * image_height * 4) as vk::DeviceSize;
-        let mip_levels = ((::std::cmp::max(image_width, image_height) as f32)
-            .log2()
-            .floor() as u32)
-            + 1;
-
-        if image_size <= 0 {
-            panic!("Failed to load texture image!")
-        }
-
-        let (staging_buffer, staging_buffer_memory) = share::create_buffer(
-            device,
-            image_size,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-            device_memory_properties,
-        );
-
-        unsafe {
-            let data_ptr = device
-                .map_memory(
-                    staging_buffer_memory,
-                    0,
-                    image_size,
-                    vk::MemoryMapFlags::empty(),
-                )
-                .expect("Failed to Map Memory") as *mut u8;
-
-            data_ptr.copy_from_nonoverlapping(image_data.as_ptr(), image_data.len());
-
-            device.unmap_memory(staging_buffer_memory);
-        }
-
-        let (texture_image, texture_image_memory) = share::v1::create_image(
-            device,
-            image_width,
-            image_height,
-            mip_levels,
-            vk::SampleCountFlags::TYPE_1,
-            vk::Format::R8G8B8A8_UNORM,
-            vk::ImageTiling::OPTIMAL,
-            vk::ImageUsageFlags::TRANSFER_SRC
-                | vk::ImageUsageFlags::TRANSFER_DST
-                | vk::ImageUsageFlags::SAMPLED,
-            vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            device_memory_properties,
-        );
-
-        share::v1::transition_image_layout(
-            device,
-            command_pool,
-            submit_queue,
-            texture_image,
-            vk::Format::R8G8B8A8_UNORM,
-            vk::ImageLayout::UNDEFINED,
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            mip_levels,
-        );
-
-        share::copy_buffer_to_image(
-            device,
-            command_pool,
-            submit_queue,
-            staging_buffer,
-            texture_image,
-            image_width,
-            image_height,
-        );
-
-        share::v1::generate_mipmaps(
-            device,
-            command_pool,
-            submit_queue,
-            texture_image,
-            image_width,
-            image_height,
-            mip_levels,
-        );
-
-        unsafe {
-            device.destroy_buffer(staging_buffer, None);
-            device.free_memory(staging_buffer_memory, None);
-        }
-
-        (texture_image, texture_image_memory, mip_levels)
-    }
-
-    fn create_texture_sampler(device: &ash::Device, mip_levels: u32) -> vk::Sampler {
-        let sampler_create_info = vk::SamplerCreateInfo {
-            s_type: vk::StructureType::SAMPLER_CREATE_INFO,
-            p_next: ptr::null(),
-            flags: vk::SamplerCreateFlags::empty(),
-            mag_filter: vk::Filter::LINEAR,
-            min_filter: vk::Filter::LINEAR,
-            address_mode_u: vk::SamplerAddressMode::REPEAT,
-            address_mode_v: vk::SamplerAddressMode::REPEAT,
-            address_mode_w: vk::SamplerAddressMode::REPEAT,
-            anisotropy_enable: vk::TRUE,
-            max_anisotropy: 16.0,
-            compare_enable: vk::FALSE,
-            compare_op: vk::CompareOp::ALWAYS,
-            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
-            min_lod: 0.0,
-            max_lod: mip_levels as f32,
-            mip_lod_bias: 0.0,
-            border_color: vk::BorderColor::INT_OPAQUE_BLACK,
-            unnormalized_coordinates: vk::FALSE,
-        };
-
-        unsafe {
-            device
-                .create_sampler(&sampler_create_info, None)
-                .expect("Failed to create Sampler!")
-        }
+        })
     }
 }
 
 // Fix content -------------------------------------------------------------------------------
 impl VulkanApp29 {
-    fn create_render_pass(
-        instance: &ash::Instance,
-        device: &ash::Device,
-        physcial_device: vk::PhysicalDevice,
-        surface_format: vk::Format,
-        msaa_samples: vk::SampleCountFlags,
-    ) -> vk::RenderPass {
-        let color_attachment = vk::AttachmentDescription {
-            flags: vk::AttachmentDescriptionFlags::empty(),
-            format: surface_format,
-            samples: msaa_samples,
-            load_op: vk::AttachmentLoadOp::CLEAR,
-            store_op: vk::AttachmentStoreOp::STORE,
-            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
-            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
-            initial_layout: vk::ImageLayout::UNDEFINED,
-            final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-        };
-
-        let depth_attachment = vk::AttachmentDescription {
-            flags: vk::AttachmentDescriptionFlags::empty(),
-            format: share::find_depth_format(instance, physcial_device),
-            samples: msaa_samples,
-            load_op: vk::AttachmentLoadOp::CLEAR,
-            store_op: vk::AttachmentStoreOp::DONT_CARE,
-            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
-            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
-            initial_layout: vk::ImageLayout::UNDEFINED,
-            final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-        };
-
-        let color_attachment_resolve = vk::AttachmentDescription {
-            flags: vk::AttachmentDescriptionFlags::empty(),
-            format: surface_format,
-            samples: vk::SampleCountFlags::TYPE_1,
-            load_op: vk::AttachmentLoadOp::DONT_CARE,
-            store_op: vk::AttachmentStoreOp::STORE,
-            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
-            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
-            initial_layout: vk::ImageLayout::UNDEFINED,
-            final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
-        };
-
-        let color_attachment_ref = vk::AttachmentReference {
-            attachment: 0,
-            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-        };
-
-        let depth_attachment_ref = vk::AttachmentReference {
-            attachment: 1,
-            layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-        };
-
-        let color_attachment_resolve_ref = vk::AttachmentReference {
-            attachment: 2,
-            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-        };
-
-        let subpasses = [vk::SubpassDescription {
-            color_attachment_count: 1,
-            p_color_attachments: &color_attachment_ref,
-            p_depth_stencil_attachment: &depth_attachment_ref,
-            flags: vk::SubpassDescriptionFlags::empty(),
-            pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
-            input_attachment_count: 0,
-            p_input_attachments: ptr::null(),
-            p_resolve_attachments: &color_attachment_resolve_ref,
-            preserve_attachment_count: 0,
-            p_preserve_attachments: ptr::null(),
-        }];
-
-        let render_pass_attachments =
-            [color_attachment, depth_attachment, color_attachment_resolve];
-
-        let subpass_dependencies = [vk::SubpassDependency {
-            src_subpass: vk::SUBPASS_EXTERNAL,
-            dst_subpass: 0,
-            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-            dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-            src_access_mask: vk::AccessFlags::empty(),
-            dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ
-                | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
-            dependency_flags: vk::DependencyFlags::empty(),
-        }];
-
-        let renderpass_create_info = vk::RenderPassCreateInfo {
-            s_type: vk::StructureType::RENDER_PASS_CREATE_INFO,
-            flags: vk::RenderPassCreateFlags::empty(),
-            p_next: ptr::null(),
-            attachment_count: render_pass_attachments.len() as u32,
-            p_attachments: render_pass_attachments.as_ptr(),
-            subpass_count: subpasses.len() as u32,
-            p_subpasses: subpasses.as_ptr(),
-            dependency_count: subpass_dependencies.len() as u32,
-            p_dependencies: subpass_dependencies.as_ptr(),
-        };
-
-        unsafe {
-            device
-                .create_render_pass(&renderpass_create_info, None)
-                .expect("Failed to create render pass!")
-        }
-    }
-
-    fn create_framebuffers(
-        device: &ash::Device,
-        render_pass: vk::RenderPass,
-        swapchain_image_views: &Vec<vk::ImageView>,
-        depth_image_view: vk::ImageView,
-        color_image_view: vk::ImageView,
-        swapchain_extent: vk::Extent2D,
-    ) -> Vec<vk::Framebuffer> {
-        let mut framebuffers = vec![];
-
-        for &image_view in swapchain_image_views.iter() {
-            let attachments = [color_image_view, depth_image_view, image_view];
-
-            let framebuffer_create_info = vk::FramebufferCreateInfo {
-                s_type: vk::StructureType::FRAMEBUFFER_CREATE_INFO,
-                p_next: ptr::null(),
-                flags: vk::FramebufferCreateFlags::empty(),
-                render_pass,
-                attachment_count: attachments.len() as u32,
-                p_attachments: attachments.as_ptr(),
-                width: swapchain_extent.width,
-                height: swapchain_extent.height,
-                layers: 1,
-            };
-
-            let framebuffer = unsafe {
-                device
-                    .create_framebuffer(&framebuffer_create_info, None)
-                    .expect("Failed to create Framebuffer!")
-            };
-
-            framebuffers.push(framebuffer);
-        }
-
-        framebuffers
-    }
-
+    /// The shader modules are destroyed when they go out of scope, and so is the pipeline layout
+    /// if the pipeline can not be created.
     fn create_graphics_pipeline(
-        device: &ash::Device,
-        render_pass: vk::RenderPass,
+        device: &Arc<Device>,
+        pipeline_cache: vk::PipelineCache,
+        render_pass: &RenderPass,
         swapchain_extent: vk::Extent2D,
         ubo_set_layout: vk::DescriptorSetLayout,
         msaa_samples: vk::SampleCountFlags,
-    ) -> (vk::Pipeline, vk::PipelineLayout) {
-        let vert_shader_module = share::create_shader_module(
+    ) -> VkTutorialResult<(Pipeline, PipelineLayout)> {
+        let vert_shader_code = include_bytes!("../../shaders/spv/26-shader-depth.vert.spv");
+        let vert_shader_module = ShaderModule::new(
             device,
-            include_bytes!("../../shaders/spv/26-shader-depth.vert.spv").to_vec(),
+            share::create_shader_module(device, vert_shader_code.to_vec())?,
         );
-        let frag_shader_module = share::create_shader_module(
+        let frag_shader_code = include_bytes!("../../shaders/spv/26-shader-depth.frag.spv");
+        let frag_shader_module = ShaderModule::new(
             device,
-            include_bytes!("../../shaders/spv/26-shader-depth.frag.spv").to_vec(),
+            share::create_shader_module(device, frag_shader_code.to_vec())?,
         );
 
         let main_function_name = CString::new("main").unwrap(); // the beginning function name in shader code.
@@ -718,7 +455,7 @@ impl VulkanApp29 {
                 s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
                 p_next: ptr::null(),
                 flags: vk::PipelineShaderStageCreateFlags::empty(),
-                module: vert_shader_module,
+                module: vert_shader_module.raw(),
                 p_name: main_function_name.as_ptr(),
                 p_specialization_info: ptr::null(),
                 stage: vk::ShaderStageFlags::VERTEX,
@@ -728,7 +465,7 @@ impl VulkanApp29 {
                 s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
                 p_next: ptr::null(),
                 flags: vk::PipelineShaderStageCreateFlags::empty(),
-                module: frag_shader_module,
+                module: frag_shader_module.raw(),
                 p_name: main_function_name.as_ptr(),
                 p_specialization_info: ptr::null(),
                 stage: vk::ShaderStageFlags::FRAGMENT,
@@ -737,6 +474,7 @@ impl VulkanApp29 {
 
         let binding_description = VertexV3::get_binding_descriptions();
         let attribute_description = VertexV3::get_attribute_descriptions();
+        ShaderReflection::new(vert_shader_code)?.validate_vertex_input(&attribute_description)?;
 
         let vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo {
             s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
@@ -866,11 +604,9 @@ impl VulkanApp29 {
             p_push_constant_ranges: ptr::null(),
         };
 
-        let pipeline_layout = unsafe {
-            device
-                .create_pipeline_layout(&pipeline_layout_create_info, None)
-                .expect("Failed to create pipeline layout!")
-        };
+        let pipeline_layout = PipelineLayout::new(device, unsafe {
+            device.create_pipeline_layout(&pipeline_layout_create_info, None)?
+        });
 
         let graphic_pipeline_create_infos = [vk::GraphicsPipelineCreateInfo {
             s_type: vk::StructureType::GRAPHICS_PIPELINE_CREATE_INFO,
@@ -887,8 +623,8 @@ impl VulkanApp29 {
             p_depth_stencil_state: &depth_state_create_info,
             p_color_blend_state: &color_blend_state,
             p_dynamic_state: ptr::null(),
-            layout: pipeline_layout,
-            render_pass,
+            layout: pipeline_layout.raw(),
+            render_pass: render_pass.raw(),
             subpass: 0,
             base_pipeline_handle: vk::Pipeline::null(),
             base_pipeline_index: -1,
@@ -896,251 +632,241 @@ impl VulkanApp29 {
 
         let graphics_pipelines = unsafe {
             device
-                .create_graphics_pipelines(
-                    vk::PipelineCache::null(),
-                    &graphic_pipeline_create_infos,
-                    None,
-                )
-                .expect("Failed to create Graphics Pipeline!.")
+                .create_graphics_pipelines(pipeline_cache, &graphic_pipeline_create_infos, None)
+                .map_err(|(_, result)| result)?
         };
 
-        unsafe {
-            device.destroy_shader_module(vert_shader_module, None);
-            device.destroy_shader_module(frag_shader_module, None);
+        Ok((
+            Pipeline::new(device, graphics_pipelines[0]),
+            pipeline_layout,
+        ))
+    }
+
+    /// Load the texture with a full mip chain, and request its upload.
+    ///
+    /// The missing levels are generated on the CPU, the transfer queue of the uploads may not blit.
+    fn create_texture_image(
+        memory_allocator: &mut MemoryAllocator,
+        upload_manager: &mut UploadManager,
+        image_path: &Path,
+    ) -> VkTutorialResult<(vk::Image, Allocation, u32)> {
+        let mut mip_chain = mipmap::load_mip_chain(image_path)?;
+        let mip_levels = mipmap::mip_level_count(mip_chain[0].width(), mip_chain[0].height());
+        if mip_chain.len() < mip_levels as usize {
+            let missing_levels = mip_levels - mip_chain.len() as u32;
+            let generated_chain = mipmap::generate_mip_chain(
+                mip_chain.last().unwrap(),
+                missing_levels + 1,
+                mipmap::CPU_MIPMAP_FILTER,
+            );
+            mip_chain.extend(generated_chain.into_iter().skip(1));
         }
 
-        (graphics_pipelines[0], pipeline_layout)
+        let levels: Vec<(u32, u32, &[u8])> = mip_chain
+            .iter()
+            .map(|mip| (mip.width(), mip.height(), &**mip))
+            .collect();
+        let (texture_image, texture_image_allocation, _) = upload_manager.create_texture_image(
+            memory_allocator,
+            vk::Format::R8G8B8A8_SRGB,
+            &levels,
+        )?;
+
+        Ok((texture_image, texture_image_allocation, mip_levels))
     }
 
-    fn create_command_buffers(
-        device: &ash::Device,
-        command_pool: vk::CommandPool,
-        graphics_pipeline: vk::Pipeline,
-        framebuffers: &Vec<vk::Framebuffer>,
-        render_pass: vk::RenderPass,
-        surface_extent: vk::Extent2D,
-        vertex_buffer: vk::Buffer,
-        index_buffer: vk::Buffer,
-        pipeline_layout: vk::PipelineLayout,
-        descriptor_sets: &Vec<vk::DescriptorSet>,
-        index_count: u32,
-    ) -> Vec<vk::CommandBuffer> {
-        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
-            s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
-            p_next: ptr::null(),
-            command_buffer_count: framebuffers.len() as u32,
-            command_pool,
-            level: vk::CommandBufferLevel::PRIMARY,
-        };
+    /// Append the sample count to the window title, the headless mode has no window to show it.
+    fn show_msaa_samples(
+        window: Option<&winit::window::Window>,
+        msaa_samples: vk::SampleCountFlags,
+    ) {
+        if let Some(window) = window {
+            window.set_title(&format!(
+                "{} (MSAA x{})",
+                WINDOW_TITLE,
+                msaa_samples.as_raw()
+            ));
+        }
+    }
 
-        let command_buffers = unsafe {
-            device
-                .allocate_command_buffers(&command_buffer_allocate_info)
-                .expect("Failed to allocate Command Buffers!")
-        };
+    /// The draws of the current frame, built again for every frame.
+    fn draw_submissions(&self, uniform_offset: u32) -> Vec<DrawSubmission> {
+        vec![DrawSubmission::draw_indexed(
+            self.graphics_pipeline.raw(),
+            self.pipeline_layout.raw(),
+            self.index_buffer,
+            vk::IndexType::UINT32,
+            self.indices.len() as u32,
+        )
+        .vertex_buffers(&[self.vertex_buffer])
+        .descriptor_sets(&[self.descriptor_set])
+        .dynamic_offsets(&[uniform_offset])]
+    }
 
-        for (i, &command_buffer) in command_buffers.iter().enumerate() {
-            let command_buffer_begin_info = vk::CommandBufferBeginInfo {
-                s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
-                p_next: ptr::null(),
-                p_inheritance_info: ptr::null(),
-                flags: vk::CommandBufferUsageFlags::SIMULTANEOUS_USE,
-            };
-
-            unsafe {
-                device
-                    .begin_command_buffer(command_buffer, &command_buffer_begin_info)
-                    .expect("Failed to begin recording Command Buffer at beginning!");
-            }
+    /// Give back the set of the last use of the current frame, whose fence is signaled,
+    /// and allocate the set of the frame from the same pools again.
+    fn allocate_frame_descriptor_set(&mut self) -> VkTutorialResult<()> {
+        let descriptor_allocator = &mut self.descriptor_allocators[self.current_frame];
+        descriptor_allocator.reset()?;
+        self.descriptor_set =
+            descriptor_allocator.allocate(self.ubo_layout, &self.ubo_layout_bindings)?;
 
-            let clear_values = [
-                vk::ClearValue {
-                    // clear value for color buffer
-                    color: vk::ClearColorValue {
-                        float32: [0.0, 0.0, 0.0, 1.0],
-                    },
-                },
-                vk::ClearValue {
-                    // clear value for depth buffer
-                    depth_stencil: vk::ClearDepthStencilValue {
-                        depth: 1.0,
-                        stencil: 0,
-                    },
-                },
-            ];
+        share::v2::update_descriptor_set(
+            &self.device,
+            self.descriptor_set,
+            self.uniform_ring.buffer,
+            self.texture_image_view.raw(),
+            self.texture_sampler,
+            true,
+        );
 
-            let render_pass_begin_info = vk::RenderPassBeginInfo {
-                s_type: vk::StructureType::RENDER_PASS_BEGIN_INFO,
-                p_next: ptr::null(),
-                render_pass,
-                framebuffer: framebuffers[i],
-                render_area: vk::Rect2D {
-                    offset: vk::Offset2D { x: 0, y: 0 },
-                    extent: surface_extent,
+        Ok(())
+    }
+
+    /// Record the command buffer of the current frame, which draws into the framebuffer of `image_index`.
+    fn record_frame(
+        &mut self,
+        image_index: usize,
+        uniform_offset: u32,
+    ) -> VkTutorialResult<vk::CommandBuffer> {
+        let clear_values = [
+            vk::ClearValue {
+                // clear value for color buffer
+                color: vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 1.0],
                 },
-                clear_value_count: clear_values.len() as u32,
-                p_clear_values: clear_values.as_ptr(),
-            };
-
-            unsafe {
-                device.cmd_begin_render_pass(
-                    command_buffer,
-                    &render_pass_begin_info,
-                    vk::SubpassContents::INLINE,
-                );
-                device.cmd_bind_pipeline(
-                    command_buffer,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    graphics_pipeline,
-                );
-
-                let vertex_buffers = [vertex_buffer];
-                let offsets = [0_u64];
-                let descriptor_sets_to_bind = [descriptor_sets[i]];
-
-                device.cmd_bind_vertex_buffers(command_buffer, 0, &vertex_buffers, &offsets);
-                device.cmd_bind_index_buffer(
-                    command_buffer,
-                    index_buffer,
-                    0,
-                    vk::IndexType::UINT32,
-                );
-                device.cmd_bind_descriptor_sets(
-                    command_buffer,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    pipeline_layout,
-                    0,
-                    &descriptor_sets_to_bind,
-                    &[],
-                );
-
-                device.cmd_draw_indexed(command_buffer, index_count, 1, 0, 0, 0);
-
-                device.cmd_end_render_pass(command_buffer);
-
-                device
-                    .end_command_buffer(command_buffer)
-                    .expect("Failed to record Command Buffer at Ending!");
-            }
-        }
+            },
+            vk::ClearValue {
+                // clear value for depth buffer
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
 
-        command_buffers
+        let command_buffer = self.frame_contexts.begin(self.current_frame)?;
+        self.frame_contexts.cmd_render_pass(
+            command_buffer,
+            self.render_pass.raw(),
+            self.swapchain_framebuffers[image_index].raw(),
+            self.swapchain_extent,
+            &clear_values,
+            &self.draw_submissions(uniform_offset),
+        );
+        self.render_target.cmd_capture(
+            command_buffer,
+            image_index as u32,
+            self.in_flight_fences[self.current_frame].raw(),
+        )?;
+        self.frame_contexts.end(command_buffer)?;
+
+        Ok(command_buffer)
     }
 
-    fn update_uniform_buffer(&mut self, current_image: usize, delta_time: f32) {
+    /// Write the transform of the current frame to its slot of the uniform ring,
+    /// and return the dynamic offset of the slot.
+    fn update_uniform_buffer(&mut self, delta_time: f32) -> VkTutorialResult<u32> {
         self.uniform_transform.model =
             Matrix4::from_axis_angle(Vector3::new(0.0, 0.0, 1.0), Deg(90.0) * delta_time)
                 * self.uniform_transform.model;
 
-        let ubos = [self.uniform_transform.clone()];
-
-        let buffer_size = (std::mem::size_of::<UniformBufferObject>() * ubos.len()) as u64;
-
-        unsafe {
-            let data_ptr =
-                self.device
-                    .map_memory(
-                        self.uniform_buffers_memory[current_image],
-                        0,
-                        buffer_size,
-                        vk::MemoryMapFlags::empty(),
-                    )
-                    .expect("Failed to Map Memory") as *mut UniformBufferObject;
-
-            data_ptr.copy_from_nonoverlapping(ubos.as_ptr(), ubos.len());
-
-            self.device
-                .unmap_memory(self.uniform_buffers_memory[current_image]);
-        }
+        self.uniform_ring.begin_frame(self.current_frame);
+        self.uniform_ring.push(&self.uniform_transform)
     }
 }
 
 impl Drop for VulkanApp29 {
     fn drop(&mut self) {
+        self.wait_device_idle();
+        self.cleanup_swapchain();
+
+        // the owned handles are destroyed with the fields, the device and the instance last.
         unsafe {
-            for i in 0..MAX_FRAMES_IN_FLIGHT {
-                self.device
-                    .destroy_semaphore(self.image_available_semaphores[i], None);
-                self.device
-                    .destroy_semaphore(self.render_finished_semaphores[i], None);
-                self.device.destroy_fence(self.in_flight_fences[i], None);
+            for descriptor_allocator in self.descriptor_allocators.iter_mut() {
+                descriptor_allocator.destroy();
             }
 
-            self.cleanup_swapchain();
-
-            self.device
-                .destroy_descriptor_pool(self.descriptor_pool, None);
-
-            for i in 0..self.uniform_buffers.len() {
-                self.device.destroy_buffer(self.uniform_buffers[i], None);
-                self.device
-                    .free_memory(self.uniform_buffers_memory[i], None);
+            self.uniform_ring.destroy(&mut self.memory_allocator);
+
+            for &(buffer, allocation) in [
+                (self.index_buffer, self.index_buffer_allocation),
+                (self.vertex_buffer, self.vertex_buffer_allocation),
+            ]
+            .iter()
+            {
+                if let Err(error) = self.memory_allocator.destroy_buffer(buffer, allocation) {
+                    eprintln!("Failed to free the mesh buffers: {}", error);
+                }
             }
 
-            self.device.destroy_buffer(self.index_buffer, None);
-            self.device.free_memory(self.index_buffer_memory, None);
+            self.sampler_cache.destroy();
 
-            self.device.destroy_buffer(self.vertex_buffer, None);
-            self.device.free_memory(self.vertex_buffer_memory, None);
+            if let Err(error) = self
+                .memory_allocator
+                .destroy_image(self.texture_image, self.texture_image_allocation)
+            {
+                eprintln!("Failed to free the texture image: {}", error);
+            }
 
-            self.device.destroy_sampler(self.texture_sampler, None);
-            self.device
-                .destroy_image_view(self.texture_image_view, None);
+            // the layouts are destroyed with their cache.
+            self.descriptor_layout_cache.destroy();
 
-            self.device.destroy_image(self.texture_image, None);
-            self.device.free_memory(self.texture_image_memory, None);
+            self.frame_contexts.destroy();
+            self.upload_manager.destroy();
 
-            self.device
-                .destroy_descriptor_set_layout(self.ubo_layout, None);
+            self.pipeline_cache.destroy();
 
-            self.device.destroy_command_pool(self.command_pool, None);
-
-            self.device.destroy_device(None);
-            self.surface_loader.destroy_surface(self.surface, None);
+            self.memory_allocator.destroy();
+            if self.window.is_some() {
+                self.surface_loader.destroy_surface(self.surface, None);
+            }
 
             if VALIDATION.is_enable {
                 self.debug_utils_loader
                     .destroy_debug_utils_messenger(self.debug_merssager, None);
             }
-            self.instance.destroy_instance(None);
         }
     }
 }
 
 impl VulkanApp for VulkanApp29 {
-    fn draw_frame(&mut self, delta_time: f32) {
-        let wait_fences = [self.in_flight_fences[self.current_frame]];
+    fn draw_frame(&mut self, delta_time: f32) -> VkTutorialResult<()> {
+        let wait_fences = [self.in_flight_fences[self.current_frame].raw()];
+
+        // submit the uploads of the last frame, and wait for the mesh only if it is not there yet.
+        self.upload_manager.flush()?;
+        self.upload_manager.poll()?;
+        self.upload_manager.wait(self.mesh_upload)?;
 
         unsafe {
             self.device
-                .wait_for_fences(&wait_fences, true, std::u64::MAX)
-                .expect("Failed to wait for Fence!");
+                .wait_for_fences(&wait_fences, true, std::u64::MAX)?;
         }
 
         let (image_index, _is_sub_optimal) = unsafe {
-            let result = self.swapchain_loader.acquire_next_image(
-                self.swapchain,
+            let result = self.render_target.acquire_next_image(
                 std::u64::MAX,
-                self.image_available_semaphores[self.current_frame],
-                vk::Fence::null(),
+                self.image_available_semaphores[self.current_frame].raw(),
             );
             match result {
                 Ok(image_index) => image_index,
                 Err(vk_result) => match vk_result {
                     vk::Result::ERROR_OUT_OF_DATE_KHR => {
-                        self.recreate_swapchain();
-                        return;
+                        return self.recreate_swapchain();
                     }
-                    _ => panic!("Failed to acquire Swap Chain Image!"),
+                    _ => return Err(VkTutorialError::from(vk_result)),
                 },
             }
         };
 
-        self.update_uniform_buffer(image_index as usize, delta_time);
+        let uniform_offset = self.update_uniform_buffer(delta_time)?;
+        self.allocate_frame_descriptor_set()?;
+        let command_buffer = self.record_frame(image_index as usize, uniform_offset)?;
 
-        let wait_semaphores = [self.image_available_semaphores[self.current_frame]];
+        let wait_semaphores = [self.image_available_semaphores[self.current_frame].raw()];
         let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-        let signal_semaphores = [self.render_finished_semaphores[self.current_frame]];
+        let signal_semaphores = [self.render_finished_semaphores[self.current_frame].raw()];
 
         let submit_infos = [vk::SubmitInfo {
             s_type: vk::StructureType::SUBMIT_INFO,
@@ -1149,59 +875,44 @@ impl VulkanApp for VulkanApp29 {
             p_wait_semaphores: wait_semaphores.as_ptr(),
             p_wait_dst_stage_mask: wait_stages.as_ptr(),
             command_buffer_count: 1,
-            p_command_buffers: &self.command_buffers[image_index as usize],
+            p_command_buffers: &command_buffer,
             signal_semaphore_count: signal_semaphores.len() as u32,
             p_signal_semaphores: signal_semaphores.as_ptr(),
         }];
 
         unsafe {
-            self.device
-                .reset_fences(&wait_fences)
-                .expect("Failed to reset Fence!");
+            self.device.reset_fences(&wait_fences)?;
 
-            self.device
-                .queue_submit(
-                    self.graphics_queue,
-                    &submit_infos,
-                    self.in_flight_fences[self.current_frame],
-                )
-                .expect("Failed to execute queue submit.");
+            self.device.queue_submit(
+                self.graphics_queue,
+                &submit_infos,
+                self.in_flight_fences[self.current_frame].raw(),
+            )?;
         }
 
-        let swapchains = [self.swapchain];
-
-        let present_info = vk::PresentInfoKHR {
-            s_type: vk::StructureType::PRESENT_INFO_KHR,
-            p_next: ptr::null(),
-            wait_semaphore_count: 1,
-            p_wait_semaphores: signal_semaphores.as_ptr(),
-            swapchain_count: 1,
-            p_swapchains: swapchains.as_ptr(),
-            p_image_indices: &image_index,
-            p_results: ptr::null_mut(),
-        };
-
         let result = unsafe {
-            self.swapchain_loader
-                .queue_present(self.present_queue, &present_info)
+            self.render_target
+                .queue_present(self.present_queue, &signal_semaphores, image_index)
         };
 
         let is_resized = match result {
             Ok(_) => self.is_framebuffer_resized,
             Err(vk_result) => match vk_result {
                 vk::Result::ERROR_OUT_OF_DATE_KHR | vk::Result::SUBOPTIMAL_KHR => true,
-                _ => panic!("Failed to execute queue present."),
+                _ => return Err(VkTutorialError::from(vk_result)),
             },
         };
         if is_resized {
             self.is_framebuffer_resized = false;
-            self.recreate_swapchain();
+            self.recreate_swapchain()?;
         }
 
         self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+
+        Ok(())
     }
 
-    fn recreate_swapchain(&mut self) {
+    fn recreate_swapchain(&mut self) -> VkTutorialResult<()> {
         // parameters -------------
         let surface_suff = SurfaceStuff {
             surface_loader: self.surface_loader.clone(),
@@ -1218,114 +929,80 @@ impl VulkanApp for VulkanApp29 {
         };
         self.cleanup_swapchain();
 
-        let swapchain_stuff = share::create_swapchain(
+        let swapchain_stuff = share::create_render_target(
             &self.instance,
             &self.device,
             self.physical_device,
-            &self.window,
+            self.window.as_ref(),
             &surface_suff,
             &self.queue_family,
-        );
-        self.swapchain_loader = swapchain_stuff.swapchain_loader;
-        self.swapchain = swapchain_stuff.swapchain;
+            self.graphics_queue,
+        )?;
+        self.render_target = swapchain_stuff.render_target;
         self.swapchain_images = swapchain_stuff.swapchain_images;
         self.swapchain_format = swapchain_stuff.swapchain_format;
         self.swapchain_extent = swapchain_stuff.swapchain_extent;
 
-        self.swapchain_imageviews = share::v1::create_image_views(
+        self.swapchain_imageviews = share::owned::create_image_views(
             &self.device,
             self.swapchain_format,
             &self.swapchain_images,
-        );
-        self.render_pass = VulkanApp29::create_render_pass(
-            &self.instance,
+        )?;
+        self.render_pass = RenderPass::new(
             &self.device,
-            self.physical_device,
-            self.swapchain_format,
-            self.msaa_samples,
+            msaa::create_render_pass(
+                &self.instance,
+                &self.device,
+                self.physical_device,
+                self.swapchain_format,
+                self.msaa_samples,
+                self.depth_resolve.as_ref(),
+            )?,
         );
         let (graphics_pipeline, pipeline_layout) = VulkanApp29::create_graphics_pipeline(
             &self.device,
-            self.render_pass,
+            self.pipeline_cache.cache,
+            &self.render_pass,
             swapchain_stuff.swapchain_extent,
             self.ubo_layout,
             self.msaa_samples,
-        );
+        )?;
         self.graphics_pipeline = graphics_pipeline;
         self.pipeline_layout = pipeline_layout;
 
-        let color_resources = VulkanApp29::create_color_resources(
-            &self.device,
-            self.swapchain_format,
-            self.swapchain_extent,
-            &self.memory_properties,
-            self.msaa_samples,
-        );
-        self.color_image = color_resources.0;
-        self.color_image_view = color_resources.1;
-        self.color_image_memory = color_resources.2;
-
-        let depth_resources = share::v1::create_depth_resources(
+        self.msaa_attachments = MsaaAttachments::new(
             &self.instance,
             &self.device,
             self.physical_device,
-            self.command_pool,
-            self.graphics_queue,
+            &mut self.memory_allocator,
+            self.swapchain_format,
             self.swapchain_extent,
-            &self.memory_properties,
             self.msaa_samples,
-        );
-        self.depth_image = depth_resources.0;
-        self.depth_image_view = depth_resources.1;
-        self.depth_image_memory = depth_resources.2;
-
-        self.swapchain_framebuffers = VulkanApp29::create_framebuffers(
-            &self.device,
-            self.render_pass,
-            &self.swapchain_imageviews,
-            self.depth_image_view,
-            self.color_image_view,
-            self.swapchain_extent,
-        );
-        self.command_buffers = VulkanApp29::create_command_buffers(
+            self.depth_resolve.is_some(),
+        )?;
+        self.swapchain_framebuffers = Framebuffer::from_raws(
             &self.device,
-            self.command_pool,
-            self.graphics_pipeline,
-            &self.swapchain_framebuffers,
-            self.render_pass,
-            self.swapchain_extent,
-            self.vertex_buffer,
-            self.index_buffer,
-            self.pipeline_layout,
-            &self.descriptor_sets,
-            self.indices.len() as u32,
+            msaa::create_framebuffers(
+                &self.device,
+                self.render_pass.raw(),
+                &handles::raw_handles(&self.swapchain_imageviews),
+                &self.msaa_attachments,
+                self.swapchain_extent,
+            )?,
         );
+
+        Ok(())
     }
 
-    fn cleanup_swapchain(&self) {
+    /// The render pass and the pipeline are destroyed when they are replaced.
+    fn cleanup_swapchain(&mut self) {
+        // the views of the swapchain images go before the swapchain.
+        self.swapchain_framebuffers.clear();
+        self.swapchain_imageviews.clear();
         unsafe {
-            self.device.destroy_image(self.depth_image, None);
-            self.device.destroy_image_view(self.depth_image_view, None);
-            self.device.free_memory(self.depth_image_memory, None);
-
-            self.device.destroy_image(self.color_image, None);
-            self.device.destroy_image_view(self.color_image_view, None);
-            self.device.free_memory(self.color_image_memory, None);
-
-            self.device
-                .free_command_buffers(self.command_pool, &self.command_buffers);
-            for &framebuffer in self.swapchain_framebuffers.iter() {
-                self.device.destroy_framebuffer(framebuffer, None);
-            }
-            self.device.destroy_pipeline(self.graphics_pipeline, None);
-            self.device
-                .destroy_pipeline_layout(self.pipeline_layout, None);
-            self.device.destroy_render_pass(self.render_pass, None);
-            for &image_view in self.swapchain_imageviews.iter() {
-                self.device.destroy_image_view(image_view, None);
-            }
-            self.swapchain_loader
-                .destroy_swapchain(self.swapchain, None);
+            self.msaa_attachments
+                .destroy(&self.device, &mut self.memory_allocator);
+            self.render_target.destroy(&self.device);
         }
     }
 
@@ -1341,14 +1018,25 @@ impl VulkanApp for VulkanApp29 {
         self.is_framebuffer_resized = true;
     }
 
-    fn window_ref(&self) -> &winit::window::Window {
-        &self.window
+    fn window_ref(&self) -> Option<&winit::window::Window> {
+        self.window.as_ref()
+    }
+
+    fn render_target_mut(&mut self) -> &mut RenderTarget {
+        &mut self.render_target
+    }
+
+    fn cycle_msaa_samples(&mut self) -> VkTutorialResult<()> {
+        self.msaa_samples = msaa::next_sample_count(self.msaa_samples, self.max_msaa_samples);
+        VulkanApp29::show_msaa_samples(self.window.as_ref(), self.msaa_samples);
+
+        self.recreate_swapchain()
     }
 }
 
 fn main() {
     let program_proc = ProgramProc::new();
-    let vulkan_app = VulkanApp29::new(&program_proc.event_loop);
+    let vulkan_app = VulkanApp29::new(&program_proc).expect("Failed to initialize the app!");
 
     program_proc.main_loop(vulkan_app);
 }
@@ -0,0 +1,922 @@
+use vulkan_tutorial_rust::{
+    utility::constants::*,
+    utility::debug::*,
+    utility::error::VkTutorialResult,
+    utility::handles::{
+        Buffer,
+        DescriptorPool,
+        DescriptorSetLayout,
+        Device,
+        DeviceMemory,
+        Fence,
+        ImageView,
+        Instance,
+        Pipeline,
+        PipelineLayout,
+        Semaphore,
+        ShaderModule,
+    },
+    utility::pipeline_cache::PipelineCache,
+    utility::reflection::{
+        self,
+        ShaderReflection,
+    },
+    utility::render_graph::{
+        AttachmentLoad,
+        ImageDescription,
+        ImageId,
+        Pass,
+        PassId,
+        RenderGraph,
+    },
+    utility::render_target::RenderTarget,
+    utility::share,
+    utility::share::compute::{
+        self,
+        BufferDescriptor,
+    },
+    utility::share::frame::{
+        DrawSubmission,
+        FrameContexts,
+    },
+    utility::share::upload::UploadManager,
+    utility::structures::*,
+    utility::window::{
+        ProgramProc,
+        VulkanApp,
+    },
+};
+
+use ash::version::DeviceV1_0;
+use ash::version::InstanceV1_0;
+use ash::vk;
+
+use std::ffi::CString;
+use std::ptr;
+use std::sync::Arc;
+
+// Constants
+const WINDOW_TITLE: &'static str = "30.Compute Particles";
+const PARTICLE_COUNT: u32 = 8192;
+const UPLOAD_STAGING_SIZE: vk::DeviceSize = 1024 * 1024;
+/// The `local_size_x` of `30-shader-particles.comp`.
+const PARTICLE_LOCAL_SIZE: u32 = 256;
+
+/// The uniform block of `30-shader-particles.comp`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct SimulationParameters {
+    delta_time: f32,
+    particle_count: u32,
+}
+
+/// Built with the owned handles of `utility::handles`, like tutorial 29.
+/// The render graph, the caches, the swapchain, the surface and the debug messenger are destroyed by hand.
+struct VulkanApp30 {
+    window: Option<winit::window::Window>,
+
+    // vulkan stuff
+    _entry: ash::Entry,
+    instance: Arc<Instance>,
+    surface_loader: ash::extensions::khr::Surface,
+    surface: vk::SurfaceKHR,
+    debug_utils_loader: ash::extensions::ext::DebugUtils,
+    debug_merssager: vk::DebugUtilsMessengerEXT,
+
+    physical_device: vk::PhysicalDevice,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    device: Arc<Device>,
+
+    queue_family: QueueFamilyIndices,
+    graphics_queue: vk::Queue,
+    present_queue: vk::Queue,
+
+    render_target: RenderTarget,
+    swapchain_images: Vec<vk::Image>,
+    swapchain_format: vk::Format,
+    swapchain_extent: vk::Extent2D,
+    swapchain_imageviews: Vec<ImageView>,
+
+    /// Simulates the particles, then draws them into the swapchain image.
+    render_graph: RenderGraph,
+    swapchain_image_id: ImageId,
+    simulate_pass: PassId,
+    draw_pass: PassId,
+
+    pipeline_cache: PipelineCache,
+    pipeline_layout: PipelineLayout,
+    graphics_pipeline: Pipeline,
+
+    _compute_set_layout: DescriptorSetLayout,
+    compute_pipeline_layout: PipelineLayout,
+    compute_pipeline: Pipeline,
+
+    /// Written by the compute shader, and read as vertex buffer by the graphics pipeline.
+    particle_buffer: Buffer,
+    _particle_buffer_memory: DeviceMemory,
+
+    _uniform_buffers: Vec<Buffer>,
+    uniform_buffers_memory: Vec<DeviceMemory>,
+
+    _descriptor_pool: DescriptorPool,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+
+    frame_contexts: FrameContexts,
+
+    image_available_semaphores: Vec<Semaphore>,
+    render_finished_semaphores: Vec<Semaphore>,
+    in_flight_fences: Vec<Fence>,
+    current_frame: usize,
+
+    is_framebuffer_resized: bool,
+}
+
+impl VulkanApp30 {
+    pub fn new(program_proc: &ProgramProc) -> VkTutorialResult<VulkanApp30> {
+        let window = program_proc.init_window(WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT);
+
+        // init vulkan stuff
+        let entry = ash::Entry::new().unwrap();
+        let instance = share::owned::create_instance(
+            &entry,
+            WINDOW_TITLE,
+            VALIDATION.is_enable,
+            &VALIDATION.required_validation_layers.to_vec(),
+            window.as_ref(),
+        )?;
+        let surface_stuff = share::create_surface(
+            &entry,
+            &instance,
+            window.as_ref(),
+            WINDOW_WIDTH,
+            WINDOW_HEIGHT,
+        )?;
+        let (debug_utils_loader, debug_merssager) =
+            setup_debug_utils(VALIDATION.is_enable, &entry, &instance);
+        let physical_device =
+            share::pick_physical_device(&instance, &surface_stuff, &DEVICE_EXTENSIONS)?;
+        let physical_device_memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let (device, queue_family) = share::owned::create_logical_device(
+            &instance,
+            physical_device,
+            &VALIDATION,
+            &DEVICE_EXTENSIONS,
+            &surface_stuff,
+        )?;
+        // the compute shader runs on the graphics queue, whose family also supports compute on the
+        // desktop GPUs, so that no ownership transfer is needed between the simulation and the draw.
+        let graphics_queue =
+            unsafe { device.get_device_queue(queue_family.graphics_family.unwrap(), 0) };
+        let present_queue =
+            unsafe { device.get_device_queue(queue_family.present_family.unwrap(), 0) };
+        let swapchain_stuff = share::create_render_target(
+            &instance,
+            &device,
+            physical_device,
+            window.as_ref(),
+            &surface_stuff,
+            &queue_family,
+            graphics_queue,
+        )?;
+        let swapchain_imageviews = share::owned::create_image_views(
+            &device,
+            swapchain_stuff.swapchain_format,
+            &swapchain_stuff.swapchain_images,
+        )?;
+        let pipeline_cache = PipelineCache::new(&instance, physical_device, &device)?;
+
+        let compute_shader_code = include_bytes!("../../shaders/spv/30-shader-particles.comp.spv");
+        let compute_shaders = [ShaderReflection::new(compute_shader_code)?];
+        let compute_layout_bindings =
+            reflection::descriptor_set_layout_bindings(&compute_shaders, 0)?;
+        let compute_set_layout = DescriptorSetLayout::new(
+            &device,
+            share::create_reflected_descriptor_set_layout(&device, &compute_shaders, 0)?,
+        );
+        let (compute_pipeline, compute_pipeline_layout) = compute::create_compute_pipeline(
+            &device,
+            pipeline_cache.cache,
+            compute_shader_code,
+            &[compute_set_layout.raw()],
+        )?;
+        let compute_pipeline_layout = PipelineLayout::new(&device, compute_pipeline_layout);
+        let compute_pipeline = Pipeline::new(&device, compute_pipeline);
+
+        let particles = VulkanApp30::create_particles(PARTICLE_COUNT);
+        let (particle_buffer, particle_buffer_memory) = share::create_buffer(
+            &device,
+            ::std::mem::size_of_val(particles.as_slice()) as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_DST
+                | vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::VERTEX_BUFFER,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            &physical_device_memory_properties,
+        )?;
+        let particle_buffer = Buffer::new(&device, particle_buffer);
+        let particle_buffer_memory = DeviceMemory::new(&device, particle_buffer_memory);
+        // the particles are uploaded once, through the transfer queue when the device has one.
+        let mut upload_manager = UploadManager::new(
+            &device,
+            &physical_device_memory_properties,
+            &queue_family,
+            graphics_queue,
+            UPLOAD_STAGING_SIZE,
+        )?;
+        let particle_upload = upload_manager.upload_buffer(
+            &particles,
+            particle_buffer.raw(),
+            0,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+        )?;
+        upload_manager.flush()?;
+        upload_manager.wait(particle_upload)?;
+        unsafe {
+            upload_manager.destroy();
+        }
+
+        let (render_graph, swapchain_image_id, simulate_pass, draw_pass) =
+            VulkanApp30::create_render_graph(
+                &device,
+                &physical_device_memory_properties,
+                swapchain_stuff.swapchain_format,
+                swapchain_stuff.swapchain_extent,
+                particle_buffer.raw(),
+            )?;
+        let (graphics_pipeline, pipeline_layout) = VulkanApp30::create_graphics_pipeline(
+            &device,
+            pipeline_cache.cache,
+            render_graph.render_pass(draw_pass),
+            swapchain_stuff.swapchain_extent,
+        )?;
+
+        let (uniform_buffers, uniform_buffers_memory) = VulkanApp30::create_uniform_buffers(
+            &device,
+            &physical_device_memory_properties,
+            swapchain_stuff.swapchain_images.len(),
+        )?;
+        let uniform_buffers = Buffer::from_raws(&device, uniform_buffers);
+        let uniform_buffers_memory = DeviceMemory::from_raws(&device, uniform_buffers_memory);
+        let descriptor_pool = DescriptorPool::new(
+            &device,
+            compute::create_descriptor_pool(
+                &device,
+                &compute_layout_bindings,
+                swapchain_stuff.swapchain_images.len() as u32,
+            )?,
+        );
+        let buffer_descriptors: Vec<Vec<BufferDescriptor>> = uniform_buffers
+            .iter()
+            .map(|uniform_buffer| {
+                vec![
+                    BufferDescriptor {
+                        binding: 0,
+                        descriptor_type: vk::DescriptorType::STORAGE_BUFFER,
+                        buffer: particle_buffer.raw(),
+                        range: vk::WHOLE_SIZE,
+                    },
+                    BufferDescriptor {
+                        binding: 1,
+                        descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+                        buffer: uniform_buffer.raw(),
+                        range: std::mem::size_of::<SimulationParameters>() as vk::DeviceSize,
+                    },
+                ]
+            })
+            .collect();
+        let descriptor_sets = compute::create_buffer_descriptor_sets(
+            &device,
+            descriptor_pool.raw(),
+            compute_set_layout.raw(),
+            &buffer_descriptors,
+        )?;
+
+        let frame_contexts = FrameContexts::new(
+            &device,
+            queue_family.graphics_family.unwrap(),
+            MAX_FRAMES_IN_FLIGHT,
+        )?;
+        let sync_ojbects = share::owned::create_sync_objects(&device, MAX_FRAMES_IN_FLIGHT)?;
+
+        // cleanup(); the 'drop' function will take care of it.
+        Ok(VulkanApp30 {
+            // winit stuff
+            window,
+
+            // vulkan stuff
+            _entry: entry,
+            instance,
+            surface: surface_stuff.surface,
+            surface_loader: surface_stuff.surface_loader,
+            debug_utils_loader,
+            debug_merssager,
+
+            physical_device,
+            memory_properties: physical_device_memory_properties,
+            device,
+
+            queue_family,
+            graphics_queue,
+            present_queue,
+
+            render_target: swapchain_stuff.render_target,
+            swapchain_format: swapchain_stuff.swapchain_format,
+            swapchain_images: swapchain_stuff.swapchain_images,
+            swapchain_extent: swapchain_stuff.swapchain_extent,
+            swapchain_imageviews,
+
+            render_graph,
+            swapchain_image_id,
+            simulate_pass,
+            draw_pass,
+
+            pipeline_cache,
+            pipeline_layout,
+            graphics_pipeline,
+
+            _compute_set_layout: compute_set_layout,
+            compute_pipeline_layout,
+            compute_pipeline,
+
+            particle_buffer,
+            _particle_buffer_memory: particle_buffer_memory,
+
+            _uniform_buffers: uniform_buffers,
+            uniform_buffers_memory,
+
+            _descriptor_pool: descriptor_pool,
+            descriptor_sets,
+
+            frame_contexts,
+
+            image_available_semaphores: sync_ojbects.image_available_semaphores,
+            render_finished_semaphores: sync_ojbects.render_finished_semaphores,
+            in_flight_fences: sync_ojbects.inflight_fences,
+            current_frame: 0,
+
+            is_framebuffer_resized: false,
+        })
+    }
+}
+
+// Fix content -------------------------------------------------------------------------------
+impl VulkanApp30 {
+    /// Spread the particles over a disk, rotating around its center with a color by angle.
+    fn create_particles(particle_count: u32) -> Vec<Particle> {
+        // the golden angle gives an even spread without any random number.
+        let golden_angle = std::f32::consts::PI * (3.0 - 5.0_f32.sqrt());
+
+        (0..particle_count)
+            .map(|i| {
+                let radius = 0.9 * ((i as f32 + 0.5) / particle_count as f32).sqrt();
+                let angle = i as f32 * golden_angle;
+                let (sin, cos) = angle.sin_cos();
+                let hue = |offset: f32| 0.5 + 0.5 * (angle + offset).cos();
+
+                Particle {
+                    position: [radius * cos, radius * sin],
+                    velocity: [-sin * 0.25 + cos * 0.05, cos * 0.25 + sin * 0.05],
+                    color: [hue(0.0), hue(2.094), hue(4.189), 1.0],
+                }
+            })
+            .collect()
+    }
+
+    fn create_uniform_buffers(
+        device: &ash::Device,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        swapchain_image_count: usize,
+    ) -> VkTutorialResult<(Vec<vk::Buffer>, Vec<vk::DeviceMemory>)> {
+        let buffer_size = ::std::mem::size_of::<SimulationParameters>();
+
+        let mut uniform_buffers = vec![];
+        let mut uniform_buffers_memory = vec![];
+
+        for _ in 0..swapchain_image_count {
+            let (uniform_buffer, uniform_buffer_memory) = share::create_buffer(
+                device,
+                buffer_size as u64,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                device_memory_properties,
+            )?;
+            uniform_buffers.push(uniform_buffer);
+            uniform_buffers_memory.push(uniform_buffer_memory);
+        }
+
+        Ok((uniform_buffers, uniform_buffers_memory))
+    }
+
+    /// The shader modules are destroyed when they go out of scope, and so is the pipeline layout
+    /// if the pipeline can not be created.
+    fn create_graphics_pipeline(
+        device: &Arc<Device>,
+        pipeline_cache: vk::PipelineCache,
+        render_pass: vk::RenderPass,
+        swapchain_extent: vk::Extent2D,
+    ) -> VkTutorialResult<(Pipeline, PipelineLayout)> {
+        let vert_shader_code = include_bytes!("../../shaders/spv/30-shader-particles.vert.spv");
+        let vert_shader_module = ShaderModule::new(
+            device,
+            share::create_shader_module(device, vert_shader_code.to_vec())?,
+        );
+        let frag_shader_code = include_bytes!("../../shaders/spv/30-shader-particles.frag.spv");
+        let frag_shader_module = ShaderModule::new(
+            device,
+            share::create_shader_module(device, frag_shader_code.to_vec())?,
+        );
+
+        let main_function_name = CString::new("main").unwrap(); // the beginning function name in shader code.
+
+        let shader_stages = [
+            vk::PipelineShaderStageCreateInfo {
+                // Vertex Shader
+                s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+                p_next: ptr::null(),
+                flags: vk::PipelineShaderStageCreateFlags::empty(),
+                module: vert_shader_module.raw(),
+                p_name: main_function_name.as_ptr(),
+                p_specialization_info: ptr::null(),
+                stage: vk::ShaderStageFlags::VERTEX,
+            },
+            vk::PipelineShaderStageCreateInfo {
+                // Fragment Shader
+                s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+                p_next: ptr::null(),
+                flags: vk::PipelineShaderStageCreateFlags::empty(),
+                module: frag_shader_module.raw(),
+                p_name: main_function_name.as_ptr(),
+                p_specialization_info: ptr::null(),
+                stage: vk::ShaderStageFlags::FRAGMENT,
+            },
+        ];
+
+        // the particles are drawn straight from the storage buffer of the simulation.
+        let binding_description = Particle::get_binding_descriptions();
+        let attribute_description = Particle::get_attribute_descriptions();
+        ShaderReflection::new(vert_shader_code)?.validate_vertex_input(&attribute_description)?;
+
+        let vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineVertexInputStateCreateFlags::empty(),
+            vertex_attribute_description_count: attribute_description.len() as u32,
+            p_vertex_attribute_descriptions: attribute_description.as_ptr(),
+            vertex_binding_description_count: binding_description.len() as u32,
+            p_vertex_binding_descriptions: binding_description.as_ptr(),
+        };
+        let vertex_input_assembly_state_info = vk::PipelineInputAssemblyStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_INPUT_ASSEMBLY_STATE_CREATE_INFO,
+            flags: vk::PipelineInputAssemblyStateCreateFlags::empty(),
+            p_next: ptr::null(),
+            primitive_restart_enable: vk::FALSE,
+            topology: vk::PrimitiveTopology::POINT_LIST,
+        };
+
+        let viewports = [vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: swapchain_extent.width as f32,
+            height: swapchain_extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }];
+
+        let scissors = [vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: swapchain_extent,
+        }];
+
+        let viewport_state_create_info = vk::PipelineViewportStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_VIEWPORT_STATE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineViewportStateCreateFlags::empty(),
+            scissor_count: scissors.len() as u32,
+            p_scissors: scissors.as_ptr(),
+            viewport_count: viewports.len() as u32,
+            p_viewports: viewports.as_ptr(),
+        };
+
+        let rasterization_statue_create_info = vk::PipelineRasterizationStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_RASTERIZATION_STATE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineRasterizationStateCreateFlags::empty(),
+            depth_clamp_enable: vk::FALSE,
+            cull_mode: vk::CullModeFlags::NONE,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            line_width: 1.0,
+            polygon_mode: vk::PolygonMode::FILL,
+            rasterizer_discard_enable: vk::FALSE,
+            depth_bias_clamp: 0.0,
+            depth_bias_constant_factor: 0.0,
+            depth_bias_enable: vk::FALSE,
+            depth_bias_slope_factor: 0.0,
+        };
+
+        let multisample_state_create_info = vk::PipelineMultisampleStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_MULTISAMPLE_STATE_CREATE_INFO,
+            flags: vk::PipelineMultisampleStateCreateFlags::empty(),
+            p_next: ptr::null(),
+            rasterization_samples: vk::SampleCountFlags::TYPE_1,
+            sample_shading_enable: vk::FALSE,
+            min_sample_shading: 0.0,
+            p_sample_mask: ptr::null(),
+            alpha_to_one_enable: vk::FALSE,
+            alpha_to_coverage_enable: vk::FALSE,
+        };
+
+        // the overlapping particles add up their colors.
+        let color_blend_attachment_states = [vk::PipelineColorBlendAttachmentState {
+            blend_enable: vk::TRUE,
+            color_write_mask: vk::ColorComponentFlags::all(),
+            src_color_blend_factor: vk::BlendFactor::ONE,
+            dst_color_blend_factor: vk::BlendFactor::ONE,
+            color_blend_op: vk::BlendOp::ADD,
+            src_alpha_blend_factor: vk::BlendFactor::ONE,
+            dst_alpha_blend_factor: vk::BlendFactor::ZERO,
+            alpha_blend_op: vk::BlendOp::ADD,
+        }];
+
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_COLOR_BLEND_STATE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineColorBlendStateCreateFlags::empty(),
+            logic_op_enable: vk::FALSE,
+            logic_op: vk::LogicOp::COPY,
+            attachment_count: color_blend_attachment_states.len() as u32,
+            p_attachments: color_blend_attachment_states.as_ptr(),
+            blend_constants: [0.0, 0.0, 0.0, 0.0],
+        };
+
+        let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo {
+            s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineLayoutCreateFlags::empty(),
+            set_layout_count: 0,
+            p_set_layouts: ptr::null(),
+            push_constant_range_count: 0,
+            p_push_constant_ranges: ptr::null(),
+        };
+
+        let pipeline_layout = PipelineLayout::new(device, unsafe {
+            device.create_pipeline_layout(&pipeline_layout_create_info, None)?
+        });
+
+        let graphic_pipeline_create_infos = [vk::GraphicsPipelineCreateInfo {
+            s_type: vk::StructureType::GRAPHICS_PIPELINE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineCreateFlags::empty(),
+            stage_count: shader_stages.len() as u32,
+            p_stages: shader_stages.as_ptr(),
+            p_vertex_input_state: &vertex_input_state_create_info,
+            p_input_assembly_state: &vertex_input_assembly_state_info,
+            p_tessellation_state: ptr::null(),
+            p_viewport_state: &viewport_state_create_info,
+            p_rasterization_state: &rasterization_statue_create_info,
+            p_multisample_state: &multisample_state_create_info,
+            p_depth_stencil_state: ptr::null(),
+            p_color_blend_state: &color_blend_state,
+            p_dynamic_state: ptr::null(),
+            layout: pipeline_layout.raw(),
+            render_pass,
+            subpass: 0,
+            base_pipeline_handle: vk::Pipeline::null(),
+            base_pipeline_index: -1,
+        }];
+
+        let graphics_pipelines = unsafe {
+            device
+                .create_graphics_pipelines(pipeline_cache, &graphic_pipeline_create_infos, None)
+                .map_err(|(_, result)| result)?
+        };
+
+        Ok((
+            Pipeline::new(device, graphics_pipelines[0]),
+            pipeline_layout,
+        ))
+    }
+
+    /// The draw pass is added first, the graph still runs the simulation before it
+    /// since the draw reads the particles written by the simulation.
+    fn create_render_graph(
+        device: &ash::Device,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        swapchain_format: vk::Format,
+        swapchain_extent: vk::Extent2D,
+        particle_buffer: vk::Buffer,
+    ) -> VkTutorialResult<(RenderGraph, ImageId, PassId, PassId)> {
+        let mut render_graph = RenderGraph::new(device, device_memory_properties);
+
+        let swapchain_image_id = render_graph.import_image(
+            ImageDescription::color(swapchain_format, swapchain_extent),
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+        );
+        let particles = render_graph.import_buffer(particle_buffer);
+
+        let clear_value = vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: [0.0, 0.0, 0.0, 1.0],
+            },
+        };
+        let draw_pass = render_graph.add_pass(
+            Pass::new("draw")
+                .vertex_buffer(particles)
+                .color_attachment(swapchain_image_id, AttachmentLoad::Clear(clear_value)),
+        )?;
+        let simulate_pass = render_graph.add_pass(Pass::new("simulate").storage_buffer(
+            particles,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            true,
+        ))?;
+
+        render_graph.compile()?;
+
+        Ok((render_graph, swapchain_image_id, simulate_pass, draw_pass))
+    }
+
+    /// Record the command buffer of the current frame, which draws into the swapchain image `image_index`.
+    fn record_frame(&mut self, image_index: usize) -> VkTutorialResult<vk::CommandBuffer> {
+        let command_buffer = self.frame_contexts.begin(self.current_frame)?;
+
+        let imported_images = [(
+            self.swapchain_image_id,
+            self.swapchain_images[image_index],
+            self.swapchain_imageviews[image_index].raw(),
+        )];
+        let device = &self.device;
+        let frame_contexts = &self.frame_contexts;
+        let (simulate_pass, draw_pass) = (self.simulate_pass, self.draw_pass);
+        let (compute_pipeline, compute_pipeline_layout) = (
+            self.compute_pipeline.raw(),
+            self.compute_pipeline_layout.raw(),
+        );
+        let descriptor_set = self.descriptor_sets[image_index];
+        let draw = DrawSubmission::draw(
+            self.graphics_pipeline.raw(),
+            self.pipeline_layout.raw(),
+            PARTICLE_COUNT,
+        )
+        .vertex_buffers(&[self.particle_buffer.raw()]);
+
+        self.render_graph
+            .execute(command_buffer, &imported_images, |pass, command_buffer| {
+                if pass == simulate_pass {
+                    compute::cmd_dispatch(
+                        device,
+                        command_buffer,
+                        compute_pipeline,
+                        compute_pipeline_layout,
+                        &[descriptor_set],
+                        &[],
+                        [
+                            compute::dispatch_group_count(PARTICLE_COUNT, PARTICLE_LOCAL_SIZE),
+                            1,
+                            1,
+                        ],
+                    );
+                } else if pass == draw_pass {
+                    frame_contexts.cmd_draw(command_buffer, &draw);
+                }
+            })?;
+
+        self.render_target.cmd_capture(
+            command_buffer,
+            image_index as u32,
+            self.in_flight_fences[self.current_frame].raw(),
+        )?;
+        self.frame_contexts.end(command_buffer)?;
+
+        Ok(command_buffer)
+    }
+
+    fn update_uniform_buffer(&mut self, current_image: usize, delta_time: f32) {
+        let parameters = [SimulationParameters {
+            delta_time,
+            particle_count: PARTICLE_COUNT,
+        }];
+
+        let buffer_size = (std::mem::size_of::<SimulationParameters>() * parameters.len()) as u64;
+
+        unsafe {
+            let data_ptr =
+                self.device
+                    .map_memory(
+                        self.uniform_buffers_memory[current_image].raw(),
+                        0,
+                        buffer_size,
+                        vk::MemoryMapFlags::empty(),
+                    )
+                    .expect("Failed to Map Memory") as *mut SimulationParameters;
+
+            data_ptr.copy_from_nonoverlapping(parameters.as_ptr(), parameters.len());
+
+            self.device
+                .unmap_memory(self.uniform_buffers_memory[current_image].raw());
+        }
+    }
+}
+
+impl Drop for VulkanApp30 {
+    fn drop(&mut self) {
+        self.wait_device_idle();
+        self.cleanup_swapchain();
+
+        // the owned handles are destroyed with the fields, the device and the instance last.
+        unsafe {
+            self.frame_contexts.destroy();
+
+            self.pipeline_cache.destroy();
+
+            if self.window.is_some() {
+                self.surface_loader.destroy_surface(self.surface, None);
+            }
+
+            if VALIDATION.is_enable {
+                self.debug_utils_loader
+                    .destroy_debug_utils_messenger(self.debug_merssager, None);
+            }
+        }
+    }
+}
+
+impl VulkanApp for VulkanApp30 {
+    fn draw_frame(&mut self, delta_time: f32) -> VkTutorialResult<()> {
+        let wait_fences = [self.in_flight_fences[self.current_frame].raw()];
+
+        unsafe {
+            self.device
+                .wait_for_fences(&wait_fences, true, std::u64::MAX)
+                .expect("Failed to wait for Fence!");
+        }
+
+        let (image_index, _is_sub_optimal) = unsafe {
+            let result = self.render_target.acquire_next_image(
+                std::u64::MAX,
+                self.image_available_semaphores[self.current_frame].raw(),
+            );
+            match result {
+                Ok(image_index) => image_index,
+                Err(vk_result) => match vk_result {
+                    vk::Result::ERROR_OUT_OF_DATE_KHR => {
+                        return self.recreate_swapchain();
+                    }
+                    _ => panic!("Failed to acquire Swap Chain Image!"),
+                },
+            }
+        };
+
+        self.update_uniform_buffer(image_index as usize, delta_time);
+        let command_buffer = self.record_frame(image_index as usize)?;
+
+        let wait_semaphores = [self.image_available_semaphores[self.current_frame].raw()];
+        let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+        let signal_semaphores = [self.render_finished_semaphores[self.current_frame].raw()];
+
+        let submit_infos = [vk::SubmitInfo {
+            s_type: vk::StructureType::SUBMIT_INFO,
+            p_next: ptr::null(),
+            wait_semaphore_count: wait_semaphores.len() as u32,
+            p_wait_semaphores: wait_semaphores.as_ptr(),
+            p_wait_dst_stage_mask: wait_stages.as_ptr(),
+            command_buffer_count: 1,
+            p_command_buffers: &command_buffer,
+            signal_semaphore_count: signal_semaphores.len() as u32,
+            p_signal_semaphores: signal_semaphores.as_ptr(),
+        }];
+
+        unsafe {
+            self.device
+                .reset_fences(&wait_fences)
+                .expect("Failed to reset Fence!");
+
+            self.device
+                .queue_submit(
+                    self.graphics_queue,
+                    &submit_infos,
+                    self.in_flight_fences[self.current_frame].raw(),
+                )
+                .expect("Failed to execute queue submit.");
+        }
+
+        let result = unsafe {
+            self.render_target
+                .queue_present(self.present_queue, &signal_semaphores, image_index)
+        };
+
+        let is_resized = match result {
+            Ok(_) => self.is_framebuffer_resized,
+            Err(vk_result) => match vk_result {
+                vk::Result::ERROR_OUT_OF_DATE_KHR | vk::Result::SUBOPTIMAL_KHR => true,
+                _ => panic!("Failed to execute queue present."),
+            },
+        };
+        if is_resized {
+            self.is_framebuffer_resized = false;
+            self.recreate_swapchain()?;
+        }
+
+        self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+
+        Ok(())
+    }
+
+    fn recreate_swapchain(&mut self) -> VkTutorialResult<()> {
+        // parameters -------------
+        let surface_suff = SurfaceStuff {
+            surface_loader: self.surface_loader.clone(),
+            surface: self.surface,
+            screen_width: WINDOW_WIDTH,
+            screen_height: WINDOW_HEIGHT,
+        };
+        // ------------------------
+
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .expect("Failed to wait device idle!")
+        };
+        self.cleanup_swapchain();
+
+        let swapchain_stuff = share::create_render_target(
+            &self.instance,
+            &self.device,
+            self.physical_device,
+            self.window.as_ref(),
+            &surface_suff,
+            &self.queue_family,
+            self.graphics_queue,
+        )?;
+        self.render_target = swapchain_stuff.render_target;
+        self.swapchain_images = swapchain_stuff.swapchain_images;
+        self.swapchain_format = swapchain_stuff.swapchain_format;
+        self.swapchain_extent = swapchain_stuff.swapchain_extent;
+
+        self.swapchain_imageviews = share::owned::create_image_views(
+            &self.device,
+            self.swapchain_format,
+            &self.swapchain_images,
+        )?;
+        let (render_graph, swapchain_image_id, simulate_pass, draw_pass) =
+            VulkanApp30::create_render_graph(
+                &self.device,
+                &self.memory_properties,
+                self.swapchain_format,
+                self.swapchain_extent,
+                self.particle_buffer.raw(),
+            )?;
+        self.render_graph = render_graph;
+        self.swapchain_image_id = swapchain_image_id;
+        self.simulate_pass = simulate_pass;
+        self.draw_pass = draw_pass;
+
+        let (graphics_pipeline, pipeline_layout) = VulkanApp30::create_graphics_pipeline(
+            &self.device,
+            self.pipeline_cache.cache,
+            self.render_graph.render_pass(self.draw_pass),
+            swapchain_stuff.swapchain_extent,
+        )?;
+        self.graphics_pipeline = graphics_pipeline;
+        self.pipeline_layout = pipeline_layout;
+
+        Ok(())
+    }
+
+    /// The pipeline is destroyed when it is replaced.
+    fn cleanup_swapchain(&mut self) {
+        unsafe {
+            self.render_graph.destroy();
+            self.swapchain_imageviews.clear();
+            self.render_target.destroy(&self.device);
+        }
+    }
+
+    fn wait_device_idle(&self) {
+        unsafe {
+            self.device
+                .device_wait_idle()
+                .expect("Failed to wait device idle!")
+        };
+    }
+
+    fn resize_framebuffer(&mut self) {
+        self.is_framebuffer_resized = true;
+    }
+
+    fn window_ref(&self) -> Option<&winit::window::Window> {
+        self.window.as_ref()
+    }
+
+    fn render_target_mut(&mut self) -> &mut RenderTarget {
+        &mut self.render_target
+    }
+}
+
+fn main() {
+    let program_proc = ProgramProc::new();
+    let vulkan_app = VulkanApp30::new(&program_proc).expect("Failed to initialize the app!");
+
+    program_proc.main_loop(vulkan_app);
+}
+// -------------------------------------------------------------------------------------------
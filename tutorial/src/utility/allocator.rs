@@ -0,0 +1,681 @@
+//! A block allocator, which sub-allocates buffers and images from a few large `vk::DeviceMemory`,
+//! instead of calling `vkAllocateMemory` for every resource like `share::create_buffer` does.
+//!
+//! Every memory type index owns a list of blocks, and every block keeps its free and used ranges
+//! sorted by offset, so that a freed range can be merged with its free neighbours.
+
+use ash::version::{
+    DeviceV1_0,
+    InstanceV1_0,
+};
+use ash::vk;
+
+use std::ptr;
+
+use crate::utility::error::{
+    VkTutorialError,
+    VkTutorialResult,
+};
+use crate::utility::share;
+
+/// The size of the memory blocks, the resources larger than it get a block of their own.
+pub const DEFAULT_BLOCK_SIZE: vk::DeviceSize = 64 * 1024 * 1024;
+
+/// `bufferImageGranularity` only applies between these two kinds of resources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    /// Buffers and the images with `vk::ImageTiling::LINEAR`.
+    Linear,
+    /// The images with `vk::ImageTiling::OPTIMAL`.
+    Optimal,
+}
+
+impl ResourceKind {
+    pub fn from_tiling(tiling: vk::ImageTiling) -> ResourceKind {
+        match tiling {
+            vk::ImageTiling::LINEAR => ResourceKind::Linear,
+            _ => ResourceKind::Optimal,
+        }
+    }
+}
+
+/// A range of a memory block, bind the resource with `memory` and `offset`.
+#[derive(Debug, Clone, Copy)]
+pub struct Allocation {
+    pub memory: vk::DeviceMemory,
+    pub offset: vk::DeviceSize,
+    pub size: vk::DeviceSize,
+    pub memory_type_index: u32,
+    /// Points to `offset` in the block, which stays mapped for its whole life.
+    /// It is null if the memory is not `HOST_VISIBLE`, do not call `vkMapMemory` on `memory` in any case.
+    pub mapped_ptr: *mut u8,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryTypeStats {
+    pub block_count: usize,
+    pub allocation_count: usize,
+    /// The total size of the blocks.
+    pub allocated_bytes: vk::DeviceSize,
+    /// The total size of the sub-allocations, excluding the padding between them.
+    pub used_bytes: vk::DeviceSize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AllocatorStats {
+    pub total: MemoryTypeStats,
+    pub memory_types: Vec<(u32, MemoryTypeStats)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Range {
+    offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+    /// `None` if the range is free.
+    kind: Option<ResourceKind>,
+}
+
+impl Range {
+    fn end(&self) -> vk::DeviceSize {
+        self.offset + self.size
+    }
+}
+
+struct MemoryBlock {
+    memory: vk::DeviceMemory,
+    size: vk::DeviceSize,
+    mapped_ptr: *mut u8,
+    ranges: Vec<Range>,
+}
+
+impl MemoryBlock {
+    fn is_empty(&self) -> bool {
+        self.ranges.iter().all(|range| range.kind.is_none())
+    }
+
+    /// Find the first free range which can hold the resource, and mark it as used.
+    fn allocate(
+        &mut self,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+        kind: ResourceKind,
+        granularity: vk::DeviceSize,
+    ) -> Option<vk::DeviceSize> {
+        for i in 0..self.ranges.len() {
+            let range = self.ranges[i];
+            if range.kind.is_some() || range.size < size {
+                continue;
+            }
+
+            let mut offset = align_up(range.offset, alignment);
+
+            // a linear and an optimal resource must not share a page of `bufferImageGranularity`.
+            if i > 0 {
+                let previous = self.ranges[i - 1];
+                if previous.kind.is_some()
+                    && previous.kind != Some(kind)
+                    && is_on_same_page(previous.end() - 1, offset, granularity)
+                {
+                    offset = align_up(offset, granularity);
+                }
+            }
+
+            let end = offset + size;
+            if end > range.end() {
+                continue;
+            }
+
+            if let Some(next) = self.ranges.get(i + 1) {
+                if next.kind.is_some()
+                    && next.kind != Some(kind)
+                    && is_on_same_page(end - 1, next.offset, granularity)
+                {
+                    continue;
+                }
+            }
+
+            // split the free range into the padding, the used range and the rest.
+            let mut replacement = Vec::with_capacity(3);
+            if offset > range.offset {
+                replacement.push(Range {
+                    offset: range.offset,
+                    size: offset - range.offset,
+                    kind: None,
+                });
+            }
+            replacement.push(Range {
+                offset,
+                size,
+                kind: Some(kind),
+            });
+            if end < range.end() {
+                replacement.push(Range {
+                    offset: end,
+                    size: range.end() - end,
+                    kind: None,
+                });
+            }
+            self.ranges.splice(i..i + 1, replacement);
+
+            return Some(offset);
+        }
+
+        None
+    }
+
+    /// Mark the used range at `offset` as free, return `false` if there is none.
+    fn free(&mut self, offset: vk::DeviceSize) -> bool {
+        let i = match self
+            .ranges
+            .iter()
+            .position(|range| range.offset == offset && range.kind.is_some())
+        {
+            Some(i) => i,
+            None => return false,
+        };
+
+        self.ranges[i].kind = None;
+
+        // merge with the free neighbours, the next one first so that `i` stays valid.
+        if i + 1 < self.ranges.len() && self.ranges[i + 1].kind.is_none() {
+            self.ranges[i].size += self.ranges[i + 1].size;
+            self.ranges.remove(i + 1);
+        }
+        if i > 0 && self.ranges[i - 1].kind.is_none() {
+            self.ranges[i - 1].size += self.ranges[i].size;
+            self.ranges.remove(i);
+        }
+
+        true
+    }
+}
+
+pub struct MemoryAllocator {
+    device: ash::Device,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    buffer_image_granularity: vk::DeviceSize,
+    block_size: vk::DeviceSize,
+    /// The blocks of each memory type index.
+    blocks: Vec<Vec<MemoryBlock>>,
+}
+
+impl MemoryAllocator {
+    pub fn new(
+        instance: &ash::Instance,
+        device: &ash::Device,
+        physical_device: vk::PhysicalDevice,
+        block_size: vk::DeviceSize,
+    ) -> MemoryAllocator {
+        let memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let device_properties = unsafe { instance.get_physical_device_properties(physical_device) };
+
+        MemoryAllocator {
+            device: device.clone(),
+            memory_properties,
+            buffer_image_granularity: device_properties.limits.buffer_image_granularity,
+            block_size,
+            blocks: (0..memory_properties.memory_type_count)
+                .map(|_| vec![])
+                .collect(),
+        }
+    }
+
+    pub fn allocate(
+        &mut self,
+        requirements: vk::MemoryRequirements,
+        required_properties: vk::MemoryPropertyFlags,
+        kind: ResourceKind,
+    ) -> VkTutorialResult<Allocation> {
+        let memory_type_index = share::find_memory_type(
+            requirements.memory_type_bits,
+            required_properties,
+            &self.memory_properties,
+        )?;
+        let granularity = self.buffer_image_granularity;
+
+        let blocks = &mut self.blocks[memory_type_index as usize];
+        for block in blocks.iter_mut() {
+            if let Some(offset) =
+                block.allocate(requirements.size, requirements.alignment, kind, granularity)
+            {
+                return Ok(Allocation {
+                    memory: block.memory,
+                    offset,
+                    size: requirements.size,
+                    memory_type_index,
+                    mapped_ptr: offset_ptr(block.mapped_ptr, offset),
+                });
+            }
+        }
+
+        let mut block =
+            self.allocate_block(memory_type_index, requirements.size.max(self.block_size))?;
+        let offset = block
+            .allocate(requirements.size, requirements.alignment, kind, granularity)
+            .expect("Failed to sub-allocate from a new memory block!");
+        let allocation = Allocation {
+            memory: block.memory,
+            offset,
+            size: requirements.size,
+            memory_type_index,
+            mapped_ptr: offset_ptr(block.mapped_ptr, offset),
+        };
+        self.blocks[memory_type_index as usize].push(block);
+
+        Ok(allocation)
+    }
+
+    /// Return the range to its block, the block is freed once all of its ranges are returned.
+    pub fn free(&mut self, allocation: Allocation) -> VkTutorialResult<()> {
+        let blocks = match self.blocks.get_mut(allocation.memory_type_index as usize) {
+            Some(blocks) => blocks,
+            None => return Err(VkTutorialError::UnknownAllocation),
+        };
+        let block_index = match blocks
+            .iter()
+            .position(|block| block.memory == allocation.memory)
+        {
+            Some(block_index) => block_index,
+            None => return Err(VkTutorialError::UnknownAllocation),
+        };
+
+        if !blocks[block_index].free(allocation.offset) {
+            return Err(VkTutorialError::UnknownAllocation);
+        }
+
+        if blocks[block_index].is_empty() {
+            let block = blocks.remove(block_index);
+            unsafe {
+                self.device.free_memory(block.memory, None);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Same as `share::create_buffer`, but sub-allocate the memory.
+    pub fn create_buffer(
+        &mut self,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        required_memory_properties: vk::MemoryPropertyFlags,
+    ) -> VkTutorialResult<(vk::Buffer, Allocation)> {
+        let buffer_create_info = vk::BufferCreateInfo {
+            s_type: vk::StructureType::BUFFER_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::BufferCreateFlags::empty(),
+            size,
+            usage,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            queue_family_index_count: 0,
+            p_queue_family_indices: ptr::null(),
+        };
+
+        let buffer = unsafe { self.device.create_buffer(&buffer_create_info, None)? };
+
+        let mem_requirements = unsafe { self.device.get_buffer_memory_requirements(buffer) };
+        let allocation = match self.allocate(
+            mem_requirements,
+            required_memory_properties,
+            ResourceKind::Linear,
+        ) {
+            Ok(allocation) => allocation,
+            Err(error) => {
+                unsafe {
+                    self.device.destroy_buffer(buffer, None);
+                }
+                return Err(error);
+            }
+        };
+
+        let bind_result = unsafe {
+            self.device
+                .bind_buffer_memory(buffer, allocation.memory, allocation.offset)
+        };
+        if let Err(result) = bind_result {
+            // the binding error is the one worth reporting, the allocation is known to be owned.
+            let _ = self.destroy_buffer(buffer, allocation);
+            return Err(VkTutorialError::from(result));
+        }
+
+        Ok((buffer, allocation))
+    }
+
+    /// Same as `share::v1::create_image`, but sub-allocate the memory.
+    pub fn create_image(
+        &mut self,
+        width: u32,
+        height: u32,
+        mip_levels: u32,
+        num_samples: vk::SampleCountFlags,
+        format: vk::Format,
+        tiling: vk::ImageTiling,
+        usage: vk::ImageUsageFlags,
+        required_memory_properties: vk::MemoryPropertyFlags,
+    ) -> VkTutorialResult<(vk::Image, Allocation)> {
+        let image_create_info = vk::ImageCreateInfo {
+            s_type: vk::StructureType::IMAGE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::ImageCreateFlags::empty(),
+            image_type: vk::ImageType::TYPE_2D,
+            format,
+            mip_levels,
+            array_layers: 1,
+            samples: num_samples,
+            tiling,
+            usage,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            queue_family_index_count: 0,
+            p_queue_family_indices: ptr::null(),
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            extent: vk::Extent3D {
+                width,
+                height,
+                depth: 1,
+            },
+        };
+
+        let image = unsafe { self.device.create_image(&image_create_info, None)? };
+
+        let mem_requirements = unsafe { self.device.get_image_memory_requirements(image) };
+        let allocation = match self.allocate(
+            mem_requirements,
+            required_memory_properties,
+            ResourceKind::from_tiling(tiling),
+        ) {
+            Ok(allocation) => allocation,
+            Err(error) => {
+                unsafe {
+                    self.device.destroy_image(image, None);
+                }
+                return Err(error);
+            }
+        };
+
+        let bind_result = unsafe {
+            self.device
+                .bind_image_memory(image, allocation.memory, allocation.offset)
+        };
+        if let Err(result) = bind_result {
+            // the binding error is the one worth reporting, the allocation is known to be owned.
+            let _ = self.destroy_image(image, allocation);
+            return Err(VkTutorialError::from(result));
+        }
+
+        Ok((image, allocation))
+    }
+
+    pub fn destroy_buffer(
+        &mut self,
+        buffer: vk::Buffer,
+        allocation: Allocation,
+    ) -> VkTutorialResult<()> {
+        unsafe {
+            self.device.destroy_buffer(buffer, None);
+        }
+        self.free(allocation)
+    }
+
+    pub fn destroy_image(
+        &mut self,
+        image: vk::Image,
+        allocation: Allocation,
+    ) -> VkTutorialResult<()> {
+        unsafe {
+            self.device.destroy_image(image, None);
+        }
+        self.free(allocation)
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        let mut stats = AllocatorStats::default();
+
+        for (memory_type_index, blocks) in self.blocks.iter().enumerate() {
+            if blocks.is_empty() {
+                continue;
+            }
+
+            let mut type_stats = MemoryTypeStats::default();
+            for block in blocks.iter() {
+                type_stats.block_count += 1;
+                type_stats.allocated_bytes += block.size;
+                for range in block.ranges.iter().filter(|range| range.kind.is_some()) {
+                    type_stats.allocation_count += 1;
+                    type_stats.used_bytes += range.size;
+                }
+            }
+
+            stats.total.block_count += type_stats.block_count;
+            stats.total.allocation_count += type_stats.allocation_count;
+            stats.total.allocated_bytes += type_stats.allocated_bytes;
+            stats.total.used_bytes += type_stats.used_bytes;
+            stats
+                .memory_types
+                .push((memory_type_index as u32, type_stats));
+        }
+
+        stats
+    }
+
+    /// Free all the blocks.
+    ///
+    /// # Safety
+    ///
+    /// The resources bound to the blocks must have been destroyed.
+    pub unsafe fn destroy(&mut self) {
+        for blocks in self.blocks.iter_mut() {
+            for block in blocks.drain(..) {
+                self.device.free_memory(block.memory, None);
+            }
+        }
+    }
+
+    fn allocate_block(
+        &self,
+        memory_type_index: u32,
+        size: vk::DeviceSize,
+    ) -> VkTutorialResult<MemoryBlock> {
+        let allocate_info = vk::MemoryAllocateInfo {
+            s_type: vk::StructureType::MEMORY_ALLOCATE_INFO,
+            p_next: ptr::null(),
+            allocation_size: size,
+            memory_type_index,
+        };
+
+        let memory = unsafe { self.device.allocate_memory(&allocate_info, None)? };
+
+        // the sub-allocations can not be mapped one by one, so map the whole block once.
+        let is_host_visible = self.memory_properties.memory_types[memory_type_index as usize]
+            .property_flags
+            .contains(vk::MemoryPropertyFlags::HOST_VISIBLE);
+        let mapped_ptr = if is_host_visible {
+            let result = unsafe {
+                self.device
+                    .map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+            };
+            match result {
+                Ok(data_ptr) => data_ptr as *mut u8,
+                Err(vk_result) => {
+                    unsafe {
+                        self.device.free_memory(memory, None);
+                    }
+                    return Err(vk_result.into());
+                }
+            }
+        } else {
+            ptr::null_mut()
+        };
+
+        Ok(MemoryBlock {
+            memory,
+            size,
+            mapped_ptr,
+            ranges: vec![Range {
+                offset: 0,
+                size,
+                kind: None,
+            }],
+        })
+    }
+}
+
+fn align_up(value: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::DeviceSize {
+    if alignment <= 1 {
+        value
+    } else {
+        // the alignments in Vulkan are always powers of two.
+        (value + alignment - 1) & !(alignment - 1)
+    }
+}
+
+fn is_on_same_page(
+    resource_a: vk::DeviceSize,
+    resource_b: vk::DeviceSize,
+    page_size: vk::DeviceSize,
+) -> bool {
+    page_size > 1 && resource_a / page_size == resource_b / page_size
+}
+
+fn offset_ptr(mapped_ptr: *mut u8, offset: vk::DeviceSize) -> *mut u8 {
+    if mapped_ptr.is_null() {
+        mapped_ptr
+    } else {
+        unsafe { mapped_ptr.add(offset as usize) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK_SIZE: vk::DeviceSize = 1024;
+
+    fn block() -> MemoryBlock {
+        MemoryBlock {
+            memory: vk::DeviceMemory::null(),
+            size: BLOCK_SIZE,
+            mapped_ptr: ptr::null_mut(),
+            ranges: vec![Range {
+                offset: 0,
+                size: BLOCK_SIZE,
+                kind: None,
+            }],
+        }
+    }
+
+    /// The `(offset, size, is_used)` of every range of `block`.
+    fn ranges(block: &MemoryBlock) -> Vec<(vk::DeviceSize, vk::DeviceSize, bool)> {
+        block
+            .ranges
+            .iter()
+            .map(|range| (range.offset, range.size, range.kind.is_some()))
+            .collect()
+    }
+
+    #[test]
+    fn allocate_splits_the_free_range() {
+        let mut block = block();
+
+        assert_eq!(block.allocate(100, 1, ResourceKind::Linear, 1), Some(0));
+        assert_eq!(block.allocate(100, 64, ResourceKind::Linear, 1), Some(128));
+        assert_eq!(
+            ranges(&block),
+            vec![
+                (0, 100, true),
+                (100, 28, false),
+                (128, 100, true),
+                (228, 796, false),
+            ]
+        );
+
+        assert_eq!(block.allocate(BLOCK_SIZE, 1, ResourceKind::Linear, 1), None);
+    }
+
+    #[test]
+    fn free_merges_with_the_free_neighbours() {
+        let mut block = block();
+        let a = block.allocate(256, 1, ResourceKind::Linear, 1).unwrap();
+        let b = block.allocate(256, 1, ResourceKind::Linear, 1).unwrap();
+        let c = block.allocate(256, 1, ResourceKind::Linear, 1).unwrap();
+
+        assert!(block.free(a));
+        assert!(block.free(c));
+        assert_eq!(
+            ranges(&block),
+            vec![(0, 256, false), (256, 256, true), (512, 512, false)]
+        );
+
+        // freed in the middle, the three ranges become one.
+        assert!(block.free(b));
+        assert_eq!(ranges(&block), vec![(0, BLOCK_SIZE, false)]);
+        assert!(block.is_empty());
+    }
+
+    #[test]
+    fn free_rejects_an_unknown_offset() {
+        let mut block = block();
+        let a = block.allocate(256, 1, ResourceKind::Linear, 1).unwrap();
+
+        assert!(!block.free(a + 1));
+        assert!(block.free(a));
+        assert!(!block.free(a));
+    }
+
+    #[test]
+    fn freed_range_is_reused() {
+        let mut block = block();
+        let a = block.allocate(256, 1, ResourceKind::Linear, 1).unwrap();
+        block.allocate(256, 1, ResourceKind::Linear, 1).unwrap();
+
+        assert!(block.free(a));
+        assert_eq!(block.allocate(128, 1, ResourceKind::Linear, 1), Some(0));
+    }
+
+    #[test]
+    fn granularity_separates_linear_and_optimal_resources() {
+        let mut block = block();
+
+        assert_eq!(block.allocate(100, 16, ResourceKind::Linear, 256), Some(0));
+        // on the page of the buffer, so moved to the next page.
+        assert_eq!(
+            block.allocate(100, 16, ResourceKind::Optimal, 256),
+            Some(256)
+        );
+        // the same kind as the previous resource can share its page.
+        assert_eq!(
+            block.allocate(100, 16, ResourceKind::Optimal, 256),
+            Some(368)
+        );
+    }
+
+    #[test]
+    fn granularity_checks_the_next_resource() {
+        let mut block = block();
+        let a = block.allocate(256, 1, ResourceKind::Linear, 256).unwrap();
+        block.allocate(256, 1, ResourceKind::Optimal, 256).unwrap();
+        assert!(block.free(a));
+
+        // a buffer ending on the page of the image does not fit before it.
+        assert_eq!(block.allocate(200, 1, ResourceKind::Linear, 512), Some(512));
+        // an image does, since it is the same kind.
+        assert_eq!(block.allocate(200, 1, ResourceKind::Optimal, 512), Some(0));
+    }
+
+    #[test]
+    fn granularity_of_one_is_ignored() {
+        let mut block = block();
+
+        assert_eq!(block.allocate(100, 4, ResourceKind::Linear, 1), Some(0));
+        assert_eq!(block.allocate(100, 4, ResourceKind::Optimal, 1), Some(100));
+    }
+
+    #[test]
+    fn align_up_rounds_to_the_alignment() {
+        assert_eq!(align_up(0, 256), 0);
+        assert_eq!(align_up(1, 256), 256);
+        assert_eq!(align_up(256, 256), 256);
+        assert_eq!(align_up(13, 0), 13);
+        assert_eq!(align_up(13, 1), 13);
+    }
+}
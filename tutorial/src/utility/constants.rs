@@ -1,4 +1,5 @@
 use crate::utility::debug::ValidationInfo;
+use crate::utility::device_selection::DeviceFeature;
 use crate::utility::structures::DeviceExtension;
 use ash::vk_make_version;
 
@@ -17,6 +18,10 @@ pub const VALIDATION: ValidationInfo = ValidationInfo {
 pub const DEVICE_EXTENSIONS: DeviceExtension = DeviceExtension {
     names: ["VK_KHR_swapchain"],
 };
+/// The features required from the physical device.
+pub const DEVICE_FEATURES: [DeviceFeature; 0] = [];
+/// The features preferred when picking the physical device, anisotropy is used since Chapter-24 when supported.
+pub const PREFERRED_DEVICE_FEATURES: [DeviceFeature; 1] = [DeviceFeature::SamplerAnisotropy];
 pub const MAX_FRAMES_IN_FLIGHT: usize = 2;
 pub const IS_PAINT_FPS_COUNTER: bool = false;
 
@@ -0,0 +1,393 @@
+//! Allocate descriptor sets without sizing a pool for them up front, and share the identical
+//! descriptor set layouts.
+//!
+//! `DescriptorAllocator` creates a new pool whenever the current one is full, and `reset` gives all
+//! its pools back at once, e.g. with one allocator per frame in flight reset when the frame begins.
+//! A set needing more descriptors of a type than a pool holds is rejected before any pool is used.
+//! `DescriptorLayoutCache` creates each distinct list of bindings once.
+
+use ash::version::DeviceV1_0;
+use ash::vk;
+
+use std::collections::HashMap;
+use std::ptr;
+
+use crate::utility::error::{
+    VkTutorialError,
+    VkTutorialResult,
+};
+
+/// The count of descriptors of each type in a pool, for each set the pool holds.
+pub const DEFAULT_POOL_SIZES: [(vk::DescriptorType, f32); 6] = [
+    (vk::DescriptorType::UNIFORM_BUFFER, 2.0),
+    (vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC, 1.0),
+    (vk::DescriptorType::STORAGE_BUFFER, 2.0),
+    (vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 4.0),
+    (vk::DescriptorType::STORAGE_IMAGE, 1.0),
+    (vk::DescriptorType::INPUT_ATTACHMENT, 1.0),
+];
+
+/// The count of sets of a pool, when no other count is given.
+pub const DEFAULT_SETS_PER_POOL: u32 = 64;
+
+pub struct DescriptorAllocator {
+    device: ash::Device,
+    sets_per_pool: u32,
+    pool_sizes: Vec<vk::DescriptorPoolSize>,
+    /// The pool sets are allocated from, `None` until the first allocation.
+    current_pool: Option<vk::DescriptorPool>,
+    /// The pools holding sets, the current one included.
+    used_pools: Vec<vk::DescriptorPool>,
+    /// The pools given back by `reset`, used again before new ones are created.
+    free_pools: Vec<vk::DescriptorPool>,
+}
+
+impl DescriptorAllocator {
+    pub fn new(device: &ash::Device) -> DescriptorAllocator {
+        DescriptorAllocator::with_pool_sizes(device, DEFAULT_SETS_PER_POOL, &DEFAULT_POOL_SIZES)
+    }
+
+    /// Each pool holds `sets_per_pool` sets, and `sets_per_pool * ratio` descriptors
+    /// of each type of `pool_size_ratios`.
+    pub fn with_pool_sizes(
+        device: &ash::Device,
+        sets_per_pool: u32,
+        pool_size_ratios: &[(vk::DescriptorType, f32)],
+    ) -> DescriptorAllocator {
+        DescriptorAllocator {
+            device: device.clone(),
+            sets_per_pool,
+            pool_sizes: pool_sizes(sets_per_pool, pool_size_ratios),
+            current_pool: None,
+            used_pools: vec![],
+            free_pools: vec![],
+        }
+    }
+
+    /// Allocate a set of `layout`, from a new pool if the current one has no room left for it.
+    ///
+    /// `layout_bindings` are the bindings `layout` is created with, a set which does not fit
+    /// in an empty pool returns `DescriptorPoolTooSmall` instead of creating pools in vain.
+    pub fn allocate(
+        &mut self,
+        layout: vk::DescriptorSetLayout,
+        layout_bindings: &[vk::DescriptorSetLayoutBinding],
+    ) -> VkTutorialResult<vk::DescriptorSet> {
+        check_pool_sizes(&self.pool_sizes, layout_bindings)?;
+
+        let pool = match self.current_pool {
+            Some(pool) => pool,
+            None => self.next_pool()?,
+        };
+
+        match allocate_descriptor_set(&self.device, pool, layout) {
+            Ok(descriptor_set) => Ok(descriptor_set),
+            Err(vk::Result::ERROR_OUT_OF_POOL_MEMORY) | Err(vk::Result::ERROR_FRAGMENTED_POOL) => {
+                // the set fits in an empty pool, any error of the new pool is reported as it is.
+                let pool = self.next_pool()?;
+                Ok(allocate_descriptor_set(&self.device, pool, layout)?)
+            }
+            Err(result) => Err(result.into()),
+        }
+    }
+
+    /// Free all the sets allocated so far, and keep their pools for the next allocations.
+    ///
+    /// None of the sets may still be used by a command buffer in flight.
+    pub fn reset(&mut self) -> VkTutorialResult<()> {
+        for &pool in self.used_pools.iter() {
+            unsafe {
+                self.device
+                    .reset_descriptor_pool(pool, vk::DescriptorPoolResetFlags::empty())?;
+            }
+        }
+        self.free_pools.append(&mut self.used_pools);
+        self.current_pool = None;
+
+        Ok(())
+    }
+
+    /// The count of pools created so far.
+    pub fn pool_count(&self) -> usize {
+        self.used_pools.len() + self.free_pools.len()
+    }
+
+    pub unsafe fn destroy(&mut self) {
+        for pool in self.used_pools.drain(..).chain(self.free_pools.drain(..)) {
+            self.device.destroy_descriptor_pool(pool, None);
+        }
+        self.current_pool = None;
+    }
+
+    /// Make a free pool, or a new one, the current pool.
+    fn next_pool(&mut self) -> VkTutorialResult<vk::DescriptorPool> {
+        let pool = match self.free_pools.pop() {
+            Some(pool) => pool,
+            None => {
+                let descriptor_pool_create_info = vk::DescriptorPoolCreateInfo {
+                    s_type: vk::StructureType::DESCRIPTOR_POOL_CREATE_INFO,
+                    p_next: ptr::null(),
+                    flags: vk::DescriptorPoolCreateFlags::empty(),
+                    max_sets: self.sets_per_pool,
+                    pool_size_count: self.pool_sizes.len() as u32,
+                    p_pool_sizes: self.pool_sizes.as_ptr(),
+                };
+                unsafe {
+                    self.device
+                        .create_descriptor_pool(&descriptor_pool_create_info, None)?
+                }
+            }
+        };
+
+        self.used_pools.push(pool);
+        self.current_pool = Some(pool);
+
+        Ok(pool)
+    }
+}
+
+/// The sizes of a pool holding `sets_per_pool` sets, at least one descriptor of each type.
+fn pool_sizes(
+    sets_per_pool: u32,
+    pool_size_ratios: &[(vk::DescriptorType, f32)],
+) -> Vec<vk::DescriptorPoolSize> {
+    pool_size_ratios
+        .iter()
+        .map(|&(ty, ratio)| vk::DescriptorPoolSize {
+            ty,
+            descriptor_count: ((sets_per_pool as f32 * ratio).ceil() as u32).max(1),
+        })
+        .collect()
+}
+
+/// Check an empty pool of `pool_sizes` holds the descriptors of each type of `layout_bindings`.
+fn check_pool_sizes(
+    pool_sizes: &[vk::DescriptorPoolSize],
+    layout_bindings: &[vk::DescriptorSetLayoutBinding],
+) -> VkTutorialResult<()> {
+    for layout_binding in layout_bindings.iter() {
+        let descriptor_type = layout_binding.descriptor_type;
+        let descriptor_count: u32 = layout_bindings
+            .iter()
+            .filter(|other| other.descriptor_type == descriptor_type)
+            .map(|other| other.descriptor_count)
+            .sum();
+        let pooled_count: u32 = pool_sizes
+            .iter()
+            .filter(|pool_size| pool_size.ty == descriptor_type)
+            .map(|pool_size| pool_size.descriptor_count)
+            .sum();
+
+        if descriptor_count > pooled_count {
+            return Err(VkTutorialError::DescriptorPoolTooSmall {
+                descriptor_type,
+                descriptor_count,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn allocate_descriptor_set(
+    device: &ash::Device,
+    descriptor_pool: vk::DescriptorPool,
+    layout: vk::DescriptorSetLayout,
+) -> Result<vk::DescriptorSet, vk::Result> {
+    let layouts = [layout];
+    let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo {
+        s_type: vk::StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
+        p_next: ptr::null(),
+        descriptor_pool,
+        descriptor_set_count: layouts.len() as u32,
+        p_set_layouts: layouts.as_ptr(),
+    };
+
+    let descriptor_sets =
+        unsafe { device.allocate_descriptor_sets(&descriptor_set_allocate_info)? };
+    Ok(descriptor_sets[0])
+}
+
+/// A binding of a set layout, compared by value. The immutable samplers are part of it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct BindingKey {
+    binding: u32,
+    descriptor_type: vk::DescriptorType,
+    descriptor_count: u32,
+    stage_flags: vk::ShaderStageFlags,
+    immutable_samplers: Vec<vk::Sampler>,
+}
+
+impl BindingKey {
+    fn new(layout_binding: &vk::DescriptorSetLayoutBinding) -> BindingKey {
+        let immutable_samplers = if layout_binding.p_immutable_samplers.is_null() {
+            vec![]
+        } else {
+            unsafe {
+                std::slice::from_raw_parts(
+                    layout_binding.p_immutable_samplers,
+                    layout_binding.descriptor_count as usize,
+                )
+                .to_vec()
+            }
+        };
+
+        BindingKey {
+            binding: layout_binding.binding,
+            descriptor_type: layout_binding.descriptor_type,
+            descriptor_count: layout_binding.descriptor_count,
+            stage_flags: layout_binding.stage_flags,
+            immutable_samplers,
+        }
+    }
+}
+
+/// Create each distinct descriptor set layout once, and destroy them all at the end.
+pub struct DescriptorLayoutCache {
+    device: ash::Device,
+    layouts: HashMap<Vec<BindingKey>, vk::DescriptorSetLayout>,
+}
+
+impl DescriptorLayoutCache {
+    pub fn new(device: &ash::Device) -> DescriptorLayoutCache {
+        DescriptorLayoutCache {
+            device: device.clone(),
+            layouts: HashMap::new(),
+        }
+    }
+
+    /// Return the layout of `layout_bindings`, creating it on the first request.
+    /// The order of the bindings does not matter.
+    pub fn get(
+        &mut self,
+        layout_bindings: &[vk::DescriptorSetLayoutBinding],
+    ) -> VkTutorialResult<vk::DescriptorSetLayout> {
+        let mut key: Vec<BindingKey> = layout_bindings.iter().map(BindingKey::new).collect();
+        key.sort_by_key(|binding_key| binding_key.binding);
+
+        if let Some(&layout) = self.layouts.get(&key) {
+            return Ok(layout);
+        }
+
+        let layout_create_info = vk::DescriptorSetLayoutCreateInfo {
+            s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::DescriptorSetLayoutCreateFlags::empty(),
+            binding_count: layout_bindings.len() as u32,
+            p_bindings: layout_bindings.as_ptr(),
+        };
+        let layout = unsafe {
+            self.device
+                .create_descriptor_set_layout(&layout_create_info, None)?
+        };
+        self.layouts.insert(key, layout);
+
+        Ok(layout)
+    }
+
+    /// The count of distinct layouts created so far.
+    pub fn layout_count(&self) -> usize {
+        self.layouts.len()
+    }
+
+    pub unsafe fn destroy(&mut self) {
+        for (_, layout) in self.layouts.drain() {
+            self.device.destroy_descriptor_set_layout(layout, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout_binding(
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        descriptor_count: u32,
+    ) -> vk::DescriptorSetLayoutBinding {
+        vk::DescriptorSetLayoutBinding {
+            binding,
+            descriptor_type,
+            descriptor_count,
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            p_immutable_samplers: ptr::null(),
+        }
+    }
+
+    #[test]
+    fn pool_sizes_scale_with_the_sets() {
+        let pool_sizes = pool_sizes(
+            10,
+            &[
+                (vk::DescriptorType::UNIFORM_BUFFER, 2.0),
+                (vk::DescriptorType::STORAGE_IMAGE, 0.01),
+            ],
+        );
+
+        assert_eq!(pool_sizes.len(), 2);
+        assert_eq!(pool_sizes[0].ty, vk::DescriptorType::UNIFORM_BUFFER);
+        assert_eq!(pool_sizes[0].descriptor_count, 20);
+        // a small ratio still makes room for one descriptor.
+        assert_eq!(pool_sizes[1].descriptor_count, 1);
+    }
+
+    #[test]
+    fn default_pool_sizes_hold_the_tutorial_sets() {
+        let pool_sizes = pool_sizes(DEFAULT_SETS_PER_POOL, &DEFAULT_POOL_SIZES);
+        let layout_bindings = [
+            layout_binding(0, vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC, 1),
+            layout_binding(1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1),
+        ];
+
+        assert!(check_pool_sizes(&pool_sizes, &layout_bindings).is_ok());
+        assert!(check_pool_sizes(&pool_sizes, &[]).is_ok());
+    }
+
+    #[test]
+    fn missing_descriptor_types_are_rejected() {
+        let pool_sizes = pool_sizes(DEFAULT_SETS_PER_POOL, &DEFAULT_POOL_SIZES);
+
+        for &descriptor_type in [
+            vk::DescriptorType::SAMPLED_IMAGE,
+            vk::DescriptorType::SAMPLER,
+        ]
+        .iter()
+        {
+            let layout_bindings = [
+                layout_binding(0, vk::DescriptorType::UNIFORM_BUFFER, 1),
+                layout_binding(1, descriptor_type, 1),
+            ];
+            match check_pool_sizes(&pool_sizes, &layout_bindings) {
+                Err(VkTutorialError::DescriptorPoolTooSmall {
+                    descriptor_type: rejected_type,
+                    descriptor_count: 1,
+                }) => assert_eq!(rejected_type, descriptor_type),
+                result => panic!("{:?} is not rejected: {:?}", descriptor_type, result),
+            }
+        }
+    }
+
+    #[test]
+    fn descriptor_counts_are_summed_over_the_bindings() {
+        let pool_sizes = pool_sizes(4, &[(vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1.0)]);
+
+        let fitting_bindings = [
+            layout_binding(0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 2),
+            layout_binding(1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 2),
+        ];
+        assert!(check_pool_sizes(&pool_sizes, &fitting_bindings).is_ok());
+
+        let oversized_bindings = [
+            layout_binding(0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 2),
+            layout_binding(1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 3),
+        ];
+        match check_pool_sizes(&pool_sizes, &oversized_bindings) {
+            Err(VkTutorialError::DescriptorPoolTooSmall {
+                descriptor_count: 5,
+                ..
+            }) => (),
+            result => panic!("the bindings are not rejected: {:?}", result),
+        }
+    }
+}
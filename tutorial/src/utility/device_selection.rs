@@ -0,0 +1,54 @@
+//! Rate the physical devices against the requirements of the tutorial, and pick the best one.
+//!
+//! The scoring lives in the `device-selection` crate, which the crate at the root of the repository
+//! uses too. This module adds the checks which need the surface of the tutorial.
+
+use ash::vk;
+
+use crate::utility::error::VkTutorialResult;
+use crate::utility::share;
+use crate::utility::structures::SurfaceStuff;
+
+pub use device_selection::{
+    available_extension_names,
+    check_requirements,
+    rate_physical_devices,
+    select_physical_device,
+    DeviceCandidate,
+    DeviceFeature,
+    DeviceRequirements,
+    DeviceScore,
+    DEVICE_OVERRIDE_VAR,
+};
+
+/// Return why `physical_device` can not draw to the surface of `surface_stuff`, in a readable form.
+pub fn check_surface_support(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    surface_stuff: &SurfaceStuff,
+) -> VkTutorialResult<Vec<String>> {
+    let mut rejection_reasons = vec![];
+
+    let indices = share::find_queue_family(instance, physical_device, surface_stuff);
+    if indices.graphics_family.is_none() {
+        rejection_reasons.push(String::from("no graphics queue family"));
+    }
+    if indices.present_family.is_none() {
+        rejection_reasons.push(String::from("no queue family presents to the surface"));
+    }
+
+    // the swapchain support can only be queried with the extension.
+    let is_swapchain_extension_available = available_extension_names(instance, physical_device)?
+        .iter()
+        .any(|available| available.as_str() == "VK_KHR_swapchain");
+    if !surface_stuff.is_headless() && is_swapchain_extension_available {
+        let swapchain_support = share::query_swapchain_support(physical_device, surface_stuff)?;
+        if swapchain_support.formats.is_empty() || swapchain_support.present_modes.is_empty() {
+            rejection_reasons.push(String::from(
+                "no surface format or present mode for the surface",
+            ));
+        }
+    }
+
+    Ok(rejection_reasons)
+}
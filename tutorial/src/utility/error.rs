@@ -0,0 +1,190 @@
+//! The errors returned by the helper functions in this tutorial.
+
+use ash::vk;
+
+use std::fmt;
+
+pub type VkTutorialResult<T> = Result<T, VkTutorialError>;
+
+#[derive(Debug)]
+pub enum VkTutorialError {
+    /// The names of the requested validation layers, at least one of them is not available.
+    MissingLayer(String),
+    /// Failed to load the functions of Vulkan instance.
+    InstanceLoad(Vec<&'static str>),
+    /// None of the physical devices meets the requirement of the tutorial,
+    /// with a description of every rejected device.
+    NoSuitableDevice(Vec<String>),
+    /// None of the candidate formats supports the features.
+    UnsupportedFormat {
+        candidates: Vec<vk::Format>,
+        features: vk::FormatFeatureFlags,
+    },
+    /// None of the memory types matches the filter and has the properties.
+    NoSuitableMemoryType {
+        type_filter: u32,
+        properties: vk::MemoryPropertyFlags,
+    },
+    /// The device or the host runs out of memory.
+    OutOfMemory(vk::Result),
+    UnsupportedLayoutTransition {
+        old_layout: vk::ImageLayout,
+        new_layout: vk::ImageLayout,
+    },
+    ShaderLoad(String),
+    /// The errors reported by the GLSL compiler.
+    ShaderCompile(String),
+    /// The vertex input or the descriptors do not match the ones declared by the shaders.
+    ShaderInterfaceMismatch(String),
+    ImageLoad(image::ImageError),
+    ModelLoad(String),
+    /// The KTX2 or DDS file is malformed, or stores a texture which is not supported.
+    TextureLoad(String),
+    /// The rendered images can not be copied from, or nothing has been rendered yet.
+    CaptureUnsupported,
+    /// The pipeline cache can not be written to its file.
+    PipelineCacheSave(String),
+    /// The passes of a render graph can not be compiled or executed.
+    RenderGraph(String),
+    /// The memory allocator does not own the allocation, or it is freed already.
+    UnknownAllocation,
+    /// All the slots of the uniform ring for the current frame are written.
+    UniformRingFull {
+        slots_per_frame: u32,
+    },
+    /// The data of an upload is empty, Vulkan can not copy zero bytes.
+    EmptyUpload,
+    /// A descriptor pool can not hold the descriptors of the type needed by a set.
+    DescriptorPoolTooSmall {
+        descriptor_type: vk::DescriptorType,
+        descriptor_count: u32,
+    },
+    /// Any other failed Vulkan call.
+    Vulkan(vk::Result),
+}
+
+impl fmt::Display for VkTutorialError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VkTutorialError::MissingLayer(layer_names) => write!(
+                f,
+                "Validation layers requested, but not available: {}",
+                layer_names
+            ),
+            VkTutorialError::InstanceLoad(function_names) => write!(
+                f,
+                "Failed to load instance functions: {}",
+                function_names.join(", ")
+            ),
+            VkTutorialError::NoSuitableDevice(rejected_devices) => {
+                write!(f, "Failed to find a suitable GPU")?;
+                for rejected_device in rejected_devices.iter() {
+                    write!(f, "\n    {}", rejected_device)?;
+                }
+                Ok(())
+            }
+            VkTutorialError::UnsupportedFormat {
+                candidates,
+                features,
+            } => write!(
+                f,
+                "None of the formats {:?} supports {:?}",
+                candidates, features
+            ),
+            VkTutorialError::NoSuitableMemoryType {
+                type_filter,
+                properties,
+            } => write!(
+                f,
+                "Failed to find suitable memory type in {:#b} with {:?}",
+                type_filter, properties
+            ),
+            VkTutorialError::OutOfMemory(result) => write!(f, "Out of memory: {}", result),
+            VkTutorialError::UnsupportedLayoutTransition {
+                old_layout,
+                new_layout,
+            } => write!(
+                f,
+                "Unsupported layout transition from {:?} to {:?}",
+                old_layout, new_layout
+            ),
+            VkTutorialError::ShaderLoad(reason) => write!(f, "Failed to load shader: {}", reason),
+            VkTutorialError::ShaderCompile(messages) => {
+                write!(f, "Failed to compile shader: {}", messages)
+            }
+            VkTutorialError::ShaderInterfaceMismatch(reason) => {
+                write!(f, "Shader interface mismatch: {}", reason)
+            }
+            VkTutorialError::ImageLoad(error) => write!(f, "Failed to load image: {}", error),
+            VkTutorialError::ModelLoad(reason) => write!(f, "Failed to load model: {}", reason),
+            VkTutorialError::TextureLoad(reason) => {
+                write!(f, "Failed to load texture: {}", reason)
+            }
+            VkTutorialError::CaptureUnsupported => write!(f, "The frame can not be captured"),
+            VkTutorialError::PipelineCacheSave(reason) => {
+                write!(f, "Failed to save the pipeline cache: {}", reason)
+            }
+            VkTutorialError::RenderGraph(reason) => write!(f, "Invalid render graph: {}", reason),
+            VkTutorialError::UnknownAllocation => {
+                write!(f, "The allocation is not owned by the memory allocator")
+            }
+            VkTutorialError::UniformRingFull { slots_per_frame } => write!(
+                f,
+                "The {} uniform slots of the frame are all used",
+                slots_per_frame
+            ),
+            VkTutorialError::EmptyUpload => write!(f, "The data to upload is empty"),
+            VkTutorialError::DescriptorPoolTooSmall {
+                descriptor_type,
+                descriptor_count,
+            } => write!(
+                f,
+                "The descriptor pools can not hold {} descriptors of type {:?}",
+                descriptor_count, descriptor_type
+            ),
+            VkTutorialError::Vulkan(result) => write!(f, "Vulkan call failed: {}", result),
+        }
+    }
+}
+
+impl std::error::Error for VkTutorialError {}
+
+impl From<vk::Result> for VkTutorialError {
+    fn from(result: vk::Result) -> VkTutorialError {
+        match result {
+            vk::Result::ERROR_OUT_OF_HOST_MEMORY | vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => {
+                VkTutorialError::OutOfMemory(result)
+            }
+            _ => VkTutorialError::Vulkan(result),
+        }
+    }
+}
+
+impl From<ash::InstanceError> for VkTutorialError {
+    fn from(error: ash::InstanceError) -> VkTutorialError {
+        match error {
+            ash::InstanceError::LoadError(function_names) => {
+                VkTutorialError::InstanceLoad(function_names)
+            }
+            ash::InstanceError::VkError(result) => VkTutorialError::from(result),
+        }
+    }
+}
+
+impl From<image::ImageError> for VkTutorialError {
+    fn from(error: image::ImageError) -> VkTutorialError {
+        VkTutorialError::ImageLoad(error)
+    }
+}
+
+impl From<tobj::LoadError> for VkTutorialError {
+    fn from(error: tobj::LoadError) -> VkTutorialError {
+        VkTutorialError::ModelLoad(format!("{:?}", error))
+    }
+}
+
+impl From<gltf::Error> for VkTutorialError {
+    fn from(error: gltf::Error) -> VkTutorialError {
+        VkTutorialError::ModelLoad(error.to_string())
+    }
+}
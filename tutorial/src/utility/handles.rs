@@ -0,0 +1,154 @@
+//! Owned Vulkan handles, which destroy themselves on drop.
+//!
+//! Every handle holds an `Arc` to the `Device` it was created from, and the device holds an `Arc`
+//! to the `Instance`, so a parent is only destroyed after all of its children have been dropped.
+//! Use `share::owned` to create them, or wrap any other handle with `new` or `from_raws`.
+
+use ash::version::{
+    DeviceV1_0,
+    InstanceV1_0,
+};
+use ash::vk;
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+pub struct Instance {
+    // the instance functions are loaded from the library of entry, so keep it alive.
+    _entry: ash::Entry,
+    raw: ash::Instance,
+}
+
+impl Instance {
+    pub fn new(entry: &ash::Entry, raw: ash::Instance) -> Arc<Instance> {
+        Arc::new(Instance {
+            _entry: entry.clone(),
+            raw,
+        })
+    }
+}
+
+impl Deref for Instance {
+    type Target = ash::Instance;
+
+    fn deref(&self) -> &ash::Instance {
+        &self.raw
+    }
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        unsafe {
+            self.raw.destroy_instance(None);
+        }
+    }
+}
+
+pub struct Device {
+    _instance: Arc<Instance>,
+    raw: ash::Device,
+}
+
+impl Device {
+    pub fn new(instance: &Arc<Instance>, raw: ash::Device) -> Arc<Device> {
+        Arc::new(Device {
+            _instance: instance.clone(),
+            raw,
+        })
+    }
+}
+
+impl Deref for Device {
+    type Target = ash::Device;
+
+    fn deref(&self) -> &ash::Device {
+        &self.raw
+    }
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        unsafe {
+            self.raw.destroy_device(None);
+        }
+    }
+}
+
+macro_rules! define_handle {
+    ($name:ident, $raw:ty, $destroy:ident) => {
+        pub struct $name {
+            device: Arc<Device>,
+            raw: $raw,
+        }
+
+        impl $name {
+            /// Take the ownership of `raw`, which must have been created from `device`.
+            pub fn new(device: &Arc<Device>, raw: $raw) -> $name {
+                $name {
+                    device: device.clone(),
+                    raw,
+                }
+            }
+
+            /// Take the ownership of every handle of `raws`, e.g. the ones created by a `share` function.
+            pub fn from_raws(device: &Arc<Device>, raws: Vec<$raw>) -> Vec<$name> {
+                raws.into_iter()
+                    .map(|raw| $name::new(device, raw))
+                    .collect()
+            }
+
+            pub fn raw(&self) -> $raw {
+                self.raw
+            }
+
+            pub fn device(&self) -> &Arc<Device> {
+                &self.device
+            }
+        }
+
+        impl Deref for $name {
+            type Target = $raw;
+
+            fn deref(&self) -> &$raw {
+                &self.raw
+            }
+        }
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                unsafe {
+                    self.device.$destroy(self.raw, None);
+                }
+            }
+        }
+    };
+}
+
+define_handle!(Buffer, vk::Buffer, destroy_buffer);
+define_handle!(DeviceMemory, vk::DeviceMemory, free_memory);
+define_handle!(Image, vk::Image, destroy_image);
+define_handle!(ImageView, vk::ImageView, destroy_image_view);
+define_handle!(Sampler, vk::Sampler, destroy_sampler);
+define_handle!(ShaderModule, vk::ShaderModule, destroy_shader_module);
+define_handle!(RenderPass, vk::RenderPass, destroy_render_pass);
+define_handle!(PipelineLayout, vk::PipelineLayout, destroy_pipeline_layout);
+define_handle!(Pipeline, vk::Pipeline, destroy_pipeline);
+define_handle!(Framebuffer, vk::Framebuffer, destroy_framebuffer);
+define_handle!(CommandPool, vk::CommandPool, destroy_command_pool);
+define_handle!(DescriptorPool, vk::DescriptorPool, destroy_descriptor_pool);
+define_handle!(
+    DescriptorSetLayout,
+    vk::DescriptorSetLayout,
+    destroy_descriptor_set_layout
+);
+define_handle!(Semaphore, vk::Semaphore, destroy_semaphore);
+define_handle!(Fence, vk::Fence, destroy_fence);
+
+/// Collect the raw handles, e.g. to pass the image views to `share::v1::create_framebuffers`.
+pub fn raw_handles<H, T>(handles: &[H]) -> Vec<T>
+where
+    H: Deref<Target = T>,
+    T: Copy,
+{
+    handles.iter().map(|handle| **handle).collect()
+}
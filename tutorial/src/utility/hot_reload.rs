@@ -0,0 +1,125 @@
+//! Compile the GLSL sources in `shaders/src` at runtime, and watch them for edits.
+//!
+//! The compiler is only built with the `hot-reload` feature:
+//! `cargo run --features hot-reload --bin 16`, then edit `shaders/src/09-shader-base.frag`.
+
+use std::fs;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::time::{
+    Duration,
+    Instant,
+    SystemTime,
+};
+
+use crate::utility::error::{
+    VkTutorialError,
+    VkTutorialResult,
+};
+
+/// How often `ShaderWatcher::poll` looks at the files.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Whether the GLSL compiler is available in this build.
+pub fn is_supported() -> bool {
+    cfg!(feature = "hot-reload")
+}
+
+/// Compile a GLSL source into SPIR-V, the shader stage is deduced from the extension of `source_path`.
+#[cfg(feature = "hot-reload")]
+pub fn compile_glsl(source_path: &Path) -> VkTutorialResult<Vec<u8>> {
+    let shader_kind = match source_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("vert") => shaderc::ShaderKind::Vertex,
+        Some("frag") => shaderc::ShaderKind::Fragment,
+        Some("comp") => shaderc::ShaderKind::Compute,
+        Some("geom") => shaderc::ShaderKind::Geometry,
+        Some("tesc") => shaderc::ShaderKind::TessControl,
+        Some("tese") => shaderc::ShaderKind::TessEvaluation,
+        _ => {
+            return Err(VkTutorialError::ShaderCompile(format!(
+                "Unknown shader stage of {:?}",
+                source_path
+            )))
+        }
+    };
+
+    let source = fs::read_to_string(source_path).map_err(|error| {
+        VkTutorialError::ShaderLoad(format!("Failed to read {:?}: {}", source_path, error))
+    })?;
+
+    let mut compiler = shaderc::Compiler::new().ok_or_else(|| {
+        VkTutorialError::ShaderCompile(String::from("Failed to initialize shaderc"))
+    })?;
+    let artifact = compiler
+        .compile_into_spirv(
+            &source,
+            shader_kind,
+            &source_path.to_string_lossy(),
+            "main",
+            None,
+        )
+        .map_err(|error| VkTutorialError::ShaderCompile(error.to_string()))?;
+
+    if artifact.get_num_warnings() > 0 {
+        eprintln!("{}", artifact.get_warning_messages());
+    }
+
+    Ok(artifact.as_binary_u8().to_vec())
+}
+
+#[cfg(not(feature = "hot-reload"))]
+pub fn compile_glsl(source_path: &Path) -> VkTutorialResult<Vec<u8>> {
+    Err(VkTutorialError::ShaderCompile(format!(
+        "Can not compile {:?}, rebuild with `--features hot-reload`",
+        source_path
+    )))
+}
+
+/// Detect the edits to some files by polling their modification time.
+pub struct ShaderWatcher {
+    sources: Vec<(PathBuf, Option<SystemTime>)>,
+    last_poll: Instant,
+}
+
+impl ShaderWatcher {
+    pub fn new(source_paths: &[&Path]) -> ShaderWatcher {
+        ShaderWatcher {
+            sources: source_paths
+                .iter()
+                .map(|path| (path.to_path_buf(), modified_time(path)))
+                .collect(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Return the files modified since the last call, it is cheap to call it every frame.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return vec![];
+        }
+        self.last_poll = Instant::now();
+
+        let mut modified_paths = vec![];
+        for (path, last_modified) in self.sources.iter_mut() {
+            let modified = modified_time(path);
+            // the editors may remove the file for a moment while saving it.
+            if modified.is_some() && modified != *last_modified {
+                *last_modified = modified;
+                modified_paths.push(path.clone());
+            }
+        }
+
+        modified_paths
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -1,11 +1,24 @@
 //! The utility mod define some fixed function using in this tutorial.
 //! Help to simplify the code.
 
+pub mod allocator;
 pub mod constants;
 pub mod debug;
+pub mod descriptor;
+pub mod device_selection;
+pub mod error;
 pub mod fps_limiter;
+pub mod handles;
+pub mod hot_reload;
+pub mod model;
+pub mod pipeline_cache;
 pub mod platforms;
+pub mod reflection;
+pub mod render_graph;
+pub mod render_target;
+pub mod sampler;
 pub mod share;
 pub mod structures;
+pub mod texture;
 pub mod tools;
 pub mod window;
@@ -0,0 +1,677 @@
+//! The models loaded from files, with their meshes, materials, images and node hierarchy.
+//!
+//! `load_gltf` reads glTF 2.0 files (`.gltf` with its buffers and images, or `.glb`),
+//! and `load_obj` reads Wavefront OBJ files with their MTL materials, whose image files are only
+//! decoded when `ModelImage::rgba` is called.
+//! Use `Model::flatten` to get a single vertex and index buffer, like the one of `share::load_model`.
+
+use cgmath::{
+    Matrix,
+    Matrix4,
+    SquareMatrix,
+    Vector4,
+};
+use image::RgbaImage;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use crate::utility::error::{
+    VkTutorialError,
+    VkTutorialResult,
+};
+use crate::utility::structures::VertexV4;
+
+pub struct Model {
+    pub vertices: Vec<VertexV4>,
+    /// The indices of all the meshes, which point directly into `vertices`.
+    pub indices: Vec<u32>,
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+    /// The images used by the materials.
+    pub images: Vec<ModelImage>,
+    pub nodes: Vec<Node>,
+    /// The nodes at the top of the hierarchy of the displayed scene.
+    pub root_nodes: Vec<usize>,
+}
+
+pub struct Mesh {
+    pub name: Option<String>,
+    pub draw_ranges: Vec<DrawRange>,
+}
+
+/// A range of `Model::indices` drawn with a single material.
+#[derive(Debug, Clone, Copy)]
+pub struct DrawRange {
+    pub first_index: u32,
+    pub index_count: u32,
+    /// The index in `Model::materials`, or `None` for the default material.
+    pub material: Option<usize>,
+}
+
+/// A metallic-roughness material, the images are indices in `Model::images`.
+pub struct Material {
+    pub name: Option<String>,
+    pub base_color_factor: [f32; 4],
+    pub base_color_image: Option<usize>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub metallic_roughness_image: Option<usize>,
+    pub normal_image: Option<usize>,
+    pub emissive_factor: [f32; 3],
+    pub emissive_image: Option<usize>,
+}
+
+pub enum ModelImage {
+    /// An image stored in the model file or its buffers, decoded as RGBA.
+    Decoded(RgbaImage),
+    /// An image file referenced by the model, not read yet.
+    File(PathBuf),
+}
+
+impl ModelImage {
+    /// The image as RGBA, the file of an image which is not decoded yet is read on each call.
+    pub fn rgba(&self) -> VkTutorialResult<Cow<'_, RgbaImage>> {
+        match self {
+            ModelImage::Decoded(image) => Ok(Cow::Borrowed(image)),
+            ModelImage::File(image_path) => Ok(Cow::Owned(image::open(image_path)?.to_rgba())),
+        }
+    }
+}
+
+pub struct Node {
+    pub name: Option<String>,
+    /// The transform relative to the parent node.
+    pub transform: Matrix4<f32>,
+    /// The index in `Model::meshes`.
+    pub mesh: Option<usize>,
+    /// The indices in `Model::nodes`.
+    pub children: Vec<usize>,
+}
+
+impl Model {
+    /// The transform of every node relative to the model, `None` for the nodes outside the scene.
+    pub fn world_transforms(&self) -> Vec<Option<Matrix4<f32>>> {
+        let mut world_transforms = vec![None; self.nodes.len()];
+
+        let mut pending_nodes: Vec<(usize, Matrix4<f32>)> = self
+            .root_nodes
+            .iter()
+            .map(|&node| (node, Matrix4::identity()))
+            .collect();
+        while let Some((node_index, parent_transform)) = pending_nodes.pop() {
+            // a node can only have a single parent, this guards against malformed files.
+            if world_transforms[node_index].is_some() {
+                continue;
+            }
+
+            let node = &self.nodes[node_index];
+            let world_transform = parent_transform * node.transform;
+            world_transforms[node_index] = Some(world_transform);
+
+            pending_nodes.extend(node.children.iter().map(|&child| (child, world_transform)));
+        }
+
+        world_transforms
+    }
+
+    /// Merge the meshes of all the nodes of the scene in one vertex and index buffer,
+    /// with the transforms of the nodes applied to the vertices.
+    /// The vertices which are identical once transformed are merged, even across the nodes.
+    pub fn flatten(&self) -> (Vec<VertexV4>, Vec<u32>) {
+        let mut vertices = vec![];
+        let mut indices = vec![];
+        let mut unique_vertices: HashMap<[u32; 12], u32> = HashMap::new();
+
+        for (node, world_transform) in self.nodes.iter().zip(self.world_transforms()) {
+            let (mesh, world_transform) = match (node.mesh, world_transform) {
+                (Some(mesh), Some(world_transform)) => (&self.meshes[mesh], world_transform),
+                _ => continue,
+            };
+            let normal_transform = world_transform
+                .invert()
+                .unwrap_or(world_transform)
+                .transpose();
+
+            // a mesh may be used by several nodes, so its vertices are transformed for each of them,
+            // once per vertex of the mesh.
+            let mut transformed_vertices: HashMap<u32, u32> = HashMap::new();
+            for draw_range in mesh.draw_ranges.iter() {
+                let first_index = draw_range.first_index as usize;
+                let last_index = first_index + draw_range.index_count as usize;
+
+                for &index in self.indices[first_index..last_index].iter() {
+                    let unique_index = *transformed_vertices.entry(index).or_insert_with(|| {
+                        let vertex = &self.vertices[index as usize];
+                        let pos = world_transform
+                            * Vector4::new(vertex.pos[0], vertex.pos[1], vertex.pos[2], 1.0);
+                        let normal = normal_transform
+                            * Vector4::new(
+                                vertex.normal[0],
+                                vertex.normal[1],
+                                vertex.normal[2],
+                                0.0,
+                            );
+                        let transformed_vertex = VertexV4 {
+                            pos: [pos.x, pos.y, pos.z],
+                            normal: normalize([normal.x, normal.y, normal.z]),
+                            ..*vertex
+                        };
+
+                        *unique_vertices
+                            .entry(vertex_key(&transformed_vertex))
+                            .or_insert_with(|| {
+                                vertices.push(transformed_vertex);
+                                (vertices.len() - 1) as u32
+                            })
+                    });
+                    indices.push(unique_index);
+                }
+            }
+        }
+
+        (vertices, indices)
+    }
+}
+
+/// Load a model with the loader matching the extension of `model_path`.
+pub fn load_model(model_path: &Path) -> VkTutorialResult<Model> {
+    match model_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("gltf") | Some("glb") => load_gltf(model_path),
+        _ => load_obj(model_path),
+    }
+}
+
+/// Load an OBJ file, every object becomes a mesh instanced by its own root node.
+/// The identical vertices of all the objects are merged, the missing normals and texture
+/// coordinates are set to zero. The image files of the materials are not read here.
+pub fn load_obj(model_path: &Path) -> VkTutorialResult<Model> {
+    let (obj_models, obj_materials) = tobj::load_obj(model_path)?;
+
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    let mut meshes = vec![];
+    let mut nodes = vec![];
+    let mut unique_vertices: HashMap<[u32; 8], u32> = HashMap::new();
+    for obj_model in obj_models.iter() {
+        let mesh = &obj_model.mesh;
+        let first_index = indices.len() as u32;
+
+        for &index in mesh.indices.iter() {
+            let i = index as usize;
+            let pos = [
+                mesh.positions[i * 3],
+                mesh.positions[i * 3 + 1],
+                mesh.positions[i * 3 + 2],
+            ];
+            let normal = if mesh.normals.is_empty() {
+                [0.0, 0.0, 0.0]
+            } else {
+                [
+                    mesh.normals[i * 3],
+                    mesh.normals[i * 3 + 1],
+                    mesh.normals[i * 3 + 2],
+                ]
+            };
+            let tex_coord = if mesh.texcoords.is_empty() {
+                [0.0, 0.0]
+            } else {
+                [mesh.texcoords[i * 2], mesh.texcoords[i * 2 + 1]]
+            };
+
+            // f32 is not hashable, so the vertices are compared bit by bit.
+            let key = [
+                pos[0].to_bits(),
+                pos[1].to_bits(),
+                pos[2].to_bits(),
+                normal[0].to_bits(),
+                normal[1].to_bits(),
+                normal[2].to_bits(),
+                tex_coord[0].to_bits(),
+                tex_coord[1].to_bits(),
+            ];
+            let unique_index = *unique_vertices.entry(key).or_insert_with(|| {
+                vertices.push(VertexV4 {
+                    pos,
+                    color: [1.0, 1.0, 1.0, 1.0],
+                    tex_coord,
+                    normal,
+                });
+                (vertices.len() - 1) as u32
+            });
+            indices.push(unique_index);
+        }
+
+        nodes.push(Node {
+            name: Some(obj_model.name.clone()),
+            transform: Matrix4::identity(),
+            mesh: Some(meshes.len()),
+            children: vec![],
+        });
+        meshes.push(Mesh {
+            name: Some(obj_model.name.clone()),
+            draw_ranges: vec![DrawRange {
+                first_index,
+                index_count: indices.len() as u32 - first_index,
+                material: mesh.material_id,
+            }],
+        });
+    }
+
+    // the texture paths of the MTL file are relative to the OBJ file.
+    let model_directory = model_path.parent().unwrap_or_else(|| Path::new(""));
+    let mut image_paths: Vec<PathBuf> = vec![];
+    let mut image_index = |texture: &str| {
+        if texture.is_empty() {
+            return None;
+        }
+        let image_path = model_directory.join(texture);
+        match image_paths.iter().position(|path| *path == image_path) {
+            Some(index) => Some(index),
+            None => {
+                image_paths.push(image_path);
+                Some(image_paths.len() - 1)
+            }
+        }
+    };
+    let materials = obj_materials
+        .iter()
+        .map(|obj_material| Material {
+            name: Some(obj_material.name.clone()),
+            base_color_factor: [
+                obj_material.diffuse[0],
+                obj_material.diffuse[1],
+                obj_material.diffuse[2],
+                obj_material.dissolve,
+            ],
+            base_color_image: image_index(&obj_material.diffuse_texture),
+            metallic_factor: 0.0,
+            roughness_factor: 1.0,
+            metallic_roughness_image: None,
+            normal_image: image_index(&obj_material.normal_texture),
+            emissive_factor: [0.0, 0.0, 0.0],
+            emissive_image: None,
+        })
+        .collect();
+
+    let images = image_paths.into_iter().map(ModelImage::File).collect();
+
+    Ok(Model {
+        vertices,
+        indices,
+        meshes,
+        materials,
+        images,
+        root_nodes: (0..nodes.len()).collect(),
+        nodes,
+    })
+}
+
+/// Load a glTF 2.0 file, the triangles of the meshes are kept, the points and lines are skipped.
+pub fn load_gltf(model_path: &Path) -> VkTutorialResult<Model> {
+    let (document, buffers, images) = gltf::import(model_path)?;
+
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    let mut meshes = vec![];
+    for mesh in document.meshes() {
+        let mut draw_ranges = vec![];
+
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                continue;
+            }
+
+            let reader = primitive.reader(|buffer| Some(&*buffers[buffer.index()]));
+            let positions: Vec<[f32; 3]> = match reader.read_positions() {
+                Some(positions) => positions.collect(),
+                None => {
+                    return Err(VkTutorialError::ModelLoad(format!(
+                        "A primitive of mesh #{} has no position",
+                        mesh.index()
+                    )))
+                }
+            };
+            let normals: Vec<[f32; 3]> = reader
+                .read_normals()
+                .map(|normals| normals.collect())
+                .unwrap_or_default();
+            let tex_coords: Vec<[f32; 2]> = reader
+                .read_tex_coords(0)
+                .map(|tex_coords| tex_coords.into_f32().collect())
+                .unwrap_or_default();
+            let colors: Vec<[f32; 4]> = reader
+                .read_colors(0)
+                .map(|colors| colors.into_rgba_f32().collect())
+                .unwrap_or_default();
+
+            let base_vertex = vertices.len() as u32;
+            for (i, &pos) in positions.iter().enumerate() {
+                vertices.push(VertexV4 {
+                    pos,
+                    color: colors.get(i).copied().unwrap_or([1.0, 1.0, 1.0, 1.0]),
+                    tex_coord: tex_coords.get(i).copied().unwrap_or([0.0, 0.0]),
+                    normal: normals.get(i).copied().unwrap_or([0.0, 0.0, 0.0]),
+                });
+            }
+
+            let first_index = indices.len() as u32;
+            match reader.read_indices() {
+                Some(primitive_indices) => indices.extend(offset_indices(
+                    primitive_indices.into_u32(),
+                    base_vertex,
+                    positions.len(),
+                    mesh.index(),
+                )?),
+                // the vertices are drawn in order when there is no index.
+                None => indices.extend(base_vertex..base_vertex + positions.len() as u32),
+            }
+
+            draw_ranges.push(DrawRange {
+                first_index,
+                index_count: indices.len() as u32 - first_index,
+                material: primitive.material().index(),
+            });
+        }
+
+        meshes.push(Mesh {
+            name: mesh.name().map(String::from),
+            draw_ranges,
+        });
+    }
+
+    let materials = document
+        .materials()
+        .map(|material| {
+            let pbr = material.pbr_metallic_roughness();
+            Material {
+                name: material.name().map(String::from),
+                base_color_factor: pbr.base_color_factor(),
+                base_color_image: pbr
+                    .base_color_texture()
+                    .map(|info| info.texture().source().index()),
+                metallic_factor: pbr.metallic_factor(),
+                roughness_factor: pbr.roughness_factor(),
+                metallic_roughness_image: pbr
+                    .metallic_roughness_texture()
+                    .map(|info| info.texture().source().index()),
+                normal_image: material
+                    .normal_texture()
+                    .map(|normal_texture| normal_texture.texture().source().index()),
+                emissive_factor: material.emissive_factor(),
+                emissive_image: material
+                    .emissive_texture()
+                    .map(|info| info.texture().source().index()),
+            }
+        })
+        .collect();
+
+    let nodes = document
+        .nodes()
+        .map(|node| Node {
+            name: node.name().map(String::from),
+            transform: Matrix4::from(node.transform().matrix()),
+            mesh: node.mesh().map(|mesh| mesh.index()),
+            children: node.children().map(|child| child.index()).collect(),
+        })
+        .collect::<Vec<_>>();
+
+    let root_nodes = match document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+        // without scene, all the nodes which are not a child of another one are displayed.
+        None => {
+            let mut is_child = vec![false; nodes.len()];
+            for &child in nodes.iter().flat_map(|node| node.children.iter()) {
+                is_child[child] = true;
+            }
+            (0..nodes.len()).filter(|&node| !is_child[node]).collect()
+        }
+    };
+
+    let images = images
+        .into_iter()
+        .map(|image| Ok(ModelImage::Decoded(convert_gltf_image(image)?)))
+        .collect::<VkTutorialResult<Vec<_>>>()?;
+
+    Ok(Model {
+        vertices,
+        indices,
+        meshes,
+        materials,
+        images,
+        nodes,
+        root_nodes,
+    })
+}
+
+/// Offset the `primitive_indices` by `base_vertex`, after checking that each one is below `vertex_count`.
+fn offset_indices(
+    primitive_indices: impl Iterator<Item = u32>,
+    base_vertex: u32,
+    vertex_count: usize,
+    mesh_index: usize,
+) -> VkTutorialResult<Vec<u32>> {
+    primitive_indices
+        .map(|index| {
+            if (index as usize) < vertex_count {
+                Ok(base_vertex + index)
+            } else {
+                Err(VkTutorialError::ModelLoad(format!(
+                    "A primitive of mesh #{} uses the vertex {}, but has only {} vertices",
+                    mesh_index, index, vertex_count
+                )))
+            }
+        })
+        .collect()
+}
+
+fn convert_gltf_image(image: gltf::image::Data) -> VkTutorialResult<RgbaImage> {
+    use gltf::image::Format;
+
+    let rgba_pixels: Vec<u8> = match image.format {
+        Format::R8G8B8A8 => image.pixels,
+        Format::B8G8R8A8 => image
+            .pixels
+            .chunks(4)
+            .flat_map(|bgra| vec![bgra[2], bgra[1], bgra[0], bgra[3]])
+            .collect(),
+        Format::R8G8B8 => image
+            .pixels
+            .chunks(3)
+            .flat_map(|rgb| vec![rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        Format::B8G8R8 => image
+            .pixels
+            .chunks(3)
+            .flat_map(|bgr| vec![bgr[2], bgr[1], bgr[0], 255])
+            .collect(),
+        Format::R8G8 => image
+            .pixels
+            .chunks(2)
+            .flat_map(|rg| vec![rg[0], rg[1], 0, 255])
+            .collect(),
+        Format::R8 => image
+            .pixels
+            .iter()
+            .flat_map(|&r| vec![r, r, r, 255])
+            .collect(),
+        #[allow(unreachable_patterns)]
+        format => {
+            return Err(VkTutorialError::ModelLoad(format!(
+                "Unsupported image format {:?}",
+                format
+            )))
+        }
+    };
+
+    RgbaImage::from_raw(image.width, image.height, rgba_pixels).ok_or_else(|| {
+        VkTutorialError::ModelLoad(String::from(
+            "The size of an image does not match its pixels",
+        ))
+    })
+}
+
+/// f32 is not hashable, so the vertices are compared bit by bit.
+fn vertex_key(vertex: &VertexV4) -> [u32; 12] {
+    [
+        vertex.pos[0].to_bits(),
+        vertex.pos[1].to_bits(),
+        vertex.pos[2].to_bits(),
+        vertex.color[0].to_bits(),
+        vertex.color[1].to_bits(),
+        vertex.color[2].to_bits(),
+        vertex.color[3].to_bits(),
+        vertex.tex_coord[0].to_bits(),
+        vertex.tex_coord[1].to_bits(),
+        vertex.normal[0].to_bits(),
+        vertex.normal[1].to_bits(),
+        vertex.normal[2].to_bits(),
+    ]
+}
+
+fn normalize(vector: [f32; 3]) -> [f32; 3] {
+    let length = (vector[0] * vector[0] + vector[1] * vector[1] + vector[2] * vector[2]).sqrt();
+    if length > 0.0 {
+        [vector[0] / length, vector[1] / length, vector[2] / length]
+    } else {
+        vector
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cgmath::Vector3;
+
+    use std::fs;
+
+    fn vertex(pos: [f32; 3]) -> VertexV4 {
+        VertexV4 {
+            pos,
+            color: [1.0, 1.0, 1.0, 1.0],
+            tex_coord: [0.0, 0.0],
+            normal: [0.0, 0.0, 1.0],
+        }
+    }
+
+    /// Two meshes sharing an edge of the unit square, each drawn by its own node.
+    fn two_triangles(second_transform: Matrix4<f32>) -> Model {
+        let mesh = |first_index| Mesh {
+            name: None,
+            draw_ranges: vec![DrawRange {
+                first_index,
+                index_count: 3,
+                material: None,
+            }],
+        };
+        let node = |mesh, transform| Node {
+            name: None,
+            transform,
+            mesh: Some(mesh),
+            children: vec![],
+        };
+
+        Model {
+            vertices: vec![
+                vertex([0.0, 0.0, 0.0]),
+                vertex([1.0, 0.0, 0.0]),
+                vertex([1.0, 1.0, 0.0]),
+                vertex([0.0, 1.0, 0.0]),
+            ],
+            indices: vec![0, 1, 2, 2, 3, 0],
+            meshes: vec![mesh(0), mesh(3)],
+            materials: vec![],
+            images: vec![],
+            nodes: vec![node(0, Matrix4::identity()), node(1, second_transform)],
+            root_nodes: vec![0, 1],
+        }
+    }
+
+    #[test]
+    fn flatten_merges_the_vertices_shared_by_the_nodes() {
+        let (vertices, indices) = two_triangles(Matrix4::identity()).flatten();
+
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(indices, vec![0, 1, 2, 2, 3, 0]);
+    }
+
+    #[test]
+    fn flatten_keeps_the_vertices_moved_apart() {
+        let translation = Matrix4::from_translation(Vector3::new(0.0, 0.0, 1.0));
+        let (vertices, indices) = two_triangles(translation).flatten();
+
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(indices, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(vertices[3].pos, [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn flatten_keeps_the_vertices_of_a_mesh_used_twice() {
+        let mut model = two_triangles(Matrix4::identity());
+        model.meshes.truncate(1);
+        model.nodes[1].mesh = Some(0);
+        model.nodes[1].transform = Matrix4::from_translation(Vector3::new(2.0, 0.0, 0.0));
+
+        let (vertices, indices) = model.flatten();
+
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(indices, vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn gltf_indices_are_offset_by_the_previous_vertices() {
+        let indices = offset_indices(vec![0, 2, 1].into_iter(), 4, 3, 0).unwrap();
+        assert_eq!(indices, vec![4, 6, 5]);
+    }
+
+    #[test]
+    fn gltf_indices_out_of_the_primitive_are_rejected() {
+        match offset_indices(vec![0, 1, 3].into_iter(), 4, 3, 0) {
+            Err(VkTutorialError::ModelLoad(_)) => (),
+            result => panic!("the index out of range is accepted: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn obj_images_are_read_when_used() {
+        let directory = std::env::temp_dir().join("vulkan-tutorial-rust-model-test");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("square.obj"),
+            "mtllib square.mtl\n\
+             v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             o first\nusemtl textured\nf 1 2 3\n\
+             o second\nusemtl textured\nf 3 4 1\n",
+        )
+        .unwrap();
+        fs::write(
+            directory.join("square.mtl"),
+            "newmtl textured\nKd 1 1 1\nmap_Kd missing.png\n",
+        )
+        .unwrap();
+
+        let model = load_obj(&directory.join("square.obj")).unwrap();
+
+        // the objects share their vertices, which stay merged after flattening.
+        assert_eq!(model.vertices.len(), 4);
+        assert_eq!(model.flatten().0.len(), 4);
+
+        assert_eq!(model.materials[0].base_color_image, Some(0));
+        match &model.images[0] {
+            ModelImage::File(image_path) => assert_eq!(*image_path, directory.join("missing.png")),
+            ModelImage::Decoded(_) => panic!("the image is decoded while loading the model"),
+        }
+        assert!(model.images[0].rgba().is_err());
+    }
+}
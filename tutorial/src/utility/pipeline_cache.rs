@@ -0,0 +1,276 @@
+//! Keep the compiled pipelines between the runs in a `vk::PipelineCache` saved on disk.
+//!
+//! A cache file is only valid for one driver of one GPU, so its name contains the vendor ID,
+//! the device ID and the `pipelineCacheUUID` of the physical device. The header of the file
+//! is checked again before it is given to the driver, and a stale or corrupted file is ignored.
+
+use ash::version::{
+    DeviceV1_0,
+    InstanceV1_0,
+};
+use ash::vk;
+
+use std::fs;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::ptr;
+
+use crate::utility::error::{
+    VkTutorialError,
+    VkTutorialResult,
+};
+
+/// Set it to the directory of the cache files, instead of the temporary directory.
+const PIPELINE_CACHE_DIR_VAR: &str = "VK_TUTORIAL_PIPELINE_CACHE_DIR";
+/// The size of `VkPipelineCacheHeaderVersionOne`.
+const PIPELINE_CACHE_HEADER_SIZE: usize = 16 + vk::UUID_SIZE;
+const PIPELINE_CACHE_HEADER_VERSION_ONE: u32 = 1;
+
+pub struct PipelineCache {
+    device: ash::Device,
+    pub cache: vk::PipelineCache,
+    path: PathBuf,
+    vendor_id: u32,
+    device_id: u32,
+    pipeline_cache_uuid: [u8; vk::UUID_SIZE],
+}
+
+impl PipelineCache {
+    /// Load the cache of `physical_device` from the directory given by `VK_TUTORIAL_PIPELINE_CACHE_DIR`,
+    /// or from the temporary directory. Start from an empty cache when the file is missing or invalid.
+    pub fn new(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &ash::Device,
+    ) -> VkTutorialResult<PipelineCache> {
+        let directory = match std::env::var_os(PIPELINE_CACHE_DIR_VAR) {
+            Some(directory) => PathBuf::from(directory),
+            None => std::env::temp_dir().join("vulkan-tutorial-rust"),
+        };
+
+        PipelineCache::with_directory(instance, physical_device, device, &directory)
+    }
+
+    pub fn with_directory(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &ash::Device,
+        directory: &Path,
+    ) -> VkTutorialResult<PipelineCache> {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+
+        let mut pipeline_cache = PipelineCache {
+            device: device.clone(),
+            cache: vk::PipelineCache::null(),
+            path: directory.join(cache_file_name(&properties)),
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            pipeline_cache_uuid: properties.pipeline_cache_uuid,
+        };
+
+        let initial_data = match fs::read(&pipeline_cache.path) {
+            Ok(data) => match validate_header(
+                &data,
+                pipeline_cache.vendor_id,
+                pipeline_cache.device_id,
+                &pipeline_cache.pipeline_cache_uuid,
+            ) {
+                Ok(()) => data,
+                Err(reason) => {
+                    eprintln!(
+                        "Discard the pipeline cache {:?}: {}",
+                        pipeline_cache.path, reason
+                    );
+                    vec![]
+                }
+            },
+            // nothing has been saved yet.
+            Err(_) => vec![],
+        };
+
+        pipeline_cache.cache = match create_pipeline_cache(device, &initial_data) {
+            Ok(cache) => cache,
+            // the driver may still reject data with a valid header.
+            Err(_) if !initial_data.is_empty() => {
+                eprintln!(
+                    "Discard the pipeline cache {:?}: rejected by the driver",
+                    pipeline_cache.path
+                );
+                create_pipeline_cache(device, &[])?
+            }
+            Err(result) => return Err(result.into()),
+        };
+
+        Ok(pipeline_cache)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write the content of the cache to its file, replacing the previous one.
+    pub fn save(&self) -> VkTutorialResult<()> {
+        let data = unsafe { self.device.get_pipeline_cache_data(self.cache)? };
+
+        let save_error = |error: std::io::Error| {
+            VkTutorialError::PipelineCacheSave(format!("{:?}: {}", self.path, error))
+        };
+
+        if let Some(directory) = self.path.parent() {
+            fs::create_dir_all(directory).map_err(save_error)?;
+        }
+        // write a temporary file first, so that an interrupted save does not leave a truncated cache.
+        let temporary_path = self.path.with_extension("tmp");
+        fs::write(&temporary_path, &data).map_err(save_error)?;
+        fs::rename(&temporary_path, &self.path).map_err(save_error)?;
+
+        Ok(())
+    }
+
+    /// Save the cache and destroy it, a failed save is only reported.
+    pub unsafe fn destroy(&mut self) {
+        if let Err(error) = self.save() {
+            eprintln!("{}", error);
+        }
+
+        self.device.destroy_pipeline_cache(self.cache, None);
+        self.cache = vk::PipelineCache::null();
+    }
+}
+
+/// Check the `VkPipelineCacheHeaderVersionOne` at the beginning of `data` against the physical device.
+fn validate_header(
+    data: &[u8],
+    expected_vendor_id: u32,
+    expected_device_id: u32,
+    expected_pipeline_cache_uuid: &[u8; vk::UUID_SIZE],
+) -> Result<(), String> {
+    if data.len() < PIPELINE_CACHE_HEADER_SIZE {
+        return Err(format!("{} bytes is too short for the header", data.len()));
+    }
+
+    let read_u32 = |offset: usize| {
+        u32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ])
+    };
+    let header_size = read_u32(0) as usize;
+    let header_version = read_u32(4);
+    let vendor_id = read_u32(8);
+    let device_id = read_u32(12);
+    let pipeline_cache_uuid = &data[16..PIPELINE_CACHE_HEADER_SIZE];
+
+    if header_size < PIPELINE_CACHE_HEADER_SIZE || header_size > data.len() {
+        return Err(format!("invalid header size {}", header_size));
+    }
+    if header_version != PIPELINE_CACHE_HEADER_VERSION_ONE {
+        return Err(format!("unknown header version {}", header_version));
+    }
+    if vendor_id != expected_vendor_id || device_id != expected_device_id {
+        return Err(format!(
+            "saved for the device {:04x}:{:04x}",
+            vendor_id, device_id
+        ));
+    }
+    if pipeline_cache_uuid != expected_pipeline_cache_uuid {
+        return Err(String::from("saved by another driver version"));
+    }
+
+    Ok(())
+}
+
+fn cache_file_name(properties: &vk::PhysicalDeviceProperties) -> String {
+    let uuid: String = properties
+        .pipeline_cache_uuid
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    format!(
+        "pipeline-cache-{:04x}-{:04x}-{}.bin",
+        properties.vendor_id, properties.device_id, uuid
+    )
+}
+
+fn create_pipeline_cache(
+    device: &ash::Device,
+    initial_data: &[u8],
+) -> Result<vk::PipelineCache, vk::Result> {
+    let pipeline_cache_create_info = vk::PipelineCacheCreateInfo {
+        s_type: vk::StructureType::PIPELINE_CACHE_CREATE_INFO,
+        p_next: ptr::null(),
+        flags: vk::PipelineCacheCreateFlags::empty(),
+        initial_data_size: initial_data.len(),
+        p_initial_data: initial_data.as_ptr() as *const std::os::raw::c_void,
+    };
+
+    unsafe { device.create_pipeline_cache(&pipeline_cache_create_info, None) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VENDOR_ID: u32 = 0x10de;
+    const DEVICE_ID: u32 = 0x1c82;
+    const PIPELINE_CACHE_UUID: [u8; vk::UUID_SIZE] = [7; vk::UUID_SIZE];
+
+    /// A cache of `payload_size` bytes after a `VkPipelineCacheHeaderVersionOne`.
+    fn cache_data(
+        vendor_id: u32,
+        device_id: u32,
+        pipeline_cache_uuid: [u8; vk::UUID_SIZE],
+        payload_size: usize,
+    ) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(&(PIPELINE_CACHE_HEADER_SIZE as u32).to_le_bytes());
+        data.extend_from_slice(&PIPELINE_CACHE_HEADER_VERSION_ONE.to_le_bytes());
+        data.extend_from_slice(&vendor_id.to_le_bytes());
+        data.extend_from_slice(&device_id.to_le_bytes());
+        data.extend_from_slice(&pipeline_cache_uuid);
+        data.extend(vec![0; payload_size]);
+        data
+    }
+
+    fn validate(data: &[u8]) -> Result<(), String> {
+        validate_header(data, VENDOR_ID, DEVICE_ID, &PIPELINE_CACHE_UUID)
+    }
+
+    #[test]
+    fn header_of_the_same_device_is_valid() {
+        assert!(validate(&cache_data(VENDOR_ID, DEVICE_ID, PIPELINE_CACHE_UUID, 64)).is_ok());
+        assert!(validate(&cache_data(VENDOR_ID, DEVICE_ID, PIPELINE_CACHE_UUID, 0)).is_ok());
+    }
+
+    #[test]
+    fn header_of_another_device_is_rejected() {
+        assert!(validate(&cache_data(0x1002, DEVICE_ID, PIPELINE_CACHE_UUID, 64)).is_err());
+        assert!(validate(&cache_data(VENDOR_ID, 0x67df, PIPELINE_CACHE_UUID, 64)).is_err());
+
+        let mut pipeline_cache_uuid = PIPELINE_CACHE_UUID;
+        pipeline_cache_uuid[vk::UUID_SIZE - 1] = 8;
+        assert!(validate(&cache_data(VENDOR_ID, DEVICE_ID, pipeline_cache_uuid, 64)).is_err());
+    }
+
+    #[test]
+    fn truncated_header_is_rejected() {
+        let data = cache_data(VENDOR_ID, DEVICE_ID, PIPELINE_CACHE_UUID, 0);
+        for length in 0..data.len() {
+            assert!(
+                validate(&data[..length]).is_err(),
+                "{} bytes are accepted",
+                length
+            );
+        }
+
+        // a header size larger than the data.
+        let mut data = data;
+        data[0] += 1;
+        assert!(validate(&data).is_err());
+    }
+}
@@ -7,7 +7,11 @@ use ash::vk;
 #[cfg(target_os = "windows")]
 use ash::extensions::khr::Win32Surface;
 #[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
-use ash::extensions::khr::XlibSurface;
+use ash::extensions::khr::{
+    WaylandSurface,
+    XcbSurface,
+    XlibSurface,
+};
 #[cfg(target_os = "macos")]
 use ash::extensions::mvk::MacOSSurface;
 
@@ -45,6 +49,7 @@ pub fn required_extension_names() -> Vec<*const i8> {
     ]
 }
 
+/// The extensions used when no window is known yet, which default to Xlib on Linux.
 #[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
 pub fn required_extension_names() -> Vec<*const i8> {
     vec![
@@ -53,6 +58,62 @@ pub fn required_extension_names() -> Vec<*const i8> {
         DebugUtils::name().as_ptr(),
     ]
 }
+
+/// The extensions required to create a surface for `window`.
+#[cfg(any(target_os = "macos", windows))]
+pub fn window_extension_names(_window: &winit::window::Window) -> Vec<*const i8> {
+    required_extension_names()
+}
+
+/// The extensions required to create a surface for `window`, see `WindowSystem::of`.
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+pub fn window_extension_names(window: &winit::window::Window) -> Vec<*const i8> {
+    let surface_extension_name = match WindowSystem::of(window) {
+        WindowSystem::Xlib => XlibSurface::name(),
+        WindowSystem::Xcb => XcbSurface::name(),
+        WindowSystem::Wayland => WaylandSurface::name(),
+    };
+
+    vec![
+        Surface::name().as_ptr(),
+        surface_extension_name.as_ptr(),
+        DebugUtils::name().as_ptr(),
+    ]
+}
+
+// no surface will be created in headless mode.
+pub fn headless_extension_names() -> Vec<*const i8> {
+    vec![DebugUtils::name().as_ptr()]
+}
+// ------------------------------------------------------------------------
+
+// window system ----------------------------------------------------------
+/// The window systems of Linux, which are only known once winit has opened the window.
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowSystem {
+    Xlib,
+    Xcb,
+    Wayland,
+}
+
+#[cfg(all(unix, not(target_os = "android"), not(target_os = "macos")))]
+impl WindowSystem {
+    /// Wayland for a Wayland window, Xlib for a X11 one.
+    /// Set `VK_TUTORIAL_X11_SURFACE=xcb` to create the surface of a X11 window with XCB instead.
+    pub fn of(window: &winit::window::Window) -> WindowSystem {
+        use winit::platform::unix::WindowExtUnix;
+
+        if window.wayland_surface().is_some() {
+            return WindowSystem::Wayland;
+        }
+
+        match std::env::var("VK_TUTORIAL_X11_SURFACE") {
+            Ok(ref surface) if surface.eq_ignore_ascii_case("xcb") => WindowSystem::Xcb,
+            _ => WindowSystem::Xlib,
+        }
+    }
+}
 // ------------------------------------------------------------------------
 
 // create surface ---------------------------------------------------------
@@ -65,17 +126,44 @@ pub unsafe fn create_surface<E: EntryV1_0, I: InstanceV1_0>(
     use std::ptr;
     use winit::platform::unix::WindowExtUnix;
 
-    let x11_display = window.xlib_display().unwrap();
-    let x11_window = window.xlib_window().unwrap();
-    let x11_create_info = vk::XlibSurfaceCreateInfoKHR {
-        s_type: vk::StructureType::XLIB_SURFACE_CREATE_INFO_KHR,
-        p_next: ptr::null(),
-        flags: Default::default(),
-        window: x11_window as vk::Window,
-        dpy: x11_display as *mut vk::Display,
-    };
-    let xlib_surface_loader = XlibSurface::new(entry, instance);
-    xlib_surface_loader.create_xlib_surface(&x11_create_info, None)
+    match WindowSystem::of(window) {
+        WindowSystem::Wayland => {
+            let wayland_create_info = vk::WaylandSurfaceCreateInfoKHR {
+                s_type: vk::StructureType::WAYLAND_SURFACE_CREATE_INFO_KHR,
+                p_next: ptr::null(),
+                flags: Default::default(),
+                display: window.wayland_display().unwrap(),
+                surface: window.wayland_surface().unwrap(),
+            };
+            let wayland_surface_loader = WaylandSurface::new(entry, instance);
+            wayland_surface_loader.create_wayland_surface(&wayland_create_info, None)
+        }
+        WindowSystem::Xcb => {
+            let xcb_create_info = vk::XcbSurfaceCreateInfoKHR {
+                s_type: vk::StructureType::XCB_SURFACE_CREATE_INFO_KHR,
+                p_next: ptr::null(),
+                flags: Default::default(),
+                connection: window.xcb_connection().unwrap() as *mut vk::xcb_connection_t,
+                // the X11 window is the same resource for Xlib and XCB.
+                window: window.xlib_window().unwrap() as vk::xcb_window_t,
+            };
+            let xcb_surface_loader = XcbSurface::new(entry, instance);
+            xcb_surface_loader.create_xcb_surface(&xcb_create_info, None)
+        }
+        WindowSystem::Xlib => {
+            let x11_display = window.xlib_display().unwrap();
+            let x11_window = window.xlib_window().unwrap();
+            let x11_create_info = vk::XlibSurfaceCreateInfoKHR {
+                s_type: vk::StructureType::XLIB_SURFACE_CREATE_INFO_KHR,
+                p_next: ptr::null(),
+                flags: Default::default(),
+                window: x11_window as vk::Window,
+                dpy: x11_display as *mut vk::Display,
+            };
+            let xlib_surface_loader = XlibSurface::new(entry, instance);
+            xlib_surface_loader.create_xlib_surface(&x11_create_info, None)
+        }
+    }
 }
 
 #[cfg(target_os = "macos")]
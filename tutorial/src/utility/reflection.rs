@@ -0,0 +1,1070 @@
+//! A minimal SPIR-V reflection, to build the descriptor set layouts and to check the vertex input
+//! from the compiled shaders, instead of keeping them in sync with the GLSL by hand.
+//!
+//! Only the instructions declaring the interface of the shader are parsed, see the
+//! [SPIR-V specification](https://www.khronos.org/registry/spir-v/specs/unified1/SPIRV.html).
+
+use ash::vk;
+
+use std::collections::HashMap;
+use std::ptr;
+
+use crate::utility::error::{
+    VkTutorialError,
+    VkTutorialResult,
+};
+
+const SPIRV_MAGIC_NUMBER: u32 = 0x0723_0203;
+const SPIRV_HEADER_WORD_COUNT: usize = 5;
+
+// opcodes
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+// decorations
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+// storage classes
+const STORAGE_CLASS_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_CLASS_INPUT: u32 = 1;
+const STORAGE_CLASS_UNIFORM: u32 = 2;
+const STORAGE_CLASS_PUSH_CONSTANT: u32 = 9;
+const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+
+// image dimensions
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+/// A resource declared by a shader with `layout(set = .., binding = ..)`.
+#[derive(Debug, Clone, Copy)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    pub descriptor_count: u32,
+}
+
+/// An input declared by a vertex shader with `layout(location = ..) in`.
+#[derive(Debug, Clone, Copy)]
+pub struct VertexInput {
+    pub location: u32,
+    /// The 32 bits format matching the type of the input, e.g. `R32G32B32_SFLOAT` for a `vec3`.
+    pub format: vk::Format,
+}
+
+/// The interface of a shader stage, parsed from its SPIR-V code.
+#[derive(Debug, Clone)]
+pub struct ShaderReflection {
+    pub stage: vk::ShaderStageFlags,
+    pub descriptor_bindings: Vec<DescriptorBinding>,
+    pub push_constant_ranges: Vec<vk::PushConstantRange>,
+    /// Always empty for the stages other than the vertex shader.
+    pub vertex_inputs: Vec<VertexInput>,
+}
+
+impl ShaderReflection {
+    /// Parse the SPIR-V code, e.g. the one returned by `tools::read_shader_code`.
+    pub fn new(code: &[u8]) -> VkTutorialResult<ShaderReflection> {
+        let module = SpirvModule::parse(code)?;
+
+        let stage = module.stage()?;
+        let mut descriptor_bindings = vec![];
+        let mut push_constant_ranges = vec![];
+        let mut vertex_inputs = vec![];
+
+        for &(variable_id, pointer_type_id, storage_class) in module.variables.iter() {
+            let pointee_type_id = match module.types.get(&pointer_type_id) {
+                Some(&SpirvType::Pointer { pointee }) => pointee,
+                _ => return Err(invalid_spirv("the type of a variable is not a pointer")),
+            };
+            let decorations = module.decorations(variable_id);
+
+            match storage_class {
+                STORAGE_CLASS_UNIFORM_CONSTANT
+                | STORAGE_CLASS_UNIFORM
+                | STORAGE_CLASS_STORAGE_BUFFER => {
+                    let binding = match decorations.binding {
+                        Some(binding) => binding,
+                        // e.g. the uniforms of the default block in OpenGL, which are not allowed in Vulkan.
+                        None => continue,
+                    };
+                    let (descriptor_type, descriptor_count) =
+                        module.descriptor_type(pointee_type_id, storage_class)?;
+
+                    descriptor_bindings.push(DescriptorBinding {
+                        set: decorations.descriptor_set.unwrap_or(0),
+                        binding,
+                        descriptor_type,
+                        descriptor_count,
+                    });
+                }
+                STORAGE_CLASS_PUSH_CONSTANT => {
+                    let (offset, size) = module.push_constant_range(pointee_type_id)?;
+
+                    push_constant_ranges.push(vk::PushConstantRange {
+                        stage_flags: stage,
+                        offset,
+                        size,
+                    });
+                }
+                STORAGE_CLASS_INPUT if stage == vk::ShaderStageFlags::VERTEX => {
+                    // skip gl_VertexIndex, gl_InstanceIndex...
+                    if decorations.built_in {
+                        continue;
+                    }
+                    let location = decorations.location.ok_or_else(|| {
+                        invalid_spirv("a vertex input is declared without location")
+                    })?;
+
+                    vertex_inputs.push(VertexInput {
+                        location,
+                        format: module.vertex_input_format(pointee_type_id)?,
+                    });
+                }
+                _ => (),
+            }
+        }
+
+        descriptor_bindings.sort_by_key(|binding| (binding.set, binding.binding));
+        vertex_inputs.sort_by_key(|input| input.location);
+
+        Ok(ShaderReflection {
+            stage,
+            descriptor_bindings,
+            push_constant_ranges,
+            vertex_inputs,
+        })
+    }
+
+    /// Check that every input of the vertex shader is fed by one of the `attributes`, with the same
+    /// numeric type. The number of components may differ, as Vulkan fills or drops the missing ones.
+    pub fn validate_vertex_input(
+        &self,
+        attributes: &[vk::VertexInputAttributeDescription],
+    ) -> VkTutorialResult<()> {
+        for input in self.vertex_inputs.iter() {
+            let attribute = attributes
+                .iter()
+                .find(|attribute| attribute.location == input.location)
+                .ok_or_else(|| {
+                    VkTutorialError::ShaderInterfaceMismatch(format!(
+                        "No vertex attribute at location {}, the shader expects {:?}",
+                        input.location, input.format
+                    ))
+                })?;
+
+            let attribute_type = numeric_type(attribute.format);
+            if attribute_type.is_none() || attribute_type != numeric_type(input.format) {
+                return Err(VkTutorialError::ShaderInterfaceMismatch(format!(
+                    "The vertex attribute at location {} is {:?}, but the shader expects {:?}",
+                    input.location, attribute.format, input.format
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Merge the bindings of descriptor `set` declared by all the `shaders` of a pipeline.
+pub fn descriptor_set_layout_bindings(
+    shaders: &[ShaderReflection],
+    set: u32,
+) -> VkTutorialResult<Vec<vk::DescriptorSetLayoutBinding>> {
+    let mut layout_bindings: Vec<vk::DescriptorSetLayoutBinding> = vec![];
+
+    for shader in shaders.iter() {
+        for descriptor in shader
+            .descriptor_bindings
+            .iter()
+            .filter(|descriptor| descriptor.set == set)
+        {
+            match layout_bindings
+                .iter_mut()
+                .find(|layout_binding| layout_binding.binding == descriptor.binding)
+            {
+                Some(layout_binding) => {
+                    if layout_binding.descriptor_type != descriptor.descriptor_type
+                        || layout_binding.descriptor_count != descriptor.descriptor_count
+                    {
+                        return Err(VkTutorialError::ShaderInterfaceMismatch(format!(
+                            "The stages declare different resources at set {}, binding {}",
+                            set, descriptor.binding
+                        )));
+                    }
+                    layout_binding.stage_flags |= shader.stage;
+                }
+                None => layout_bindings.push(vk::DescriptorSetLayoutBinding {
+                    binding: descriptor.binding,
+                    descriptor_type: descriptor.descriptor_type,
+                    descriptor_count: descriptor.descriptor_count,
+                    stage_flags: shader.stage,
+                    p_immutable_samplers: ptr::null(),
+                }),
+            }
+        }
+    }
+
+    layout_bindings.sort_by_key(|layout_binding| layout_binding.binding);
+    Ok(layout_bindings)
+}
+
+/// Collect the push constant ranges of all the `shaders`, the identical ranges are merged.
+pub fn push_constant_ranges(shaders: &[ShaderReflection]) -> Vec<vk::PushConstantRange> {
+    let mut ranges: Vec<vk::PushConstantRange> = vec![];
+
+    for range in shaders
+        .iter()
+        .flat_map(|shader| shader.push_constant_ranges.iter())
+    {
+        match ranges
+            .iter_mut()
+            .find(|merged| merged.offset == range.offset && merged.size == range.size)
+        {
+            Some(merged) => merged.stage_flags |= range.stage_flags,
+            None => ranges.push(*range),
+        }
+    }
+
+    ranges
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NumericType {
+    Float,
+    SInt,
+    UInt,
+}
+
+fn numeric_type(format: vk::Format) -> Option<NumericType> {
+    match format {
+        vk::Format::R32_SFLOAT
+        | vk::Format::R32G32_SFLOAT
+        | vk::Format::R32G32B32_SFLOAT
+        | vk::Format::R32G32B32A32_SFLOAT
+        | vk::Format::R16_SFLOAT
+        | vk::Format::R16G16_SFLOAT
+        | vk::Format::R16G16B16_SFLOAT
+        | vk::Format::R16G16B16A16_SFLOAT
+        | vk::Format::R8_UNORM
+        | vk::Format::R8G8_UNORM
+        | vk::Format::R8G8B8_UNORM
+        | vk::Format::R8G8B8A8_UNORM
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::R8_SNORM
+        | vk::Format::R8G8_SNORM
+        | vk::Format::R8G8B8_SNORM
+        | vk::Format::R8G8B8A8_SNORM
+        | vk::Format::R16_UNORM
+        | vk::Format::R16G16_UNORM
+        | vk::Format::R16G16B16_UNORM
+        | vk::Format::R16G16B16A16_UNORM
+        | vk::Format::R16_SNORM
+        | vk::Format::R16G16_SNORM
+        | vk::Format::R16G16B16_SNORM
+        | vk::Format::R16G16B16A16_SNORM => Some(NumericType::Float),
+        vk::Format::R32_SINT
+        | vk::Format::R32G32_SINT
+        | vk::Format::R32G32B32_SINT
+        | vk::Format::R32G32B32A32_SINT
+        | vk::Format::R16_SINT
+        | vk::Format::R16G16_SINT
+        | vk::Format::R16G16B16_SINT
+        | vk::Format::R16G16B16A16_SINT
+        | vk::Format::R8_SINT
+        | vk::Format::R8G8_SINT
+        | vk::Format::R8G8B8_SINT
+        | vk::Format::R8G8B8A8_SINT => Some(NumericType::SInt),
+        vk::Format::R32_UINT
+        | vk::Format::R32G32_UINT
+        | vk::Format::R32G32B32_UINT
+        | vk::Format::R32G32B32A32_UINT
+        | vk::Format::R16_UINT
+        | vk::Format::R16G16_UINT
+        | vk::Format::R16G16B16_UINT
+        | vk::Format::R16G16B16A16_UINT
+        | vk::Format::R8_UINT
+        | vk::Format::R8G8_UINT
+        | vk::Format::R8G8B8_UINT
+        | vk::Format::R8G8B8A8_UINT => Some(NumericType::UInt),
+        _ => None,
+    }
+}
+
+fn size_overflow() -> VkTutorialError {
+    invalid_spirv("the size of a block does not fit in 32 bits")
+}
+
+fn invalid_spirv(reason: &str) -> VkTutorialError {
+    VkTutorialError::ShaderLoad(format!("Invalid SPIR-V code, {}", reason))
+}
+
+enum SpirvType {
+    Int { width: u32, is_signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: u32 },
+    RuntimeArray,
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 },
+}
+
+#[derive(Default, Clone, Copy)]
+struct Decorations {
+    binding: Option<u32>,
+    descriptor_set: Option<u32>,
+    location: Option<u32>,
+    offset: Option<u32>,
+    array_stride: Option<u32>,
+    matrix_stride: Option<u32>,
+    built_in: bool,
+    block: bool,
+    buffer_block: bool,
+}
+
+impl Decorations {
+    fn apply(&mut self, decoration: u32, literal: Option<u32>) {
+        match decoration {
+            DECORATION_BINDING => self.binding = literal,
+            DECORATION_DESCRIPTOR_SET => self.descriptor_set = literal,
+            DECORATION_LOCATION => self.location = literal,
+            DECORATION_OFFSET => self.offset = literal,
+            DECORATION_ARRAY_STRIDE => self.array_stride = literal,
+            DECORATION_MATRIX_STRIDE => self.matrix_stride = literal,
+            DECORATION_BUILT_IN => self.built_in = true,
+            DECORATION_BLOCK => self.block = true,
+            DECORATION_BUFFER_BLOCK => self.buffer_block = true,
+            _ => (),
+        }
+    }
+}
+
+struct SpirvModule {
+    execution_model: Option<u32>,
+    types: HashMap<u32, SpirvType>,
+    constants: HashMap<u32, u32>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), Decorations>,
+    /// (result id, pointer type id, storage class) of the global variables.
+    variables: Vec<(u32, u32, u32)>,
+}
+
+impl SpirvModule {
+    fn parse(code: &[u8]) -> VkTutorialResult<SpirvModule> {
+        if code.len() % 4 != 0 || code.len() < SPIRV_HEADER_WORD_COUNT * 4 {
+            return Err(invalid_spirv("the code is not a sequence of 32 bits words"));
+        }
+
+        let mut words: Vec<u32> = code
+            .chunks(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        if words[0] == SPIRV_MAGIC_NUMBER.swap_bytes() {
+            words.iter_mut().for_each(|word| *word = word.swap_bytes());
+        } else if words[0] != SPIRV_MAGIC_NUMBER {
+            return Err(invalid_spirv("the magic number is missing"));
+        }
+
+        let mut module = SpirvModule {
+            execution_model: None,
+            types: HashMap::new(),
+            constants: HashMap::new(),
+            decorations: HashMap::new(),
+            member_decorations: HashMap::new(),
+            variables: vec![],
+        };
+
+        let mut cursor = SPIRV_HEADER_WORD_COUNT;
+        while cursor < words.len() {
+            let word_count = (words[cursor] >> 16) as usize;
+            let opcode = words[cursor] & 0xffff;
+            if word_count == 0 || cursor + word_count > words.len() {
+                return Err(invalid_spirv("an instruction is truncated"));
+            }
+            let operands = &words[cursor + 1..cursor + word_count];
+            cursor += word_count;
+
+            let operand = |index: usize| {
+                operands
+                    .get(index)
+                    .copied()
+                    .ok_or_else(|| invalid_spirv("an instruction misses some operands"))
+            };
+
+            match opcode {
+                // the tutorial shaders have a single entry point.
+                OP_ENTRY_POINT if module.execution_model.is_none() => {
+                    module.execution_model = Some(operand(0)?);
+                }
+                OP_TYPE_INT => {
+                    let spirv_type = SpirvType::Int {
+                        width: operand(1)?,
+                        is_signed: operand(2)? != 0,
+                    };
+                    module.insert_type(operand(0)?, spirv_type)?;
+                }
+                OP_TYPE_FLOAT => {
+                    let spirv_type = SpirvType::Float { width: operand(1)? };
+                    module.insert_type(operand(0)?, spirv_type)?;
+                }
+                OP_TYPE_VECTOR => {
+                    let spirv_type = SpirvType::Vector {
+                        component: operand(1)?,
+                        count: operand(2)?,
+                    };
+                    module.insert_type(operand(0)?, spirv_type)?;
+                }
+                OP_TYPE_MATRIX => {
+                    let spirv_type = SpirvType::Matrix {
+                        column: operand(1)?,
+                        count: operand(2)?,
+                    };
+                    module.insert_type(operand(0)?, spirv_type)?;
+                }
+                OP_TYPE_IMAGE => {
+                    let spirv_type = SpirvType::Image {
+                        dim: operand(2)?,
+                        sampled: operand(6)?,
+                    };
+                    module.insert_type(operand(0)?, spirv_type)?;
+                }
+                OP_TYPE_SAMPLER => {
+                    module.insert_type(operand(0)?, SpirvType::Sampler)?;
+                }
+                OP_TYPE_SAMPLED_IMAGE => {
+                    module.insert_type(operand(0)?, SpirvType::SampledImage)?;
+                }
+                OP_TYPE_ARRAY => {
+                    let spirv_type = SpirvType::Array {
+                        element: operand(1)?,
+                        length: operand(2)?,
+                    };
+                    module.insert_type(operand(0)?, spirv_type)?;
+                }
+                OP_TYPE_RUNTIME_ARRAY => {
+                    module.insert_type(operand(0)?, SpirvType::RuntimeArray)?;
+                }
+                OP_TYPE_STRUCT => {
+                    let spirv_type = SpirvType::Struct {
+                        members: operands.get(1..).unwrap_or_default().to_vec(),
+                    };
+                    module.insert_type(operand(0)?, spirv_type)?;
+                }
+                OP_TYPE_POINTER => {
+                    let spirv_type = SpirvType::Pointer {
+                        pointee: operand(2)?,
+                    };
+                    module.insert_type(operand(0)?, spirv_type)?;
+                }
+                OP_CONSTANT => {
+                    // only the first word matters, the constants are used as array lengths.
+                    module.constants.insert(operand(1)?, operand(2)?);
+                }
+                OP_VARIABLE => {
+                    module
+                        .variables
+                        .push((operand(1)?, operand(0)?, operand(2)?));
+                }
+                OP_DECORATE => {
+                    let literal = operands.get(2).copied();
+                    module
+                        .decorations
+                        .entry(operand(0)?)
+                        .or_default()
+                        .apply(operand(1)?, literal);
+                }
+                OP_MEMBER_DECORATE => {
+                    let literal = operands.get(3).copied();
+                    module
+                        .member_decorations
+                        .entry((operand(0)?, operand(1)?))
+                        .or_default()
+                        .apply(operand(2)?, literal);
+                }
+                _ => (),
+            }
+        }
+
+        Ok(module)
+    }
+
+    /// Add the type declared as `id`, the types it is made of must be declared before,
+    /// so the types can not contain themselves.
+    fn insert_type(&mut self, id: u32, spirv_type: SpirvType) -> VkTutorialResult<()> {
+        if self.types.contains_key(&id) {
+            return Err(invalid_spirv("a type is declared twice"));
+        }
+
+        let is_declared = |type_id: &u32| self.types.contains_key(type_id);
+        let is_complete = match &spirv_type {
+            SpirvType::Vector { component, .. } => is_declared(component),
+            SpirvType::Matrix { column, .. } => is_declared(column),
+            SpirvType::Array { element, .. } => is_declared(element),
+            SpirvType::Struct { members } => members.iter().all(is_declared),
+            _ => true,
+        };
+        if !is_complete {
+            return Err(invalid_spirv("a type is used before its declaration"));
+        }
+
+        self.types.insert(id, spirv_type);
+        Ok(())
+    }
+
+    fn stage(&self) -> VkTutorialResult<vk::ShaderStageFlags> {
+        match self.execution_model {
+            Some(0) => Ok(vk::ShaderStageFlags::VERTEX),
+            Some(1) => Ok(vk::ShaderStageFlags::TESSELLATION_CONTROL),
+            Some(2) => Ok(vk::ShaderStageFlags::TESSELLATION_EVALUATION),
+            Some(3) => Ok(vk::ShaderStageFlags::GEOMETRY),
+            Some(4) => Ok(vk::ShaderStageFlags::FRAGMENT),
+            Some(5) => Ok(vk::ShaderStageFlags::COMPUTE),
+            Some(_) => Err(invalid_spirv(
+                "the execution model is not supported by Vulkan",
+            )),
+            None => Err(invalid_spirv("the entry point is missing")),
+        }
+    }
+
+    fn decorations(&self, id: u32) -> Decorations {
+        self.decorations.get(&id).copied().unwrap_or_default()
+    }
+
+    fn descriptor_type(
+        &self,
+        type_id: u32,
+        storage_class: u32,
+    ) -> VkTutorialResult<(vk::DescriptorType, u32)> {
+        let descriptor_type = match self.types.get(&type_id) {
+            Some(&SpirvType::Array { element, length }) => {
+                let (descriptor_type, _) = self.descriptor_type(element, storage_class)?;
+                let descriptor_count = self
+                    .constants
+                    .get(&length)
+                    .copied()
+                    .ok_or_else(|| invalid_spirv("the length of an array is not a constant"))?;
+                return Ok((descriptor_type, descriptor_count));
+            }
+            Some(SpirvType::RuntimeArray) => {
+                return Err(VkTutorialError::ShaderLoad(String::from(
+                    "The runtime arrays of descriptors are not supported",
+                )))
+            }
+            Some(SpirvType::SampledImage) => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            Some(SpirvType::Sampler) => vk::DescriptorType::SAMPLER,
+            Some(&SpirvType::Image { dim, sampled }) => match (dim, sampled) {
+                (DIM_SUBPASS_DATA, _) => vk::DescriptorType::INPUT_ATTACHMENT,
+                (DIM_BUFFER, 2) => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+                (DIM_BUFFER, _) => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+                (_, 2) => vk::DescriptorType::STORAGE_IMAGE,
+                _ => vk::DescriptorType::SAMPLED_IMAGE,
+            },
+            Some(SpirvType::Struct { .. }) => {
+                let decorations = self.decorations(type_id);
+                if storage_class == STORAGE_CLASS_STORAGE_BUFFER || decorations.buffer_block {
+                    vk::DescriptorType::STORAGE_BUFFER
+                } else if decorations.block {
+                    vk::DescriptorType::UNIFORM_BUFFER
+                } else {
+                    return Err(invalid_spirv(
+                        "a uniform struct is not decorated as a block",
+                    ));
+                }
+            }
+            _ => return Err(invalid_spirv("a descriptor has an unknown type")),
+        };
+
+        Ok((descriptor_type, 1))
+    }
+
+    /// Return the offset of the first member of the push constant block, and its size from there.
+    fn push_constant_range(&self, type_id: u32) -> VkTutorialResult<(u32, u32)> {
+        let members = match self.types.get(&type_id) {
+            Some(SpirvType::Struct { members }) => members,
+            _ => return Err(invalid_spirv("the push constants are not a struct")),
+        };
+
+        let offset = (0..members.len() as u32)
+            .filter_map(|index| self.member_decorations(type_id, index).offset)
+            .min()
+            .unwrap_or(0);
+        let size = self.type_size(type_id, Decorations::default())?;
+
+        Ok((offset, size.checked_sub(offset).ok_or_else(size_overflow)?))
+    }
+
+    fn member_decorations(&self, struct_id: u32, index: u32) -> Decorations {
+        self.member_decorations
+            .get(&(struct_id, index))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The size of a type in a block, `decorations` are the ones of the struct member of this type.
+    fn type_size(&self, type_id: u32, decorations: Decorations) -> VkTutorialResult<u32> {
+        let size = match self.types.get(&type_id) {
+            Some(&SpirvType::Int { width, .. }) | Some(&SpirvType::Float { width }) => width / 8,
+            Some(&SpirvType::Vector { component, count }) => self
+                .type_size(component, Decorations::default())?
+                .checked_mul(count)
+                .ok_or_else(size_overflow)?,
+            Some(&SpirvType::Matrix { column, count }) => {
+                let matrix_stride = match decorations.matrix_stride {
+                    Some(matrix_stride) => matrix_stride,
+                    None => self.type_size(column, Decorations::default())?,
+                };
+                matrix_stride.checked_mul(count).ok_or_else(size_overflow)?
+            }
+            Some(&SpirvType::Array { element, length }) => {
+                let length = self
+                    .constants
+                    .get(&length)
+                    .copied()
+                    .ok_or_else(|| invalid_spirv("the length of an array is not a constant"))?;
+                let stride = match self.decorations(type_id).array_stride {
+                    Some(array_stride) => array_stride,
+                    None => self.type_size(element, decorations)?,
+                };
+                stride.checked_mul(length).ok_or_else(size_overflow)?
+            }
+            Some(SpirvType::Struct { members }) => {
+                let mut size = 0;
+                for (index, &member) in members.iter().enumerate() {
+                    let member_decorations = self.member_decorations(type_id, index as u32);
+                    let member_end = member_decorations
+                        .offset
+                        .unwrap_or(size)
+                        .checked_add(self.type_size(member, member_decorations)?)
+                        .ok_or_else(size_overflow)?;
+                    size = size.max(member_end);
+                }
+                size
+            }
+            _ => return Err(invalid_spirv("a block member has an opaque type")),
+        };
+
+        Ok(size)
+    }
+
+    fn vertex_input_format(&self, type_id: u32) -> VkTutorialResult<vk::Format> {
+        let (component, count) = match self.types.get(&type_id) {
+            Some(&SpirvType::Vector { component, count }) => (component, count),
+            Some(_) => (type_id, 1),
+            None => return Err(invalid_spirv("a vertex input has an unknown type")),
+        };
+
+        let format = match (self.types.get(&component), count) {
+            (Some(SpirvType::Float { width: 32 }), 1) => vk::Format::R32_SFLOAT,
+            (Some(SpirvType::Float { width: 32 }), 2) => vk::Format::R32G32_SFLOAT,
+            (Some(SpirvType::Float { width: 32 }), 3) => vk::Format::R32G32B32_SFLOAT,
+            (Some(SpirvType::Float { width: 32 }), 4) => vk::Format::R32G32B32A32_SFLOAT,
+            (
+                Some(SpirvType::Int {
+                    width: 32,
+                    is_signed: true,
+                }),
+                1,
+            ) => vk::Format::R32_SINT,
+            (
+                Some(SpirvType::Int {
+                    width: 32,
+                    is_signed: true,
+                }),
+                2,
+            ) => vk::Format::R32G32_SINT,
+            (
+                Some(SpirvType::Int {
+                    width: 32,
+                    is_signed: true,
+                }),
+                3,
+            ) => vk::Format::R32G32B32_SINT,
+            (
+                Some(SpirvType::Int {
+                    width: 32,
+                    is_signed: true,
+                }),
+                4,
+            ) => vk::Format::R32G32B32A32_SINT,
+            (
+                Some(SpirvType::Int {
+                    width: 32,
+                    is_signed: false,
+                }),
+                1,
+            ) => vk::Format::R32_UINT,
+            (
+                Some(SpirvType::Int {
+                    width: 32,
+                    is_signed: false,
+                }),
+                2,
+            ) => vk::Format::R32G32_UINT,
+            (
+                Some(SpirvType::Int {
+                    width: 32,
+                    is_signed: false,
+                }),
+                3,
+            ) => vk::Format::R32G32B32_UINT,
+            (
+                Some(SpirvType::Int {
+                    width: 32,
+                    is_signed: false,
+                }),
+                4,
+            ) => vk::Format::R32G32B32A32_UINT,
+            _ => {
+                return Err(VkTutorialError::ShaderLoad(String::from(
+                    "Only the 32 bits scalars and vectors are supported as vertex inputs",
+                )))
+            }
+        };
+
+        Ok(format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERT_26: &[u8] = include_bytes!("../../shaders/spv/26-shader-depth.vert.spv");
+    const FRAG_26: &[u8] = include_bytes!("../../shaders/spv/26-shader-depth.frag.spv");
+
+    fn reflect(code: &[u8]) -> ShaderReflection {
+        ShaderReflection::new(code).unwrap()
+    }
+
+    fn descriptor_types(shader: &ShaderReflection) -> Vec<(u32, vk::DescriptorType, u32)> {
+        shader
+            .descriptor_bindings
+            .iter()
+            .map(|descriptor| {
+                assert_eq!(descriptor.set, 0);
+                (
+                    descriptor.binding,
+                    descriptor.descriptor_type,
+                    descriptor.descriptor_count,
+                )
+            })
+            .collect()
+    }
+
+    fn vertex_formats(shader: &ShaderReflection) -> Vec<(u32, vk::Format)> {
+        shader
+            .vertex_inputs
+            .iter()
+            .map(|input| (input.location, input.format))
+            .collect()
+    }
+
+    fn attribute(location: u32, format: vk::Format) -> vk::VertexInputAttributeDescription {
+        vk::VertexInputAttributeDescription {
+            location,
+            binding: 0,
+            format,
+            offset: 0,
+        }
+    }
+
+    /// A SPIR-V module made of `instructions`, each one being its opcode followed by its operands.
+    fn spirv(instructions: &[&[u32]]) -> Vec<u8> {
+        let mut words = vec![SPIRV_MAGIC_NUMBER, 0x0001_0000, 0, 16, 0];
+        for instruction in instructions.iter() {
+            words.push((instruction.len() as u32) << 16 | instruction[0]);
+            words.extend_from_slice(&instruction[1..]);
+        }
+
+        words
+            .iter()
+            .flat_map(|word| word.to_le_bytes().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn vertex_shaders_declare_their_inputs() {
+        let base = reflect(include_bytes!("../../shaders/spv/09-shader-base.vert.spv"));
+        assert_eq!(base.stage, vk::ShaderStageFlags::VERTEX);
+        // gl_VertexIndex is a built-in, not an input of the vertex buffers.
+        assert!(base.vertex_inputs.is_empty());
+        assert!(base.descriptor_bindings.is_empty());
+
+        let vertex_buffer = reflect(include_bytes!(
+            "../../shaders/spv/17-shader-vertexbuffer.vert.spv"
+        ));
+        assert_eq!(
+            vertex_formats(&vertex_buffer),
+            vec![
+                (0, vk::Format::R32G32_SFLOAT),
+                (1, vk::Format::R32G32B32_SFLOAT),
+            ]
+        );
+
+        let depth = reflect(VERT_26);
+        assert_eq!(
+            vertex_formats(&depth),
+            vec![
+                (0, vk::Format::R32G32B32_SFLOAT),
+                (1, vk::Format::R32G32B32_SFLOAT),
+                (2, vk::Format::R32G32_SFLOAT),
+            ]
+        );
+
+        let particles = reflect(include_bytes!(
+            "../../shaders/spv/30-shader-particles.vert.spv"
+        ));
+        assert_eq!(
+            vertex_formats(&particles),
+            vec![
+                (0, vk::Format::R32G32_SFLOAT),
+                (1, vk::Format::R32G32B32A32_SFLOAT),
+            ]
+        );
+    }
+
+    #[test]
+    fn shaders_declare_their_descriptors() {
+        let ubo = reflect(include_bytes!("../../shaders/spv/21-shader-ubo.vert.spv"));
+        assert_eq!(
+            descriptor_types(&ubo),
+            vec![(0, vk::DescriptorType::UNIFORM_BUFFER, 1)]
+        );
+
+        let textures = reflect(include_bytes!(
+            "../../shaders/spv/25-shader-textures.frag.spv"
+        ));
+        assert_eq!(textures.stage, vk::ShaderStageFlags::FRAGMENT);
+        assert!(textures.vertex_inputs.is_empty());
+        assert_eq!(
+            descriptor_types(&textures),
+            vec![(1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1)]
+        );
+
+        let particles = reflect(include_bytes!(
+            "../../shaders/spv/30-shader-particles.comp.spv"
+        ));
+        assert_eq!(particles.stage, vk::ShaderStageFlags::COMPUTE);
+        assert_eq!(
+            descriptor_types(&particles),
+            vec![
+                (0, vk::DescriptorType::STORAGE_BUFFER, 1),
+                (1, vk::DescriptorType::UNIFORM_BUFFER, 1),
+            ]
+        );
+        assert!(particles.push_constant_ranges.is_empty());
+    }
+
+    #[test]
+    fn bindings_of_the_stages_are_merged() {
+        let layout_bindings =
+            descriptor_set_layout_bindings(&[reflect(VERT_26), reflect(FRAG_26)], 0).unwrap();
+
+        assert_eq!(layout_bindings.len(), 2);
+        assert_eq!(layout_bindings[0].binding, 0);
+        assert_eq!(
+            layout_bindings[0].descriptor_type,
+            vk::DescriptorType::UNIFORM_BUFFER
+        );
+        assert_eq!(layout_bindings[0].stage_flags, vk::ShaderStageFlags::VERTEX);
+        assert_eq!(layout_bindings[1].binding, 1);
+        assert_eq!(
+            layout_bindings[1].descriptor_type,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+        );
+        assert_eq!(
+            layout_bindings[1].stage_flags,
+            vk::ShaderStageFlags::FRAGMENT
+        );
+
+        assert!(descriptor_set_layout_bindings(&[reflect(VERT_26)], 1)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn vertex_input_is_validated() {
+        let shader = reflect(VERT_26);
+
+        let attributes = [
+            attribute(0, vk::Format::R32G32B32_SFLOAT),
+            attribute(1, vk::Format::R32G32B32A32_SFLOAT),
+            attribute(2, vk::Format::R32G32_SFLOAT),
+        ];
+        assert!(shader.validate_vertex_input(&attributes).is_ok());
+
+        let missing_attributes = [
+            attribute(0, vk::Format::R32G32B32_SFLOAT),
+            attribute(1, vk::Format::R32G32B32_SFLOAT),
+        ];
+        match shader.validate_vertex_input(&missing_attributes) {
+            Err(VkTutorialError::ShaderInterfaceMismatch(_)) => (),
+            result => panic!("the missing attribute is not reported: {:?}", result),
+        }
+
+        let integer_attributes = [
+            attribute(0, vk::Format::R32G32B32_SFLOAT),
+            attribute(1, vk::Format::R32G32B32_SFLOAT),
+            attribute(2, vk::Format::R32G32_SINT),
+        ];
+        match shader.validate_vertex_input(&integer_attributes) {
+            Err(VkTutorialError::ShaderInterfaceMismatch(_)) => (),
+            result => panic!("the integer attribute is not reported: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn push_constant_range_starts_at_the_first_member() {
+        let code = spirv(&[
+            &[OP_ENTRY_POINT, 0, 1],
+            &[OP_MEMBER_DECORATE, 4, 0, DECORATION_OFFSET, 16],
+            &[OP_MEMBER_DECORATE, 4, 1, DECORATION_OFFSET, 32],
+            &[OP_DECORATE, 4, DECORATION_BLOCK],
+            &[OP_TYPE_FLOAT, 2, 32],
+            &[OP_TYPE_VECTOR, 3, 2, 4],
+            &[OP_TYPE_STRUCT, 4, 2, 3],
+            &[OP_TYPE_POINTER, 5, STORAGE_CLASS_PUSH_CONSTANT, 4],
+            &[OP_VARIABLE, 5, 6, STORAGE_CLASS_PUSH_CONSTANT],
+        ]);
+
+        let shader = reflect(&code);
+
+        assert_eq!(shader.push_constant_ranges.len(), 1);
+        assert_eq!(shader.push_constant_ranges[0].offset, 16);
+        assert_eq!(shader.push_constant_ranges[0].size, 32);
+        assert_eq!(
+            shader.push_constant_ranges[0].stage_flags,
+            vk::ShaderStageFlags::VERTEX
+        );
+    }
+
+    #[test]
+    fn oversized_blocks_return_an_error() {
+        let oversized_modules = [
+            // a member at the end of the 32 bits range.
+            spirv(&[
+                &[OP_ENTRY_POINT, 0, 1],
+                &[OP_MEMBER_DECORATE, 4, 0, DECORATION_OFFSET, 0xFFFF_FFF0],
+                &[OP_DECORATE, 4, DECORATION_BLOCK],
+                &[OP_TYPE_FLOAT, 2, 32],
+                &[OP_TYPE_VECTOR, 3, 2, 8],
+                &[OP_TYPE_STRUCT, 4, 3],
+                &[OP_TYPE_POINTER, 5, STORAGE_CLASS_PUSH_CONSTANT, 4],
+                &[OP_VARIABLE, 5, 6, STORAGE_CLASS_PUSH_CONSTANT],
+            ]),
+            // an array too long for its stride.
+            spirv(&[
+                &[OP_ENTRY_POINT, 0, 1],
+                &[OP_DECORATE, 5, DECORATION_ARRAY_STRIDE, 16],
+                &[OP_DECORATE, 6, DECORATION_BLOCK],
+                &[OP_TYPE_INT, 2, 32, 0],
+                &[OP_CONSTANT, 2, 3, 0x8000_0000],
+                &[OP_TYPE_FLOAT, 4, 32],
+                &[OP_TYPE_ARRAY, 5, 4, 3],
+                &[OP_TYPE_STRUCT, 6, 5],
+                &[OP_TYPE_POINTER, 7, STORAGE_CLASS_PUSH_CONSTANT, 6],
+                &[OP_VARIABLE, 7, 8, STORAGE_CLASS_PUSH_CONSTANT],
+            ]),
+            // a matrix stride too large for its columns.
+            spirv(&[
+                &[OP_ENTRY_POINT, 0, 1],
+                &[OP_MEMBER_DECORATE, 5, 0, DECORATION_MATRIX_STRIDE, 1 << 31],
+                &[OP_DECORATE, 5, DECORATION_BLOCK],
+                &[OP_TYPE_FLOAT, 2, 32],
+                &[OP_TYPE_VECTOR, 3, 2, 4],
+                &[OP_TYPE_MATRIX, 4, 3, 4],
+                &[OP_TYPE_STRUCT, 5, 4],
+                &[OP_TYPE_POINTER, 6, STORAGE_CLASS_PUSH_CONSTANT, 5],
+                &[OP_VARIABLE, 6, 7, STORAGE_CLASS_PUSH_CONSTANT],
+            ]),
+        ];
+
+        for (index, code) in oversized_modules.iter().enumerate() {
+            match ShaderReflection::new(code) {
+                Err(VkTutorialError::ShaderLoad(_)) => (),
+                result => panic!("the oversized module #{} is accepted: {:?}", index, result),
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_code_returns_an_error() {
+        for &code in [VERT_26, FRAG_26].iter() {
+            for length in 0..code.len() {
+                // the cuts between two instructions may still hold the whole interface.
+                let result = ShaderReflection::new(&code[..length]);
+                if length % 4 != 0 || length < SPIRV_HEADER_WORD_COUNT * 4 {
+                    assert!(result.is_err(), "{} bytes are accepted", length);
+                }
+            }
+        }
+
+        let truncated_instruction = spirv(&[&[OP_ENTRY_POINT, 0, 1]]);
+        let length = truncated_instruction.len() - 4;
+        assert!(ShaderReflection::new(&truncated_instruction[..length]).is_err());
+    }
+
+    #[test]
+    fn malformed_code_returns_an_error() {
+        let malformed_modules = [
+            // no magic number.
+            vec![0; SPIRV_HEADER_WORD_COUNT * 4],
+            // no entry point.
+            spirv(&[&[OP_TYPE_FLOAT, 2, 32]]),
+            // an instruction of zero words.
+            spirv(&[&[OP_ENTRY_POINT, 0, 1]])
+                .into_iter()
+                .chain(vec![0; 4])
+                .collect(),
+            // a struct without result id.
+            spirv(&[&[OP_ENTRY_POINT, 0, 1], &[OP_TYPE_STRUCT]]),
+            // a type declared twice.
+            spirv(&[
+                &[OP_ENTRY_POINT, 0, 1],
+                &[OP_TYPE_FLOAT, 2, 32],
+                &[OP_TYPE_FLOAT, 2, 32],
+            ]),
+            // a struct containing itself through an array.
+            spirv(&[
+                &[OP_ENTRY_POINT, 0, 1],
+                &[OP_TYPE_FLOAT, 2, 32],
+                &[OP_TYPE_STRUCT, 3, 2, 4],
+                &[OP_TYPE_ARRAY, 4, 3, 5],
+            ]),
+        ];
+
+        for (index, code) in malformed_modules.iter().enumerate() {
+            assert!(
+                ShaderReflection::new(code).is_err(),
+                "the malformed module #{} is accepted",
+                index
+            );
+        }
+    }
+}
@@ -0,0 +1,1482 @@
+//! A render graph, whose passes declare the images and buffers they read and write.
+//!
+//! `compile` orders the passes, culls the ones whose results are never used, allocates the
+//! transient images and creates the render passes of the passes drawing into attachments.
+//! `execute` then records the passes into the command buffer of a frame, with the pipeline
+//! barriers and the image layout transitions needed between them.
+//!
+//! The graph is executed once per frame, so the first accesses of a frame are synchronized
+//! with the last ones of the previous frame. The content of the transient images is not kept
+//! from one frame to the next.
+
+use ash::version::DeviceV1_0;
+use ash::vk;
+
+use std::collections::{
+    BTreeSet,
+    HashMap,
+};
+use std::ptr;
+
+use crate::utility::error::{
+    VkTutorialError,
+    VkTutorialResult,
+};
+use crate::utility::share;
+use crate::utility::share::layout;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BufferId(usize);
+
+/// Given back to the recording callback of `RenderGraph::execute`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PassId(usize);
+
+/// The format and the size of an image of the graph.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageDescription {
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+    pub samples: vk::SampleCountFlags,
+    pub aspect_mask: vk::ImageAspectFlags,
+}
+
+impl ImageDescription {
+    pub fn color(format: vk::Format, extent: vk::Extent2D) -> ImageDescription {
+        ImageDescription {
+            format,
+            extent,
+            samples: vk::SampleCountFlags::TYPE_1,
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+        }
+    }
+
+    pub fn depth(format: vk::Format, extent: vk::Extent2D) -> ImageDescription {
+        let aspect_mask = if share::has_stencil_component(format) {
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        } else {
+            vk::ImageAspectFlags::DEPTH
+        };
+
+        ImageDescription {
+            format,
+            extent,
+            samples: vk::SampleCountFlags::TYPE_1,
+            aspect_mask,
+        }
+    }
+
+    pub fn samples(mut self, samples: vk::SampleCountFlags) -> ImageDescription {
+        self.samples = samples;
+        self
+    }
+}
+
+/// What the render pass of a pass does with the content of an attachment at its beginning.
+#[derive(Clone, Copy)]
+pub enum AttachmentLoad {
+    Clear(vk::ClearValue),
+    Load,
+    DontCare,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Resource {
+    Image(usize),
+    Buffer(usize),
+}
+
+/// How a pass uses an image or a buffer, `layout` is ignored for the buffers.
+#[derive(Debug, Clone, Copy)]
+struct ResourceAccess {
+    resource: Resource,
+    layout: vk::ImageLayout,
+    stage_mask: vk::PipelineStageFlags,
+    access_mask: vk::AccessFlags,
+    is_write: bool,
+}
+
+/// A pass of the graph, described by its accesses to the resources before it is added.
+pub struct Pass {
+    name: String,
+    color_attachments: Vec<(ImageId, AttachmentLoad)>,
+    depth_attachment: Option<(ImageId, AttachmentLoad)>,
+    accesses: Vec<ResourceAccess>,
+}
+
+impl Pass {
+    pub fn new(name: &str) -> Pass {
+        Pass {
+            name: name.to_string(),
+            color_attachments: vec![],
+            depth_attachment: None,
+            accesses: vec![],
+        }
+    }
+
+    /// Draw into `image`, the color attachments are numbered in the order they are added.
+    pub fn color_attachment(mut self, image: ImageId, load: AttachmentLoad) -> Pass {
+        self.color_attachments.push((image, load));
+        self.layout_access(image, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL, true)
+    }
+
+    pub fn depth_attachment(mut self, image: ImageId, load: AttachmentLoad) -> Pass {
+        self.depth_attachment = Some((image, load));
+        self.layout_access(
+            image,
+            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            true,
+        )
+    }
+
+    /// Sample `image` from the shaders of `stage_mask`.
+    pub fn sampled_image(self, image: ImageId, stage_mask: vk::PipelineStageFlags) -> Pass {
+        let (_, read_access_mask, _) =
+            layout::layout_stage_access(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        self.image_access(
+            image,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            stage_mask,
+            read_access_mask,
+            false,
+        )
+    }
+
+    /// Load from `image`, and store into it when `is_write` is set, from the shaders of `stage_mask`.
+    pub fn storage_image(
+        self,
+        image: ImageId,
+        stage_mask: vk::PipelineStageFlags,
+        is_write: bool,
+    ) -> Pass {
+        self.image_access(
+            image,
+            vk::ImageLayout::GENERAL,
+            stage_mask,
+            shader_access_mask(is_write),
+            is_write,
+        )
+    }
+
+    pub fn transfer_src_image(self, image: ImageId) -> Pass {
+        self.layout_access(image, vk::ImageLayout::TRANSFER_SRC_OPTIMAL, false)
+    }
+
+    pub fn transfer_dst_image(self, image: ImageId) -> Pass {
+        self.layout_access(image, vk::ImageLayout::TRANSFER_DST_OPTIMAL, true)
+    }
+
+    pub fn vertex_buffer(self, buffer: BufferId) -> Pass {
+        self.buffer_access(
+            buffer,
+            vk::PipelineStageFlags::VERTEX_INPUT,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
+            false,
+        )
+    }
+
+    pub fn index_buffer(self, buffer: BufferId) -> Pass {
+        self.buffer_access(
+            buffer,
+            vk::PipelineStageFlags::VERTEX_INPUT,
+            vk::AccessFlags::INDEX_READ,
+            false,
+        )
+    }
+
+    pub fn uniform_buffer(self, buffer: BufferId, stage_mask: vk::PipelineStageFlags) -> Pass {
+        self.buffer_access(buffer, stage_mask, vk::AccessFlags::UNIFORM_READ, false)
+    }
+
+    /// Load from `buffer`, and store into it when `is_write` is set, from the shaders of `stage_mask`.
+    pub fn storage_buffer(
+        self,
+        buffer: BufferId,
+        stage_mask: vk::PipelineStageFlags,
+        is_write: bool,
+    ) -> Pass {
+        self.buffer_access(buffer, stage_mask, shader_access_mask(is_write), is_write)
+    }
+
+    pub fn transfer_src_buffer(self, buffer: BufferId) -> Pass {
+        self.buffer_access(
+            buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_READ,
+            false,
+        )
+    }
+
+    pub fn transfer_dst_buffer(self, buffer: BufferId) -> Pass {
+        self.buffer_access(
+            buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+            true,
+        )
+    }
+
+    /// Access `image` with the stages and the accesses of `layout`, see `layout::layout_stage_access`.
+    fn layout_access(self, image: ImageId, layout: vk::ImageLayout, is_write: bool) -> Pass {
+        let (stage_mask, read_access_mask, write_access_mask) = layout::layout_stage_access(layout);
+        let access_mask = if is_write {
+            read_access_mask | write_access_mask
+        } else {
+            read_access_mask
+        };
+
+        self.image_access(image, layout, stage_mask, access_mask, is_write)
+    }
+
+    fn image_access(
+        mut self,
+        image: ImageId,
+        layout: vk::ImageLayout,
+        stage_mask: vk::PipelineStageFlags,
+        access_mask: vk::AccessFlags,
+        is_write: bool,
+    ) -> Pass {
+        self.accesses.push(ResourceAccess {
+            resource: Resource::Image(image.0),
+            layout,
+            stage_mask,
+            access_mask,
+            is_write,
+        });
+        self
+    }
+
+    fn buffer_access(
+        mut self,
+        buffer: BufferId,
+        stage_mask: vk::PipelineStageFlags,
+        access_mask: vk::AccessFlags,
+        is_write: bool,
+    ) -> Pass {
+        self.accesses.push(ResourceAccess {
+            resource: Resource::Buffer(buffer.0),
+            layout: vk::ImageLayout::UNDEFINED,
+            stage_mask,
+            access_mask,
+            is_write,
+        });
+        self
+    }
+
+    fn is_graphics(&self) -> bool {
+        !self.color_attachments.is_empty() || self.depth_attachment.is_some()
+    }
+
+    /// The attachments in the order of the render pass, the depth attachment last.
+    fn attachments(&self) -> Vec<(ImageId, AttachmentLoad)> {
+        self.color_attachments
+            .iter()
+            .cloned()
+            .chain(self.depth_attachment.iter().cloned())
+            .collect()
+    }
+}
+
+fn shader_access_mask(is_write: bool) -> vk::AccessFlags {
+    if is_write {
+        vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE
+    } else {
+        vk::AccessFlags::SHADER_READ
+    }
+}
+
+enum ImageSource {
+    /// Bound to an image at every `execute`, e.g. a swapchain image.
+    Imported {
+        initial_layout: vk::ImageLayout,
+        final_layout: vk::ImageLayout,
+    },
+    /// Allocated by `compile`, unless no pass uses it.
+    Transient {
+        image: vk::Image,
+        image_memory: vk::DeviceMemory,
+        image_view: vk::ImageView,
+    },
+}
+
+struct GraphImage {
+    description: ImageDescription,
+    source: ImageSource,
+}
+
+/// What the accesses to a resource have synchronized so far.
+#[derive(Debug, Clone, Copy)]
+struct SyncState {
+    layout: vk::ImageLayout,
+    write_stage_mask: vk::PipelineStageFlags,
+    write_access_mask: vk::AccessFlags,
+    /// The stages and accesses which already see the last write.
+    visible_stage_mask: vk::PipelineStageFlags,
+    visible_access_mask: vk::AccessFlags,
+    /// The stages which have read the resource since the last write.
+    read_stage_mask: vk::PipelineStageFlags,
+}
+
+/// The source of the barrier needed before an access.
+struct Dependency {
+    src_stage_mask: vk::PipelineStageFlags,
+    src_access_mask: vk::AccessFlags,
+    old_layout: vk::ImageLayout,
+}
+
+impl SyncState {
+    fn new(layout: vk::ImageLayout) -> SyncState {
+        SyncState {
+            layout,
+            write_stage_mask: vk::PipelineStageFlags::empty(),
+            write_access_mask: vk::AccessFlags::empty(),
+            visible_stage_mask: vk::PipelineStageFlags::empty(),
+            visible_access_mask: vk::AccessFlags::empty(),
+            read_stage_mask: vk::PipelineStageFlags::empty(),
+        }
+    }
+
+    /// Update the state with `access`, and return the dependency it needs if any.
+    fn access(&mut self, access: &ResourceAccess) -> Option<Dependency> {
+        let is_transition = access.layout != self.layout;
+
+        if access.is_write || is_transition {
+            // wait for the last write and, when writing, for the reads since then.
+            let dependency = Dependency {
+                src_stage_mask: self.write_stage_mask | self.read_stage_mask,
+                src_access_mask: self.write_access_mask,
+                old_layout: self.layout,
+            };
+            let is_needed = is_transition || !dependency.src_stage_mask.is_empty();
+
+            *self = if access.is_write {
+                SyncState {
+                    layout: access.layout,
+                    write_stage_mask: access.stage_mask,
+                    write_access_mask: access.access_mask,
+                    ..SyncState::new(access.layout)
+                }
+            } else {
+                // the layout transition is a write, made visible to this read by the barrier.
+                SyncState {
+                    layout: access.layout,
+                    write_stage_mask: access.stage_mask,
+                    write_access_mask: vk::AccessFlags::empty(),
+                    visible_stage_mask: access.stage_mask,
+                    visible_access_mask: access.access_mask,
+                    read_stage_mask: access.stage_mask,
+                }
+            };
+
+            if is_needed {
+                Some(dependency)
+            } else {
+                None
+            }
+        } else {
+            self.read_stage_mask |= access.stage_mask;
+
+            let is_visible = self.write_stage_mask.is_empty()
+                || (self.visible_stage_mask.contains(access.stage_mask)
+                    && self.visible_access_mask.contains(access.access_mask));
+            if is_visible {
+                None
+            } else {
+                self.visible_stage_mask |= access.stage_mask;
+                self.visible_access_mask |= access.access_mask;
+
+                Some(Dependency {
+                    src_stage_mask: self.write_stage_mask,
+                    src_access_mask: self.write_access_mask,
+                    old_layout: self.layout,
+                })
+            }
+        }
+    }
+}
+
+struct ImageBarrier {
+    image: usize,
+    old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout,
+    src_access_mask: vk::AccessFlags,
+    dst_access_mask: vk::AccessFlags,
+}
+
+struct BufferBarrier {
+    buffer: usize,
+    src_access_mask: vk::AccessFlags,
+    dst_access_mask: vk::AccessFlags,
+}
+
+/// The barriers recorded together by one `vkCmdPipelineBarrier`.
+struct Barriers {
+    src_stage_mask: vk::PipelineStageFlags,
+    dst_stage_mask: vk::PipelineStageFlags,
+    images: Vec<ImageBarrier>,
+    buffers: Vec<BufferBarrier>,
+}
+
+impl Barriers {
+    fn new() -> Barriers {
+        Barriers {
+            src_stage_mask: vk::PipelineStageFlags::empty(),
+            dst_stage_mask: vk::PipelineStageFlags::empty(),
+            images: vec![],
+            buffers: vec![],
+        }
+    }
+
+    fn add(
+        &mut self,
+        resource: Resource,
+        dependency: Dependency,
+        new_layout: vk::ImageLayout,
+        dst_stage_mask: vk::PipelineStageFlags,
+        dst_access_mask: vk::AccessFlags,
+    ) {
+        self.src_stage_mask |= dependency.src_stage_mask;
+        self.dst_stage_mask |= dst_stage_mask;
+
+        match resource {
+            Resource::Image(image) => self.images.push(ImageBarrier {
+                image,
+                old_layout: dependency.old_layout,
+                new_layout,
+                src_access_mask: dependency.src_access_mask,
+                dst_access_mask,
+            }),
+            Resource::Buffer(buffer) => self.buffers.push(BufferBarrier {
+                buffer,
+                src_access_mask: dependency.src_access_mask,
+                dst_access_mask,
+            }),
+        }
+    }
+
+    fn record(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        images: &[(vk::Image, vk::ImageView)],
+        image_descriptions: &[ImageDescription],
+        buffers: &[vk::Buffer],
+    ) {
+        if self.images.is_empty() && self.buffers.is_empty() {
+            return;
+        }
+
+        let image_barriers: Vec<vk::ImageMemoryBarrier> = self
+            .images
+            .iter()
+            .map(|barrier| vk::ImageMemoryBarrier {
+                s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
+                p_next: ptr::null(),
+                src_access_mask: barrier.src_access_mask,
+                dst_access_mask: barrier.dst_access_mask,
+                old_layout: barrier.old_layout,
+                new_layout: barrier.new_layout,
+                src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                image: images[barrier.image].0,
+                // the imported images may have several mip levels or array layers.
+                subresource_range: vk::ImageSubresourceRange {
+                    aspect_mask: image_descriptions[barrier.image].aspect_mask,
+                    base_mip_level: 0,
+                    level_count: vk::REMAINING_MIP_LEVELS,
+                    base_array_layer: 0,
+                    layer_count: vk::REMAINING_ARRAY_LAYERS,
+                },
+            })
+            .collect();
+        let buffer_barriers: Vec<vk::BufferMemoryBarrier> = self
+            .buffers
+            .iter()
+            .map(|barrier| vk::BufferMemoryBarrier {
+                s_type: vk::StructureType::BUFFER_MEMORY_BARRIER,
+                p_next: ptr::null(),
+                src_access_mask: barrier.src_access_mask,
+                dst_access_mask: barrier.dst_access_mask,
+                src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                buffer: buffers[barrier.buffer],
+                offset: 0,
+                size: vk::WHOLE_SIZE,
+            })
+            .collect();
+
+        // a layout transition of a resource never accessed before has nothing to wait for.
+        let src_stage_mask = if self.src_stage_mask.is_empty() {
+            vk::PipelineStageFlags::TOP_OF_PIPE
+        } else {
+            self.src_stage_mask
+        };
+
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stage_mask,
+                self.dst_stage_mask,
+                vk::DependencyFlags::empty(),
+                &[],
+                &buffer_barriers,
+                &image_barriers,
+            );
+        }
+    }
+}
+
+/// A pass kept by `compile`, with what is recorded before it.
+struct CompiledPass {
+    pass: usize,
+    barriers: Barriers,
+    /// Null when the pass has no attachment.
+    render_pass: vk::RenderPass,
+    extent: vk::Extent2D,
+    clear_values: Vec<vk::ClearValue>,
+}
+
+pub struct RenderGraph {
+    device: ash::Device,
+    device_memory_properties: vk::PhysicalDeviceMemoryProperties,
+
+    images: Vec<GraphImage>,
+    buffers: Vec<vk::Buffer>,
+    passes: Vec<Pass>,
+
+    compiled_passes: Vec<CompiledPass>,
+    /// The transitions of the imported images to their final layout.
+    final_barriers: Barriers,
+    /// The framebuffers of each pass, for each set of attachment views seen by `execute`.
+    framebuffers: HashMap<(usize, Vec<vk::ImageView>), vk::Framebuffer>,
+}
+
+impl RenderGraph {
+    pub fn new(
+        device: &ash::Device,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    ) -> RenderGraph {
+        RenderGraph {
+            device: device.clone(),
+            device_memory_properties: *device_memory_properties,
+
+            images: vec![],
+            buffers: vec![],
+            passes: vec![],
+
+            compiled_passes: vec![],
+            final_barriers: Barriers::new(),
+            framebuffers: HashMap::new(),
+        }
+    }
+
+    /// Add an image bound by `execute`, which is in `initial_layout` when the frame begins
+    /// and is left in `final_layout`. The first barrier of the image waits for the stages
+    /// of its first use, so it follows a semaphore waited for at these stages.
+    pub fn import_image(
+        &mut self,
+        description: ImageDescription,
+        initial_layout: vk::ImageLayout,
+        final_layout: vk::ImageLayout,
+    ) -> ImageId {
+        self.images.push(GraphImage {
+            description,
+            source: ImageSource::Imported {
+                initial_layout,
+                final_layout,
+            },
+        });
+
+        ImageId(self.images.len() - 1)
+    }
+
+    /// Add an image allocated by the graph, which only lives during a frame.
+    pub fn create_image(&mut self, description: ImageDescription) -> ImageId {
+        self.images.push(GraphImage {
+            description,
+            source: ImageSource::Transient {
+                image: vk::Image::null(),
+                image_memory: vk::DeviceMemory::null(),
+                image_view: vk::ImageView::null(),
+            },
+        });
+
+        ImageId(self.images.len() - 1)
+    }
+
+    pub fn import_buffer(&mut self, buffer: vk::Buffer) -> BufferId {
+        self.buffers.push(buffer);
+
+        BufferId(self.buffers.len() - 1)
+    }
+
+    /// Add `pass`. The accesses of a pass to the same image must use the same layout.
+    pub fn add_pass(&mut self, mut pass: Pass) -> VkTutorialResult<PassId> {
+        let mut accesses: Vec<ResourceAccess> = vec![];
+        for access in pass.accesses.iter() {
+            match accesses
+                .iter_mut()
+                .find(|merged| merged.resource == access.resource)
+            {
+                Some(merged) if merged.layout != access.layout => {
+                    return Err(VkTutorialError::RenderGraph(format!(
+                        "the pass {} uses an image in the layouts {:?} and {:?}",
+                        pass.name, merged.layout, access.layout
+                    )));
+                }
+                Some(merged) => {
+                    merged.stage_mask |= access.stage_mask;
+                    merged.access_mask |= access.access_mask;
+                    merged.is_write |= access.is_write;
+                }
+                None => accesses.push(*access),
+            }
+        }
+        pass.accesses = accesses;
+
+        self.passes.push(pass);
+
+        Ok(PassId(self.passes.len() - 1))
+    }
+
+    /// Order the passes, allocate the transient images, create the render passes and work out the barriers.
+    pub fn compile(&mut self) -> VkTutorialResult<()> {
+        let order = schedule(&self.images, &self.passes)?;
+
+        self.create_transient_images(&order)?;
+
+        let (pass_barriers, final_barriers) =
+            frame_barriers(&self.images, self.buffers.len(), &self.passes, &order);
+
+        let mut compiled_passes = vec![];
+        for (position, (&pass, barriers)) in order.iter().zip(pass_barriers).enumerate() {
+            let (render_pass, extent, clear_values) = if self.passes[pass].is_graphics() {
+                self.create_render_pass(&order, position)?
+            } else {
+                (vk::RenderPass::null(), vk::Extent2D::default(), vec![])
+            };
+
+            compiled_passes.push(CompiledPass {
+                pass,
+                barriers,
+                render_pass,
+                extent,
+                clear_values,
+            });
+        }
+        self.compiled_passes = compiled_passes;
+        self.final_barriers = final_barriers;
+
+        Ok(())
+    }
+
+    /// The passes kept by `compile`, in the order they are recorded.
+    pub fn execution_order(&self) -> Vec<PassId> {
+        self.compiled_passes
+            .iter()
+            .map(|compiled_pass| PassId(compiled_pass.pass))
+            .collect()
+    }
+
+    /// The render pass created for `pass` by `compile`, for the pipelines used in this pass.
+    pub fn render_pass(&self, pass: PassId) -> vk::RenderPass {
+        self.compiled_passes
+            .iter()
+            .find(|compiled_pass| compiled_pass.pass == pass.0)
+            .map(|compiled_pass| compiled_pass.render_pass)
+            .unwrap_or_else(vk::RenderPass::null)
+    }
+
+    /// Record the passes into `command_buffer`, `record_pass` records the commands of each pass
+    /// inside its render pass. `imported_images` binds all the imported images with their views.
+    pub fn execute<F>(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        imported_images: &[(ImageId, vk::Image, vk::ImageView)],
+        mut record_pass: F,
+    ) -> VkTutorialResult<()>
+    where
+        F: FnMut(PassId, vk::CommandBuffer),
+    {
+        let mut images = vec![];
+        for (index, graph_image) in self.images.iter().enumerate() {
+            images.push(match graph_image.source {
+                ImageSource::Transient {
+                    image, image_view, ..
+                } => (image, image_view),
+                ImageSource::Imported { .. } => {
+                    match imported_images.iter().find(|(id, _, _)| id.0 == index) {
+                        Some(&(_, image, image_view)) => (image, image_view),
+                        None => {
+                            return Err(VkTutorialError::RenderGraph(format!(
+                                "the imported image {} is not bound",
+                                index
+                            )))
+                        }
+                    }
+                }
+            });
+        }
+        let image_descriptions: Vec<ImageDescription> = self
+            .images
+            .iter()
+            .map(|graph_image| graph_image.description)
+            .collect();
+
+        for index in 0..self.compiled_passes.len() {
+            let compiled_pass = &self.compiled_passes[index];
+            compiled_pass.barriers.record(
+                &self.device,
+                command_buffer,
+                &images,
+                &image_descriptions,
+                &self.buffers,
+            );
+
+            let pass = compiled_pass.pass;
+            if compiled_pass.render_pass == vk::RenderPass::null() {
+                record_pass(PassId(pass), command_buffer);
+                continue;
+            }
+
+            let framebuffer = self.framebuffer(index, &images)?;
+            let compiled_pass = &self.compiled_passes[index];
+            let render_pass_begin_info = vk::RenderPassBeginInfo {
+                s_type: vk::StructureType::RENDER_PASS_BEGIN_INFO,
+                p_next: ptr::null(),
+                render_pass: compiled_pass.render_pass,
+                framebuffer,
+                render_area: vk::Rect2D {
+                    offset: vk::Offset2D { x: 0, y: 0 },
+                    extent: compiled_pass.extent,
+                },
+                clear_value_count: compiled_pass.clear_values.len() as u32,
+                p_clear_values: compiled_pass.clear_values.as_ptr(),
+            };
+
+            unsafe {
+                self.device.cmd_begin_render_pass(
+                    command_buffer,
+                    &render_pass_begin_info,
+                    vk::SubpassContents::INLINE,
+                );
+            }
+            record_pass(PassId(pass), command_buffer);
+            unsafe {
+                self.device.cmd_end_render_pass(command_buffer);
+            }
+        }
+
+        self.final_barriers.record(
+            &self.device,
+            command_buffer,
+            &images,
+            &image_descriptions,
+            &self.buffers,
+        );
+
+        Ok(())
+    }
+
+    /// Destroy the framebuffers, the render passes and the transient images, not the imported resources.
+    pub unsafe fn destroy(&self) {
+        for &framebuffer in self.framebuffers.values() {
+            self.device.destroy_framebuffer(framebuffer, None);
+        }
+        for compiled_pass in self.compiled_passes.iter() {
+            if compiled_pass.render_pass != vk::RenderPass::null() {
+                self.device
+                    .destroy_render_pass(compiled_pass.render_pass, None);
+            }
+        }
+        for graph_image in self.images.iter() {
+            if let ImageSource::Transient {
+                image,
+                image_memory,
+                image_view,
+            } = graph_image.source
+            {
+                if image != vk::Image::null() {
+                    self.device.destroy_image_view(image_view, None);
+                    self.device.destroy_image(image, None);
+                    self.device.free_memory(image_memory, None);
+                }
+            }
+        }
+    }
+
+    /// Allocate the transient images used by the passes of `order`, with the usage of these passes.
+    fn create_transient_images(&mut self, order: &[usize]) -> VkTutorialResult<()> {
+        for index in 0..self.images.len() {
+            let mut usage = vk::ImageUsageFlags::empty();
+            let mut user_count = 0;
+            for &pass in order.iter() {
+                for access in self.passes[pass].accesses.iter() {
+                    if access.resource == Resource::Image(index) {
+                        usage |= image_usage(access.layout);
+                        user_count += 1;
+                    }
+                }
+            }
+
+            let description = self.images[index].description;
+            match self.images[index].source {
+                ImageSource::Transient { .. } if user_count > 0 => (),
+                _ => continue,
+            }
+
+            // an attachment of a single pass is not stored by its render pass, see `is_used_later`,
+            // so it can live in lazily allocated memory which stays in the tile memory of a tiled GPU.
+            let mut memory_properties = vk::MemoryPropertyFlags::DEVICE_LOCAL;
+            if user_count == 1
+                && (usage
+                    & !(vk::ImageUsageFlags::COLOR_ATTACHMENT
+                        | vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT))
+                    .is_empty()
+            {
+                usage |= vk::ImageUsageFlags::TRANSIENT_ATTACHMENT;
+                if self.has_lazily_allocated_memory() {
+                    memory_properties |= vk::MemoryPropertyFlags::LAZILY_ALLOCATED;
+                }
+            }
+
+            let (image, image_memory) = share::v1::create_image(
+                &self.device,
+                description.extent.width,
+                description.extent.height,
+                1,
+                description.samples,
+                description.format,
+                vk::ImageTiling::OPTIMAL,
+                usage,
+                memory_properties,
+                &self.device_memory_properties,
+            )?;
+            let image_view = share::v1::create_image_view(
+                &self.device,
+                image,
+                description.format,
+                description.aspect_mask,
+                1,
+            )?;
+
+            self.images[index].source = ImageSource::Transient {
+                image,
+                image_memory,
+                image_view,
+            };
+        }
+
+        Ok(())
+    }
+
+    fn has_lazily_allocated_memory(&self) -> bool {
+        let memory_type_count = self.device_memory_properties.memory_type_count as usize;
+        self.device_memory_properties.memory_types[..memory_type_count]
+            .iter()
+            .any(|memory_type| {
+                memory_type.property_flags.contains(
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL
+                        | vk::MemoryPropertyFlags::LAZILY_ALLOCATED,
+                )
+            })
+    }
+
+    /// Create the render pass of the pass at `position` in `order`, whose attachments are already
+    /// in their layout when it begins and stay in it, the transitions are done by the barriers of the graph.
+    /// The attachments which are not used later are not stored.
+    fn create_render_pass(
+        &self,
+        order: &[usize],
+        position: usize,
+    ) -> VkTutorialResult<(vk::RenderPass, vk::Extent2D, Vec<vk::ClearValue>)> {
+        let pass = &self.passes[order[position]];
+        let attachments = pass.attachments();
+        let extent = self.images[(attachments[0].0).0].description.extent;
+
+        let mut attachment_descriptions = vec![];
+        let mut clear_values = vec![];
+        for (index, &(image, load)) in attachments.iter().enumerate() {
+            let description = self.images[image.0].description;
+            if description.extent.width != extent.width
+                || description.extent.height != extent.height
+            {
+                return Err(VkTutorialError::RenderGraph(format!(
+                    "the attachments of the pass {} have different sizes",
+                    pass.name
+                )));
+            }
+
+            let is_depth = pass.depth_attachment.is_some() && index == attachments.len() - 1;
+            let layout = if is_depth {
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+            } else {
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+            };
+            let load_op = match load {
+                AttachmentLoad::Clear(clear_value) => {
+                    clear_values.push(clear_value);
+                    vk::AttachmentLoadOp::CLEAR
+                }
+                AttachmentLoad::Load => {
+                    clear_values.push(vk::ClearValue::default());
+                    vk::AttachmentLoadOp::LOAD
+                }
+                AttachmentLoad::DontCare => {
+                    clear_values.push(vk::ClearValue::default());
+                    vk::AttachmentLoadOp::DONT_CARE
+                }
+            };
+
+            let store_op = if is_used_later(&self.images, &self.passes, order, position, image.0) {
+                vk::AttachmentStoreOp::STORE
+            } else {
+                vk::AttachmentStoreOp::DONT_CARE
+            };
+
+            attachment_descriptions.push(vk::AttachmentDescription {
+                flags: vk::AttachmentDescriptionFlags::empty(),
+                format: description.format,
+                samples: description.samples,
+                load_op,
+                store_op,
+                stencil_load_op: if is_depth {
+                    load_op
+                } else {
+                    vk::AttachmentLoadOp::DONT_CARE
+                },
+                stencil_store_op: if is_depth {
+                    store_op
+                } else {
+                    vk::AttachmentStoreOp::DONT_CARE
+                },
+                initial_layout: layout,
+                final_layout: layout,
+            });
+        }
+
+        let color_attachment_refs: Vec<vk::AttachmentReference> = (0..pass.color_attachments.len())
+            .map(|index| vk::AttachmentReference {
+                attachment: index as u32,
+                layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            })
+            .collect();
+        let depth_attachment_ref = vk::AttachmentReference {
+            attachment: pass.color_attachments.len() as u32,
+            layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        };
+
+        let subpasses = [vk::SubpassDescription {
+            flags: vk::SubpassDescriptionFlags::empty(),
+            pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
+            input_attachment_count: 0,
+            p_input_attachments: ptr::null(),
+            color_attachment_count: color_attachment_refs.len() as u32,
+            p_color_attachments: color_attachment_refs.as_ptr(),
+            p_resolve_attachments: ptr::null(),
+            p_depth_stencil_attachment: if pass.depth_attachment.is_some() {
+                &depth_attachment_ref
+            } else {
+                ptr::null()
+            },
+            preserve_attachment_count: 0,
+            p_preserve_attachments: ptr::null(),
+        }];
+
+        let renderpass_create_info = vk::RenderPassCreateInfo {
+            s_type: vk::StructureType::RENDER_PASS_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::RenderPassCreateFlags::empty(),
+            attachment_count: attachment_descriptions.len() as u32,
+            p_attachments: attachment_descriptions.as_ptr(),
+            subpass_count: subpasses.len() as u32,
+            p_subpasses: subpasses.as_ptr(),
+            dependency_count: 0,
+            p_dependencies: ptr::null(),
+        };
+
+        let render_pass = unsafe {
+            self.device
+                .create_render_pass(&renderpass_create_info, None)?
+        };
+
+        Ok((render_pass, extent, clear_values))
+    }
+
+    /// The framebuffer of the compiled pass `index`, with the attachments bound by this execution.
+    fn framebuffer(
+        &mut self,
+        index: usize,
+        images: &[(vk::Image, vk::ImageView)],
+    ) -> VkTutorialResult<vk::Framebuffer> {
+        let compiled_pass = &self.compiled_passes[index];
+        let attachments: Vec<vk::ImageView> = self.passes[compiled_pass.pass]
+            .attachments()
+            .iter()
+            .map(|(image, _)| images[image.0].1)
+            .collect();
+
+        let key = (index, attachments);
+        if let Some(&framebuffer) = self.framebuffers.get(&key) {
+            return Ok(framebuffer);
+        }
+
+        let framebuffer_create_info = vk::FramebufferCreateInfo {
+            s_type: vk::StructureType::FRAMEBUFFER_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::FramebufferCreateFlags::empty(),
+            render_pass: compiled_pass.render_pass,
+            attachment_count: key.1.len() as u32,
+            p_attachments: key.1.as_ptr(),
+            width: compiled_pass.extent.width,
+            height: compiled_pass.extent.height,
+            layers: 1,
+        };
+
+        let framebuffer = unsafe {
+            self.device
+                .create_framebuffer(&framebuffer_create_info, None)?
+        };
+        self.framebuffers.insert(key, framebuffer);
+
+        Ok(framebuffer)
+    }
+}
+
+/// The passes to record in their order, the culled passes are left out.
+fn schedule(images: &[GraphImage], passes: &[Pass]) -> VkTutorialResult<Vec<usize>> {
+    // the writers of a resource run in the order they are added,
+    // and the passes only reading it run after all of them.
+    let mut writers: HashMap<Resource, Vec<usize>> = HashMap::new();
+    for (index, pass) in passes.iter().enumerate() {
+        for access in pass.accesses.iter().filter(|access| access.is_write) {
+            writers.entry(access.resource).or_default().push(index);
+        }
+    }
+
+    let mut dependencies: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); passes.len()];
+    for (index, pass) in passes.iter().enumerate() {
+        for access in pass.accesses.iter() {
+            let resource_writers = match writers.get(&access.resource) {
+                Some(resource_writers) => resource_writers,
+                None => continue,
+            };
+            if access.is_write {
+                if let Some(&previous) = resource_writers
+                    .iter()
+                    .rev()
+                    .find(|&&writer| writer < index)
+                {
+                    dependencies[index].insert(previous);
+                }
+            } else {
+                dependencies[index].extend(resource_writers.iter().cloned());
+            }
+        }
+    }
+
+    // keep the passes writing an imported resource, and the passes they depend on.
+    let mut is_kept: Vec<bool> = passes
+        .iter()
+        .map(|pass| {
+            pass.accesses
+                .iter()
+                .any(|access| access.is_write && is_imported(images, access.resource))
+        })
+        .collect();
+    let mut stack: Vec<usize> = (0..passes.len()).filter(|&i| is_kept[i]).collect();
+    while let Some(index) = stack.pop() {
+        for &dependency in dependencies[index].iter() {
+            if !is_kept[dependency] {
+                is_kept[dependency] = true;
+                stack.push(dependency);
+            }
+        }
+    }
+
+    // a topological sort, which keeps the passes in the order they are added when possible.
+    let mut order = vec![];
+    let mut is_done = vec![false; passes.len()];
+    let kept_count = is_kept.iter().filter(|&&kept| kept).count();
+    while order.len() < kept_count {
+        let next = (0..passes.len()).find(|&index| {
+            is_kept[index]
+                && !is_done[index]
+                && dependencies[index]
+                    .iter()
+                    .all(|&dependency| is_done[dependency])
+        });
+        match next {
+            Some(index) => {
+                is_done[index] = true;
+                order.push(index);
+            }
+            None => {
+                let names: Vec<&str> = (0..passes.len())
+                    .filter(|&index| is_kept[index] && !is_done[index])
+                    .map(|index| passes[index].name.as_str())
+                    .collect();
+                return Err(VkTutorialError::RenderGraph(format!(
+                    "the passes {} depend on each other",
+                    names.join(", ")
+                )));
+            }
+        }
+    }
+
+    Ok(order)
+}
+
+fn is_imported(images: &[GraphImage], resource: Resource) -> bool {
+    match resource {
+        Resource::Image(image) => match images[image].source {
+            ImageSource::Imported { .. } => true,
+            ImageSource::Transient { .. } => false,
+        },
+        Resource::Buffer(_) => true,
+    }
+}
+
+/// Whether the content of `image` is used after the pass at `position` in `order`, i.e. whether the
+/// image is imported or accessed by a later pass. A transient image is discarded at the end of a frame.
+fn is_used_later(
+    images: &[GraphImage],
+    passes: &[Pass],
+    order: &[usize],
+    position: usize,
+    image: usize,
+) -> bool {
+    let resource = Resource::Image(image);
+
+    is_imported(images, resource)
+        || order[position + 1..].iter().any(|&pass| {
+            passes[pass]
+                .accesses
+                .iter()
+                .any(|access| access.resource == resource)
+        })
+}
+
+/// Simulate the accesses of a frame to find the barriers before each pass of `order`,
+/// and the transitions of the imported images to their final layout.
+fn frame_barriers(
+    images: &[GraphImage],
+    buffer_count: usize,
+    passes: &[Pass],
+    order: &[usize],
+) -> (Vec<Barriers>, Barriers) {
+    // a first frame gives the state left by the previous frame, which the next one waits for.
+    let initial = initial_states(images, buffer_count, passes, order, None);
+    let (_, last_states) = simulate(passes, order, initial);
+    let initial = initial_states(images, buffer_count, passes, order, Some(&last_states));
+    let (pass_barriers, last_states) = simulate(passes, order, initial);
+
+    let mut final_barriers = Barriers::new();
+    for (index, graph_image) in images.iter().enumerate() {
+        if let ImageSource::Imported { final_layout, .. } = graph_image.source {
+            let state = &last_states[&Resource::Image(index)];
+            if state.layout != final_layout {
+                let (dst_stage_mask, read_access_mask, write_access_mask) =
+                    layout::layout_stage_access(final_layout);
+                final_barriers.add(
+                    Resource::Image(index),
+                    Dependency {
+                        src_stage_mask: state.write_stage_mask | state.read_stage_mask,
+                        src_access_mask: state.write_access_mask,
+                        old_layout: state.layout,
+                    },
+                    final_layout,
+                    dst_stage_mask,
+                    read_access_mask | write_access_mask,
+                );
+            }
+        }
+    }
+
+    (pass_barriers, final_barriers)
+}
+
+/// The state of the resources when a frame begins, from the state at the end of the previous one.
+fn initial_states(
+    images: &[GraphImage],
+    buffer_count: usize,
+    passes: &[Pass],
+    order: &[usize],
+    last_states: Option<&HashMap<Resource, SyncState>>,
+) -> HashMap<Resource, SyncState> {
+    let mut states = HashMap::new();
+
+    for (index, graph_image) in images.iter().enumerate() {
+        let resource = Resource::Image(index);
+        let state = match graph_image.source {
+            ImageSource::Imported { initial_layout, .. } => {
+                // the image is synchronized outside of the graph, e.g. by a semaphore.
+                let first_access = order
+                    .iter()
+                    .flat_map(|&pass| passes[pass].accesses.iter())
+                    .find(|access| access.resource == resource);
+
+                match first_access {
+                    Some(first_access) => SyncState {
+                        write_stage_mask: first_access.stage_mask,
+                        visible_stage_mask: first_access.stage_mask,
+                        visible_access_mask: first_access.access_mask,
+                        ..SyncState::new(initial_layout)
+                    },
+                    None => SyncState::new(initial_layout),
+                }
+            }
+            // the content of a transient image is discarded.
+            ImageSource::Transient { .. } => SyncState {
+                layout: vk::ImageLayout::UNDEFINED,
+                ..last_states
+                    .map(|last_states| last_states[&resource])
+                    .unwrap_or_else(|| SyncState::new(vk::ImageLayout::UNDEFINED))
+            },
+        };
+        states.insert(resource, state);
+    }
+
+    for index in 0..buffer_count {
+        let resource = Resource::Buffer(index);
+        let state = last_states
+            .map(|last_states| last_states[&resource])
+            .unwrap_or_else(|| SyncState::new(vk::ImageLayout::UNDEFINED));
+        states.insert(resource, state);
+    }
+
+    states
+}
+
+fn simulate(
+    passes: &[Pass],
+    order: &[usize],
+    mut states: HashMap<Resource, SyncState>,
+) -> (Vec<Barriers>, HashMap<Resource, SyncState>) {
+    let mut pass_barriers = vec![];
+
+    for &pass in order.iter() {
+        let mut barriers = Barriers::new();
+        for access in passes[pass].accesses.iter() {
+            let state = states.get_mut(&access.resource).unwrap();
+            if let Some(dependency) = state.access(access) {
+                barriers.add(
+                    access.resource,
+                    dependency,
+                    access.layout,
+                    access.stage_mask,
+                    access.access_mask,
+                );
+            }
+        }
+        pass_barriers.push(barriers);
+    }
+
+    (pass_barriers, states)
+}
+
+/// The usage an image needs to be accessed in `layout`.
+fn image_usage(layout: vk::ImageLayout) -> vk::ImageUsageFlags {
+    match layout {
+        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL => vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL => {
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+        }
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => vk::ImageUsageFlags::SAMPLED,
+        vk::ImageLayout::GENERAL => vk::ImageUsageFlags::STORAGE,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL => vk::ImageUsageFlags::TRANSFER_SRC,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL => vk::ImageUsageFlags::TRANSFER_DST,
+        _ => vk::ImageUsageFlags::empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXTENT: vk::Extent2D = vk::Extent2D {
+        width: 4,
+        height: 4,
+    };
+
+    fn swapchain_image() -> GraphImage {
+        GraphImage {
+            description: ImageDescription::color(vk::Format::B8G8R8A8_SRGB, EXTENT),
+            source: ImageSource::Imported {
+                initial_layout: vk::ImageLayout::UNDEFINED,
+                final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+            },
+        }
+    }
+
+    fn transient_image() -> GraphImage {
+        GraphImage {
+            description: ImageDescription::color(vk::Format::R8G8B8A8_UNORM, EXTENT),
+            source: ImageSource::Transient {
+                image: vk::Image::null(),
+                image_memory: vk::DeviceMemory::null(),
+                image_view: vk::ImageView::null(),
+            },
+        }
+    }
+
+    /// A draw into the swapchain image 0 sampling the transient image 1, drawn by the shadow pass added after it.
+    fn shadow_and_draw_passes() -> (Vec<GraphImage>, Vec<Pass>) {
+        let images = vec![swapchain_image(), transient_image()];
+        let passes = vec![
+            Pass::new("draw")
+                .sampled_image(ImageId(1), vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .color_attachment(ImageId(0), AttachmentLoad::DontCare),
+            Pass::new("shadow").color_attachment(ImageId(1), AttachmentLoad::DontCare),
+        ];
+
+        (images, passes)
+    }
+
+    #[test]
+    fn passes_run_after_the_writers_of_their_inputs() {
+        let (images, passes) = shadow_and_draw_passes();
+
+        assert_eq!(schedule(&images, &passes).unwrap(), [1, 0]);
+    }
+
+    #[test]
+    fn passes_whose_results_are_not_used_are_culled() {
+        let images = vec![swapchain_image(), transient_image()];
+        let passes = vec![
+            Pass::new("unused").color_attachment(ImageId(1), AttachmentLoad::DontCare),
+            Pass::new("draw").color_attachment(ImageId(0), AttachmentLoad::DontCare),
+        ];
+
+        assert_eq!(schedule(&images, &passes).unwrap(), [1]);
+    }
+
+    #[test]
+    fn passes_depending_on_each_other_are_rejected() {
+        let images = vec![swapchain_image(), transient_image(), transient_image()];
+        let passes = vec![
+            Pass::new("first")
+                .sampled_image(ImageId(1), vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .color_attachment(ImageId(2), AttachmentLoad::DontCare),
+            Pass::new("second")
+                .sampled_image(ImageId(2), vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .color_attachment(ImageId(1), AttachmentLoad::DontCare)
+                .color_attachment(ImageId(0), AttachmentLoad::DontCare),
+        ];
+
+        assert!(schedule(&images, &passes).is_err());
+    }
+
+    #[test]
+    fn sampled_attachments_are_transitioned_between_the_passes() {
+        let (images, passes) = shadow_and_draw_passes();
+        let (pass_barriers, final_barriers) = frame_barriers(&images, 0, &passes, &[1, 0]);
+
+        // the shadow pass waits for the draw of the previous frame to sample its image.
+        let shadow_barriers = &pass_barriers[0];
+        assert_eq!(shadow_barriers.images.len(), 1);
+        assert_eq!(shadow_barriers.images[0].image, 1);
+        assert_eq!(
+            shadow_barriers.images[0].old_layout,
+            vk::ImageLayout::UNDEFINED
+        );
+        assert_eq!(
+            shadow_barriers.images[0].new_layout,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        );
+        assert!(shadow_barriers
+            .src_stage_mask
+            .contains(vk::PipelineStageFlags::FRAGMENT_SHADER));
+
+        let draw_barriers = &pass_barriers[1];
+        let sampled_barrier = draw_barriers
+            .images
+            .iter()
+            .find(|barrier| barrier.image == 1)
+            .unwrap();
+        assert_eq!(
+            sampled_barrier.old_layout,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        );
+        assert_eq!(
+            sampled_barrier.new_layout,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+        );
+        assert!(sampled_barrier
+            .src_access_mask
+            .contains(vk::AccessFlags::COLOR_ATTACHMENT_WRITE));
+        assert_eq!(
+            sampled_barrier.dst_access_mask,
+            vk::AccessFlags::SHADER_READ
+        );
+        assert!(draw_barriers
+            .src_stage_mask
+            .contains(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT));
+        assert!(draw_barriers
+            .dst_stage_mask
+            .contains(vk::PipelineStageFlags::FRAGMENT_SHADER));
+        let swapchain_barrier = draw_barriers
+            .images
+            .iter()
+            .find(|barrier| barrier.image == 0)
+            .unwrap();
+        assert_eq!(swapchain_barrier.old_layout, vk::ImageLayout::UNDEFINED);
+        assert_eq!(
+            swapchain_barrier.new_layout,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        );
+
+        assert_eq!(final_barriers.images.len(), 1);
+        assert_eq!(
+            final_barriers.images[0].old_layout,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        );
+        assert_eq!(
+            final_barriers.images[0].new_layout,
+            vk::ImageLayout::PRESENT_SRC_KHR
+        );
+    }
+
+    #[test]
+    fn reads_of_a_visible_write_need_no_barrier() {
+        let images = vec![swapchain_image()];
+        let buffer = BufferId(0);
+        let passes = vec![
+            Pass::new("simulate").storage_buffer(
+                buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                true,
+            ),
+            Pass::new("draw")
+                .vertex_buffer(buffer)
+                .color_attachment(ImageId(0), AttachmentLoad::DontCare),
+            Pass::new("overlay")
+                .vertex_buffer(buffer)
+                .color_attachment(ImageId(0), AttachmentLoad::Load),
+        ];
+        let (pass_barriers, _) = frame_barriers(&images, 1, &passes, &[0, 1, 2]);
+
+        assert_eq!(pass_barriers[1].buffers.len(), 1);
+        assert_eq!(
+            pass_barriers[1].buffers[0].dst_access_mask,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ
+        );
+        assert!(pass_barriers[1]
+            .src_stage_mask
+            .contains(vk::PipelineStageFlags::COMPUTE_SHADER));
+        assert!(pass_barriers[2].buffers.is_empty());
+    }
+
+    #[test]
+    fn only_the_attachments_used_later_are_stored() {
+        let (images, passes) = shadow_and_draw_passes();
+        let order = [1, 0];
+
+        assert!(is_used_later(&images, &passes, &order, 0, 1));
+        assert!(!is_used_later(&images, &passes, &order, 1, 1));
+        assert!(is_used_later(&images, &passes, &order, 1, 0));
+    }
+}
@@ -0,0 +1,470 @@
+//! The images the apps render into, either owned by a swapchain or allocated offscreen for headless mode.
+
+use ash::prelude::VkResult;
+use ash::version::DeviceV1_0;
+use ash::vk;
+
+use std::ptr;
+
+use crate::utility::error::{
+    VkTutorialError,
+    VkTutorialResult,
+};
+use crate::utility::share;
+use crate::utility::share::layout::{
+    self,
+    ImageTransition,
+};
+
+pub enum Presentation {
+    Swapchain {
+        swapchain_loader: ash::extensions::khr::Swapchain,
+        swapchain: vk::SwapchainKHR,
+    },
+    Offscreen {
+        images_memory: Vec<vk::DeviceMemory>,
+        next_image: u32,
+    },
+}
+
+pub struct RenderTarget {
+    pub device: ash::Device,
+    pub submit_queue: vk::Queue,
+    pub device_memory_properties: vk::PhysicalDeviceMemoryProperties,
+    pub presentation: Presentation,
+    pub images: Vec<vk::Image>,
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+    /// Whether the images were created with `TRANSFER_SRC` usage.
+    pub is_capturable: bool,
+    /// Whether the next recorded frame is copied into a staging buffer.
+    pub is_capture_requested: bool,
+    /// The copy of a frame submitted but not read back yet.
+    pub pending_capture: Option<PendingCapture>,
+}
+
+/// A frame copied into a staging buffer by the commands of its own submission.
+pub struct PendingCapture {
+    staging_buffer: vk::Buffer,
+    staging_buffer_memory: vk::DeviceMemory,
+    /// The command buffer recording the copy, when it is not the command buffer of the frame.
+    command_buffer: Option<(vk::CommandPool, vk::CommandBuffer)>,
+    /// The fence signaled by the submission of the frame.
+    fence: vk::Fence,
+    extent: vk::Extent2D,
+    is_bgra: bool,
+}
+
+impl RenderTarget {
+    pub fn is_headless(&self) -> bool {
+        match self.presentation {
+            Presentation::Swapchain { .. } => false,
+            Presentation::Offscreen { .. } => true,
+        }
+    }
+
+    /// Same as `vkAcquireNextImageKHR`, the offscreen images are handed out in turn.
+    pub unsafe fn acquire_next_image(
+        &mut self,
+        timeout: u64,
+        semaphore: vk::Semaphore,
+    ) -> VkResult<(u32, bool)> {
+        match &mut self.presentation {
+            Presentation::Swapchain {
+                swapchain_loader,
+                swapchain,
+            } => swapchain_loader.acquire_next_image(
+                *swapchain,
+                timeout,
+                semaphore,
+                vk::Fence::null(),
+            ),
+            Presentation::Offscreen { next_image, .. } => {
+                let image_index = *next_image;
+                *next_image = (*next_image + 1) % self.images.len() as u32;
+
+                // there is no presentation engine to signal the semaphore, so an empty batch does it.
+                let signal_semaphores = [semaphore];
+                let submit_infos = [vk::SubmitInfo {
+                    s_type: vk::StructureType::SUBMIT_INFO,
+                    p_next: ptr::null(),
+                    wait_semaphore_count: 0,
+                    p_wait_semaphores: ptr::null(),
+                    p_wait_dst_stage_mask: ptr::null(),
+                    command_buffer_count: 0,
+                    p_command_buffers: ptr::null(),
+                    signal_semaphore_count: signal_semaphores.len() as u32,
+                    p_signal_semaphores: signal_semaphores.as_ptr(),
+                }];
+
+                self.device
+                    .queue_submit(self.submit_queue, &submit_infos, vk::Fence::null())
+                    .map(|_| (image_index, false))
+            }
+        }
+    }
+
+    /// Same as `vkQueuePresentKHR`, the offscreen images only consume the wait semaphores.
+    pub unsafe fn queue_present(
+        &self,
+        queue: vk::Queue,
+        wait_semaphores: &[vk::Semaphore],
+        image_index: u32,
+    ) -> VkResult<bool> {
+        match &self.presentation {
+            Presentation::Swapchain {
+                swapchain_loader,
+                swapchain,
+            } => {
+                let swapchains = [*swapchain];
+
+                let present_info = vk::PresentInfoKHR {
+                    s_type: vk::StructureType::PRESENT_INFO_KHR,
+                    p_next: ptr::null(),
+                    wait_semaphore_count: wait_semaphores.len() as u32,
+                    p_wait_semaphores: wait_semaphores.as_ptr(),
+                    swapchain_count: 1,
+                    p_swapchains: swapchains.as_ptr(),
+                    p_image_indices: &image_index,
+                    p_results: ptr::null_mut(),
+                };
+
+                swapchain_loader.queue_present(queue, &present_info)
+            }
+            Presentation::Offscreen { .. } => {
+                let wait_stages = vec![vk::PipelineStageFlags::ALL_COMMANDS; wait_semaphores.len()];
+                let submit_infos = [vk::SubmitInfo {
+                    s_type: vk::StructureType::SUBMIT_INFO,
+                    p_next: ptr::null(),
+                    wait_semaphore_count: wait_semaphores.len() as u32,
+                    p_wait_semaphores: wait_semaphores.as_ptr(),
+                    p_wait_dst_stage_mask: wait_stages.as_ptr(),
+                    command_buffer_count: 0,
+                    p_command_buffers: ptr::null(),
+                    signal_semaphore_count: 0,
+                    p_signal_semaphores: ptr::null(),
+                }];
+
+                self.device
+                    .queue_submit(queue, &submit_infos, vk::Fence::null())?;
+
+                Ok(false)
+            }
+        }
+    }
+
+    /// Copy the next recorded frame into a staging buffer, read back by `take_capture`.
+    pub fn request_capture(&mut self) -> VkTutorialResult<()> {
+        if !self.is_capturable || capture_is_bgra(self.format).is_none() {
+            return Err(VkTutorialError::CaptureUnsupported);
+        }
+
+        self.is_capture_requested = true;
+        Ok(())
+    }
+
+    /// When a capture is requested, record the copy of `image_index` into `command_buffer`.
+    ///
+    /// It goes after the render pass which leaves the image in `PRESENT_SRC_KHR`, before the
+    /// command buffer ends, and `fence` is the one its submission signals.
+    pub fn cmd_capture(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        image_index: u32,
+        fence: vk::Fence,
+    ) -> VkTutorialResult<()> {
+        if !self.is_capture_requested {
+            return Ok(());
+        }
+        self.is_capture_requested = false;
+        self.discard_pending_capture();
+
+        let pending_capture = self.create_pending_capture(fence)?;
+        let result = self.cmd_copy_to_staging_buffer(command_buffer, image_index, &pending_capture);
+        match result {
+            Ok(()) => {
+                self.pending_capture = Some(pending_capture);
+                Ok(())
+            }
+            Err(error) => {
+                unsafe { self.destroy_pending_capture(&pending_capture) };
+                Err(error)
+            }
+        }
+    }
+
+    /// When a capture is requested, record the copy of `image_index` into a new command buffer
+    /// of `command_pool`.
+    ///
+    /// For the apps recording their command buffers up front: the copy of the frame goes in the same
+    /// batch, right after the command buffer of the frame and before the presentation, and `fence`
+    /// is the one this batch signals.
+    pub fn record_capture(
+        &mut self,
+        command_pool: vk::CommandPool,
+        image_index: u32,
+        fence: vk::Fence,
+    ) -> VkTutorialResult<Option<vk::CommandBuffer>> {
+        if !self.is_capture_requested {
+            return Ok(None);
+        }
+        self.is_capture_requested = false;
+        self.discard_pending_capture();
+
+        let mut pending_capture = self.create_pending_capture(fence)?;
+        let result =
+            self.record_capture_command_buffer(command_pool, image_index, &mut pending_capture);
+        match result {
+            Ok(command_buffer) => {
+                self.pending_capture = Some(pending_capture);
+                Ok(Some(command_buffer))
+            }
+            Err(error) => {
+                unsafe { self.destroy_pending_capture(&pending_capture) };
+                Err(error)
+            }
+        }
+    }
+
+    /// Wait for the frame copied by `cmd_capture` or `record_capture`, and read it back with
+    /// its channels in RGBA order. Return `None` when no capture was submitted.
+    pub fn take_capture(&mut self) -> VkTutorialResult<Option<image::RgbaImage>> {
+        let pending_capture = match self.pending_capture.take() {
+            Some(pending_capture) => pending_capture,
+            None => return Ok(None),
+        };
+
+        let result = unsafe { self.read_pending_capture(&pending_capture) };
+        unsafe { self.destroy_pending_capture(&pending_capture) };
+
+        result.map(Some)
+    }
+
+    fn create_pending_capture(&self, fence: vk::Fence) -> VkTutorialResult<PendingCapture> {
+        let is_bgra = capture_is_bgra(self.format).ok_or(VkTutorialError::CaptureUnsupported)?;
+        let buffer_size = (self.extent.width * self.extent.height * 4) as vk::DeviceSize;
+
+        let (staging_buffer, staging_buffer_memory) = share::create_buffer(
+            &self.device,
+            buffer_size,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            &self.device_memory_properties,
+        )?;
+
+        Ok(PendingCapture {
+            staging_buffer,
+            staging_buffer_memory,
+            command_buffer: None,
+            fence,
+            extent: self.extent,
+            is_bgra,
+        })
+    }
+
+    fn record_capture_command_buffer(
+        &self,
+        command_pool: vk::CommandPool,
+        image_index: u32,
+        pending_capture: &mut PendingCapture,
+    ) -> VkTutorialResult<vk::CommandBuffer> {
+        let allocate_info = vk::CommandBufferAllocateInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+            p_next: ptr::null(),
+            command_buffer_count: 1,
+            command_pool,
+            level: vk::CommandBufferLevel::PRIMARY,
+        };
+        let command_buffer = unsafe { self.device.allocate_command_buffers(&allocate_info)?[0] };
+        pending_capture.command_buffer = Some((command_pool, command_buffer));
+
+        let begin_info = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            p_next: ptr::null(),
+            p_inheritance_info: ptr::null(),
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+        };
+        unsafe {
+            self.device
+                .begin_command_buffer(command_buffer, &begin_info)?;
+        }
+        self.cmd_copy_to_staging_buffer(command_buffer, image_index, pending_capture)?;
+        unsafe {
+            self.device.end_command_buffer(command_buffer)?;
+        }
+
+        Ok(command_buffer)
+    }
+
+    fn cmd_copy_to_staging_buffer(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        image_index: u32,
+        pending_capture: &PendingCapture,
+    ) -> VkTutorialResult<()> {
+        let image = self.images[image_index as usize];
+
+        // the image was written earlier in the same submission, then transitioned to `PRESENT_SRC_KHR`
+        // by a dependency ending at `BOTTOM_OF_PIPE`, which this barrier chains with.
+        layout::cmd_transition_image_layouts(
+            &self.device,
+            command_buffer,
+            &[ImageTransition::new(
+                image,
+                self.format,
+                vk::ImageLayout::PRESENT_SRC_KHR,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            )
+            .src(
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            )],
+        )?;
+
+        let region = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+            image_extent: vk::Extent3D {
+                width: pending_capture.extent.width,
+                height: pending_capture.extent.height,
+                depth: 1,
+            },
+        };
+        unsafe {
+            self.device.cmd_copy_image_to_buffer(
+                command_buffer,
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                pending_capture.staging_buffer,
+                &[region],
+            );
+        }
+
+        layout::cmd_transition_image_layouts(
+            &self.device,
+            command_buffer,
+            &[ImageTransition::new(
+                image,
+                self.format,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::ImageLayout::PRESENT_SRC_KHR,
+            )],
+        )?;
+
+        // make the copy visible to the host once the fence is signaled.
+        let buffer_barriers = [vk::BufferMemoryBarrier {
+            s_type: vk::StructureType::BUFFER_MEMORY_BARRIER,
+            p_next: ptr::null(),
+            src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            dst_access_mask: vk::AccessFlags::HOST_READ,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            buffer: pending_capture.staging_buffer,
+            offset: 0,
+            size: vk::WHOLE_SIZE,
+        }];
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[],
+                &buffer_barriers,
+                &[],
+            );
+        }
+
+        Ok(())
+    }
+
+    unsafe fn read_pending_capture(
+        &self,
+        pending_capture: &PendingCapture,
+    ) -> VkTutorialResult<image::RgbaImage> {
+        self.device
+            .wait_for_fences(&[pending_capture.fence], true, std::u64::MAX)?;
+
+        let extent = pending_capture.extent;
+        let buffer_size = (extent.width * extent.height * 4) as vk::DeviceSize;
+        let data_ptr = self.device.map_memory(
+            pending_capture.staging_buffer_memory,
+            0,
+            buffer_size,
+            vk::MemoryMapFlags::empty(),
+        )? as *const u8;
+        let mut pixels = std::slice::from_raw_parts(data_ptr, buffer_size as usize).to_vec();
+        self.device
+            .unmap_memory(pending_capture.staging_buffer_memory);
+
+        if pending_capture.is_bgra {
+            for pixel in pixels.chunks_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        image::RgbaImage::from_raw(extent.width, extent.height, pixels)
+            .ok_or(VkTutorialError::CaptureUnsupported)
+    }
+
+    /// Drop a capture never read back, after its submission completed.
+    fn discard_pending_capture(&mut self) {
+        if let Some(pending_capture) = self.pending_capture.take() {
+            unsafe {
+                let _ = self
+                    .device
+                    .wait_for_fences(&[pending_capture.fence], true, std::u64::MAX);
+                self.destroy_pending_capture(&pending_capture);
+            }
+        }
+    }
+
+    unsafe fn destroy_pending_capture(&self, pending_capture: &PendingCapture) {
+        if let Some((command_pool, command_buffer)) = pending_capture.command_buffer {
+            self.device
+                .free_command_buffers(command_pool, &[command_buffer]);
+        }
+        self.device
+            .destroy_buffer(pending_capture.staging_buffer, None);
+        self.device
+            .free_memory(pending_capture.staging_buffer_memory, None);
+    }
+
+    /// A capture not read back yet is lost, the device must be idle.
+    pub unsafe fn destroy(&self, device: &ash::Device) {
+        if let Some(pending_capture) = &self.pending_capture {
+            self.destroy_pending_capture(pending_capture);
+        }
+
+        match &self.presentation {
+            Presentation::Swapchain {
+                swapchain_loader,
+                swapchain,
+            } => swapchain_loader.destroy_swapchain(*swapchain, None),
+            Presentation::Offscreen { images_memory, .. } => {
+                for (&image, &image_memory) in self.images.iter().zip(images_memory.iter()) {
+                    device.destroy_image(image, None);
+                    device.free_memory(image_memory, None);
+                }
+            }
+        }
+    }
+}
+
+/// Whether the captured pixels are in BGRA order, `None` for the formats which can not be captured.
+fn capture_is_bgra(format: vk::Format) -> Option<bool> {
+    match format {
+        vk::Format::B8G8R8A8_SRGB | vk::Format::B8G8R8A8_UNORM => Some(true),
+        vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM => Some(false),
+        _ => None,
+    }
+}
@@ -0,0 +1,305 @@
+//! Describe the samplers instead of filling `vk::SamplerCreateInfo`, and share the identical ones.
+
+use ash::version::{
+    DeviceV1_0,
+    InstanceV1_0,
+};
+use ash::vk;
+
+use std::collections::HashMap;
+use std::hash::{
+    Hash,
+    Hasher,
+};
+use std::ptr;
+
+use crate::utility::error::VkTutorialResult;
+
+/// The state of a sampler, the default one is the trilinear and anisotropic sampler of the tutorial.
+#[derive(Debug, Clone, Copy)]
+pub struct SamplerDescription {
+    pub mag_filter: vk::Filter,
+    pub min_filter: vk::Filter,
+    pub mipmap_mode: vk::SamplerMipmapMode,
+    pub address_mode_u: vk::SamplerAddressMode,
+    pub address_mode_v: vk::SamplerAddressMode,
+    pub address_mode_w: vk::SamplerAddressMode,
+    /// `None` disables the anisotropic filtering, the value is clamped to `maxSamplerAnisotropy`.
+    pub max_anisotropy: Option<f32>,
+    pub min_lod: f32,
+    pub max_lod: f32,
+    pub mip_lod_bias: f32,
+    /// `Some` for the samplers of depth textures compared to a reference value.
+    pub compare_op: Option<vk::CompareOp>,
+    pub border_color: vk::BorderColor,
+}
+
+impl Default for SamplerDescription {
+    fn default() -> SamplerDescription {
+        SamplerDescription {
+            mag_filter: vk::Filter::LINEAR,
+            min_filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            address_mode_u: vk::SamplerAddressMode::REPEAT,
+            address_mode_v: vk::SamplerAddressMode::REPEAT,
+            address_mode_w: vk::SamplerAddressMode::REPEAT,
+            max_anisotropy: Some(16.0),
+            min_lod: 0.0,
+            max_lod: vk::LOD_CLAMP_NONE,
+            mip_lod_bias: 0.0,
+            compare_op: None,
+            border_color: vk::BorderColor::INT_OPAQUE_BLACK,
+        }
+    }
+}
+
+impl SamplerDescription {
+    /// Same filter for magnification and minification, `NEAREST` also picks the nearest mip level.
+    pub fn filter(mut self, filter: vk::Filter) -> SamplerDescription {
+        self.mag_filter = filter;
+        self.min_filter = filter;
+        self.mipmap_mode = if filter == vk::Filter::NEAREST {
+            vk::SamplerMipmapMode::NEAREST
+        } else {
+            vk::SamplerMipmapMode::LINEAR
+        };
+        self
+    }
+
+    /// Same address mode for the three coordinates.
+    pub fn address_mode(mut self, address_mode: vk::SamplerAddressMode) -> SamplerDescription {
+        self.address_mode_u = address_mode;
+        self.address_mode_v = address_mode;
+        self.address_mode_w = address_mode;
+        self
+    }
+
+    pub fn anisotropy(mut self, max_anisotropy: Option<f32>) -> SamplerDescription {
+        self.max_anisotropy = max_anisotropy;
+        self
+    }
+
+    pub fn lod_range(mut self, min_lod: f32, max_lod: f32) -> SamplerDescription {
+        self.min_lod = min_lod;
+        self.max_lod = max_lod;
+        self
+    }
+
+    pub fn mip_lod_bias(mut self, mip_lod_bias: f32) -> SamplerDescription {
+        self.mip_lod_bias = mip_lod_bias;
+        self
+    }
+
+    pub fn compare_op(mut self, compare_op: Option<vk::CompareOp>) -> SamplerDescription {
+        self.compare_op = compare_op;
+        self
+    }
+
+    pub fn border_color(mut self, border_color: vk::BorderColor) -> SamplerDescription {
+        self.border_color = border_color;
+        self
+    }
+
+    /// `device_max_anisotropy` is `None` when the device does not support anisotropic filtering.
+    pub fn to_create_info(&self, device_max_anisotropy: Option<f32>) -> vk::SamplerCreateInfo {
+        let max_anisotropy = match (self.max_anisotropy, device_max_anisotropy) {
+            (Some(max_anisotropy), Some(device_max_anisotropy)) => {
+                Some(max_anisotropy.min(device_max_anisotropy).max(1.0))
+            }
+            _ => None,
+        };
+
+        vk::SamplerCreateInfo {
+            s_type: vk::StructureType::SAMPLER_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::SamplerCreateFlags::empty(),
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_mode: self.mipmap_mode,
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: self.address_mode_w,
+            mip_lod_bias: self.mip_lod_bias,
+            anisotropy_enable: if max_anisotropy.is_some() {
+                vk::TRUE
+            } else {
+                vk::FALSE
+            },
+            max_anisotropy: max_anisotropy.unwrap_or(1.0),
+            compare_enable: if self.compare_op.is_some() {
+                vk::TRUE
+            } else {
+                vk::FALSE
+            },
+            compare_op: self.compare_op.unwrap_or(vk::CompareOp::ALWAYS),
+            min_lod: self.min_lod,
+            max_lod: self.max_lod,
+            border_color: self.border_color,
+            unnormalized_coordinates: vk::FALSE,
+        }
+    }
+
+    /// Compare the floats bit by bit, so that the descriptions can be hashed.
+    fn key(&self) -> [i32; 14] {
+        [
+            self.mag_filter.as_raw(),
+            self.min_filter.as_raw(),
+            self.mipmap_mode.as_raw(),
+            self.address_mode_u.as_raw(),
+            self.address_mode_v.as_raw(),
+            self.address_mode_w.as_raw(),
+            self.max_anisotropy.is_some() as i32,
+            self.max_anisotropy.unwrap_or(0.0).to_bits() as i32,
+            self.min_lod.to_bits() as i32,
+            self.max_lod.to_bits() as i32,
+            self.mip_lod_bias.to_bits() as i32,
+            self.compare_op.is_some() as i32,
+            self.compare_op
+                .map(|compare_op| compare_op.as_raw())
+                .unwrap_or(0),
+            self.border_color.as_raw(),
+        ]
+    }
+}
+
+impl PartialEq for SamplerDescription {
+    fn eq(&self, other: &SamplerDescription) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for SamplerDescription {}
+
+impl Hash for SamplerDescription {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+/// The `maxSamplerAnisotropy` of the device, `None` when it does not support anisotropic filtering.
+///
+/// `share::create_logical_device` enables the feature whenever it is supported.
+pub fn device_max_anisotropy(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+) -> Option<f32> {
+    let features = unsafe { instance.get_physical_device_features(physical_device) };
+
+    if features.sampler_anisotropy == vk::TRUE {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        Some(properties.limits.max_sampler_anisotropy)
+    } else {
+        None
+    }
+}
+
+/// Create each described sampler once, and destroy them all at the end.
+pub struct SamplerCache {
+    device: ash::Device,
+    /// `None` when the device does not support anisotropic filtering.
+    device_max_anisotropy: Option<f32>,
+    samplers: HashMap<SamplerDescription, vk::Sampler>,
+}
+
+impl SamplerCache {
+    pub fn new(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &ash::Device,
+    ) -> SamplerCache {
+        SamplerCache {
+            device: device.clone(),
+            device_max_anisotropy: device_max_anisotropy(instance, physical_device),
+            samplers: HashMap::new(),
+        }
+    }
+
+    /// Return the sampler of `description`, creating it on the first request.
+    pub fn get(&mut self, description: &SamplerDescription) -> VkTutorialResult<vk::Sampler> {
+        if let Some(&sampler) = self.samplers.get(description) {
+            return Ok(sampler);
+        }
+
+        let sampler_create_info = description.to_create_info(self.device_max_anisotropy);
+        let sampler = unsafe { self.device.create_sampler(&sampler_create_info, None)? };
+        self.samplers.insert(*description, sampler);
+
+        Ok(sampler)
+    }
+
+    /// The count of distinct samplers created so far.
+    pub fn sampler_count(&self) -> usize {
+        self.samplers.len()
+    }
+
+    pub unsafe fn destroy(&mut self) {
+        for (_, sampler) in self.samplers.drain() {
+            self.device.destroy_sampler(sampler, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashSet;
+
+    fn hash(description: &SamplerDescription) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        description.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn identical_descriptions_share_a_key() {
+        let description = SamplerDescription::default().lod_range(0.0, 9.0);
+        let same_description = SamplerDescription::default()
+            .lod_range(0.0, 4.5)
+            .lod_range(0.0, 9.0);
+
+        assert_eq!(description, same_description);
+        assert_eq!(hash(&description), hash(&same_description));
+
+        // the floats are compared bit by bit, so that a NaN still equals itself.
+        let nan_bias = SamplerDescription::default().mip_lod_bias(f32::NAN);
+        assert_eq!(nan_bias, nan_bias);
+        assert_eq!(hash(&nan_bias), hash(&nan_bias));
+    }
+
+    #[test]
+    fn different_descriptions_have_different_keys() {
+        let descriptions = [
+            SamplerDescription::default(),
+            SamplerDescription::default().filter(vk::Filter::NEAREST),
+            SamplerDescription::default().address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE),
+            SamplerDescription::default().anisotropy(None),
+            SamplerDescription::default().anisotropy(Some(0.0)),
+            SamplerDescription::default().anisotropy(Some(4.0)),
+            SamplerDescription::default().lod_range(0.0, 9.0),
+            SamplerDescription::default().lod_range(1.0, vk::LOD_CLAMP_NONE),
+            SamplerDescription::default().mip_lod_bias(0.5),
+            // the negative zero has other bits than the zero of the default description.
+            SamplerDescription::default().mip_lod_bias(-0.0),
+            SamplerDescription::default().compare_op(Some(vk::CompareOp::NEVER)),
+            SamplerDescription::default().border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE),
+        ];
+
+        let keys: HashSet<SamplerDescription> = descriptions.iter().copied().collect();
+        assert_eq!(keys.len(), descriptions.len());
+    }
+
+    #[test]
+    fn anisotropy_is_clamped_to_the_device() {
+        let description = SamplerDescription::default().anisotropy(Some(16.0));
+
+        let create_info = description.to_create_info(Some(8.0));
+        assert_eq!(create_info.anisotropy_enable, vk::TRUE);
+        assert_eq!(create_info.max_anisotropy, 8.0);
+
+        let create_info = description.to_create_info(None);
+        assert_eq!(create_info.anisotropy_enable, vk::FALSE);
+        assert_eq!(create_info.max_anisotropy, 1.0);
+    }
+}
@@ -0,0 +1,285 @@
+//! Compute pipelines, the descriptors of the buffers they read and write, and their dispatch.
+//!
+//! The layouts come from the reflection of the compute shader, so the storage buffers, uniform
+//! buffers and push constants it declares do not have to be repeated on the Rust side.
+
+use ash::version::DeviceV1_0;
+use ash::vk;
+
+use std::ffi::CString;
+use std::ptr;
+
+use crate::utility::error::{
+    VkTutorialError,
+    VkTutorialResult,
+};
+use crate::utility::reflection::{
+    self,
+    ShaderReflection,
+};
+use crate::utility::share;
+
+/// A buffer bound to a descriptor set, see `update_buffer_descriptors`.
+#[derive(Debug, Clone, Copy)]
+pub struct BufferDescriptor {
+    pub binding: u32,
+    /// `STORAGE_BUFFER` or `UNIFORM_BUFFER`.
+    pub descriptor_type: vk::DescriptorType,
+    pub buffer: vk::Buffer,
+    /// `vk::WHOLE_SIZE` binds the buffer from the start to its end.
+    pub range: vk::DeviceSize,
+}
+
+/// Create a compute pipeline from `shader_code`, with `set_layouts` bound from set 0
+/// and the push constants declared by the shader.
+pub fn create_compute_pipeline(
+    device: &ash::Device,
+    pipeline_cache: vk::PipelineCache,
+    shader_code: &[u8],
+    set_layouts: &[vk::DescriptorSetLayout],
+) -> VkTutorialResult<(vk::Pipeline, vk::PipelineLayout)> {
+    let shader_reflection = ShaderReflection::new(shader_code)?;
+    if shader_reflection.stage != vk::ShaderStageFlags::COMPUTE {
+        return Err(VkTutorialError::ShaderInterfaceMismatch(format!(
+            "A compute pipeline needs a compute shader, not a {:?} shader",
+            shader_reflection.stage
+        )));
+    }
+    let push_constant_ranges = reflection::push_constant_ranges(&[shader_reflection]);
+
+    let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo {
+        s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+        p_next: ptr::null(),
+        flags: vk::PipelineLayoutCreateFlags::empty(),
+        set_layout_count: set_layouts.len() as u32,
+        p_set_layouts: set_layouts.as_ptr(),
+        push_constant_range_count: push_constant_ranges.len() as u32,
+        p_push_constant_ranges: push_constant_ranges.as_ptr(),
+    };
+    let pipeline_layout =
+        unsafe { device.create_pipeline_layout(&pipeline_layout_create_info, None)? };
+
+    let shader_module = share::create_shader_module(device, shader_code.to_vec())?;
+    let main_function_name = CString::new("main").unwrap();
+
+    let compute_pipeline_create_infos = [vk::ComputePipelineCreateInfo {
+        s_type: vk::StructureType::COMPUTE_PIPELINE_CREATE_INFO,
+        p_next: ptr::null(),
+        flags: vk::PipelineCreateFlags::empty(),
+        stage: vk::PipelineShaderStageCreateInfo {
+            s_type: vk::StructureType::PIPELINE_SHADER_STAGE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineShaderStageCreateFlags::empty(),
+            module: shader_module,
+            p_name: main_function_name.as_ptr(),
+            p_specialization_info: ptr::null(),
+            stage: vk::ShaderStageFlags::COMPUTE,
+        },
+        layout: pipeline_layout,
+        base_pipeline_handle: vk::Pipeline::null(),
+        base_pipeline_index: -1,
+    }];
+
+    let compute_pipelines = unsafe {
+        device.create_compute_pipelines(pipeline_cache, &compute_pipeline_create_infos, None)
+    };
+
+    unsafe {
+        device.destroy_shader_module(shader_module, None);
+    }
+
+    match compute_pipelines {
+        Ok(compute_pipelines) => Ok((compute_pipelines[0], pipeline_layout)),
+        Err((_, result)) => {
+            unsafe {
+                device.destroy_pipeline_layout(pipeline_layout, None);
+            }
+            Err(result.into())
+        }
+    }
+}
+
+/// Create a pool holding `set_count` descriptor sets of the layout made of `layout_bindings`,
+/// e.g. the ones returned by `reflection::descriptor_set_layout_bindings`.
+pub fn create_descriptor_pool(
+    device: &ash::Device,
+    layout_bindings: &[vk::DescriptorSetLayoutBinding],
+    set_count: u32,
+) -> VkTutorialResult<vk::DescriptorPool> {
+    let mut pool_sizes: Vec<vk::DescriptorPoolSize> = vec![];
+    for layout_binding in layout_bindings.iter() {
+        let descriptor_count = layout_binding.descriptor_count * set_count;
+        match pool_sizes
+            .iter_mut()
+            .find(|pool_size| pool_size.ty == layout_binding.descriptor_type)
+        {
+            Some(pool_size) => pool_size.descriptor_count += descriptor_count,
+            None => pool_sizes.push(vk::DescriptorPoolSize {
+                ty: layout_binding.descriptor_type,
+                descriptor_count,
+            }),
+        }
+    }
+
+    let descriptor_pool_create_info = vk::DescriptorPoolCreateInfo {
+        s_type: vk::StructureType::DESCRIPTOR_POOL_CREATE_INFO,
+        p_next: ptr::null(),
+        flags: vk::DescriptorPoolCreateFlags::empty(),
+        max_sets: set_count,
+        pool_size_count: pool_sizes.len() as u32,
+        p_pool_sizes: pool_sizes.as_ptr(),
+    };
+
+    Ok(unsafe { device.create_descriptor_pool(&descriptor_pool_create_info, None)? })
+}
+
+/// Allocate one descriptor set of `descriptor_set_layout` for each element of `buffer_descriptors`,
+/// and bind its buffers.
+pub fn create_buffer_descriptor_sets(
+    device: &ash::Device,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    buffer_descriptors: &[Vec<BufferDescriptor>],
+) -> VkTutorialResult<Vec<vk::DescriptorSet>> {
+    let layouts = vec![descriptor_set_layout; buffer_descriptors.len()];
+
+    let descriptor_set_allocate_info = vk::DescriptorSetAllocateInfo {
+        s_type: vk::StructureType::DESCRIPTOR_SET_ALLOCATE_INFO,
+        p_next: ptr::null(),
+        descriptor_pool,
+        descriptor_set_count: layouts.len() as u32,
+        p_set_layouts: layouts.as_ptr(),
+    };
+
+    let descriptor_sets =
+        unsafe { device.allocate_descriptor_sets(&descriptor_set_allocate_info)? };
+
+    for (&descriptor_set, descriptors) in descriptor_sets.iter().zip(buffer_descriptors.iter()) {
+        update_buffer_descriptors(device, descriptor_set, descriptors);
+    }
+
+    Ok(descriptor_sets)
+}
+
+/// Bind the buffers of `buffer_descriptors` to `descriptor_set`.
+pub fn update_buffer_descriptors(
+    device: &ash::Device,
+    descriptor_set: vk::DescriptorSet,
+    buffer_descriptors: &[BufferDescriptor],
+) {
+    let buffer_infos: Vec<vk::DescriptorBufferInfo> = buffer_descriptors
+        .iter()
+        .map(|descriptor| vk::DescriptorBufferInfo {
+            buffer: descriptor.buffer,
+            offset: 0,
+            range: descriptor.range,
+        })
+        .collect();
+
+    let descriptor_write_sets: Vec<vk::WriteDescriptorSet> = buffer_descriptors
+        .iter()
+        .zip(buffer_infos.iter())
+        .map(|(descriptor, buffer_info)| vk::WriteDescriptorSet {
+            s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+            p_next: ptr::null(),
+            dst_set: descriptor_set,
+            dst_binding: descriptor.binding,
+            dst_array_element: 0,
+            descriptor_count: 1,
+            descriptor_type: descriptor.descriptor_type,
+            p_image_info: ptr::null(),
+            p_buffer_info: buffer_info,
+            p_texel_buffer_view: ptr::null(),
+        })
+        .collect();
+
+    unsafe {
+        device.update_descriptor_sets(&descriptor_write_sets, &[]);
+    }
+}
+
+/// The count of workgroups of `local_size` invocations covering `element_count` elements.
+pub fn dispatch_group_count(element_count: u32, local_size: u32) -> u32 {
+    (element_count + local_size - 1) / local_size
+}
+
+/// Record the dispatch of `group_count` workgroups of `compute_pipeline`,
+/// with `descriptor_sets` bound from set 0 and `push_constants` from offset 0.
+pub fn cmd_dispatch(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    compute_pipeline: vk::Pipeline,
+    pipeline_layout: vk::PipelineLayout,
+    descriptor_sets: &[vk::DescriptorSet],
+    push_constants: &[u8],
+    group_count: [u32; 3],
+) {
+    unsafe {
+        device.cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            compute_pipeline,
+        );
+        if !descriptor_sets.is_empty() {
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline_layout,
+                0,
+                descriptor_sets,
+                &[],
+            );
+        }
+        if !push_constants.is_empty() {
+            device.cmd_push_constants(
+                command_buffer,
+                pipeline_layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                push_constants,
+            );
+        }
+        device.cmd_dispatch(
+            command_buffer,
+            group_count[0],
+            group_count[1],
+            group_count[2],
+        );
+    }
+}
+
+/// Record a barrier making the `src_access_mask` writes to the whole `buffer`
+/// visible to the `dst_access_mask` accesses of the next pass.
+pub fn cmd_buffer_barrier(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    buffer: vk::Buffer,
+    src_stage_mask: vk::PipelineStageFlags,
+    src_access_mask: vk::AccessFlags,
+    dst_stage_mask: vk::PipelineStageFlags,
+    dst_access_mask: vk::AccessFlags,
+) {
+    let buffer_barriers = [vk::BufferMemoryBarrier {
+        s_type: vk::StructureType::BUFFER_MEMORY_BARRIER,
+        p_next: ptr::null(),
+        src_access_mask,
+        dst_access_mask,
+        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+        buffer,
+        offset: 0,
+        size: vk::WHOLE_SIZE,
+    }];
+
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            src_stage_mask,
+            dst_stage_mask,
+            vk::DependencyFlags::empty(),
+            &[],
+            &buffer_barriers,
+            &[],
+        );
+    }
+}
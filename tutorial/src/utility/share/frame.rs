@@ -0,0 +1,293 @@
+//! Record the command buffer of each frame while drawing it, instead of once at startup.
+//!
+//! Each frame in flight owns a command pool, which is reset when the frame begins again,
+//! so its command buffer can be recorded from the list of `DrawSubmission` of this frame.
+//! The meshes drawn and their counts can then change from one frame to the next.
+
+use ash::version::DeviceV1_0;
+use ash::vk;
+
+use std::ptr;
+
+use crate::utility::error::VkTutorialResult;
+
+/// One draw call of a frame, and the state it is recorded with.
+#[derive(Debug, Clone)]
+pub struct DrawSubmission {
+    pub pipeline: vk::Pipeline,
+    pub pipeline_layout: vk::PipelineLayout,
+    /// Bound from set 0.
+    pub descriptor_sets: Vec<vk::DescriptorSet>,
+    /// The offsets of the dynamic descriptors of `descriptor_sets`, in binding order.
+    pub dynamic_offsets: Vec<u32>,
+    /// Bound from binding 0, with their offsets.
+    pub vertex_buffers: Vec<(vk::Buffer, vk::DeviceSize)>,
+    /// `Some` for an indexed draw, `count` is then a count of indices.
+    pub index_buffer: Option<(vk::Buffer, vk::IndexType)>,
+    pub count: u32,
+    pub first: u32,
+    pub vertex_offset: i32,
+    pub instance_count: u32,
+    pub first_instance: u32,
+    /// Pushed from offset 0 to the `push_constant_stages`.
+    pub push_constants: Vec<u8>,
+    pub push_constant_stages: vk::ShaderStageFlags,
+}
+
+impl DrawSubmission {
+    /// Draw `vertex_count` vertices with `pipeline`.
+    pub fn draw(
+        pipeline: vk::Pipeline,
+        pipeline_layout: vk::PipelineLayout,
+        vertex_count: u32,
+    ) -> DrawSubmission {
+        DrawSubmission {
+            pipeline,
+            pipeline_layout,
+            descriptor_sets: vec![],
+            dynamic_offsets: vec![],
+            vertex_buffers: vec![],
+            index_buffer: None,
+            count: vertex_count,
+            first: 0,
+            vertex_offset: 0,
+            instance_count: 1,
+            first_instance: 0,
+            push_constants: vec![],
+            push_constant_stages: vk::ShaderStageFlags::empty(),
+        }
+    }
+
+    /// Draw `index_count` indices of `index_buffer` with `pipeline`.
+    pub fn draw_indexed(
+        pipeline: vk::Pipeline,
+        pipeline_layout: vk::PipelineLayout,
+        index_buffer: vk::Buffer,
+        index_type: vk::IndexType,
+        index_count: u32,
+    ) -> DrawSubmission {
+        DrawSubmission {
+            index_buffer: Some((index_buffer, index_type)),
+            ..DrawSubmission::draw(pipeline, pipeline_layout, index_count)
+        }
+    }
+
+    pub fn descriptor_sets(mut self, descriptor_sets: &[vk::DescriptorSet]) -> DrawSubmission {
+        self.descriptor_sets = descriptor_sets.to_vec();
+        self
+    }
+
+    pub fn dynamic_offsets(mut self, dynamic_offsets: &[u32]) -> DrawSubmission {
+        self.dynamic_offsets = dynamic_offsets.to_vec();
+        self
+    }
+
+    pub fn vertex_buffers(mut self, vertex_buffers: &[vk::Buffer]) -> DrawSubmission {
+        self.vertex_buffers = vertex_buffers.iter().map(|&buffer| (buffer, 0)).collect();
+        self
+    }
+
+    /// Draw the `count` vertices or indices starting at `first`.
+    pub fn range(mut self, first: u32, count: u32) -> DrawSubmission {
+        self.first = first;
+        self.count = count;
+        self
+    }
+
+    pub fn instances(mut self, first_instance: u32, instance_count: u32) -> DrawSubmission {
+        self.first_instance = first_instance;
+        self.instance_count = instance_count;
+        self
+    }
+
+    pub fn push_constants(mut self, stages: vk::ShaderStageFlags, data: &[u8]) -> DrawSubmission {
+        self.push_constant_stages = stages;
+        self.push_constants = data.to_vec();
+        self
+    }
+}
+
+/// The command pool and the command buffer of each frame in flight.
+pub struct FrameContexts {
+    device: ash::Device,
+    command_pools: Vec<vk::CommandPool>,
+    command_buffers: Vec<vk::CommandBuffer>,
+}
+
+impl FrameContexts {
+    /// Create the contexts of `frames_in_flight` frames, submitted to a queue of `queue_family_index`.
+    pub fn new(
+        device: &ash::Device,
+        queue_family_index: u32,
+        frames_in_flight: usize,
+    ) -> VkTutorialResult<FrameContexts> {
+        let mut frame_contexts = FrameContexts {
+            device: device.clone(),
+            command_pools: Vec::with_capacity(frames_in_flight),
+            command_buffers: Vec::with_capacity(frames_in_flight),
+        };
+
+        for _ in 0..frames_in_flight {
+            let command_pool_create_info = vk::CommandPoolCreateInfo {
+                s_type: vk::StructureType::COMMAND_POOL_CREATE_INFO,
+                p_next: ptr::null(),
+                // the command buffers only live until the pool is reset at the next use of the frame.
+                flags: vk::CommandPoolCreateFlags::TRANSIENT,
+                queue_family_index,
+            };
+            let command_pool =
+                unsafe { device.create_command_pool(&command_pool_create_info, None)? };
+            frame_contexts.command_pools.push(command_pool);
+
+            let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
+                s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+                p_next: ptr::null(),
+                command_buffer_count: 1,
+                command_pool,
+                level: vk::CommandBufferLevel::PRIMARY,
+            };
+            let command_buffers =
+                unsafe { device.allocate_command_buffers(&command_buffer_allocate_info)? };
+            frame_contexts.command_buffers.push(command_buffers[0]);
+        }
+
+        Ok(frame_contexts)
+    }
+
+    /// Reset the command buffer of `frame_index` and begin its recording.
+    ///
+    /// The fence of the previous submission of this frame must have been waited for.
+    pub fn begin(&self, frame_index: usize) -> VkTutorialResult<vk::CommandBuffer> {
+        let command_buffer = self.command_buffers[frame_index];
+        let command_buffer_begin_info = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            p_next: ptr::null(),
+            p_inheritance_info: ptr::null(),
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+        };
+
+        unsafe {
+            self.device.reset_command_pool(
+                self.command_pools[frame_index],
+                vk::CommandPoolResetFlags::empty(),
+            )?;
+            self.device
+                .begin_command_buffer(command_buffer, &command_buffer_begin_info)?;
+        }
+
+        Ok(command_buffer)
+    }
+
+    /// Record a render pass over the whole `framebuffer` which draws the `draws` in order.
+    pub fn cmd_render_pass(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        render_pass: vk::RenderPass,
+        framebuffer: vk::Framebuffer,
+        extent: vk::Extent2D,
+        clear_values: &[vk::ClearValue],
+        draws: &[DrawSubmission],
+    ) {
+        let render_pass_begin_info = vk::RenderPassBeginInfo {
+            s_type: vk::StructureType::RENDER_PASS_BEGIN_INFO,
+            p_next: ptr::null(),
+            render_pass,
+            framebuffer,
+            render_area: vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            },
+            clear_value_count: clear_values.len() as u32,
+            p_clear_values: clear_values.as_ptr(),
+        };
+
+        unsafe {
+            self.device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            );
+        }
+        for draw in draws.iter() {
+            self.cmd_draw(command_buffer, draw);
+        }
+        unsafe {
+            self.device.cmd_end_render_pass(command_buffer);
+        }
+    }
+
+    /// Record `draw`, inside a render pass.
+    pub fn cmd_draw(&self, command_buffer: vk::CommandBuffer, draw: &DrawSubmission) {
+        let device = &self.device;
+
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                draw.pipeline,
+            );
+            if !draw.descriptor_sets.is_empty() {
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    draw.pipeline_layout,
+                    0,
+                    &draw.descriptor_sets,
+                    &draw.dynamic_offsets,
+                );
+            }
+            if !draw.push_constants.is_empty() {
+                device.cmd_push_constants(
+                    command_buffer,
+                    draw.pipeline_layout,
+                    draw.push_constant_stages,
+                    0,
+                    &draw.push_constants,
+                );
+            }
+            if !draw.vertex_buffers.is_empty() {
+                let (buffers, offsets): (Vec<vk::Buffer>, Vec<vk::DeviceSize>) =
+                    draw.vertex_buffers.iter().cloned().unzip();
+                device.cmd_bind_vertex_buffers(command_buffer, 0, &buffers, &offsets);
+            }
+
+            match draw.index_buffer {
+                Some((index_buffer, index_type)) => {
+                    device.cmd_bind_index_buffer(command_buffer, index_buffer, 0, index_type);
+                    device.cmd_draw_indexed(
+                        command_buffer,
+                        draw.count,
+                        draw.instance_count,
+                        draw.first,
+                        draw.vertex_offset,
+                        draw.first_instance,
+                    );
+                }
+                None => device.cmd_draw(
+                    command_buffer,
+                    draw.count,
+                    draw.instance_count,
+                    draw.first,
+                    draw.first_instance,
+                ),
+            }
+        }
+    }
+
+    pub fn end(&self, command_buffer: vk::CommandBuffer) -> VkTutorialResult<()> {
+        unsafe {
+            self.device.end_command_buffer(command_buffer)?;
+        }
+
+        Ok(())
+    }
+
+    pub unsafe fn destroy(&mut self) {
+        // the command buffers are freed with their pools.
+        for &command_pool in self.command_pools.iter() {
+            self.device.destroy_command_pool(command_pool, None);
+        }
+        self.command_pools.clear();
+        self.command_buffers.clear();
+    }
+}
@@ -0,0 +1,363 @@
+//! Record image layout transitions into any command buffer, with the stage and access masks
+//! derived from the layouts.
+//!
+//! The masks of a layout cover every use of an image in this layout, e.g. all the fragment tests
+//! for a depth attachment. An `ImageTransition` can still be given narrower masks, such as the
+//! compute stage for an image sampled by a compute shader.
+
+use ash::version::DeviceV1_0;
+use ash::vk;
+
+use std::ptr;
+
+use crate::utility::error::{
+    VkTutorialError,
+    VkTutorialResult,
+};
+
+/// The transition of some mip levels and array layers of an image from `old_layout` to `new_layout`.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageTransition {
+    pub image: vk::Image,
+    pub old_layout: vk::ImageLayout,
+    pub new_layout: vk::ImageLayout,
+    pub subresource_range: vk::ImageSubresourceRange,
+    /// The uses of the image which are waited for, `None` derives them from `old_layout`.
+    pub src: Option<(vk::PipelineStageFlags, vk::AccessFlags)>,
+    /// The uses of the image which wait for the transition, `None` derives them from `new_layout`.
+    pub dst: Option<(vk::PipelineStageFlags, vk::AccessFlags)>,
+}
+
+impl ImageTransition {
+    /// Transition all the mip levels and array layers, of all the aspects of `format`.
+    pub fn new(
+        image: vk::Image,
+        format: vk::Format,
+        old_layout: vk::ImageLayout,
+        new_layout: vk::ImageLayout,
+    ) -> ImageTransition {
+        ImageTransition {
+            image,
+            old_layout,
+            new_layout,
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: format_aspect_mask(format),
+                base_mip_level: 0,
+                level_count: vk::REMAINING_MIP_LEVELS,
+                base_array_layer: 0,
+                layer_count: vk::REMAINING_ARRAY_LAYERS,
+            },
+            src: None,
+            dst: None,
+        }
+    }
+
+    /// e.g. only the depth aspect of a depth and stencil format.
+    pub fn aspect_mask(mut self, aspect_mask: vk::ImageAspectFlags) -> ImageTransition {
+        self.subresource_range.aspect_mask = aspect_mask;
+        self
+    }
+
+    pub fn mip_levels(mut self, base_mip_level: u32, level_count: u32) -> ImageTransition {
+        self.subresource_range.base_mip_level = base_mip_level;
+        self.subresource_range.level_count = level_count;
+        self
+    }
+
+    pub fn array_layers(mut self, base_array_layer: u32, layer_count: u32) -> ImageTransition {
+        self.subresource_range.base_array_layer = base_array_layer;
+        self.subresource_range.layer_count = layer_count;
+        self
+    }
+
+    pub fn src(
+        mut self,
+        stage_mask: vk::PipelineStageFlags,
+        access_mask: vk::AccessFlags,
+    ) -> ImageTransition {
+        self.src = Some((stage_mask, access_mask));
+        self
+    }
+
+    pub fn dst(
+        mut self,
+        stage_mask: vk::PipelineStageFlags,
+        access_mask: vk::AccessFlags,
+    ) -> ImageTransition {
+        self.dst = Some((stage_mask, access_mask));
+        self
+    }
+
+    /// The stages to wait for, and the writes to make available.
+    pub fn src_stage_access(&self) -> (vk::PipelineStageFlags, vk::AccessFlags) {
+        self.src.unwrap_or_else(|| {
+            let (stage_mask, _, write_access_mask) = layout_stage_access(self.old_layout);
+            (stage_mask, write_access_mask)
+        })
+    }
+
+    /// The stages which wait, and the accesses the image is made visible to.
+    pub fn dst_stage_access(&self) -> (vk::PipelineStageFlags, vk::AccessFlags) {
+        self.dst.unwrap_or_else(|| {
+            let (stage_mask, read_access_mask, write_access_mask) =
+                layout_stage_access(self.new_layout);
+            (stage_mask, read_access_mask | write_access_mask)
+        })
+    }
+}
+
+/// The stages which use an image in `layout`, with their read and write accesses.
+///
+/// An unknown layout gets all the commands and all the memory accesses, which is always correct.
+pub fn layout_stage_access(
+    layout: vk::ImageLayout,
+) -> (vk::PipelineStageFlags, vk::AccessFlags, vk::AccessFlags) {
+    match layout {
+        vk::ImageLayout::UNDEFINED => (
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::AccessFlags::empty(),
+            vk::AccessFlags::empty(),
+        ),
+        vk::ImageLayout::PREINITIALIZED => (
+            vk::PipelineStageFlags::HOST,
+            vk::AccessFlags::empty(),
+            vk::AccessFlags::HOST_WRITE,
+        ),
+        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL => (
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::AccessFlags::COLOR_ATTACHMENT_READ,
+            vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        ),
+        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL => (
+            vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ,
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        ),
+        vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL => (
+            vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags::SHADER_READ,
+            vk::AccessFlags::empty(),
+        ),
+        // the compute stage is left out, a graphics queue family may not support it.
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => (
+            vk::PipelineStageFlags::VERTEX_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::AccessFlags::SHADER_READ,
+            vk::AccessFlags::empty(),
+        ),
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL => (
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_READ,
+            vk::AccessFlags::empty(),
+        ),
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL => (
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::empty(),
+            vk::AccessFlags::TRANSFER_WRITE,
+        ),
+        // the presentation is ordered by semaphores, and `BOTTOM_OF_PIPE` as source
+        // waits for all the commands, so it chains with the wait of a semaphore.
+        vk::ImageLayout::PRESENT_SRC_KHR => (
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::AccessFlags::empty(),
+            vk::AccessFlags::empty(),
+        ),
+        // `GENERAL` allows any use.
+        _ => (
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::AccessFlags::MEMORY_READ,
+            vk::AccessFlags::MEMORY_WRITE,
+        ),
+    }
+}
+
+/// The aspects of the images of `format`.
+pub fn format_aspect_mask(format: vk::Format) -> vk::ImageAspectFlags {
+    match format {
+        vk::Format::D16_UNORM | vk::Format::X8_D24_UNORM_PACK32 | vk::Format::D32_SFLOAT => {
+            vk::ImageAspectFlags::DEPTH
+        }
+        vk::Format::S8_UINT => vk::ImageAspectFlags::STENCIL,
+        vk::Format::D16_UNORM_S8_UINT
+        | vk::Format::D24_UNORM_S8_UINT
+        | vk::Format::D32_SFLOAT_S8_UINT => {
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        }
+        _ => vk::ImageAspectFlags::COLOR,
+    }
+}
+
+/// Record `transitions` into `command_buffer` with one pipeline barrier.
+///
+/// An image can not be transitioned to `UNDEFINED` or `PREINITIALIZED`.
+pub fn cmd_transition_image_layouts(
+    device: &ash::Device,
+    command_buffer: vk::CommandBuffer,
+    transitions: &[ImageTransition],
+) -> VkTutorialResult<()> {
+    let mut src_stage_mask = vk::PipelineStageFlags::empty();
+    let mut dst_stage_mask = vk::PipelineStageFlags::empty();
+    let mut image_barriers = Vec::with_capacity(transitions.len());
+
+    for transition in transitions.iter() {
+        if transition.new_layout == vk::ImageLayout::UNDEFINED
+            || transition.new_layout == vk::ImageLayout::PREINITIALIZED
+        {
+            return Err(VkTutorialError::UnsupportedLayoutTransition {
+                old_layout: transition.old_layout,
+                new_layout: transition.new_layout,
+            });
+        }
+
+        let (src_stage, src_access_mask) = transition.src_stage_access();
+        let (dst_stage, dst_access_mask) = transition.dst_stage_access();
+        src_stage_mask |= src_stage;
+        dst_stage_mask |= dst_stage;
+
+        image_barriers.push(vk::ImageMemoryBarrier {
+            s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
+            p_next: ptr::null(),
+            src_access_mask,
+            dst_access_mask,
+            old_layout: transition.old_layout,
+            new_layout: transition.new_layout,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            image: transition.image,
+            subresource_range: transition.subresource_range,
+        });
+    }
+
+    if image_barriers.is_empty() {
+        return Ok(());
+    }
+
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            src_stage_mask,
+            dst_stage_mask,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &image_barriers,
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layouts_have_their_stages_and_accesses() {
+        let table = [
+            (
+                vk::ImageLayout::UNDEFINED,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::AccessFlags::empty(),
+                vk::AccessFlags::empty(),
+            ),
+            (
+                vk::ImageLayout::PREINITIALIZED,
+                vk::PipelineStageFlags::HOST,
+                vk::AccessFlags::empty(),
+                vk::AccessFlags::HOST_WRITE,
+            ),
+            (
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::AccessFlags::COLOR_ATTACHMENT_READ,
+                vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            ),
+            (
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ,
+                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            ),
+            (
+                vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags::SHADER_READ,
+                vk::AccessFlags::empty(),
+            ),
+            (
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                vk::PipelineStageFlags::VERTEX_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::AccessFlags::SHADER_READ,
+                vk::AccessFlags::empty(),
+            ),
+            (
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_READ,
+                vk::AccessFlags::empty(),
+            ),
+            (
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::empty(),
+                vk::AccessFlags::TRANSFER_WRITE,
+            ),
+            (
+                vk::ImageLayout::PRESENT_SRC_KHR,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::AccessFlags::empty(),
+                vk::AccessFlags::empty(),
+            ),
+            (
+                vk::ImageLayout::GENERAL,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::AccessFlags::MEMORY_READ,
+                vk::AccessFlags::MEMORY_WRITE,
+            ),
+        ];
+
+        for &(layout, stage_mask, read_access_mask, write_access_mask) in table.iter() {
+            assert_eq!(
+                layout_stage_access(layout),
+                (stage_mask, read_access_mask, write_access_mask),
+                "{:?}",
+                layout
+            );
+        }
+    }
+
+    #[test]
+    fn transitions_cover_all_the_aspects_of_the_format() {
+        let table = [
+            (vk::Format::B8G8R8A8_UNORM, vk::ImageAspectFlags::COLOR),
+            (vk::Format::D32_SFLOAT, vk::ImageAspectFlags::DEPTH),
+            (
+                vk::Format::D24_UNORM_S8_UINT,
+                vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL,
+            ),
+            (
+                vk::Format::D32_SFLOAT_S8_UINT,
+                vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL,
+            ),
+        ];
+
+        for &(format, aspect_mask) in table.iter() {
+            let transition = ImageTransition::new(
+                vk::Image::null(),
+                format,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            );
+            assert_eq!(
+                transition.subresource_range.aspect_mask, aspect_mask,
+                "{:?}",
+                format
+            );
+        }
+    }
+}
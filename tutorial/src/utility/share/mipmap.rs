@@ -0,0 +1,104 @@
+//! Mipmapped textures for every device.
+//!
+//! The mip levels are blitted on the GPU when the format supports linear blits, and generated
+//! on the CPU otherwise (e.g. on some software rasterizers), see tutorial 28.
+//! The precomputed levels found next to the texture are used as they are,
+//! level `n` of `texture.jpg` being `texture.mip<n>.jpg`.
+
+use ash::version::InstanceV1_0;
+use ash::vk;
+use image::imageops::FilterType;
+use image::RgbaImage;
+
+use std::cmp::max;
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use crate::utility::error::{
+    VkTutorialError,
+    VkTutorialResult,
+};
+
+/// The filter used to generate the mip levels on the CPU.
+pub const CPU_MIPMAP_FILTER: FilterType = FilterType::Lanczos3;
+
+/// The count of levels of a full mip chain, down to 1x1.
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - max(max(width, height), 1).leading_zeros()
+}
+
+/// Whether `vkCmdBlitImage` can generate the mip levels of an image of `image_format`.
+pub fn is_linear_blit_supported(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    image_format: vk::Format,
+) -> bool {
+    let format_properties =
+        unsafe { instance.get_physical_device_format_properties(physical_device, image_format) };
+
+    format_properties.optimal_tiling_features.contains(
+        vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR
+            | vk::FormatFeatureFlags::BLIT_SRC
+            | vk::FormatFeatureFlags::BLIT_DST,
+    )
+}
+
+/// Downsample `base` into a mip chain of `mip_levels` images, `base` included.
+pub fn generate_mip_chain(base: &RgbaImage, mip_levels: u32, filter: FilterType) -> Vec<RgbaImage> {
+    let mut mip_chain = vec![base.clone()];
+
+    for _ in 1..mip_levels {
+        let previous = mip_chain.last().unwrap();
+        let mip_width = max(previous.width() / 2, 1);
+        let mip_height = max(previous.height() / 2, 1);
+        let mip = image::imageops::resize(previous, mip_width, mip_height, filter);
+        mip_chain.push(mip);
+    }
+
+    mip_chain
+}
+
+/// The path of the precomputed level `mip_level` of the texture at `image_path`.
+pub fn mip_level_path(image_path: &Path, mip_level: u32) -> PathBuf {
+    let stem = image_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file_name = match image_path.extension() {
+        Some(extension) => format!("{}.mip{}.{}", stem, mip_level, extension.to_string_lossy()),
+        None => format!("{}.mip{}", stem, mip_level),
+    };
+
+    image_path.with_file_name(file_name)
+}
+
+/// Load the texture at `image_path`, followed by its precomputed mip levels until one is missing.
+///
+/// The images are flipped vertically as the other textures of the tutorial.
+pub fn load_mip_chain(image_path: &Path) -> VkTutorialResult<Vec<RgbaImage>> {
+    let base = image::open(image_path)?.flipv().to_rgba();
+    let mip_levels = mip_level_count(base.width(), base.height());
+    let mut mip_chain = vec![base];
+
+    for mip_level in 1..mip_levels {
+        let mip_path = mip_level_path(image_path, mip_level);
+        if !mip_path.is_file() {
+            break;
+        }
+
+        let mip = image::open(&mip_path)?.flipv().to_rgba();
+        let previous = mip_chain.last().unwrap();
+        if mip.width() != max(previous.width() / 2, 1)
+            || mip.height() != max(previous.height() / 2, 1)
+        {
+            return Err(VkTutorialError::ImageLoad(
+                image::ImageError::DimensionError,
+            ));
+        }
+        mip_chain.push(mip);
+    }
+
+    Ok(mip_chain)
+}
@@ -1,5 +1,14 @@
 //! Split reduplicate functions in this share module
 
+pub mod compute;
+pub mod frame;
+pub mod layout;
+pub mod mipmap;
+pub mod msaa;
+pub mod owned;
+pub mod texture;
+pub mod uniform;
+pub mod upload;
 pub mod v1;
 pub mod v2;
 
@@ -16,7 +25,24 @@ use std::ptr;
 
 use crate::utility::constants::*;
 use crate::utility::debug;
+use crate::utility::device_selection::{
+    self,
+    DeviceRequirements,
+};
+use crate::utility::error::{
+    VkTutorialError,
+    VkTutorialResult,
+};
+use crate::utility::model;
 use crate::utility::platforms;
+use crate::utility::reflection::{
+    self,
+    ShaderReflection,
+};
+use crate::utility::render_target::{
+    Presentation,
+    RenderTarget,
+};
 use crate::utility::structures::*;
 
 pub fn create_instance(
@@ -24,11 +50,14 @@ pub fn create_instance(
     window_title: &str,
     is_enable_debug: bool,
     required_validation_layers: &Vec<&str>,
-) -> ash::Instance {
+    window: Option<&winit::window::Window>,
+) -> VkTutorialResult<ash::Instance> {
     if is_enable_debug
         && debug::check_validation_layer_support(entry, required_validation_layers) == false
     {
-        panic!("Validation layers requested, but not available!");
+        return Err(VkTutorialError::MissingLayer(
+            required_validation_layers.join(", "),
+        ));
     }
 
     let app_name = CString::new(window_title).unwrap();
@@ -47,7 +76,10 @@ pub fn create_instance(
     let debug_utils_create_info = debug::populate_debug_messenger_create_info();
 
     // VK_EXT debug report has been requested here.
-    let extension_names = platforms::required_extension_names();
+    let extension_names = match window {
+        Some(window) => platforms::window_extension_names(window),
+        None => platforms::headless_extension_names(),
+    };
 
     let requred_validation_layer_raw_names: Vec<CString> = required_validation_layers
         .iter()
@@ -81,66 +113,57 @@ pub fn create_instance(
         enabled_extension_count: extension_names.len() as u32,
     };
 
-    let instance: ash::Instance = unsafe {
-        entry
-            .create_instance(&create_info, None)
-            .expect("Failed to create instance!")
-    };
+    let instance: ash::Instance = unsafe { entry.create_instance(&create_info, None)? };
 
-    instance
+    Ok(instance)
 }
 
+/// Pass `None` as window to get a headless surface, see `SurfaceStuff::is_headless`.
 pub fn create_surface(
     entry: &ash::Entry,
     instance: &ash::Instance,
-    window: &winit::window::Window,
+    window: Option<&winit::window::Window>,
     screen_width: u32,
     screen_height: u32,
-) -> SurfaceStuff {
-    let surface = unsafe {
-        platforms::create_surface(entry, instance, window).expect("Failed to create surface.")
+) -> VkTutorialResult<SurfaceStuff> {
+    let surface = match window {
+        Some(window) => unsafe { platforms::create_surface(entry, instance, window)? },
+        None => vk::SurfaceKHR::null(),
     };
     let surface_loader = ash::extensions::khr::Surface::new(entry, instance);
 
-    SurfaceStuff {
+    Ok(SurfaceStuff {
         surface_loader,
         surface,
         screen_width,
         screen_height,
-    }
+    })
 }
 
+/// Pick the best physical device meeting the requirements, see `utility::device_selection`.
 pub fn pick_physical_device(
     instance: &ash::Instance,
     surface_stuff: &SurfaceStuff,
     required_device_extensions: &DeviceExtension,
-) -> vk::PhysicalDevice {
-    let physical_devices = unsafe {
-        instance
-            .enumerate_physical_devices()
-            .expect("Failed to enumerate Physical Devices!")
+) -> VkTutorialResult<vk::PhysicalDevice> {
+    let requirements = DeviceRequirements {
+        extensions: &required_device_extensions.names,
+        features: &DEVICE_FEATURES,
+        preferred_features: &PREFERRED_DEVICE_FEATURES,
     };
-
-    let result = physical_devices.iter().find(|physical_device| {
-        let is_suitable = is_physical_device_suitable(
-            instance,
-            **physical_device,
-            surface_stuff,
-            required_device_extensions,
-        );
-
-        // if is_suitable {
-        //     let device_properties = instance.get_physical_device_properties(**physical_device);
-        //     let device_name = super::tools::vk_to_string(&device_properties.device_name);
-        //     println!("Using GPU: {}", device_name);
-        // }
-
-        is_suitable
-    });
-
-    match result {
-        Some(p_physical_device) => *p_physical_device,
-        None => panic!("Failed to find a suitable GPU!"),
+    let candidates =
+        device_selection::rate_physical_devices(instance, &requirements, |physical_device| {
+            device_selection::check_surface_support(instance, physical_device, surface_stuff)
+        })?;
+
+    match device_selection::select_physical_device(&candidates) {
+        Some(candidate) => Ok(candidate.physical_device),
+        None => Err(VkTutorialError::NoSuitableDevice(
+            candidates
+                .iter()
+                .map(|candidate| candidate.to_string())
+                .collect(),
+        )),
     }
 }
 
@@ -149,26 +172,21 @@ pub fn is_physical_device_suitable(
     physical_device: vk::PhysicalDevice,
     surface_stuff: &SurfaceStuff,
     required_device_extensions: &DeviceExtension,
-) -> bool {
-    let device_features = unsafe { instance.get_physical_device_features(physical_device) };
-
-    let indices = find_queue_family(instance, physical_device, surface_stuff);
-
-    let is_queue_family_supported = indices.is_complete();
-    let is_device_extension_supported =
-        check_device_extension_support(instance, physical_device, required_device_extensions);
-    let is_swapchain_supported = if is_device_extension_supported {
-        let swapchain_support = query_swapchain_support(physical_device, surface_stuff);
-        !swapchain_support.formats.is_empty() && !swapchain_support.present_modes.is_empty()
-    } else {
-        false
+) -> VkTutorialResult<bool> {
+    let requirements = DeviceRequirements {
+        extensions: &required_device_extensions.names,
+        features: &DEVICE_FEATURES,
+        preferred_features: &PREFERRED_DEVICE_FEATURES,
     };
-    let is_support_sampler_anisotropy = device_features.sampler_anisotropy == 1;
+    let mut rejection_reasons =
+        device_selection::check_surface_support(instance, physical_device, surface_stuff)?;
+    rejection_reasons.extend(device_selection::check_requirements(
+        instance,
+        physical_device,
+        &requirements,
+    )?);
 
-    return is_queue_family_supported
-        && is_device_extension_supported
-        && is_swapchain_supported
-        && is_support_sampler_anisotropy;
+    Ok(rejection_reasons.is_empty())
 }
 
 pub fn create_logical_device(
@@ -177,13 +195,34 @@ pub fn create_logical_device(
     validation: &super::debug::ValidationInfo,
     device_extensions: &DeviceExtension,
     surface_stuff: &SurfaceStuff,
-) -> (ash::Device, QueueFamilyIndices) {
+) -> VkTutorialResult<(ash::Device, QueueFamilyIndices)> {
+    create_logical_device_with_extensions(
+        instance,
+        physical_device,
+        validation,
+        device_extensions,
+        &[],
+        surface_stuff,
+    )
+}
+
+/// Same as `create_logical_device`, but also enable `extra_extensions`, which the caller checked
+/// the device supports.
+pub fn create_logical_device_with_extensions(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    validation: &super::debug::ValidationInfo,
+    device_extensions: &DeviceExtension,
+    extra_extensions: &[&str],
+    surface_stuff: &SurfaceStuff,
+) -> VkTutorialResult<(ash::Device, QueueFamilyIndices)> {
     let indices = find_queue_family(instance, physical_device, surface_stuff);
 
     use std::collections::HashSet;
     let mut unique_queue_families = HashSet::new();
     unique_queue_families.insert(indices.graphics_family.unwrap());
     unique_queue_families.insert(indices.present_family.unwrap());
+    unique_queue_families.extend(indices.transfer_family);
 
     let queue_priorities = [1.0_f32];
     let mut queue_create_infos = vec![];
@@ -199,8 +238,10 @@ pub fn create_logical_device(
         queue_create_infos.push(queue_create_info);
     }
 
+    // enable anisotropy device feature from Chapter-24, the samplers do without it when it is not supported.
+    let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
     let physical_device_features = vk::PhysicalDeviceFeatures {
-        sampler_anisotropy: vk::TRUE, // enable anisotropy device feature from Chapter-24.
+        sampler_anisotropy: supported_features.sampler_anisotropy,
         ..Default::default()
     };
 
@@ -214,7 +255,16 @@ pub fn create_logical_device(
         .map(|layer_name| layer_name.as_ptr())
         .collect();
 
-    let enable_extension_names = device_extensions.get_extensions_raw_names();
+    let extra_extension_raw_names: Vec<CString> = extra_extensions
+        .iter()
+        .map(|extension_name| CString::new(*extension_name).unwrap())
+        .collect();
+    let mut enable_extension_names = device_extensions.get_extensions_raw_names().to_vec();
+    enable_extension_names.extend(
+        extra_extension_raw_names
+            .iter()
+            .map(|extension_name| extension_name.as_ptr()),
+    );
 
     let device_create_info = vk::DeviceCreateInfo {
         s_type: vk::StructureType::DEVICE_CREATE_INFO,
@@ -237,13 +287,10 @@ pub fn create_logical_device(
         p_enabled_features: &physical_device_features,
     };
 
-    let device: ash::Device = unsafe {
-        instance
-            .create_device(physical_device, &device_create_info, None)
-            .expect("Failed to create logical Device!")
-    };
+    let device: ash::Device =
+        unsafe { instance.create_device(physical_device, &device_create_info, None)? };
 
-    (device, indices)
+    Ok((device, indices))
 }
 
 pub fn find_queue_family(
@@ -258,27 +305,42 @@ pub fn find_queue_family(
 
     let mut index = 0;
     for queue_family in queue_families.iter() {
-        if queue_family.queue_count > 0
-            && queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
-        {
-            queue_family_indices.graphics_family = Some(index);
-        }
-
-        let is_present_support = unsafe {
-            surface_stuff
-                .surface_loader
-                .get_physical_device_surface_support(
-                    physical_device,
-                    index as u32,
-                    surface_stuff.surface,
-                )
-        };
-        if queue_family.queue_count > 0 && is_present_support {
-            queue_family_indices.present_family = Some(index);
+        let is_graphics = queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS);
+        let is_compute = queue_family.queue_flags.contains(vk::QueueFlags::COMPUTE);
+
+        // the dedicated family runs beside the graphics one, a transfer only family is preferred.
+        if queue_family.queue_count > 0 && !is_graphics {
+            // compute families support transfers even without reporting it.
+            let is_transfer =
+                is_compute || queue_family.queue_flags.contains(vk::QueueFlags::TRANSFER);
+            if is_transfer && (queue_family_indices.transfer_family.is_none() || !is_compute) {
+                queue_family_indices.transfer_family = Some(index);
+            }
         }
 
-        if queue_family_indices.is_complete() {
-            break;
+        // the graphics and present families are not changed once both are found.
+        if !queue_family_indices.is_complete() {
+            if queue_family.queue_count > 0 && is_graphics {
+                queue_family_indices.graphics_family = Some(index);
+            }
+
+            // offscreen images are never presented, so the graphics queue is used as present queue.
+            let is_present_support = if surface_stuff.is_headless() {
+                queue_family_indices.graphics_family == Some(index)
+            } else {
+                unsafe {
+                    surface_stuff
+                        .surface_loader
+                        .get_physical_device_surface_support(
+                            physical_device,
+                            index as u32,
+                            surface_stuff.surface,
+                        )
+                }
+            };
+            if queue_family.queue_count > 0 && is_present_support {
+                queue_family_indices.present_family = Some(index);
+            }
         }
 
         index += 1;
@@ -291,12 +353,9 @@ pub fn check_device_extension_support(
     instance: &ash::Instance,
     physical_device: vk::PhysicalDevice,
     device_extensions: &DeviceExtension,
-) -> bool {
-    let available_extensions = unsafe {
-        instance
-            .enumerate_device_extension_properties(physical_device)
-            .expect("Failed to get device extension properties.")
-    };
+) -> VkTutorialResult<bool> {
+    let available_extensions =
+        unsafe { instance.enumerate_device_extension_properties(physical_device)? };
 
     let mut available_extension_names = vec![];
 
@@ -316,32 +375,29 @@ pub fn check_device_extension_support(
         required_extensions.remove(extension_name);
     }
 
-    return required_extensions.is_empty();
+    Ok(required_extensions.is_empty())
 }
 
 pub fn query_swapchain_support(
     physical_device: vk::PhysicalDevice,
     surface_stuff: &SurfaceStuff,
-) -> SwapChainSupportDetail {
+) -> VkTutorialResult<SwapChainSupportDetail> {
     unsafe {
         let capabilities = surface_stuff
             .surface_loader
-            .get_physical_device_surface_capabilities(physical_device, surface_stuff.surface)
-            .expect("Failed to query for surface capabilities.");
+            .get_physical_device_surface_capabilities(physical_device, surface_stuff.surface)?;
         let formats = surface_stuff
             .surface_loader
-            .get_physical_device_surface_formats(physical_device, surface_stuff.surface)
-            .expect("Failed to query for surface formats.");
+            .get_physical_device_surface_formats(physical_device, surface_stuff.surface)?;
         let present_modes = surface_stuff
             .surface_loader
-            .get_physical_device_surface_present_modes(physical_device, surface_stuff.surface)
-            .expect("Failed to query for surface present mode.");
+            .get_physical_device_surface_present_modes(physical_device, surface_stuff.surface)?;
 
-        SwapChainSupportDetail {
+        Ok(SwapChainSupportDetail {
             capabilities,
             formats,
             present_modes,
-        }
+        })
     }
 }
 
@@ -352,8 +408,8 @@ pub fn create_swapchain(
     window: &winit::window::Window,
     surface_stuff: &SurfaceStuff,
     queue_family: &QueueFamilyIndices,
-) -> SwapChainStuff {
-    let swapchain_support = query_swapchain_support(physical_device, surface_stuff);
+) -> VkTutorialResult<SwapChainStuff> {
+    let swapchain_support = query_swapchain_support(physical_device, surface_stuff)?;
 
     let surface_format = choose_swapchain_format(&swapchain_support.formats);
     let present_mode = choose_swapchain_present_mode(&swapchain_support.present_modes);
@@ -366,6 +422,17 @@ pub fn create_swapchain(
         image_count
     };
 
+    // the images can be read back for screenshots when the surface allows it.
+    let image_usage = if swapchain_support
+        .capabilities
+        .supported_usage_flags
+        .contains(vk::ImageUsageFlags::TRANSFER_SRC)
+    {
+        vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC
+    } else {
+        vk::ImageUsageFlags::COLOR_ATTACHMENT
+    };
+
     let (image_sharing_mode, queue_family_index_count, queue_family_indices) =
         if queue_family.graphics_family != queue_family.present_family {
             (
@@ -389,7 +456,7 @@ pub fn create_swapchain(
         image_color_space: surface_format.color_space,
         image_format: surface_format.format,
         image_extent: extent,
-        image_usage: vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        image_usage,
         image_sharing_mode,
         p_queue_family_indices: queue_family_indices.as_ptr(),
         queue_family_index_count,
@@ -402,25 +469,137 @@ pub fn create_swapchain(
     };
 
     let swapchain_loader = ash::extensions::khr::Swapchain::new(instance, device);
-    let swapchain = unsafe {
-        swapchain_loader
-            .create_swapchain(&swapchain_create_info, None)
-            .expect("Failed to create Swapchain!")
-    };
+    let swapchain = unsafe { swapchain_loader.create_swapchain(&swapchain_create_info, None)? };
 
-    let swapchain_images = unsafe {
-        swapchain_loader
-            .get_swapchain_images(swapchain)
-            .expect("Failed to get Swapchain Images.")
-    };
+    let swapchain_images = unsafe { swapchain_loader.get_swapchain_images(swapchain)? };
 
-    SwapChainStuff {
+    Ok(SwapChainStuff {
         swapchain_loader,
         swapchain,
         swapchain_format: surface_format.format,
         swapchain_extent: extent,
         swapchain_images,
+        swapchain_image_usage: image_usage,
+    })
+}
+
+/// Create the swapchain, or the offscreen images in place of it when the surface is headless.
+pub fn create_render_target(
+    instance: &ash::Instance,
+    device: &ash::Device,
+    physical_device: vk::PhysicalDevice,
+    window: Option<&winit::window::Window>,
+    surface_stuff: &SurfaceStuff,
+    queue_family: &QueueFamilyIndices,
+    submit_queue: vk::Queue,
+) -> VkTutorialResult<RenderTargetStuff> {
+    Ok(match window {
+        Some(window) => {
+            let swapchain_stuff = create_swapchain(
+                instance,
+                device,
+                physical_device,
+                window,
+                surface_stuff,
+                queue_family,
+            )?;
+            let device_memory_properties =
+                unsafe { instance.get_physical_device_memory_properties(physical_device) };
+
+            RenderTargetStuff {
+                render_target: RenderTarget {
+                    device: device.clone(),
+                    submit_queue,
+                    device_memory_properties,
+                    presentation: Presentation::Swapchain {
+                        swapchain_loader: swapchain_stuff.swapchain_loader,
+                        swapchain: swapchain_stuff.swapchain,
+                    },
+                    images: swapchain_stuff.swapchain_images.clone(),
+                    format: swapchain_stuff.swapchain_format,
+                    extent: swapchain_stuff.swapchain_extent,
+                    is_capturable: swapchain_stuff
+                        .swapchain_image_usage
+                        .contains(vk::ImageUsageFlags::TRANSFER_SRC),
+                    is_capture_requested: false,
+                    pending_capture: None,
+                },
+                swapchain_images: swapchain_stuff.swapchain_images,
+                swapchain_format: swapchain_stuff.swapchain_format,
+                swapchain_extent: swapchain_stuff.swapchain_extent,
+            }
+        }
+        None => create_offscreen_target(
+            instance,
+            device,
+            physical_device,
+            surface_stuff,
+            submit_queue,
+        )?,
+    })
+}
+
+pub fn create_offscreen_target(
+    instance: &ash::Instance,
+    device: &ash::Device,
+    physical_device: vk::PhysicalDevice,
+    surface_stuff: &SurfaceStuff,
+    submit_queue: vk::Queue,
+) -> VkTutorialResult<RenderTargetStuff> {
+    let format = find_supported_format(
+        instance,
+        physical_device,
+        &[vk::Format::B8G8R8A8_SRGB, vk::Format::R8G8B8A8_SRGB],
+        vk::ImageTiling::OPTIMAL,
+        vk::FormatFeatureFlags::COLOR_ATTACHMENT,
+    )?;
+    let extent = vk::Extent2D {
+        width: surface_stuff.screen_width,
+        height: surface_stuff.screen_height,
+    };
+    let device_memory_properties =
+        unsafe { instance.get_physical_device_memory_properties(physical_device) };
+
+    // one image per frame in flight, so an image is never rendered while the previous frame still uses it.
+    let mut images = vec![];
+    let mut images_memory = vec![];
+    for _ in 0..MAX_FRAMES_IN_FLIGHT {
+        let (image, image_memory) = v1::create_image(
+            device,
+            extent.width,
+            extent.height,
+            1,
+            vk::SampleCountFlags::TYPE_1,
+            format,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            &device_memory_properties,
+        )?;
+        images.push(image);
+        images_memory.push(image_memory);
     }
+
+    Ok(RenderTargetStuff {
+        render_target: RenderTarget {
+            device: device.clone(),
+            submit_queue,
+            device_memory_properties,
+            presentation: Presentation::Offscreen {
+                images_memory,
+                next_image: 0,
+            },
+            images: images.clone(),
+            format,
+            extent,
+            is_capturable: true,
+            is_capture_requested: false,
+            pending_capture: None,
+        },
+        swapchain_images: images,
+        swapchain_format: format,
+        swapchain_extent: extent,
+    })
 }
 
 pub fn choose_swapchain_format(
@@ -479,7 +658,18 @@ pub fn choose_swapchain_extent(
     }
 }
 
-pub fn create_shader_module(device: &ash::Device, code: Vec<u8>) -> vk::ShaderModule {
+pub fn create_shader_module(
+    device: &ash::Device,
+    code: Vec<u8>,
+) -> VkTutorialResult<vk::ShaderModule> {
+    // SPIR-V is a stream of 32-bit words.
+    if code.is_empty() || code.len() % 4 != 0 {
+        return Err(VkTutorialError::ShaderLoad(format!(
+            "{} bytes is not valid SPIR-V code",
+            code.len()
+        )));
+    }
+
     let shader_module_create_info = vk::ShaderModuleCreateInfo {
         s_type: vk::StructureType::SHADER_MODULE_CREATE_INFO,
         p_next: ptr::null(),
@@ -488,11 +678,33 @@ pub fn create_shader_module(device: &ash::Device, code: Vec<u8>) -> vk::ShaderMo
         p_code: code.as_ptr() as *const u32,
     };
 
-    unsafe {
-        device
-            .create_shader_module(&shader_module_create_info, None)
-            .expect("Failed to create Shader Module!")
-    }
+    Ok(unsafe { device.create_shader_module(&shader_module_create_info, None)? })
+}
+
+/// Create the layout of descriptor `set` from the bindings declared by the `shaders` of a pipeline.
+pub fn create_reflected_descriptor_set_layout(
+    device: &ash::Device,
+    shaders: &[ShaderReflection],
+    set: u32,
+) -> VkTutorialResult<vk::DescriptorSetLayout> {
+    let layout_bindings = reflection::descriptor_set_layout_bindings(shaders, set)?;
+
+    create_descriptor_set_layout_from_bindings(device, &layout_bindings)
+}
+
+pub fn create_descriptor_set_layout_from_bindings(
+    device: &ash::Device,
+    layout_bindings: &[vk::DescriptorSetLayoutBinding],
+) -> VkTutorialResult<vk::DescriptorSetLayout> {
+    let layout_create_info = vk::DescriptorSetLayoutCreateInfo {
+        s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
+        p_next: ptr::null(),
+        flags: vk::DescriptorSetLayoutCreateFlags::empty(),
+        binding_count: layout_bindings.len() as u32,
+        p_bindings: layout_bindings.as_ptr(),
+    };
+
+    Ok(unsafe { device.create_descriptor_set_layout(&layout_create_info, None)? })
 }
 
 pub fn create_buffer(
@@ -501,7 +713,7 @@ pub fn create_buffer(
     usage: vk::BufferUsageFlags,
     required_memory_properties: vk::MemoryPropertyFlags,
     device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
-) -> (vk::Buffer, vk::DeviceMemory) {
+) -> VkTutorialResult<(vk::Buffer, vk::DeviceMemory)> {
     let buffer_create_info = vk::BufferCreateInfo {
         s_type: vk::StructureType::BUFFER_CREATE_INFO,
         p_next: ptr::null(),
@@ -513,18 +725,83 @@ pub fn create_buffer(
         p_queue_family_indices: ptr::null(),
     };
 
-    let buffer = unsafe {
-        device
-            .create_buffer(&buffer_create_info, None)
-            .expect("Failed to create Vertex Buffer")
-    };
+    let buffer = unsafe { device.create_buffer(&buffer_create_info, None)? };
 
     let mem_requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+    let buffer_memory = allocate_bound_memory(
+        device,
+        mem_requirements,
+        required_memory_properties,
+        device_memory_properties,
+        |memory| unsafe { device.bind_buffer_memory(buffer, memory, 0) },
+    );
+
+    match buffer_memory {
+        Ok(buffer_memory) => Ok((buffer, buffer_memory)),
+        Err(error) => {
+            unsafe {
+                device.destroy_buffer(buffer, None);
+            }
+            Err(error)
+        }
+    }
+}
+
+/// Copy `data` to the start of `memory`, which must be host visible and coherent.
+pub fn write_memory<T>(
+    device: &ash::Device,
+    memory: vk::DeviceMemory,
+    data: &[T],
+) -> VkTutorialResult<()> {
+    unsafe {
+        let data_ptr = device.map_memory(
+            memory,
+            0,
+            ::std::mem::size_of_val(data) as vk::DeviceSize,
+            vk::MemoryMapFlags::empty(),
+        )? as *mut T;
+
+        data_ptr.copy_from_nonoverlapping(data.as_ptr(), data.len());
+
+        device.unmap_memory(memory);
+    }
+
+    Ok(())
+}
+
+/// Destroy `buffer` and free its memory, e.g. when a later step of its creation fails.
+pub fn destroy_buffer(device: &ash::Device, buffer: vk::Buffer, buffer_memory: vk::DeviceMemory) {
+    unsafe {
+        device.destroy_buffer(buffer, None);
+        device.free_memory(buffer_memory, None);
+    }
+}
+
+/// Destroy `image` and free its memory, e.g. when a later step of its creation fails.
+pub fn destroy_image(device: &ash::Device, image: vk::Image, image_memory: vk::DeviceMemory) {
+    unsafe {
+        device.destroy_image(image, None);
+        device.free_memory(image_memory, None);
+    }
+}
+
+/// Allocate the memory of a buffer or an image and bind it to the resource with `bind`,
+/// the memory is freed again if the binding fails.
+fn allocate_bound_memory<F>(
+    device: &ash::Device,
+    mem_requirements: vk::MemoryRequirements,
+    required_memory_properties: vk::MemoryPropertyFlags,
+    device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    bind: F,
+) -> VkTutorialResult<vk::DeviceMemory>
+where
+    F: FnOnce(vk::DeviceMemory) -> Result<(), vk::Result>,
+{
     let memory_type = find_memory_type(
         mem_requirements.memory_type_bits,
         required_memory_properties,
         device_memory_properties,
-    );
+    )?;
 
     let allocate_info = vk::MemoryAllocateInfo {
         s_type: vk::StructureType::MEMORY_ALLOCATE_INFO,
@@ -533,19 +810,16 @@ pub fn create_buffer(
         memory_type_index: memory_type,
     };
 
-    let buffer_memory = unsafe {
-        device
-            .allocate_memory(&allocate_info, None)
-            .expect("Failed to allocate vertex buffer memory!")
-    };
+    let memory = unsafe { device.allocate_memory(&allocate_info, None)? };
 
-    unsafe {
-        device
-            .bind_buffer_memory(buffer, buffer_memory, 0)
-            .expect("Failed to bind Buffer");
+    if let Err(result) = bind(memory) {
+        unsafe {
+            device.free_memory(memory, None);
+        }
+        return Err(VkTutorialError::from(result));
     }
 
-    (buffer, buffer_memory)
+    Ok(memory)
 }
 
 pub fn copy_buffer(
@@ -555,8 +829,8 @@ pub fn copy_buffer(
     src_buffer: vk::Buffer,
     dst_buffer: vk::Buffer,
     size: vk::DeviceSize,
-) {
-    let command_buffer = begin_single_time_command(device, command_pool);
+) -> VkTutorialResult<()> {
+    let command_buffer = begin_single_time_command(device, command_pool)?;
 
     let copy_regions = [vk::BufferCopy {
         src_offset: 0,
@@ -568,13 +842,15 @@ pub fn copy_buffer(
         device.cmd_copy_buffer(command_buffer, src_buffer, dst_buffer, &copy_regions);
     }
 
-    end_single_time_command(device, command_pool, submit_queue, command_buffer);
+    end_single_time_command(device, command_pool, submit_queue, command_buffer)?;
+
+    Ok(())
 }
 
 pub fn begin_single_time_command(
     device: &ash::Device,
     command_pool: vk::CommandPool,
-) -> vk::CommandBuffer {
+) -> VkTutorialResult<vk::CommandBuffer> {
     let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
         s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
         p_next: ptr::null(),
@@ -583,11 +859,8 @@ pub fn begin_single_time_command(
         level: vk::CommandBufferLevel::PRIMARY,
     };
 
-    let command_buffer = unsafe {
-        device
-            .allocate_command_buffers(&command_buffer_allocate_info)
-            .expect("Failed to allocate Command Buffers!")
-    }[0];
+    let command_buffer =
+        unsafe { device.allocate_command_buffers(&command_buffer_allocate_info)? }[0];
 
     let command_buffer_begin_info = vk::CommandBufferBeginInfo {
         s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
@@ -597,12 +870,10 @@ pub fn begin_single_time_command(
     };
 
     unsafe {
-        device
-            .begin_command_buffer(command_buffer, &command_buffer_begin_info)
-            .expect("Failed to begin recording Command Buffer at beginning!");
+        device.begin_command_buffer(command_buffer, &command_buffer_begin_info)?;
     }
 
-    command_buffer
+    Ok(command_buffer)
 }
 
 pub fn end_single_time_command(
@@ -610,11 +881,9 @@ pub fn end_single_time_command(
     command_pool: vk::CommandPool,
     submit_queue: vk::Queue,
     command_buffer: vk::CommandBuffer,
-) {
+) -> VkTutorialResult<()> {
     unsafe {
-        device
-            .end_command_buffer(command_buffer)
-            .expect("Failed to record Command Buffer at Ending!");
+        device.end_command_buffer(command_buffer)?;
     }
 
     let buffers_to_submit = [command_buffer];
@@ -632,29 +901,30 @@ pub fn end_single_time_command(
     }];
 
     unsafe {
-        device
-            .queue_submit(submit_queue, &sumbit_infos, vk::Fence::null())
-            .expect("Failed to Queue Submit!");
-        device
-            .queue_wait_idle(submit_queue)
-            .expect("Failed to wait Queue idle!");
+        device.queue_submit(submit_queue, &sumbit_infos, vk::Fence::null())?;
+        device.queue_wait_idle(submit_queue)?;
         device.free_command_buffers(command_pool, &buffers_to_submit);
     }
+
+    Ok(())
 }
 
 pub fn find_memory_type(
     type_filter: u32,
     required_properties: vk::MemoryPropertyFlags,
     mem_properties: &vk::PhysicalDeviceMemoryProperties,
-) -> u32 {
+) -> VkTutorialResult<u32> {
     for (i, memory_type) in mem_properties.memory_types.iter().enumerate() {
         if (type_filter & (1 << i)) > 0 && memory_type.property_flags.contains(required_properties)
         {
-            return i as u32;
+            return Ok(i as u32);
         }
     }
 
-    panic!("Failed to find suitable memory type!")
+    Err(VkTutorialError::NoSuitableMemoryType {
+        type_filter,
+        properties: required_properties,
+    })
 }
 
 pub fn has_stencil_component(format: vk::Format) -> bool {
@@ -669,8 +939,8 @@ pub fn copy_buffer_to_image(
     image: vk::Image,
     width: u32,
     height: u32,
-) {
-    let command_buffer = begin_single_time_command(device, command_pool);
+) -> VkTutorialResult<()> {
+    let command_buffer = begin_single_time_command(device, command_pool)?;
 
     let buffer_image_regions = [vk::BufferImageCopy {
         image_subresource: vk::ImageSubresourceLayers {
@@ -700,14 +970,16 @@ pub fn copy_buffer_to_image(
         );
     }
 
-    end_single_time_command(device, command_pool, submit_queue, command_buffer);
+    end_single_time_command(device, command_pool, submit_queue, command_buffer)?;
+
+    Ok(())
 }
 
 pub fn find_depth_format(
     instance: &ash::Instance,
     physical_device: vk::PhysicalDevice,
-) -> vk::Format {
-    find_supported_format(
+) -> VkTutorialResult<vk::Format> {
+    Ok(find_supported_format(
         instance,
         physical_device,
         &[
@@ -717,7 +989,7 @@ pub fn find_depth_format(
         ],
         vk::ImageTiling::OPTIMAL,
         vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
-    )
+    )?)
 }
 
 pub fn find_supported_format(
@@ -726,64 +998,42 @@ pub fn find_supported_format(
     candidate_formats: &[vk::Format],
     tiling: vk::ImageTiling,
     features: vk::FormatFeatureFlags,
-) -> vk::Format {
+) -> VkTutorialResult<vk::Format> {
     for &format in candidate_formats.iter() {
         let format_properties =
             unsafe { instance.get_physical_device_format_properties(physical_device, format) };
         if tiling == vk::ImageTiling::LINEAR
             && format_properties.linear_tiling_features.contains(features)
         {
-            return format.clone();
+            return Ok(format.clone());
         } else if tiling == vk::ImageTiling::OPTIMAL
             && format_properties.optimal_tiling_features.contains(features)
         {
-            return format.clone();
+            return Ok(format.clone());
         }
     }
 
-    panic!("Failed to find supported format!")
+    Err(VkTutorialError::UnsupportedFormat {
+        candidates: candidate_formats.to_vec(),
+        features,
+    })
 }
 
-pub fn load_model(model_path: &Path) -> (Vec<VertexV3>, Vec<u32>) {
-    let model_obj = tobj::load_obj(model_path).expect("Failed to load model object!");
-
-    let mut vertices = vec![];
-    let mut indices = vec![];
-
-    let (models, _) = model_obj;
-    for m in models.iter() {
-        let mesh = &m.mesh;
-
-        if mesh.texcoords.len() == 0 {
-            panic!("Missing texture coordinate for the model.")
-        }
-
-        let total_vertices_count = mesh.positions.len() / 3;
-        for i in 0..total_vertices_count {
-            let vertex = VertexV3 {
-                pos: [
-                    mesh.positions[i * 3],
-                    mesh.positions[i * 3 + 1],
-                    mesh.positions[i * 3 + 2],
-                    1.0,
-                ],
-                color: [1.0, 1.0, 1.0, 1.0],
-                tex_coord: [mesh.texcoords[i * 2], mesh.texcoords[i * 2 + 1]],
-            };
-            vertices.push(vertex);
-        }
-
-        indices = mesh.indices.clone();
-    }
+/// Load an OBJ or a glTF model, with all its meshes merged, see `utility::model`.
+pub fn load_model(model_path: &Path) -> VkTutorialResult<(Vec<VertexV3>, Vec<u32>)> {
+    let (vertices, indices) = model::load_model(model_path)?.flatten();
 
-    (vertices, indices)
+    Ok((
+        vertices.iter().map(VertexV4::to_vertex_v3).collect(),
+        indices,
+    ))
 }
 
 pub fn check_mipmap_support(
     instance: &ash::Instance,
     physcial_device: vk::PhysicalDevice,
     image_format: vk::Format,
-) {
+) -> VkTutorialResult<()> {
     let format_properties =
         unsafe { instance.get_physical_device_format_properties(physcial_device, image_format) };
 
@@ -792,6 +1042,11 @@ pub fn check_mipmap_support(
         .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR);
 
     if is_sample_image_filter_linear_support == false {
-        panic!("Texture Image format does not support linear blitting!")
+        return Err(VkTutorialError::UnsupportedFormat {
+            candidates: vec![image_format],
+            features: vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+        });
     }
+
+    Ok(())
 }
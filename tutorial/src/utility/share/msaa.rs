@@ -0,0 +1,636 @@
+//! Multisampled render targets, the samples are resolved into the swapchain images.
+//!
+//! With `TYPE_1` the render pass writes into the swapchain images directly, so the sample count
+//! can be changed at runtime by recreating the swapchain stuff with another one.
+//! The depth attachment is multisampled as the color one. Its samples are resolved too when the
+//! device has `VK_KHR_depth_stencil_resolve`, see `DepthResolve`, otherwise they are dropped at
+//! the end of the render pass.
+
+use ash::version::{
+    DeviceV1_0,
+    InstanceV1_0,
+};
+use ash::vk;
+
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+
+use crate::utility::allocator::{
+    Allocation,
+    MemoryAllocator,
+};
+use crate::utility::device_selection;
+use crate::utility::error::VkTutorialResult;
+use crate::utility::share;
+
+/// The sample counts the tutorial renders with, in increasing order.
+pub const SAMPLE_COUNTS: [vk::SampleCountFlags; 4] = [
+    vk::SampleCountFlags::TYPE_1,
+    vk::SampleCountFlags::TYPE_2,
+    vk::SampleCountFlags::TYPE_4,
+    vk::SampleCountFlags::TYPE_8,
+];
+
+/// The highest count of `SAMPLE_COUNTS` supported by both the color and the depth attachments.
+pub fn get_max_usable_sample_count(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+) -> vk::SampleCountFlags {
+    let physical_device_properties =
+        unsafe { instance.get_physical_device_properties(physical_device) };
+
+    let counts = physical_device_properties
+        .limits
+        .framebuffer_color_sample_counts
+        & physical_device_properties
+            .limits
+            .framebuffer_depth_sample_counts;
+
+    SAMPLE_COUNTS
+        .iter()
+        .rev()
+        .find(|&&samples| counts.contains(samples))
+        .copied()
+        .unwrap_or(vk::SampleCountFlags::TYPE_1)
+}
+
+/// Convert `sample_count` to the nearest lower count of `SAMPLE_COUNTS`, not higher than `max_samples`.
+pub fn clamp_sample_count(
+    sample_count: u32,
+    max_samples: vk::SampleCountFlags,
+) -> vk::SampleCountFlags {
+    SAMPLE_COUNTS
+        .iter()
+        .rev()
+        .find(|&&samples| {
+            samples.as_raw() <= sample_count && samples.as_raw() <= max_samples.as_raw()
+        })
+        .copied()
+        .unwrap_or(vk::SampleCountFlags::TYPE_1)
+}
+
+/// The count following `samples` in `SAMPLE_COUNTS`, back to `TYPE_1` after `max_samples`.
+pub fn next_sample_count(
+    samples: vk::SampleCountFlags,
+    max_samples: vk::SampleCountFlags,
+) -> vk::SampleCountFlags {
+    let next_samples = vk::SampleCountFlags::from_raw(samples.as_raw() * 2);
+    if next_samples.as_raw() > max_samples.as_raw() {
+        vk::SampleCountFlags::TYPE_1
+    } else {
+        next_samples
+    }
+}
+
+/// The device extensions needed to resolve the depth samples, with their dependencies on Vulkan 1.0.
+pub const DEPTH_RESOLVE_EXTENSIONS: [&str; 4] = [
+    "VK_KHR_multiview",
+    "VK_KHR_maintenance2",
+    "VK_KHR_create_renderpass2",
+    "VK_KHR_depth_stencil_resolve",
+];
+
+/// Creates the render passes which resolve the depth samples as well, through
+/// `vkCreateRenderPass2KHR` since `VkRenderPassCreateInfo` has no depth resolve attachment.
+///
+/// The depth keeps the value of its first sample, the only resolve mode every device supports.
+#[derive(Clone)]
+pub struct DepthResolve {
+    handle: vk::Device,
+    create_renderpass2_fn: vk::KhrCreateRenderpass2Fn,
+}
+
+impl DepthResolve {
+    /// Whether `physical_device` has all the `DEPTH_RESOLVE_EXTENSIONS`.
+    pub fn is_supported(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> VkTutorialResult<bool> {
+        let available_extensions =
+            device_selection::available_extension_names(instance, physical_device)?;
+
+        Ok(DEPTH_RESOLVE_EXTENSIONS.iter().all(|&extension| {
+            available_extensions
+                .iter()
+                .any(|available| available.as_str() == extension)
+        }))
+    }
+
+    /// `device` must be created with the `DEPTH_RESOLVE_EXTENSIONS`.
+    pub fn new(instance: &ash::Instance, device: &ash::Device) -> DepthResolve {
+        let create_renderpass2_fn = vk::KhrCreateRenderpass2Fn::load(|name| unsafe {
+            mem::transmute(instance.get_device_proc_addr(device.handle(), name.as_ptr()))
+        });
+
+        DepthResolve {
+            handle: device.handle(),
+            create_renderpass2_fn,
+        }
+    }
+
+    unsafe fn create_render_pass(
+        &self,
+        create_info: &vk::RenderPassCreateInfo2KHR,
+    ) -> VkTutorialResult<vk::RenderPass> {
+        let mut render_pass = vk::RenderPass::null();
+        let result = self.create_renderpass2_fn.create_render_pass2_khr(
+            self.handle,
+            create_info,
+            ptr::null(),
+            &mut render_pass,
+        );
+
+        match result {
+            vk::Result::SUCCESS => Ok(render_pass),
+            _ => Err(result.into()),
+        }
+    }
+}
+
+/// The color and depth images rendered into before the resolve, recreated with the swapchain.
+pub struct MsaaAttachments {
+    pub samples: vk::SampleCountFlags,
+    /// `None` with `TYPE_1`, the fragments are written into the swapchain image.
+    pub color_resources: Option<(vk::Image, vk::ImageView, Allocation)>,
+    pub depth_image: vk::Image,
+    pub depth_image_view: vk::ImageView,
+    pub depth_image_allocation: Allocation,
+    /// The single sample depth the samples are resolved into, `None` without `DepthResolve`
+    /// or with `TYPE_1`.
+    pub depth_resolve_resources: Option<(vk::Image, vk::ImageView, Allocation)>,
+}
+
+impl MsaaAttachments {
+    /// The images are sub-allocated by `allocator`, and must be destroyed with it.
+    ///
+    /// `is_depth_resolved` must match the `depth_resolve` the render pass is created with.
+    pub fn new(
+        instance: &ash::Instance,
+        device: &ash::Device,
+        physical_device: vk::PhysicalDevice,
+        allocator: &mut MemoryAllocator,
+        swapchain_format: vk::Format,
+        swapchain_extent: vk::Extent2D,
+        samples: vk::SampleCountFlags,
+        is_depth_resolved: bool,
+    ) -> VkTutorialResult<MsaaAttachments> {
+        let depth_format = share::find_depth_format(instance, physical_device)?;
+
+        let color_resources = if samples == vk::SampleCountFlags::TYPE_1 {
+            None
+        } else {
+            Some(create_color_resources(
+                device,
+                allocator,
+                swapchain_format,
+                swapchain_extent,
+                samples,
+            )?)
+        };
+
+        let (depth_image, depth_image_view, depth_image_allocation) = match create_depth_resources(
+            device,
+            allocator,
+            depth_format,
+            swapchain_extent,
+            samples,
+        ) {
+            Ok(depth_resources) => depth_resources,
+            Err(error) => {
+                destroy_resources(device, allocator, color_resources);
+                return Err(error);
+            }
+        };
+
+        let depth_resolve_resources =
+            if is_depth_resolved && samples != vk::SampleCountFlags::TYPE_1 {
+                match create_depth_resources(
+                    device,
+                    allocator,
+                    depth_format,
+                    swapchain_extent,
+                    vk::SampleCountFlags::TYPE_1,
+                ) {
+                    Ok(depth_resolve_resources) => Some(depth_resolve_resources),
+                    Err(error) => {
+                        destroy_resources(
+                            device,
+                            allocator,
+                            Some((depth_image, depth_image_view, depth_image_allocation)),
+                        );
+                        destroy_resources(device, allocator, color_resources);
+                        return Err(error);
+                    }
+                }
+            } else {
+                None
+            };
+
+        Ok(MsaaAttachments {
+            samples,
+            color_resources,
+            depth_image,
+            depth_image_view,
+            depth_image_allocation,
+            depth_resolve_resources,
+        })
+    }
+
+    /// The views of a framebuffer, in the order of the attachments of `create_render_pass`.
+    pub fn framebuffer_attachments(
+        &self,
+        swapchain_image_view: vk::ImageView,
+    ) -> Vec<vk::ImageView> {
+        let mut attachments = match self.color_resources {
+            Some((_, color_image_view, _)) => {
+                vec![
+                    color_image_view,
+                    self.depth_image_view,
+                    swapchain_image_view,
+                ]
+            }
+            None => vec![swapchain_image_view, self.depth_image_view],
+        };
+        if let Some((_, depth_resolve_image_view, _)) = self.depth_resolve_resources {
+            attachments.push(depth_resolve_image_view);
+        }
+
+        attachments
+    }
+
+    pub unsafe fn destroy(&self, device: &ash::Device, allocator: &mut MemoryAllocator) {
+        destroy_resources(device, allocator, self.depth_resolve_resources);
+        destroy_resources(
+            device,
+            allocator,
+            Some((
+                self.depth_image,
+                self.depth_image_view,
+                self.depth_image_allocation,
+            )),
+        );
+        destroy_resources(device, allocator, self.color_resources);
+    }
+}
+
+pub fn create_color_resources(
+    device: &ash::Device,
+    allocator: &mut MemoryAllocator,
+    swapchain_format: vk::Format,
+    swapchain_extent: vk::Extent2D,
+    msaa_samples: vk::SampleCountFlags,
+) -> VkTutorialResult<(vk::Image, vk::ImageView, Allocation)> {
+    let color_format = swapchain_format;
+
+    let (color_image, color_image_allocation) = allocator.create_image(
+        swapchain_extent.width,
+        swapchain_extent.height,
+        1,
+        msaa_samples,
+        color_format,
+        vk::ImageTiling::OPTIMAL,
+        vk::ImageUsageFlags::TRANSIENT_ATTACHMENT | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    )?;
+
+    let color_image_view = match share::v1::create_image_view(
+        device,
+        color_image,
+        color_format,
+        vk::ImageAspectFlags::COLOR,
+        1,
+    ) {
+        Ok(color_image_view) => color_image_view,
+        Err(error) => {
+            let _ = allocator.destroy_image(color_image, color_image_allocation);
+            return Err(error);
+        }
+    };
+
+    Ok((color_image, color_image_view, color_image_allocation))
+}
+
+fn create_depth_resources(
+    device: &ash::Device,
+    allocator: &mut MemoryAllocator,
+    depth_format: vk::Format,
+    swapchain_extent: vk::Extent2D,
+    samples: vk::SampleCountFlags,
+) -> VkTutorialResult<(vk::Image, vk::ImageView, Allocation)> {
+    let (depth_image, depth_image_allocation) = allocator.create_image(
+        swapchain_extent.width,
+        swapchain_extent.height,
+        1,
+        samples,
+        depth_format,
+        vk::ImageTiling::OPTIMAL,
+        vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    )?;
+
+    let depth_image_view = match share::v1::create_image_view(
+        device,
+        depth_image,
+        depth_format,
+        vk::ImageAspectFlags::DEPTH,
+        1,
+    ) {
+        Ok(depth_image_view) => depth_image_view,
+        Err(error) => {
+            let _ = allocator.destroy_image(depth_image, depth_image_allocation);
+            return Err(error);
+        }
+    };
+
+    Ok((depth_image, depth_image_view, depth_image_allocation))
+}
+
+fn destroy_resources(
+    device: &ash::Device,
+    allocator: &mut MemoryAllocator,
+    resources: Option<(vk::Image, vk::ImageView, Allocation)>,
+) {
+    if let Some((image, image_view, image_allocation)) = resources {
+        unsafe {
+            device.destroy_image_view(image_view, None);
+        }
+        if let Err(error) = allocator.destroy_image(image, image_allocation) {
+            eprintln!("Failed to free the attachment: {}", error);
+        }
+    }
+}
+
+/// A render pass with a color and a depth attachment of `msaa_samples` samples,
+/// and a resolve attachment for the swapchain image unless `msaa_samples` is `TYPE_1`.
+///
+/// With `depth_resolve`, the depth samples are resolved into a fourth attachment as well.
+pub fn create_render_pass(
+    instance: &ash::Instance,
+    device: &ash::Device,
+    physical_device: vk::PhysicalDevice,
+    surface_format: vk::Format,
+    msaa_samples: vk::SampleCountFlags,
+    depth_resolve: Option<&DepthResolve>,
+) -> VkTutorialResult<vk::RenderPass> {
+    let is_resolved = msaa_samples != vk::SampleCountFlags::TYPE_1;
+    let depth_format = share::find_depth_format(instance, physical_device)?;
+
+    let color_attachment = vk::AttachmentDescription {
+        flags: vk::AttachmentDescriptionFlags::empty(),
+        format: surface_format,
+        samples: msaa_samples,
+        load_op: vk::AttachmentLoadOp::CLEAR,
+        store_op: if is_resolved {
+            vk::AttachmentStoreOp::DONT_CARE
+        } else {
+            vk::AttachmentStoreOp::STORE
+        },
+        stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+        stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+        initial_layout: vk::ImageLayout::UNDEFINED,
+        final_layout: if is_resolved {
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        } else {
+            vk::ImageLayout::PRESENT_SRC_KHR
+        },
+    };
+
+    let depth_attachment = vk::AttachmentDescription {
+        flags: vk::AttachmentDescriptionFlags::empty(),
+        format: depth_format,
+        samples: msaa_samples,
+        load_op: vk::AttachmentLoadOp::CLEAR,
+        store_op: vk::AttachmentStoreOp::DONT_CARE,
+        stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+        stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+        initial_layout: vk::ImageLayout::UNDEFINED,
+        final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+    };
+
+    let color_attachment_resolve = vk::AttachmentDescription {
+        flags: vk::AttachmentDescriptionFlags::empty(),
+        format: surface_format,
+        samples: vk::SampleCountFlags::TYPE_1,
+        load_op: vk::AttachmentLoadOp::DONT_CARE,
+        store_op: vk::AttachmentStoreOp::STORE,
+        stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+        stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+        initial_layout: vk::ImageLayout::UNDEFINED,
+        final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+    };
+
+    let color_attachment_ref = vk::AttachmentReference {
+        attachment: 0,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    };
+
+    let depth_attachment_ref = vk::AttachmentReference {
+        attachment: 1,
+        layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+    };
+
+    let color_attachment_resolve_ref = vk::AttachmentReference {
+        attachment: 2,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    };
+
+    let subpasses = [vk::SubpassDescription {
+        color_attachment_count: 1,
+        p_color_attachments: &color_attachment_ref,
+        p_depth_stencil_attachment: &depth_attachment_ref,
+        flags: vk::SubpassDescriptionFlags::empty(),
+        pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
+        input_attachment_count: 0,
+        p_input_attachments: ptr::null(),
+        p_resolve_attachments: if is_resolved {
+            &color_attachment_resolve_ref
+        } else {
+            ptr::null()
+        },
+        preserve_attachment_count: 0,
+        p_preserve_attachments: ptr::null(),
+    }];
+
+    let render_pass_attachments = if is_resolved {
+        vec![color_attachment, depth_attachment, color_attachment_resolve]
+    } else {
+        vec![color_attachment, depth_attachment]
+    };
+
+    let subpass_dependencies = [vk::SubpassDependency {
+        src_subpass: vk::SUBPASS_EXTERNAL,
+        dst_subpass: 0,
+        src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        src_access_mask: vk::AccessFlags::empty(),
+        dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_READ
+            | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        dependency_flags: vk::DependencyFlags::empty(),
+    }];
+
+    if let (true, Some(depth_resolve)) = (is_resolved, depth_resolve) {
+        let depth_attachment_resolve = vk::AttachmentDescription {
+            flags: vk::AttachmentDescriptionFlags::empty(),
+            format: depth_format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::DONT_CARE,
+            store_op: vk::AttachmentStoreOp::STORE,
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        };
+        let depth_attachment_resolve_ref = vk::AttachmentReference {
+            attachment: 3,
+            layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        };
+
+        let mut attachments = render_pass_attachments;
+        attachments.push(depth_attachment_resolve);
+        let attachments: Vec<vk::AttachmentDescription2KHR> =
+            attachments.iter().map(attachment_description2).collect();
+
+        let color_attachment_ref =
+            attachment_reference2(&color_attachment_ref, vk::ImageAspectFlags::COLOR);
+        let depth_attachment_ref =
+            attachment_reference2(&depth_attachment_ref, vk::ImageAspectFlags::DEPTH);
+        let color_attachment_resolve_ref =
+            attachment_reference2(&color_attachment_resolve_ref, vk::ImageAspectFlags::COLOR);
+        let depth_attachment_resolve_ref =
+            attachment_reference2(&depth_attachment_resolve_ref, vk::ImageAspectFlags::DEPTH);
+
+        // the stencil must be resolved with the same mode as the depth for the formats which have one.
+        let depth_stencil_resolve = vk::SubpassDescriptionDepthStencilResolveKHR {
+            s_type: vk::StructureType::SUBPASS_DESCRIPTION_DEPTH_STENCIL_RESOLVE_KHR,
+            p_next: ptr::null(),
+            depth_resolve_mode: vk::ResolveModeFlagsKHR::SAMPLE_ZERO,
+            stencil_resolve_mode: vk::ResolveModeFlagsKHR::SAMPLE_ZERO,
+            p_depth_stencil_resolve_attachment: &depth_attachment_resolve_ref,
+        };
+
+        let subpasses = [vk::SubpassDescription2KHR {
+            s_type: vk::StructureType::SUBPASS_DESCRIPTION_2_KHR,
+            p_next: &depth_stencil_resolve as *const _ as *const c_void,
+            flags: vk::SubpassDescriptionFlags::empty(),
+            pipeline_bind_point: vk::PipelineBindPoint::GRAPHICS,
+            view_mask: 0,
+            input_attachment_count: 0,
+            p_input_attachments: ptr::null(),
+            color_attachment_count: 1,
+            p_color_attachments: &color_attachment_ref,
+            p_resolve_attachments: &color_attachment_resolve_ref,
+            p_depth_stencil_attachment: &depth_attachment_ref,
+            preserve_attachment_count: 0,
+            p_preserve_attachments: ptr::null(),
+        }];
+
+        let subpass_dependencies: Vec<vk::SubpassDependency2KHR> = subpass_dependencies
+            .iter()
+            .map(|dependency| vk::SubpassDependency2KHR {
+                s_type: vk::StructureType::SUBPASS_DEPENDENCY_2_KHR,
+                p_next: ptr::null(),
+                src_subpass: dependency.src_subpass,
+                dst_subpass: dependency.dst_subpass,
+                src_stage_mask: dependency.src_stage_mask,
+                dst_stage_mask: dependency.dst_stage_mask,
+                src_access_mask: dependency.src_access_mask,
+                dst_access_mask: dependency.dst_access_mask,
+                dependency_flags: dependency.dependency_flags,
+                view_offset: 0,
+            })
+            .collect();
+
+        let renderpass_create_info = vk::RenderPassCreateInfo2KHR {
+            s_type: vk::StructureType::RENDER_PASS_CREATE_INFO_2_KHR,
+            p_next: ptr::null(),
+            flags: vk::RenderPassCreateFlags::empty(),
+            attachment_count: attachments.len() as u32,
+            p_attachments: attachments.as_ptr(),
+            subpass_count: subpasses.len() as u32,
+            p_subpasses: subpasses.as_ptr(),
+            dependency_count: subpass_dependencies.len() as u32,
+            p_dependencies: subpass_dependencies.as_ptr(),
+            correlated_view_mask_count: 0,
+            p_correlated_view_masks: ptr::null(),
+        };
+
+        return unsafe { depth_resolve.create_render_pass(&renderpass_create_info) };
+    }
+
+    let renderpass_create_info = vk::RenderPassCreateInfo {
+        s_type: vk::StructureType::RENDER_PASS_CREATE_INFO,
+        flags: vk::RenderPassCreateFlags::empty(),
+        p_next: ptr::null(),
+        attachment_count: render_pass_attachments.len() as u32,
+        p_attachments: render_pass_attachments.as_ptr(),
+        subpass_count: subpasses.len() as u32,
+        p_subpasses: subpasses.as_ptr(),
+        dependency_count: subpass_dependencies.len() as u32,
+        p_dependencies: subpass_dependencies.as_ptr(),
+    };
+
+    Ok(unsafe { device.create_render_pass(&renderpass_create_info, None)? })
+}
+
+fn attachment_description2(
+    attachment: &vk::AttachmentDescription,
+) -> vk::AttachmentDescription2KHR {
+    vk::AttachmentDescription2KHR {
+        s_type: vk::StructureType::ATTACHMENT_DESCRIPTION_2_KHR,
+        p_next: ptr::null(),
+        flags: attachment.flags,
+        format: attachment.format,
+        samples: attachment.samples,
+        load_op: attachment.load_op,
+        store_op: attachment.store_op,
+        stencil_load_op: attachment.stencil_load_op,
+        stencil_store_op: attachment.stencil_store_op,
+        initial_layout: attachment.initial_layout,
+        final_layout: attachment.final_layout,
+    }
+}
+
+fn attachment_reference2(
+    reference: &vk::AttachmentReference,
+    aspect_mask: vk::ImageAspectFlags,
+) -> vk::AttachmentReference2KHR {
+    vk::AttachmentReference2KHR {
+        s_type: vk::StructureType::ATTACHMENT_REFERENCE_2_KHR,
+        p_next: ptr::null(),
+        attachment: reference.attachment,
+        layout: reference.layout,
+        aspect_mask,
+    }
+}
+
+pub fn create_framebuffers(
+    device: &ash::Device,
+    render_pass: vk::RenderPass,
+    swapchain_image_views: &[vk::ImageView],
+    msaa_attachments: &MsaaAttachments,
+    swapchain_extent: vk::Extent2D,
+) -> VkTutorialResult<Vec<vk::Framebuffer>> {
+    let mut framebuffers = vec![];
+
+    for &image_view in swapchain_image_views.iter() {
+        let attachments = msaa_attachments.framebuffer_attachments(image_view);
+
+        let framebuffer_create_info = vk::FramebufferCreateInfo {
+            s_type: vk::StructureType::FRAMEBUFFER_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::FramebufferCreateFlags::empty(),
+            render_pass,
+            attachment_count: attachments.len() as u32,
+            p_attachments: attachments.as_ptr(),
+            width: swapchain_extent.width,
+            height: swapchain_extent.height,
+            layers: 1,
+        };
+
+        let framebuffer = unsafe { device.create_framebuffer(&framebuffer_create_info, None)? };
+        framebuffers.push(framebuffer);
+    }
+
+    Ok(framebuffers)
+}
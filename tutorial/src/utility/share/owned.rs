@@ -0,0 +1,168 @@
+//! Same as some functions of `share` and `share::v1`, but return owned handles which destroy
+//! themselves on drop, see `utility::handles`. Tutorials 16, 29 and 30 are built with them.
+
+use ash::vk;
+
+use std::sync::Arc;
+
+use super::*;
+use crate::utility::handles::{
+    self,
+    CommandPool,
+    Device,
+    Fence,
+    Framebuffer,
+    ImageView,
+    Instance,
+    Pipeline,
+    PipelineLayout,
+    RenderPass,
+    Semaphore,
+};
+
+pub struct SyncObjects {
+    pub image_available_semaphores: Vec<Semaphore>,
+    pub render_finished_semaphores: Vec<Semaphore>,
+    pub inflight_fences: Vec<Fence>,
+}
+
+pub fn create_instance(
+    entry: &ash::Entry,
+    window_title: &str,
+    is_enable_debug: bool,
+    required_validation_layers: &Vec<&str>,
+    window: Option<&winit::window::Window>,
+) -> VkTutorialResult<Arc<Instance>> {
+    let instance = super::create_instance(
+        entry,
+        window_title,
+        is_enable_debug,
+        required_validation_layers,
+        window,
+    )?;
+
+    Ok(Instance::new(entry, instance))
+}
+
+pub fn create_logical_device(
+    instance: &Arc<Instance>,
+    physical_device: vk::PhysicalDevice,
+    validation: &debug::ValidationInfo,
+    device_extensions: &DeviceExtension,
+    surface_stuff: &SurfaceStuff,
+) -> VkTutorialResult<(Arc<Device>, QueueFamilyIndices)> {
+    create_logical_device_with_extensions(
+        instance,
+        physical_device,
+        validation,
+        device_extensions,
+        &[],
+        surface_stuff,
+    )
+}
+
+pub fn create_logical_device_with_extensions(
+    instance: &Arc<Instance>,
+    physical_device: vk::PhysicalDevice,
+    validation: &debug::ValidationInfo,
+    device_extensions: &DeviceExtension,
+    extra_extensions: &[&str],
+    surface_stuff: &SurfaceStuff,
+) -> VkTutorialResult<(Arc<Device>, QueueFamilyIndices)> {
+    let (device, queue_family) = super::create_logical_device_with_extensions(
+        instance,
+        physical_device,
+        validation,
+        device_extensions,
+        extra_extensions,
+        surface_stuff,
+    )?;
+
+    Ok((Device::new(instance, device), queue_family))
+}
+
+pub fn create_image_views(
+    device: &Arc<Device>,
+    surface_format: vk::Format,
+    images: &Vec<vk::Image>,
+) -> VkTutorialResult<Vec<ImageView>> {
+    let image_views = v1::create_image_views(device, surface_format, images)?;
+
+    Ok(ImageView::from_raws(device, image_views))
+}
+
+pub fn create_render_pass(
+    device: &Arc<Device>,
+    surface_format: vk::Format,
+) -> VkTutorialResult<RenderPass> {
+    let render_pass = v1::create_render_pass(device, surface_format)?;
+
+    Ok(RenderPass::new(device, render_pass))
+}
+
+pub fn create_graphics_pipeline_from_code(
+    device: &Arc<Device>,
+    render_pass: &RenderPass,
+    swapchain_extent: vk::Extent2D,
+    vert_shader_code: &[u8],
+    frag_shader_code: &[u8],
+    pipeline_cache: vk::PipelineCache,
+) -> VkTutorialResult<(Pipeline, PipelineLayout)> {
+    let (graphics_pipeline, pipeline_layout) = v1::create_graphics_pipeline_from_code(
+        device,
+        render_pass.raw(),
+        swapchain_extent,
+        vert_shader_code,
+        frag_shader_code,
+        pipeline_cache,
+    )?;
+
+    Ok((
+        Pipeline::new(device, graphics_pipeline),
+        PipelineLayout::new(device, pipeline_layout),
+    ))
+}
+
+pub fn create_framebuffers(
+    device: &Arc<Device>,
+    render_pass: &RenderPass,
+    image_views: &[ImageView],
+    swapchain_extent: vk::Extent2D,
+) -> VkTutorialResult<Vec<Framebuffer>> {
+    let framebuffers = v1::create_framebuffers(
+        device,
+        render_pass.raw(),
+        &handles::raw_handles(image_views),
+        swapchain_extent,
+    )?;
+
+    Ok(Framebuffer::from_raws(device, framebuffers))
+}
+
+pub fn create_command_pool(
+    device: &Arc<Device>,
+    queue_families: &QueueFamilyIndices,
+) -> VkTutorialResult<CommandPool> {
+    let command_pool = v1::create_command_pool(device, queue_families)?;
+
+    Ok(CommandPool::new(device, command_pool))
+}
+
+pub fn create_sync_objects(
+    device: &Arc<Device>,
+    max_frame_in_flight: usize,
+) -> VkTutorialResult<SyncObjects> {
+    let sync_objects = v1::create_sync_objects(device, max_frame_in_flight)?;
+
+    Ok(SyncObjects {
+        image_available_semaphores: Semaphore::from_raws(
+            device,
+            sync_objects.image_available_semaphores,
+        ),
+        render_finished_semaphores: Semaphore::from_raws(
+            device,
+            sync_objects.render_finished_semaphores,
+        ),
+        inflight_fences: Fence::from_raws(device, sync_objects.inflight_fences),
+    })
+}
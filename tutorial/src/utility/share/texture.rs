@@ -0,0 +1,215 @@
+//! Upload the mip chains of the textures, compressed or not, into sampled images.
+
+use ash::version::DeviceV1_0;
+use ash::vk;
+
+use std::path::Path;
+
+use crate::utility::error::VkTutorialResult;
+use crate::utility::share;
+use crate::utility::texture::{
+    self,
+    TextureData,
+};
+
+/// Copy `levels`, given as `(width, height, data)` from the base level, into the mip levels of `image`.
+///
+/// `image` must be in `TRANSFER_DST_OPTIMAL` layout, `data` in the layout of the format of `image`.
+pub fn copy_levels_to_image(
+    device: &ash::Device,
+    command_pool: vk::CommandPool,
+    submit_queue: vk::Queue,
+    device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    image: vk::Image,
+    levels: &[(u32, u32, &[u8])],
+) -> VkTutorialResult<()> {
+    let buffer_size: usize = levels.iter().map(|(_, _, data)| data.len()).sum();
+    let (staging_buffer, staging_buffer_memory) = share::create_buffer(
+        device,
+        buffer_size as vk::DeviceSize,
+        vk::BufferUsageFlags::TRANSFER_SRC,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        device_memory_properties,
+    )?;
+
+    let copied = write_levels(device, staging_buffer_memory, buffer_size, levels).and_then(
+        |buffer_image_regions| {
+            let command_buffer = share::begin_single_time_command(device, command_pool)?;
+            unsafe {
+                device.cmd_copy_buffer_to_image(
+                    command_buffer,
+                    staging_buffer,
+                    image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &buffer_image_regions,
+                );
+            }
+            share::end_single_time_command(device, command_pool, submit_queue, command_buffer)
+        },
+    );
+
+    share::destroy_buffer(device, staging_buffer, staging_buffer_memory);
+
+    copied
+}
+
+/// Write `levels` one after the other into `memory`, and return the regions to copy them from.
+fn write_levels(
+    device: &ash::Device,
+    memory: vk::DeviceMemory,
+    buffer_size: usize,
+    levels: &[(u32, u32, &[u8])],
+) -> VkTutorialResult<Vec<vk::BufferImageCopy>> {
+    let mut buffer_image_regions = Vec::with_capacity(levels.len());
+    unsafe {
+        let data_ptr = device.map_memory(
+            memory,
+            0,
+            buffer_size as vk::DeviceSize,
+            vk::MemoryMapFlags::empty(),
+        )? as *mut u8;
+
+        let mut buffer_offset = 0;
+        for (mip_level, &(width, height, data)) in levels.iter().enumerate() {
+            data_ptr
+                .add(buffer_offset)
+                .copy_from_nonoverlapping(data.as_ptr(), data.len());
+
+            buffer_image_regions.push(vk::BufferImageCopy {
+                image_subresource: vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level: mip_level as u32,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                },
+                image_extent: vk::Extent3D {
+                    width,
+                    height,
+                    depth: 1,
+                },
+                buffer_offset: buffer_offset as vk::DeviceSize,
+                buffer_image_height: 0,
+                buffer_row_length: 0,
+                image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+            });
+            buffer_offset += data.len();
+        }
+
+        device.unmap_memory(memory);
+    }
+
+    Ok(buffer_image_regions)
+}
+
+/// Create a sampled image from the KTX2 or DDS file at `texture_path`, with the mip chain of the file.
+///
+/// The levels are uploaded as they are stored when the device can sample their format,
+/// and decoded on the CPU otherwise. Return the image, its memory, its format and its count of mip levels.
+pub fn create_compressed_texture_image(
+    instance: &ash::Instance,
+    device: &ash::Device,
+    physical_device: vk::PhysicalDevice,
+    command_pool: vk::CommandPool,
+    submit_queue: vk::Queue,
+    device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    texture_path: &Path,
+) -> VkTutorialResult<(vk::Image, vk::DeviceMemory, vk::Format, u32)> {
+    let texture_data = texture::load_texture(texture_path)?;
+    let texture_data = match share::find_supported_format(
+        instance,
+        physical_device,
+        &[texture_data.format],
+        vk::ImageTiling::OPTIMAL,
+        vk::FormatFeatureFlags::SAMPLED_IMAGE,
+    ) {
+        Ok(_) => texture_data,
+        // report the unsupported format if it can not be decoded either.
+        Err(error) => texture_data.decompress().map_err(|_| error)?,
+    };
+
+    create_texture_image_from_data(
+        device,
+        command_pool,
+        submit_queue,
+        device_memory_properties,
+        &texture_data,
+    )
+}
+
+pub fn create_texture_image_from_data(
+    device: &ash::Device,
+    command_pool: vk::CommandPool,
+    submit_queue: vk::Queue,
+    device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    texture_data: &TextureData,
+) -> VkTutorialResult<(vk::Image, vk::DeviceMemory, vk::Format, u32)> {
+    let mip_levels = texture_data.mip_levels();
+
+    let (texture_image, texture_image_memory) = share::v1::create_image(
+        device,
+        texture_data.width,
+        texture_data.height,
+        mip_levels,
+        vk::SampleCountFlags::TYPE_1,
+        texture_data.format,
+        vk::ImageTiling::OPTIMAL,
+        vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        device_memory_properties,
+    )?;
+
+    let levels: Vec<(u32, u32, &[u8])> = texture_data
+        .levels
+        .iter()
+        .enumerate()
+        .map(|(mip_level, level)| {
+            let (width, height) = texture_data.level_extent(mip_level as u32);
+            (width, height, level.as_slice())
+        })
+        .collect();
+
+    let uploaded = share::v1::transition_image_layout(
+        device,
+        command_pool,
+        submit_queue,
+        texture_image,
+        texture_data.format,
+        vk::ImageLayout::UNDEFINED,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        mip_levels,
+    )
+    .and_then(|()| {
+        copy_levels_to_image(
+            device,
+            command_pool,
+            submit_queue,
+            device_memory_properties,
+            texture_image,
+            &levels,
+        )
+    })
+    .and_then(|()| {
+        share::v1::transition_image_layout(
+            device,
+            command_pool,
+            submit_queue,
+            texture_image,
+            texture_data.format,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            mip_levels,
+        )
+    });
+
+    if let Err(error) = uploaded {
+        share::destroy_image(device, texture_image, texture_image_memory);
+        return Err(error);
+    }
+
+    Ok((
+        texture_image,
+        texture_image_memory,
+        texture_data.format,
+        mip_levels,
+    ))
+}
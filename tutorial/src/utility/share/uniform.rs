@@ -0,0 +1,128 @@
+//! Write the uniforms of every frame into one persistently mapped buffer, bound with dynamic offsets.
+//!
+//! Each frame in flight owns `slots_per_frame` slots of the buffer, aligned to
+//! `minUniformBufferOffsetAlignment`. The uniforms of a frame are written while the GPU still reads
+//! the slots of the other frames, and the descriptor set stays the same for all the frames:
+//! only the dynamic offset returned by `push` changes.
+
+use ash::version::InstanceV1_0;
+use ash::vk;
+
+use std::cmp::max;
+use std::marker::PhantomData;
+
+use crate::utility::allocator::{
+    Allocation,
+    MemoryAllocator,
+};
+use crate::utility::error::{
+    VkTutorialError,
+    VkTutorialResult,
+};
+
+pub struct UniformRing<T> {
+    pub buffer: vk::Buffer,
+    /// Sub-allocated from host visible memory, which stays mapped.
+    allocation: Allocation,
+    mapped_ptr: *mut u8,
+    slot_size: vk::DeviceSize,
+    slots_per_frame: u32,
+    frames_in_flight: usize,
+    frame_index: usize,
+    /// The count of slots of the current frame already written.
+    used_slots: u32,
+    _uniform: PhantomData<T>,
+}
+
+impl<T: Copy> UniformRing<T> {
+    /// Create a ring holding `slots_per_frame` uniforms of type `T` for each of the `frames_in_flight`.
+    pub fn new(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        allocator: &mut MemoryAllocator,
+        frames_in_flight: usize,
+        slots_per_frame: u32,
+    ) -> VkTutorialResult<UniformRing<T>> {
+        let limits = unsafe {
+            instance
+                .get_physical_device_properties(physical_device)
+                .limits
+        };
+        // both alignments are powers of two.
+        let alignment = max(
+            limits.min_uniform_buffer_offset_alignment,
+            ::std::mem::align_of::<T>() as vk::DeviceSize,
+        );
+        let slot_size =
+            (::std::mem::size_of::<T>() as vk::DeviceSize + alignment - 1) & !(alignment - 1);
+        let buffer_size = slot_size * slots_per_frame as vk::DeviceSize * frames_in_flight as u64;
+
+        let (buffer, allocation) = allocator.create_buffer(
+            buffer_size,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+
+        Ok(UniformRing {
+            buffer,
+            allocation,
+            mapped_ptr: allocation.mapped_ptr,
+            slot_size,
+            slots_per_frame,
+            frames_in_flight,
+            frame_index: 0,
+            used_slots: 0,
+            _uniform: PhantomData,
+        })
+    }
+
+    /// The distance between two slots, in bytes.
+    pub fn slot_size(&self) -> vk::DeviceSize {
+        self.slot_size
+    }
+
+    /// Start writing the uniforms of `frame_index`, which reuses the slots of its previous use.
+    ///
+    /// The fence of the previous submission of this frame must have been waited for.
+    pub fn begin_frame(&mut self, frame_index: usize) {
+        assert!(frame_index < self.frames_in_flight);
+
+        self.frame_index = frame_index;
+        self.used_slots = 0;
+    }
+
+    /// Write `uniform` into the next slot of the current frame, and return its dynamic offset.
+    pub fn push(&mut self, uniform: &T) -> VkTutorialResult<u32> {
+        if self.used_slots == self.slots_per_frame {
+            return Err(VkTutorialError::UniformRingFull {
+                slots_per_frame: self.slots_per_frame,
+            });
+        }
+
+        let slot = self.frame_index as u64 * self.slots_per_frame as u64 + self.used_slots as u64;
+        let offset = slot * self.slot_size;
+        unsafe {
+            let slot_ptr = self.mapped_ptr.add(offset as usize) as *mut T;
+            slot_ptr.copy_from_nonoverlapping(uniform, 1);
+        }
+        self.used_slots += 1;
+
+        Ok(offset as u32)
+    }
+
+    /// The descriptor of one slot, written to a `UNIFORM_BUFFER_DYNAMIC` binding.
+    pub fn descriptor_buffer_info(&self) -> vk::DescriptorBufferInfo {
+        vk::DescriptorBufferInfo {
+            buffer: self.buffer,
+            offset: 0,
+            range: ::std::mem::size_of::<T>() as vk::DeviceSize,
+        }
+    }
+
+    pub unsafe fn destroy(&mut self, allocator: &mut MemoryAllocator) {
+        if let Err(error) = allocator.destroy_buffer(self.buffer, self.allocation) {
+            eprintln!("Failed to free the uniform ring: {}", error);
+        }
+        self.mapped_ptr = ::std::ptr::null_mut();
+    }
+}
@@ -0,0 +1,757 @@
+//! Upload buffers and images without waiting for the queue to be idle.
+//!
+//! The data is written into a persistently mapped ring staging buffer, and the copies requested
+//! during a frame are recorded into one command buffer which `flush` submits with a fence.
+//! Every request returns an `UploadHandle`, which is resident once the fence of its batch signals.
+//! With a dedicated transfer family the batch releases the resources, and a command buffer
+//! waiting on a semaphore acquires them on the graphics queue.
+
+use ash::version::DeviceV1_0;
+use ash::vk;
+
+use std::collections::VecDeque;
+use std::ptr;
+
+use crate::utility::allocator::{
+    Allocation,
+    MemoryAllocator,
+};
+use crate::utility::error::{
+    VkTutorialError,
+    VkTutorialResult,
+};
+use crate::utility::share;
+use crate::utility::structures::QueueFamilyIndices;
+
+/// The offsets in the staging buffer are aligned for any texel or block size.
+const STAGING_ALIGNMENT: vk::DeviceSize = 16;
+
+/// Tell whether an upload is done, see `UploadManager::is_resident`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UploadHandle(u64);
+
+/// The copies submitted together, and what to release once they are done.
+struct UploadBatch {
+    fence: vk::Fence,
+    command_buffer: vk::CommandBuffer,
+    acquire_command_buffer: Option<vk::CommandBuffer>,
+    semaphore: Option<vk::Semaphore>,
+    /// The staging buffers of the uploads which did not fit in the ring.
+    dedicated_staging_buffers: Vec<(vk::Buffer, vk::DeviceMemory)>,
+    /// The ring can be reused up to this offset once the batch is done.
+    ring_end: vk::DeviceSize,
+    last_handle: UploadHandle,
+}
+
+/// The batch being recorded, submitted by the next `flush`.
+struct PendingBatch {
+    command_buffer: vk::CommandBuffer,
+    buffer_acquire_barriers: Vec<vk::BufferMemoryBarrier>,
+    image_acquire_barriers: Vec<vk::ImageMemoryBarrier>,
+    acquire_stage_mask: vk::PipelineStageFlags,
+    dedicated_staging_buffers: Vec<(vk::Buffer, vk::DeviceMemory)>,
+}
+
+pub struct UploadManager {
+    device: ash::Device,
+    device_memory_properties: vk::PhysicalDeviceMemoryProperties,
+
+    transfer_family: u32,
+    transfer_queue: vk::Queue,
+    transfer_command_pool: vk::CommandPool,
+    graphics_family: u32,
+    graphics_queue: vk::Queue,
+    graphics_command_pool: vk::CommandPool,
+
+    staging_buffer: vk::Buffer,
+    staging_buffer_memory: vk::DeviceMemory,
+    staging_ptr: *mut u8,
+    staging_size: vk::DeviceSize,
+    /// The data of the batches not done yet is in `ring_tail..ring_head`, wrapping around the end.
+    ring_head: vk::DeviceSize,
+    ring_tail: vk::DeviceSize,
+
+    pending: Option<PendingBatch>,
+    in_flight: VecDeque<UploadBatch>,
+    next_handle: u64,
+    /// Every upload before this one is resident.
+    resident_until: u64,
+}
+
+impl UploadManager {
+    /// `staging_size` is the size of the ring, a bigger upload gets its own staging buffer.
+    pub fn new(
+        device: &ash::Device,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        queue_family: &QueueFamilyIndices,
+        graphics_queue: vk::Queue,
+        staging_size: vk::DeviceSize,
+    ) -> VkTutorialResult<UploadManager> {
+        let graphics_family = queue_family.graphics_family.unwrap();
+        let transfer_family = queue_family.transfer_family.unwrap_or(graphics_family);
+        let transfer_queue = unsafe { device.get_device_queue(transfer_family, 0) };
+
+        let transfer_command_pool = create_command_pool(device, transfer_family)?;
+        let graphics_command_pool = if transfer_family != graphics_family {
+            create_command_pool(device, graphics_family)?
+        } else {
+            transfer_command_pool
+        };
+
+        let (staging_buffer, staging_buffer_memory) = share::create_buffer(
+            device,
+            staging_size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            device_memory_properties,
+        )?;
+        let staging_ptr = unsafe {
+            device.map_memory(
+                staging_buffer_memory,
+                0,
+                staging_size,
+                vk::MemoryMapFlags::empty(),
+            )? as *mut u8
+        };
+
+        Ok(UploadManager {
+            device: device.clone(),
+            device_memory_properties: *device_memory_properties,
+
+            transfer_family,
+            transfer_queue,
+            transfer_command_pool,
+            graphics_family,
+            graphics_queue,
+            graphics_command_pool,
+
+            staging_buffer,
+            staging_buffer_memory,
+            staging_ptr,
+            staging_size,
+            ring_head: 0,
+            ring_tail: 0,
+
+            pending: None,
+            in_flight: VecDeque::new(),
+            next_handle: 0,
+            resident_until: 0,
+        })
+    }
+
+    pub fn is_dedicated(&self) -> bool {
+        self.transfer_family != self.graphics_family
+    }
+
+    /// Create a device local buffer with `usage` from `allocator`, and request the upload of `data` into it.
+    pub fn create_device_local_buffer<T>(
+        &mut self,
+        allocator: &mut MemoryAllocator,
+        usage: vk::BufferUsageFlags,
+        dst_stage_mask: vk::PipelineStageFlags,
+        dst_access_mask: vk::AccessFlags,
+        data: &[T],
+    ) -> VkTutorialResult<(vk::Buffer, Allocation, UploadHandle)> {
+        let buffer_size = ::std::mem::size_of_val(data) as vk::DeviceSize;
+        if buffer_size == 0 {
+            return Err(VkTutorialError::EmptyUpload);
+        }
+        let (buffer, allocation) = allocator.create_buffer(
+            buffer_size,
+            vk::BufferUsageFlags::TRANSFER_DST | usage,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        match self.upload_buffer(data, buffer, 0, dst_stage_mask, dst_access_mask) {
+            Ok(upload_handle) => Ok((buffer, allocation, upload_handle)),
+            Err(error) => {
+                let _ = allocator.destroy_buffer(buffer, allocation);
+                Err(error)
+            }
+        }
+    }
+
+    /// Create a sampled image of `format` from `allocator`, with one mip level per element of `levels`,
+    /// and request the upload of `levels` into it, see `upload_image`.
+    pub fn create_texture_image(
+        &mut self,
+        allocator: &mut MemoryAllocator,
+        format: vk::Format,
+        levels: &[(u32, u32, &[u8])],
+    ) -> VkTutorialResult<(vk::Image, Allocation, UploadHandle)> {
+        let (width, height, _) = *levels.first().ok_or(VkTutorialError::EmptyUpload)?;
+        let mip_levels = levels.len() as u32;
+        let (image, allocation) = allocator.create_image(
+            width,
+            height,
+            mip_levels,
+            vk::SampleCountFlags::TYPE_1,
+            format,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        match self.upload_image(levels, image, mip_levels) {
+            Ok(upload_handle) => Ok((image, allocation, upload_handle)),
+            Err(error) => {
+                let _ = allocator.destroy_image(image, allocation);
+                Err(error)
+            }
+        }
+    }
+
+    /// Request the copy of `data` into `dst_buffer` at `dst_offset`, to be read at `dst_stage_mask`.
+    pub fn upload_buffer<T>(
+        &mut self,
+        data: &[T],
+        dst_buffer: vk::Buffer,
+        dst_offset: vk::DeviceSize,
+        dst_stage_mask: vk::PipelineStageFlags,
+        dst_access_mask: vk::AccessFlags,
+    ) -> VkTutorialResult<UploadHandle> {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(data.as_ptr() as *const u8, ::std::mem::size_of_val(data))
+        };
+        if bytes.is_empty() {
+            return Err(VkTutorialError::EmptyUpload);
+        }
+        let (src_buffer, src_offset) = self.stage(&[bytes])?;
+
+        let mut buffer_barrier = vk::BufferMemoryBarrier {
+            s_type: vk::StructureType::BUFFER_MEMORY_BARRIER,
+            p_next: ptr::null(),
+            src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            dst_access_mask,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            buffer: dst_buffer,
+            offset: dst_offset,
+            size: bytes.len() as vk::DeviceSize,
+        };
+        if self.is_dedicated() {
+            buffer_barrier.src_queue_family_index = self.transfer_family;
+            buffer_barrier.dst_queue_family_index = self.graphics_family;
+        }
+
+        let is_dedicated = self.is_dedicated();
+        let command_buffer = self.pending_batch()?.command_buffer;
+        let copy_regions = [vk::BufferCopy {
+            src_offset,
+            dst_offset,
+            size: bytes.len() as vk::DeviceSize,
+        }];
+        unsafe {
+            self.device
+                .cmd_copy_buffer(command_buffer, src_buffer, dst_buffer, &copy_regions);
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                if is_dedicated {
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE
+                } else {
+                    dst_stage_mask
+                },
+                vk::DependencyFlags::empty(),
+                &[],
+                &[buffer_barrier],
+                &[],
+            );
+        }
+        let pending = self.pending_batch()?;
+        pending.buffer_acquire_barriers.push(buffer_barrier);
+        pending.acquire_stage_mask |= dst_stage_mask;
+
+        Ok(self.next_upload_handle())
+    }
+
+    /// Request the copy of `levels`, given as `(width, height, data)` from the base level,
+    /// into `image`, which is left in `SHADER_READ_ONLY_OPTIMAL` layout for the fragment shader.
+    pub fn upload_image(
+        &mut self,
+        levels: &[(u32, u32, &[u8])],
+        image: vk::Image,
+        mip_levels: u32,
+    ) -> VkTutorialResult<UploadHandle> {
+        if levels.is_empty() || levels.iter().any(|&(_, _, data)| data.is_empty()) {
+            return Err(VkTutorialError::EmptyUpload);
+        }
+        let level_data: Vec<&[u8]> = levels.iter().map(|&(_, _, data)| data).collect();
+        let (src_buffer, src_offset) = self.stage(&level_data)?;
+
+        let mut buffer_offset = src_offset;
+        let mut buffer_image_regions = Vec::with_capacity(levels.len());
+        for (mip_level, &(width, height, data)) in levels.iter().enumerate() {
+            buffer_image_regions.push(vk::BufferImageCopy {
+                image_subresource: vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level: mip_level as u32,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                },
+                image_extent: vk::Extent3D {
+                    width,
+                    height,
+                    depth: 1,
+                },
+                buffer_offset,
+                buffer_image_height: 0,
+                buffer_row_length: 0,
+                image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+            });
+            buffer_offset += align(data.len() as vk::DeviceSize);
+        }
+
+        let transfer_dst_barrier = vk::ImageMemoryBarrier {
+            s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
+            p_next: ptr::null(),
+            src_access_mask: vk::AccessFlags::empty(),
+            dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            old_layout: vk::ImageLayout::UNDEFINED,
+            new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            image,
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: mip_levels,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+        };
+        let mut shader_read_barrier = vk::ImageMemoryBarrier {
+            src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            dst_access_mask: vk::AccessFlags::SHADER_READ,
+            old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ..transfer_dst_barrier
+        };
+        if self.is_dedicated() {
+            shader_read_barrier.src_queue_family_index = self.transfer_family;
+            shader_read_barrier.dst_queue_family_index = self.graphics_family;
+        }
+
+        let is_dedicated = self.is_dedicated();
+        let command_buffer = self.pending_batch()?.command_buffer;
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[transfer_dst_barrier],
+            );
+            self.device.cmd_copy_buffer_to_image(
+                command_buffer,
+                src_buffer,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &buffer_image_regions,
+            );
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                if is_dedicated {
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE
+                } else {
+                    vk::PipelineStageFlags::FRAGMENT_SHADER
+                },
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[shader_read_barrier],
+            );
+        }
+        let pending = self.pending_batch()?;
+        pending.image_acquire_barriers.push(shader_read_barrier);
+        pending.acquire_stage_mask |= vk::PipelineStageFlags::FRAGMENT_SHADER;
+
+        Ok(self.next_upload_handle())
+    }
+
+    /// Submit the uploads requested since the last call, usually once per frame.
+    pub fn flush(&mut self) -> VkTutorialResult<()> {
+        let pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+
+        let fence_create_info = vk::FenceCreateInfo {
+            s_type: vk::StructureType::FENCE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::FenceCreateFlags::empty(),
+        };
+        let fence = unsafe { self.device.create_fence(&fence_create_info, None)? };
+
+        unsafe {
+            self.device.end_command_buffer(pending.command_buffer)?;
+        }
+
+        let (semaphore, acquire_command_buffer) = if self.is_dedicated() {
+            let semaphore_create_info = vk::SemaphoreCreateInfo {
+                s_type: vk::StructureType::SEMAPHORE_CREATE_INFO,
+                p_next: ptr::null(),
+                flags: vk::SemaphoreCreateFlags::empty(),
+            };
+            let semaphore = unsafe { self.device.create_semaphore(&semaphore_create_info, None)? };
+
+            let acquire_command_buffer =
+                begin_command_buffer(&self.device, self.graphics_command_pool)?;
+            unsafe {
+                self.device.cmd_pipeline_barrier(
+                    acquire_command_buffer,
+                    vk::PipelineStageFlags::TOP_OF_PIPE,
+                    pending.acquire_stage_mask,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &pending.buffer_acquire_barriers,
+                    &pending.image_acquire_barriers,
+                );
+                self.device.end_command_buffer(acquire_command_buffer)?;
+            }
+
+            (Some(semaphore), Some(acquire_command_buffer))
+        } else {
+            (None, None)
+        };
+
+        let command_buffers = [pending.command_buffer];
+        let signal_semaphores: Vec<vk::Semaphore> = semaphore.into_iter().collect();
+        let submit_infos = [vk::SubmitInfo {
+            s_type: vk::StructureType::SUBMIT_INFO,
+            p_next: ptr::null(),
+            wait_semaphore_count: 0,
+            p_wait_semaphores: ptr::null(),
+            p_wait_dst_stage_mask: ptr::null(),
+            command_buffer_count: command_buffers.len() as u32,
+            p_command_buffers: command_buffers.as_ptr(),
+            signal_semaphore_count: signal_semaphores.len() as u32,
+            p_signal_semaphores: signal_semaphores.as_ptr(),
+        }];
+
+        match acquire_command_buffer {
+            Some(acquire_command_buffer) => unsafe {
+                self.device
+                    .queue_submit(self.transfer_queue, &submit_infos, vk::Fence::null())?;
+
+                let acquire_command_buffers = [acquire_command_buffer];
+                let wait_stages = [pending.acquire_stage_mask];
+                let acquire_submit_infos = [vk::SubmitInfo {
+                    s_type: vk::StructureType::SUBMIT_INFO,
+                    p_next: ptr::null(),
+                    wait_semaphore_count: signal_semaphores.len() as u32,
+                    p_wait_semaphores: signal_semaphores.as_ptr(),
+                    p_wait_dst_stage_mask: wait_stages.as_ptr(),
+                    command_buffer_count: acquire_command_buffers.len() as u32,
+                    p_command_buffers: acquire_command_buffers.as_ptr(),
+                    signal_semaphore_count: 0,
+                    p_signal_semaphores: ptr::null(),
+                }];
+                self.device
+                    .queue_submit(self.graphics_queue, &acquire_submit_infos, fence)?;
+            },
+            None => unsafe {
+                self.device
+                    .queue_submit(self.transfer_queue, &submit_infos, fence)?;
+            },
+        }
+
+        self.in_flight.push_back(UploadBatch {
+            fence,
+            command_buffer: pending.command_buffer,
+            acquire_command_buffer,
+            semaphore,
+            dedicated_staging_buffers: pending.dedicated_staging_buffers,
+            ring_end: self.ring_head,
+            last_handle: UploadHandle(self.next_handle - 1),
+        });
+
+        Ok(())
+    }
+
+    /// Release the batches which are done, without waiting for the others.
+    pub fn poll(&mut self) -> VkTutorialResult<()> {
+        while let Some(batch) = self.in_flight.front() {
+            match unsafe { self.device.get_fence_status(batch.fence) } {
+                Ok(()) => self.retire_oldest_batch(),
+                Err(vk::Result::NOT_READY) => break,
+                Err(vk_result) => return Err(vk_result.into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the upload of `upload_handle` is done, as of the last `poll` or `wait`.
+    pub fn is_resident(&self, upload_handle: UploadHandle) -> bool {
+        upload_handle.0 < self.resident_until
+    }
+
+    /// Block until the upload of `upload_handle` is done, flushing it if needed.
+    pub fn wait(&mut self, upload_handle: UploadHandle) -> VkTutorialResult<()> {
+        if self.is_resident(upload_handle) {
+            return Ok(());
+        }
+        let is_submitted = match self.in_flight.back() {
+            Some(batch) => upload_handle <= batch.last_handle,
+            None => false,
+        };
+        if !is_submitted {
+            self.flush()?;
+        }
+
+        while !self.is_resident(upload_handle) && !self.in_flight.is_empty() {
+            self.wait_oldest_batch()?;
+        }
+
+        Ok(())
+    }
+
+    /// Submit the pending uploads and wait until all of them are done.
+    pub fn wait_idle(&mut self) -> VkTutorialResult<()> {
+        self.flush()?;
+        while !self.in_flight.is_empty() {
+            self.wait_oldest_batch()?;
+        }
+
+        Ok(())
+    }
+
+    /// Wait for the uploads, then release the staging memory and the batches.
+    ///
+    /// A failed wait (e.g. a lost device) is reported, and the batches are released anyway.
+    pub unsafe fn destroy(&mut self) {
+        if let Err(error) = self.wait_idle() {
+            eprintln!("Failed to wait for the uploads: {}", error);
+            let _ = self.device.device_wait_idle();
+        }
+        while !self.in_flight.is_empty() {
+            self.retire_oldest_batch();
+        }
+        // the command buffer of a batch which failed to be submitted is freed with its pool.
+        if let Some(pending) = self.pending.take() {
+            for (buffer, buffer_memory) in pending.dedicated_staging_buffers {
+                self.device.destroy_buffer(buffer, None);
+                self.device.free_memory(buffer_memory, None);
+            }
+        }
+
+        self.device.unmap_memory(self.staging_buffer_memory);
+        self.device.destroy_buffer(self.staging_buffer, None);
+        self.device.free_memory(self.staging_buffer_memory, None);
+
+        if self.is_dedicated() {
+            self.device
+                .destroy_command_pool(self.graphics_command_pool, None);
+        }
+        self.device
+            .destroy_command_pool(self.transfer_command_pool, None);
+    }
+
+    /// Copy `chunks` one after the other in the staging memory, each one aligned.
+    /// Return the staging buffer and the offset of the first chunk.
+    fn stage(&mut self, chunks: &[&[u8]]) -> VkTutorialResult<(vk::Buffer, vk::DeviceSize)> {
+        let size: vk::DeviceSize = chunks
+            .iter()
+            .map(|chunk| align(chunk.len() as vk::DeviceSize))
+            .sum();
+
+        if size > self.staging_size {
+            return self.stage_dedicated(chunks, size);
+        }
+
+        let offset = loop {
+            if let Some(offset) = self.allocate_ring(size) {
+                break offset;
+            }
+            // the ring is full, wait for the oldest uploads to give their space back.
+            if self.in_flight.is_empty() {
+                self.flush()?;
+            }
+            self.wait_oldest_batch()?;
+        };
+
+        let mut chunk_offset = offset;
+        for chunk in chunks.iter() {
+            unsafe {
+                self.staging_ptr
+                    .add(chunk_offset as usize)
+                    .copy_from_nonoverlapping(chunk.as_ptr(), chunk.len());
+            }
+            chunk_offset += align(chunk.len() as vk::DeviceSize);
+        }
+
+        Ok((self.staging_buffer, offset))
+    }
+
+    /// Stage an upload bigger than the ring in a buffer of its own, freed with its batch.
+    fn stage_dedicated(
+        &mut self,
+        chunks: &[&[u8]],
+        size: vk::DeviceSize,
+    ) -> VkTutorialResult<(vk::Buffer, vk::DeviceSize)> {
+        let (buffer, buffer_memory) = share::create_buffer(
+            &self.device,
+            size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            &self.device_memory_properties,
+        )?;
+
+        unsafe {
+            let data_ptr =
+                self.device
+                    .map_memory(buffer_memory, 0, size, vk::MemoryMapFlags::empty())?
+                    as *mut u8;
+
+            let mut chunk_offset = 0;
+            for chunk in chunks.iter() {
+                data_ptr
+                    .add(chunk_offset)
+                    .copy_from_nonoverlapping(chunk.as_ptr(), chunk.len());
+                chunk_offset += align(chunk.len() as vk::DeviceSize) as usize;
+            }
+
+            self.device.unmap_memory(buffer_memory);
+        }
+
+        self.pending_batch()?
+            .dedicated_staging_buffers
+            .push((buffer, buffer_memory));
+
+        Ok((buffer, 0))
+    }
+
+    /// Reserve `size` bytes of the ring, or return `None` if the free space is too small.
+    fn allocate_ring(&mut self, size: vk::DeviceSize) -> Option<vk::DeviceSize> {
+        if self.in_flight.is_empty() && self.pending.is_none() {
+            self.ring_head = 0;
+            self.ring_tail = 0;
+        }
+
+        // the head never catches up with the tail, so that an empty ring is told from a full one.
+        let offset = if self.ring_head >= self.ring_tail {
+            if self.ring_head + size <= self.staging_size {
+                self.ring_head
+            } else if size < self.ring_tail {
+                0
+            } else {
+                return None;
+            }
+        } else if self.ring_head + size < self.ring_tail {
+            self.ring_head
+        } else {
+            return None;
+        };
+
+        self.ring_head = offset + size;
+        Some(offset)
+    }
+
+    fn pending_batch(&mut self) -> VkTutorialResult<&mut PendingBatch> {
+        if self.pending.is_none() {
+            let command_buffer = begin_command_buffer(&self.device, self.transfer_command_pool)?;
+            self.pending = Some(PendingBatch {
+                command_buffer,
+                buffer_acquire_barriers: vec![],
+                image_acquire_barriers: vec![],
+                acquire_stage_mask: vk::PipelineStageFlags::empty(),
+                dedicated_staging_buffers: vec![],
+            });
+        }
+
+        Ok(self.pending.as_mut().unwrap())
+    }
+
+    fn next_upload_handle(&mut self) -> UploadHandle {
+        self.next_handle += 1;
+        UploadHandle(self.next_handle - 1)
+    }
+
+    fn wait_oldest_batch(&mut self) -> VkTutorialResult<()> {
+        if let Some(batch) = self.in_flight.front() {
+            unsafe {
+                self.device
+                    .wait_for_fences(&[batch.fence], true, std::u64::MAX)?;
+            }
+            self.retire_oldest_batch();
+        }
+
+        Ok(())
+    }
+
+    fn retire_oldest_batch(&mut self) {
+        let batch = match self.in_flight.pop_front() {
+            Some(batch) => batch,
+            None => return,
+        };
+
+        unsafe {
+            self.device.destroy_fence(batch.fence, None);
+            self.device
+                .free_command_buffers(self.transfer_command_pool, &[batch.command_buffer]);
+            if let Some(acquire_command_buffer) = batch.acquire_command_buffer {
+                self.device
+                    .free_command_buffers(self.graphics_command_pool, &[acquire_command_buffer]);
+            }
+            if let Some(semaphore) = batch.semaphore {
+                self.device.destroy_semaphore(semaphore, None);
+            }
+            for (buffer, buffer_memory) in batch.dedicated_staging_buffers {
+                self.device.destroy_buffer(buffer, None);
+                self.device.free_memory(buffer_memory, None);
+            }
+        }
+
+        self.ring_tail = batch.ring_end;
+        self.resident_until = batch.last_handle.0 + 1;
+    }
+}
+
+fn align(size: vk::DeviceSize) -> vk::DeviceSize {
+    (size + STAGING_ALIGNMENT - 1) / STAGING_ALIGNMENT * STAGING_ALIGNMENT
+}
+
+fn create_command_pool(
+    device: &ash::Device,
+    queue_family_index: u32,
+) -> VkTutorialResult<vk::CommandPool> {
+    let command_pool_create_info = vk::CommandPoolCreateInfo {
+        s_type: vk::StructureType::COMMAND_POOL_CREATE_INFO,
+        p_next: ptr::null(),
+        flags: vk::CommandPoolCreateFlags::TRANSIENT,
+        queue_family_index,
+    };
+
+    Ok(unsafe { device.create_command_pool(&command_pool_create_info, None)? })
+}
+
+fn begin_command_buffer(
+    device: &ash::Device,
+    command_pool: vk::CommandPool,
+) -> VkTutorialResult<vk::CommandBuffer> {
+    let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
+        s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+        p_next: ptr::null(),
+        command_buffer_count: 1,
+        command_pool,
+        level: vk::CommandBufferLevel::PRIMARY,
+    };
+    let command_buffer =
+        unsafe { device.allocate_command_buffers(&command_buffer_allocate_info)? }[0];
+
+    let command_buffer_begin_info = vk::CommandBufferBeginInfo {
+        s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+        p_next: ptr::null(),
+        p_inheritance_info: ptr::null(),
+        flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+    };
+    unsafe {
+        device.begin_command_buffer(command_buffer, &command_buffer_begin_info)?;
+    }
+
+    Ok(command_buffer)
+}
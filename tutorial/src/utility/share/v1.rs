@@ -8,8 +8,15 @@ use std::path::Path;
 use std::ptr;
 
 use super::*;
+use crate::utility::sampler::{
+    self,
+    SamplerDescription,
+};
 
-pub fn create_render_pass(device: &ash::Device, surface_format: vk::Format) -> vk::RenderPass {
+pub fn create_render_pass(
+    device: &ash::Device,
+    surface_format: vk::Format,
+) -> VkTutorialResult<vk::RenderPass> {
     let color_attachment = vk::AttachmentDescription {
         format: surface_format,
         flags: vk::AttachmentDescriptionFlags::empty(),
@@ -64,27 +71,72 @@ pub fn create_render_pass(device: &ash::Device, surface_format: vk::Format) -> v
         p_dependencies: subpass_dependencies.as_ptr(),
     };
 
-    unsafe {
-        device
-            .create_render_pass(&renderpass_create_info, None)
-            .expect("Failed to create render pass!")
-    }
+    Ok(unsafe { device.create_render_pass(&renderpass_create_info, None)? })
 }
 
 pub fn create_graphics_pipeline(
     device: &ash::Device,
     render_pass: vk::RenderPass,
     swapchain_extent: vk::Extent2D,
-) -> (vk::Pipeline, vk::PipelineLayout) {
-    let vert_shader_module = create_shader_module(
+    pipeline_cache: vk::PipelineCache,
+) -> VkTutorialResult<(vk::Pipeline, vk::PipelineLayout)> {
+    create_graphics_pipeline_from_code(
         device,
-        include_bytes!("../../../shaders/spv/09-shader-base.vert.spv").to_vec(),
-    );
-    let frag_shader_module = create_shader_module(
+        render_pass,
+        swapchain_extent,
+        include_bytes!("../../../shaders/spv/09-shader-base.vert.spv"),
+        include_bytes!("../../../shaders/spv/09-shader-base.frag.spv"),
+        pipeline_cache,
+    )
+}
+
+/// Same as `create_graphics_pipeline`, but with the SPIR-V code loaded or compiled at runtime,
+/// and the compiled pipeline looked up in `pipeline_cache` (which may be null).
+pub fn create_graphics_pipeline_from_code(
+    device: &ash::Device,
+    render_pass: vk::RenderPass,
+    swapchain_extent: vk::Extent2D,
+    vert_shader_code: &[u8],
+    frag_shader_code: &[u8],
+    pipeline_cache: vk::PipelineCache,
+) -> VkTutorialResult<(vk::Pipeline, vk::PipelineLayout)> {
+    let vert_shader_module = create_shader_module(device, vert_shader_code.to_vec())?;
+    let frag_shader_module = match create_shader_module(device, frag_shader_code.to_vec()) {
+        Ok(frag_shader_module) => frag_shader_module,
+        Err(error) => {
+            unsafe {
+                device.destroy_shader_module(vert_shader_module, None);
+            }
+            return Err(error);
+        }
+    };
+
+    let graphics_pipeline = create_graphics_pipeline_from_modules(
         device,
-        include_bytes!("../../../shaders/spv/09-shader-base.frag.spv").to_vec(),
+        render_pass,
+        swapchain_extent,
+        vert_shader_module,
+        frag_shader_module,
+        pipeline_cache,
     );
 
+    unsafe {
+        device.destroy_shader_module(vert_shader_module, None);
+        device.destroy_shader_module(frag_shader_module, None);
+    }
+
+    graphics_pipeline
+}
+
+/// The shader modules are still owned by the caller, the pipeline layout is destroyed if the pipeline can not be created.
+fn create_graphics_pipeline_from_modules(
+    device: &ash::Device,
+    render_pass: vk::RenderPass,
+    swapchain_extent: vk::Extent2D,
+    vert_shader_module: vk::ShaderModule,
+    frag_shader_module: vk::ShaderModule,
+    pipeline_cache: vk::PipelineCache,
+) -> VkTutorialResult<(vk::Pipeline, vk::PipelineLayout)> {
     let main_function_name = CString::new("main").unwrap(); // the beginning function name in shader code.
 
     let shader_stages = [
@@ -235,11 +287,8 @@ pub fn create_graphics_pipeline(
         p_push_constant_ranges: ptr::null(),
     };
 
-    let pipeline_layout = unsafe {
-        device
-            .create_pipeline_layout(&pipeline_layout_create_info, None)
-            .expect("Failed to create pipeline layout!")
-    };
+    let pipeline_layout =
+        unsafe { device.create_pipeline_layout(&pipeline_layout_create_info, None)? };
 
     let graphic_pipeline_create_infos = [vk::GraphicsPipelineCreateInfo {
         s_type: vk::StructureType::GRAPHICS_PIPELINE_CREATE_INFO,
@@ -264,21 +313,18 @@ pub fn create_graphics_pipeline(
     }];
 
     let graphics_pipelines = unsafe {
-        device
-            .create_graphics_pipelines(
-                vk::PipelineCache::null(),
-                &graphic_pipeline_create_infos,
-                None,
-            )
-            .expect("Failed to create Graphics Pipeline!.")
+        device.create_graphics_pipelines(pipeline_cache, &graphic_pipeline_create_infos, None)
     };
 
-    unsafe {
-        device.destroy_shader_module(vert_shader_module, None);
-        device.destroy_shader_module(frag_shader_module, None);
+    match graphics_pipelines {
+        Ok(graphics_pipelines) => Ok((graphics_pipelines[0], pipeline_layout)),
+        Err((_, result)) => {
+            unsafe {
+                device.destroy_pipeline_layout(pipeline_layout, None);
+            }
+            Err(VkTutorialError::from(result))
+        }
     }
-
-    (graphics_pipelines[0], pipeline_layout)
 }
 
 pub fn create_framebuffers(
@@ -286,7 +332,7 @@ pub fn create_framebuffers(
     render_pass: vk::RenderPass,
     image_views: &Vec<vk::ImageView>,
     swapchain_extent: vk::Extent2D,
-) -> Vec<vk::Framebuffer> {
+) -> VkTutorialResult<Vec<vk::Framebuffer>> {
     let mut framebuffers = vec![];
 
     for &image_view in image_views.iter() {
@@ -304,22 +350,18 @@ pub fn create_framebuffers(
             layers: 1,
         };
 
-        let framebuffer = unsafe {
-            device
-                .create_framebuffer(&framebuffer_create_info, None)
-                .expect("Failed to create Framebuffer!")
-        };
+        let framebuffer = unsafe { device.create_framebuffer(&framebuffer_create_info, None)? };
 
         framebuffers.push(framebuffer);
     }
 
-    framebuffers
+    Ok(framebuffers)
 }
 
 pub fn create_command_pool(
     device: &ash::Device,
     queue_families: &QueueFamilyIndices,
-) -> vk::CommandPool {
+) -> VkTutorialResult<vk::CommandPool> {
     let command_pool_create_info = vk::CommandPoolCreateInfo {
         s_type: vk::StructureType::COMMAND_POOL_CREATE_INFO,
         p_next: ptr::null(),
@@ -327,11 +369,7 @@ pub fn create_command_pool(
         queue_family_index: queue_families.graphics_family.unwrap(),
     };
 
-    unsafe {
-        device
-            .create_command_pool(&command_pool_create_info, None)
-            .expect("Failed to create Command Pool!")
-    }
+    Ok(unsafe { device.create_command_pool(&command_pool_create_info, None)? })
 }
 
 pub fn create_command_buffers(
@@ -341,7 +379,7 @@ pub fn create_command_buffers(
     framebuffers: &Vec<vk::Framebuffer>,
     render_pass: vk::RenderPass,
     surface_extent: vk::Extent2D,
-) -> Vec<vk::CommandBuffer> {
+) -> VkTutorialResult<Vec<vk::CommandBuffer>> {
     let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
         s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
         p_next: ptr::null(),
@@ -350,11 +388,8 @@ pub fn create_command_buffers(
         level: vk::CommandBufferLevel::PRIMARY,
     };
 
-    let command_buffers = unsafe {
-        device
-            .allocate_command_buffers(&command_buffer_allocate_info)
-            .expect("Failed to allocate Command Buffers!")
-    };
+    let command_buffers =
+        unsafe { device.allocate_command_buffers(&command_buffer_allocate_info)? };
 
     for (i, &command_buffer) in command_buffers.iter().enumerate() {
         let command_buffer_begin_info = vk::CommandBufferBeginInfo {
@@ -365,9 +400,7 @@ pub fn create_command_buffers(
         };
 
         unsafe {
-            device
-                .begin_command_buffer(command_buffer, &command_buffer_begin_info)
-                .expect("Failed to begin recording Command Buffer at beginning!");
+            device.begin_command_buffer(command_buffer, &command_buffer_begin_info)?;
         }
 
         let clear_values = [vk::ClearValue {
@@ -404,16 +437,17 @@ pub fn create_command_buffers(
 
             device.cmd_end_render_pass(command_buffer);
 
-            device
-                .end_command_buffer(command_buffer)
-                .expect("Failed to record Command Buffer at Ending!");
+            device.end_command_buffer(command_buffer)?;
         }
     }
 
-    command_buffers
+    Ok(command_buffers)
 }
 
-pub fn create_sync_objects(device: &ash::Device, max_frame_in_flight: usize) -> SyncObjects {
+pub fn create_sync_objects(
+    device: &ash::Device,
+    max_frame_in_flight: usize,
+) -> VkTutorialResult<SyncObjects> {
     let mut sync_objects = SyncObjects {
         image_available_semaphores: vec![],
         render_finished_semaphores: vec![],
@@ -433,28 +467,57 @@ pub fn create_sync_objects(device: &ash::Device, max_frame_in_flight: usize) ->
     };
 
     for _ in 0..max_frame_in_flight {
-        unsafe {
-            let image_available_semaphore = device
-                .create_semaphore(&semaphore_create_info, None)
-                .expect("Failed to create Semaphore Object!");
-            let render_finished_semaphore = device
-                .create_semaphore(&semaphore_create_info, None)
-                .expect("Failed to create Semaphore Object!");
-            let inflight_fence = device
-                .create_fence(&fence_create_info, None)
-                .expect("Failed to create Fence Object!");
-
-            sync_objects
-                .image_available_semaphores
-                .push(image_available_semaphore);
-            sync_objects
-                .render_finished_semaphores
-                .push(render_finished_semaphore);
-            sync_objects.inflight_fences.push(inflight_fence);
+        let frame_sync_objects = unsafe {
+            push_frame_sync_objects(
+                device,
+                &mut sync_objects,
+                &semaphore_create_info,
+                &fence_create_info,
+            )
+        };
+
+        if let Err(error) = frame_sync_objects {
+            destroy_sync_objects(device, &sync_objects);
+            return Err(error);
         }
     }
 
+    Ok(sync_objects)
+}
+
+/// Each object is pushed as soon as it is created, so that a failure leaves nothing behind `sync_objects`.
+unsafe fn push_frame_sync_objects(
+    device: &ash::Device,
+    sync_objects: &mut SyncObjects,
+    semaphore_create_info: &vk::SemaphoreCreateInfo,
+    fence_create_info: &vk::FenceCreateInfo,
+) -> VkTutorialResult<()> {
+    sync_objects
+        .image_available_semaphores
+        .push(device.create_semaphore(semaphore_create_info, None)?);
     sync_objects
+        .render_finished_semaphores
+        .push(device.create_semaphore(semaphore_create_info, None)?);
+    sync_objects
+        .inflight_fences
+        .push(device.create_fence(fence_create_info, None)?);
+
+    Ok(())
+}
+
+fn destroy_sync_objects(device: &ash::Device, sync_objects: &SyncObjects) {
+    unsafe {
+        for &semaphore in sync_objects
+            .image_available_semaphores
+            .iter()
+            .chain(sync_objects.render_finished_semaphores.iter())
+        {
+            device.destroy_semaphore(semaphore, None);
+        }
+        for &fence in sync_objects.inflight_fences.iter() {
+            device.destroy_fence(fence, None);
+        }
+    }
 }
 
 pub fn create_vertex_buffer<T>(
@@ -463,55 +526,15 @@ pub fn create_vertex_buffer<T>(
     command_pool: vk::CommandPool,
     submit_queue: vk::Queue,
     data: &[T],
-) -> (vk::Buffer, vk::DeviceMemory) {
-    let buffer_size = ::std::mem::size_of_val(data) as vk::DeviceSize;
-
-    let (staging_buffer, staging_buffer_memory) = create_buffer(
-        device,
-        buffer_size,
-        vk::BufferUsageFlags::TRANSFER_SRC,
-        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-        &device_memory_properties,
-    );
-
-    unsafe {
-        let data_ptr = device
-            .map_memory(
-                staging_buffer_memory,
-                0,
-                buffer_size,
-                vk::MemoryMapFlags::empty(),
-            )
-            .expect("Failed to Map Memory") as *mut T;
-
-        data_ptr.copy_from_nonoverlapping(data.as_ptr(), data.len());
-
-        device.unmap_memory(staging_buffer_memory);
-    }
-
-    let (vertex_buffer, vertex_buffer_memory) = create_buffer(
-        device,
-        buffer_size,
-        vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER,
-        vk::MemoryPropertyFlags::DEVICE_LOCAL,
-        &device_memory_properties,
-    );
-
-    copy_buffer(
+) -> VkTutorialResult<(vk::Buffer, vk::DeviceMemory)> {
+    create_device_local_buffer(
         device,
-        submit_queue,
+        device_memory_properties,
         command_pool,
-        staging_buffer,
-        vertex_buffer,
-        buffer_size,
-    );
-
-    unsafe {
-        device.destroy_buffer(staging_buffer, None);
-        device.free_memory(staging_buffer_memory, None);
-    }
-
-    (vertex_buffer, vertex_buffer_memory)
+        submit_queue,
+        data,
+        vk::BufferUsageFlags::VERTEX_BUFFER,
+    )
 }
 
 pub fn create_index_buffer(
@@ -520,7 +543,26 @@ pub fn create_index_buffer(
     command_pool: vk::CommandPool,
     submit_queue: vk::Queue,
     data: &[u32],
-) -> (vk::Buffer, vk::DeviceMemory) {
+) -> VkTutorialResult<(vk::Buffer, vk::DeviceMemory)> {
+    create_device_local_buffer(
+        device,
+        device_memory_properties,
+        command_pool,
+        submit_queue,
+        data,
+        vk::BufferUsageFlags::INDEX_BUFFER,
+    )
+}
+
+/// Upload `data` into a new device local buffer through a staging buffer, which is destroyed on every path.
+fn create_device_local_buffer<T>(
+    device: &ash::Device,
+    device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    command_pool: vk::CommandPool,
+    submit_queue: vk::Queue,
+    data: &[T],
+    usage: vk::BufferUsageFlags,
+) -> VkTutorialResult<(vk::Buffer, vk::DeviceMemory)> {
     let buffer_size = ::std::mem::size_of_val(data) as vk::DeviceSize;
 
     let (staging_buffer, staging_buffer_memory) = create_buffer(
@@ -528,53 +570,45 @@ pub fn create_index_buffer(
         buffer_size,
         vk::BufferUsageFlags::TRANSFER_SRC,
         vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-        &device_memory_properties,
-    );
+        device_memory_properties,
+    )?;
 
-    unsafe {
-        let data_ptr = device
-            .map_memory(
-                staging_buffer_memory,
-                0,
+    let device_local_buffer = write_memory(device, staging_buffer_memory, data)
+        .and_then(|()| {
+            create_buffer(
+                device,
                 buffer_size,
-                vk::MemoryMapFlags::empty(),
+                vk::BufferUsageFlags::TRANSFER_DST | usage,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                device_memory_properties,
             )
-            .expect("Failed to Map Memory") as *mut u32;
-
-        data_ptr.copy_from_nonoverlapping(data.as_ptr(), data.len());
-
-        device.unmap_memory(staging_buffer_memory);
-    }
-
-    let (index_buffer, index_buffer_memory) = create_buffer(
-        device,
-        buffer_size,
-        vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDEX_BUFFER,
-        vk::MemoryPropertyFlags::DEVICE_LOCAL,
-        &device_memory_properties,
-    );
-
-    copy_buffer(
-        device,
-        submit_queue,
-        command_pool,
-        staging_buffer,
-        index_buffer,
-        buffer_size,
-    );
-
-    unsafe {
-        device.destroy_buffer(staging_buffer, None);
-        device.free_memory(staging_buffer_memory, None);
-    }
-
-    (index_buffer, index_buffer_memory)
+        })
+        .and_then(|(buffer, buffer_memory)| {
+            match copy_buffer(
+                device,
+                submit_queue,
+                command_pool,
+                staging_buffer,
+                buffer,
+                buffer_size,
+            ) {
+                Ok(()) => Ok((buffer, buffer_memory)),
+                Err(error) => {
+                    destroy_buffer(device, buffer, buffer_memory);
+                    Err(error)
+                }
+            }
+        });
+
+    destroy_buffer(device, staging_buffer, staging_buffer_memory);
+
+    device_local_buffer
 }
 
 pub fn create_descriptor_pool(
     device: &ash::Device,
     swapchain_images_size: usize,
-) -> vk::DescriptorPool {
+) -> VkTutorialResult<vk::DescriptorPool> {
     let pool_sizes = [vk::DescriptorPoolSize {
         ty: vk::DescriptorType::UNIFORM_BUFFER,
         descriptor_count: swapchain_images_size as u32,
@@ -589,11 +623,7 @@ pub fn create_descriptor_pool(
         p_pool_sizes: pool_sizes.as_ptr(),
     };
 
-    unsafe {
-        device
-            .create_descriptor_pool(&descriptor_pool_create_info, None)
-            .expect("Failed to create Descriptor Pool!")
-    }
+    Ok(unsafe { device.create_descriptor_pool(&descriptor_pool_create_info, None)? })
 }
 
 pub fn create_descriptor_sets(
@@ -602,7 +632,7 @@ pub fn create_descriptor_sets(
     descriptor_set_layout: vk::DescriptorSetLayout,
     uniforms_buffers: &Vec<vk::Buffer>,
     swapchain_images_size: usize,
-) -> Vec<vk::DescriptorSet> {
+) -> VkTutorialResult<Vec<vk::DescriptorSet>> {
     let mut layouts: Vec<vk::DescriptorSetLayout> = vec![];
     for _ in 0..swapchain_images_size {
         layouts.push(descriptor_set_layout);
@@ -616,11 +646,8 @@ pub fn create_descriptor_sets(
         p_set_layouts: layouts.as_ptr(),
     };
 
-    let descriptor_sets = unsafe {
-        device
-            .allocate_descriptor_sets(&descriptor_set_allocate_info)
-            .expect("Failed to allocate descriptor sets!")
-    };
+    let descriptor_sets =
+        unsafe { device.allocate_descriptor_sets(&descriptor_set_allocate_info)? };
 
     for (i, &descritptor_set) in descriptor_sets.iter().enumerate() {
         let descriptor_buffer_info = [vk::DescriptorBufferInfo {
@@ -647,38 +674,30 @@ pub fn create_descriptor_sets(
         }
     }
 
-    descriptor_sets
+    Ok(descriptor_sets)
 }
 
-pub fn create_descriptor_set_layout(device: &ash::Device) -> vk::DescriptorSetLayout {
-    let ubo_layout_bindings = [vk::DescriptorSetLayoutBinding {
-        binding: 0,
-        descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
-        descriptor_count: 1,
-        stage_flags: vk::ShaderStageFlags::VERTEX,
-        p_immutable_samplers: ptr::null(),
-    }];
-
-    let ubo_layout_create_info = vk::DescriptorSetLayoutCreateInfo {
-        s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
-        p_next: ptr::null(),
-        flags: vk::DescriptorSetLayoutCreateFlags::empty(),
-        binding_count: ubo_layout_bindings.len() as u32,
-        p_bindings: ubo_layout_bindings.as_ptr(),
-    };
+/// The layout of the bindings declared in `21-shader-ubo.vert` and `21-shader-ubo.frag`.
+pub fn create_descriptor_set_layout(
+    device: &ash::Device,
+) -> VkTutorialResult<vk::DescriptorSetLayout> {
+    let shaders = [
+        ShaderReflection::new(include_bytes!(
+            "../../../shaders/spv/21-shader-ubo.vert.spv"
+        ))?,
+        ShaderReflection::new(include_bytes!(
+            "../../../shaders/spv/21-shader-ubo.frag.spv"
+        ))?,
+    ];
 
-    unsafe {
-        device
-            .create_descriptor_set_layout(&ubo_layout_create_info, None)
-            .expect("Failed to create Descriptor Set Layout!")
-    }
+    create_reflected_descriptor_set_layout(device, &shaders, 0)
 }
 
 pub fn create_uniform_buffers(
     device: &ash::Device,
     device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
     swapchain_image_count: usize,
-) -> (Vec<vk::Buffer>, Vec<vk::DeviceMemory>) {
+) -> VkTutorialResult<(Vec<vk::Buffer>, Vec<vk::DeviceMemory>)> {
     let buffer_size = ::std::mem::size_of::<UniformBufferObject>();
 
     let mut uniform_buffers = vec![];
@@ -691,12 +710,12 @@ pub fn create_uniform_buffers(
             vk::BufferUsageFlags::UNIFORM_BUFFER,
             vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
             device_memory_properties,
-        );
+        )?;
         uniform_buffers.push(uniform_buffer);
         uniform_buffers_memory.push(uniform_buffer_memory);
     }
 
-    (uniform_buffers, uniform_buffers_memory)
+    Ok((uniform_buffers, uniform_buffers_memory))
 }
 
 pub fn create_image(
@@ -710,7 +729,7 @@ pub fn create_image(
     usage: vk::ImageUsageFlags,
     required_memory_properties: vk::MemoryPropertyFlags,
     device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
-) -> (vk::Image, vk::DeviceMemory) {
+) -> VkTutorialResult<(vk::Image, vk::DeviceMemory)> {
     let image_create_info = vk::ImageCreateInfo {
         s_type: vk::StructureType::IMAGE_CREATE_INFO,
         p_next: ptr::null(),
@@ -733,135 +752,81 @@ pub fn create_image(
         },
     };
 
-    let texture_image = unsafe {
-        device
-            .create_image(&image_create_info, None)
-            .expect("Failed to create Texture Image!")
-    };
+    let texture_image = unsafe { device.create_image(&image_create_info, None)? };
 
     let image_memory_requirement = unsafe { device.get_image_memory_requirements(texture_image) };
-    let memory_allocate_info = vk::MemoryAllocateInfo {
-        s_type: vk::StructureType::MEMORY_ALLOCATE_INFO,
-        p_next: ptr::null(),
-        allocation_size: image_memory_requirement.size,
-        memory_type_index: find_memory_type(
-            image_memory_requirement.memory_type_bits,
-            required_memory_properties,
-            device_memory_properties,
-        ),
-    };
-
-    let texture_image_memory = unsafe {
-        device
-            .allocate_memory(&memory_allocate_info, None)
-            .expect("Failed to allocate Texture Image memory!")
-    };
+    let texture_image_memory = allocate_bound_memory(
+        device,
+        image_memory_requirement,
+        required_memory_properties,
+        device_memory_properties,
+        |memory| unsafe { device.bind_image_memory(texture_image, memory, 0) },
+    );
 
-    unsafe {
-        device
-            .bind_image_memory(texture_image, texture_image_memory, 0)
-            .expect("Failed to bind Image Memmory!");
+    match texture_image_memory {
+        Ok(texture_image_memory) => Ok((texture_image, texture_image_memory)),
+        Err(error) => {
+            unsafe {
+                device.destroy_image(texture_image, None);
+            }
+            Err(error)
+        }
     }
-
-    (texture_image, texture_image_memory)
 }
 
+/// Transition all the `mip_levels` of `image` with a single time command,
+/// see `layout::cmd_transition_image_layouts` to record it into another command buffer.
 pub fn transition_image_layout(
     device: &ash::Device,
     command_pool: vk::CommandPool,
     submit_queue: vk::Queue,
     image: vk::Image,
-    _format: vk::Format,
+    format: vk::Format,
     old_layout: vk::ImageLayout,
     new_layout: vk::ImageLayout,
     mip_levels: u32,
-) {
-    let command_buffer = begin_single_time_command(device, command_pool);
-
-    let src_access_mask;
-    let dst_access_mask;
-    let source_stage;
-    let destination_stage;
-
-    if old_layout == vk::ImageLayout::UNDEFINED
-        && new_layout == vk::ImageLayout::TRANSFER_DST_OPTIMAL
-    {
-        src_access_mask = vk::AccessFlags::empty();
-        dst_access_mask = vk::AccessFlags::TRANSFER_WRITE;
-        source_stage = vk::PipelineStageFlags::TOP_OF_PIPE;
-        destination_stage = vk::PipelineStageFlags::TRANSFER;
-    } else if old_layout == vk::ImageLayout::TRANSFER_DST_OPTIMAL
-        && new_layout == vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
-    {
-        src_access_mask = vk::AccessFlags::TRANSFER_WRITE;
-        dst_access_mask = vk::AccessFlags::SHADER_READ;
-        source_stage = vk::PipelineStageFlags::TRANSFER;
-        destination_stage = vk::PipelineStageFlags::FRAGMENT_SHADER;
-    } else if old_layout == vk::ImageLayout::UNDEFINED
-        && new_layout == vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
-    {
-        src_access_mask = vk::AccessFlags::empty();
-        dst_access_mask =
-            vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE;
-        source_stage = vk::PipelineStageFlags::TOP_OF_PIPE;
-        destination_stage = vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT;
-    } else {
-        panic!("Unsupported layout transition!")
-    }
+) -> VkTutorialResult<()> {
+    let command_buffer = begin_single_time_command(device, command_pool)?;
 
-    let image_barriers = [vk::ImageMemoryBarrier {
-        s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
-        p_next: ptr::null(),
-        src_access_mask,
-        dst_access_mask,
-        old_layout,
-        new_layout,
-        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
-        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
-        image,
-        subresource_range: vk::ImageSubresourceRange {
-            aspect_mask: vk::ImageAspectFlags::COLOR,
-            base_mip_level: 0,
-            level_count: mip_levels,
-            base_array_layer: 0,
-            layer_count: 1,
-        },
-    }];
+    let transition = layout::ImageTransition::new(image, format, old_layout, new_layout)
+        .mip_levels(0, mip_levels)
+        .array_layers(0, 1);
+    let result = layout::cmd_transition_image_layouts(device, command_buffer, &[transition]);
 
-    unsafe {
-        device.cmd_pipeline_barrier(
-            command_buffer,
-            source_stage,
-            destination_stage,
-            vk::DependencyFlags::empty(),
-            &[],
-            &[],
-            &image_barriers,
-        );
-    }
+    // the command buffer is still submitted and freed when the transition is rejected.
+    end_single_time_command(device, command_pool, submit_queue, command_buffer)?;
 
-    end_single_time_command(device, command_pool, submit_queue, command_buffer);
+    result
 }
 
 pub fn create_image_views(
     device: &ash::Device,
     surface_format: vk::Format,
     images: &Vec<vk::Image>,
-) -> Vec<vk::ImageView> {
-    let swapchain_imageviews: Vec<vk::ImageView> = images
-        .iter()
-        .map(|&image| {
-            create_image_view(
-                device,
-                image,
-                surface_format,
-                vk::ImageAspectFlags::COLOR,
-                1,
-            )
-        })
-        .collect();
+) -> VkTutorialResult<Vec<vk::ImageView>> {
+    let mut image_views = Vec::with_capacity(images.len());
 
-    swapchain_imageviews
+    for &image in images.iter() {
+        match create_image_view(
+            device,
+            image,
+            surface_format,
+            vk::ImageAspectFlags::COLOR,
+            1,
+        ) {
+            Ok(image_view) => image_views.push(image_view),
+            Err(error) => {
+                unsafe {
+                    for &image_view in image_views.iter() {
+                        device.destroy_image_view(image_view, None);
+                    }
+                }
+                return Err(error);
+            }
+        }
+    }
+
+    Ok(image_views)
 }
 
 pub fn create_image_view(
@@ -870,7 +835,7 @@ pub fn create_image_view(
     format: vk::Format,
     aspect_flags: vk::ImageAspectFlags,
     mip_levels: u32,
-) -> vk::ImageView {
+) -> VkTutorialResult<vk::ImageView> {
     let imageview_create_info = vk::ImageViewCreateInfo {
         s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
         p_next: ptr::null(),
@@ -893,54 +858,36 @@ pub fn create_image_view(
         image,
     };
 
-    unsafe {
-        device
-            .create_image_view(&imageview_create_info, None)
-            .expect("Failed to create Image View!")
-    }
+    Ok(unsafe { device.create_image_view(&imageview_create_info, None)? })
 }
 
 pub fn create_texture_image_view(
     device: &ash::Device,
     texture_image: vk::Image,
     mip_levels: u32,
-) -> vk::ImageView {
-    create_image_view(
+) -> VkTutorialResult<vk::ImageView> {
+    Ok(create_image_view(
         device,
         texture_image,
         vk::Format::R8G8B8A8_SRGB,
         vk::ImageAspectFlags::COLOR,
         mip_levels,
-    )
+    )?)
 }
 
-pub fn create_texture_sampler(device: &ash::Device) -> vk::Sampler {
-    let sampler_create_info = vk::SamplerCreateInfo {
-        s_type: vk::StructureType::SAMPLER_CREATE_INFO,
-        p_next: ptr::null(),
-        flags: vk::SamplerCreateFlags::empty(),
-        mag_filter: vk::Filter::LINEAR,
-        min_filter: vk::Filter::LINEAR,
-        address_mode_u: vk::SamplerAddressMode::REPEAT,
-        address_mode_v: vk::SamplerAddressMode::REPEAT,
-        address_mode_w: vk::SamplerAddressMode::REPEAT,
-        max_anisotropy: 16.0,
-        compare_enable: vk::FALSE,
-        compare_op: vk::CompareOp::ALWAYS,
-        mipmap_mode: vk::SamplerMipmapMode::LINEAR,
-        min_lod: 0.0,
-        max_lod: 0.0,
-        mip_lod_bias: 0.0,
-        border_color: vk::BorderColor::INT_OPAQUE_BLACK,
-        anisotropy_enable: vk::TRUE,
-        unnormalized_coordinates: vk::FALSE,
-    };
+/// The default sampler of `SamplerDescription` without mipmaps, see `SamplerCache` for the other ones.
+///
+/// The anisotropy is clamped to the limit of the device, and disabled if it is not supported.
+pub fn create_texture_sampler(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    device: &ash::Device,
+) -> VkTutorialResult<vk::Sampler> {
+    let sampler_create_info = SamplerDescription::default()
+        .lod_range(0.0, 0.0)
+        .to_create_info(sampler::device_max_anisotropy(instance, physical_device));
 
-    unsafe {
-        device
-            .create_sampler(&sampler_create_info, None)
-            .expect("Failed to create Sampler!")
-    }
+    Ok(unsafe { device.create_sampler(&sampler_create_info, None)? })
 }
 
 pub fn create_texture_image(
@@ -949,8 +896,8 @@ pub fn create_texture_image(
     submit_queue: vk::Queue,
     device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
     image_path: &Path,
-) -> (vk::Image, vk::DeviceMemory) {
-    let mut image_object = image::open(image_path).unwrap(); // this function is slow in debug mode.
+) -> VkTutorialResult<(vk::Image, vk::DeviceMemory)> {
+    let mut image_object = image::open(image_path)?; // this function is slow in debug mode.
     image_object = image_object.flipv();
     let (image_width, image_height) = (image_object.width(), image_object.height());
     let image_data = match &image_object {
@@ -965,7 +912,9 @@ pub fn create_texture_image(
         (::std::mem::size_of::<u8>() as u32 * image_width * image_height * 4) as vk::DeviceSize;
 
     if image_size <= 0 {
-        panic!("Failed to load texture image!")
+        return Err(VkTutorialError::ImageLoad(
+            image::ImageError::DimensionError,
+        ));
     }
 
     let (staging_buffer, staging_buffer_memory) = create_buffer(
@@ -974,74 +923,70 @@ pub fn create_texture_image(
         vk::BufferUsageFlags::TRANSFER_SRC,
         vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
         device_memory_properties,
-    );
+    )?;
 
-    unsafe {
-        let data_ptr = device
-            .map_memory(
-                staging_buffer_memory,
-                0,
-                image_size,
-                vk::MemoryMapFlags::empty(),
+    let texture = write_memory(device, staging_buffer_memory, &image_data)
+        .and_then(|()| {
+            create_image(
+                device,
+                image_width,
+                image_height,
+                1,
+                vk::SampleCountFlags::TYPE_1,
+                vk::Format::R8G8B8A8_SRGB,
+                vk::ImageTiling::OPTIMAL,
+                vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                device_memory_properties,
             )
-            .expect("Failed to Map Memory") as *mut u8;
-
-        data_ptr.copy_from_nonoverlapping(image_data.as_ptr(), image_data.len());
-
-        device.unmap_memory(staging_buffer_memory);
-    }
-
-    let (texture_image, texture_image_memory) = create_image(
-        device,
-        image_width,
-        image_height,
-        1,
-        vk::SampleCountFlags::TYPE_1,
-        vk::Format::R8G8B8A8_SRGB,
-        vk::ImageTiling::OPTIMAL,
-        vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
-        vk::MemoryPropertyFlags::DEVICE_LOCAL,
-        device_memory_properties,
-    );
-
-    transition_image_layout(
-        device,
-        command_pool,
-        submit_queue,
-        texture_image,
-        vk::Format::R8G8B8A8_SRGB,
-        vk::ImageLayout::UNDEFINED,
-        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-        1,
-    );
-
-    copy_buffer_to_image(
-        device,
-        command_pool,
-        submit_queue,
-        staging_buffer,
-        texture_image,
-        image_width,
-        image_height,
-    );
-
-    transition_image_layout(
-        device,
-        command_pool,
-        submit_queue,
-        texture_image,
-        vk::Format::R8G8B8A8_UNORM,
-        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-        1,
-    );
-
-    unsafe {
-        device.destroy_buffer(staging_buffer, None);
-        device.free_memory(staging_buffer_memory, None);
-    }
-
-    (texture_image, texture_image_memory)
+        })
+        .and_then(|(texture_image, texture_image_memory)| {
+            let uploaded = transition_image_layout(
+                device,
+                command_pool,
+                submit_queue,
+                texture_image,
+                vk::Format::R8G8B8A8_SRGB,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                1,
+            )
+            .and_then(|()| {
+                copy_buffer_to_image(
+                    device,
+                    command_pool,
+                    submit_queue,
+                    staging_buffer,
+                    texture_image,
+                    image_width,
+                    image_height,
+                )
+            })
+            .and_then(|()| {
+                transition_image_layout(
+                    device,
+                    command_pool,
+                    submit_queue,
+                    texture_image,
+                    vk::Format::R8G8B8A8_UNORM,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    1,
+                )
+            });
+
+            match uploaded {
+                Ok(()) => Ok((texture_image, texture_image_memory)),
+                Err(error) => {
+                    destroy_image(device, texture_image, texture_image_memory);
+                    Err(error)
+                }
+            }
+        });
+
+    destroy_buffer(device, staging_buffer, staging_buffer_memory);
+
+    texture
 }
 
 pub fn create_depth_resources(
@@ -1053,8 +998,8 @@ pub fn create_depth_resources(
     swapchain_extent: vk::Extent2D,
     device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
     msaa_samples: vk::SampleCountFlags,
-) -> (vk::Image, vk::ImageView, vk::DeviceMemory) {
-    let depth_format = find_depth_format(instance, physical_device);
+) -> VkTutorialResult<(vk::Image, vk::ImageView, vk::DeviceMemory)> {
+    let depth_format = find_depth_format(instance, physical_device)?;
     let (depth_image, depth_image_memory) = create_image(
         device,
         swapchain_extent.width,
@@ -1066,16 +1011,22 @@ pub fn create_depth_resources(
         vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
         vk::MemoryPropertyFlags::DEVICE_LOCAL,
         device_memory_properties,
-    );
-    let depth_image_view = create_image_view(
+    )?;
+    let depth_image_view = match create_image_view(
         device,
         depth_image,
         depth_format,
         vk::ImageAspectFlags::DEPTH,
         1,
-    );
+    ) {
+        Ok(depth_image_view) => depth_image_view,
+        Err(error) => {
+            destroy_image(device, depth_image, depth_image_memory);
+            return Err(error);
+        }
+    };
 
-    (depth_image, depth_image_view, depth_image_memory)
+    Ok((depth_image, depth_image_view, depth_image_memory))
 }
 
 pub fn generate_mipmaps(
@@ -1086,8 +1037,8 @@ pub fn generate_mipmaps(
     tex_width: u32,
     tex_height: u32,
     mip_levels: u32,
-) {
-    let command_buffer = begin_single_time_command(device, command_pool);
+) -> VkTutorialResult<()> {
+    let command_buffer = begin_single_time_command(device, command_pool)?;
 
     let mut image_barrier = vk::ImageMemoryBarrier {
         s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
@@ -1212,5 +1163,7 @@ pub fn generate_mipmaps(
         );
     }
 
-    end_single_time_command(device, command_pool, submit_queue, command_buffer);
+    end_single_time_command(device, command_pool, submit_queue, command_buffer)?;
+
+    Ok(())
 }
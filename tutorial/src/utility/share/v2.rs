@@ -4,14 +4,24 @@ use std::ptr;
 
 use super::*;
 
+/// The type of the transform uniform, `UNIFORM_BUFFER_DYNAMIC` when it is bound with a dynamic offset.
+fn uniform_buffer_type(dynamic_uniform_buffer: bool) -> vk::DescriptorType {
+    if dynamic_uniform_buffer {
+        vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC
+    } else {
+        vk::DescriptorType::UNIFORM_BUFFER
+    }
+}
+
 pub fn create_descriptor_pool(
     device: &ash::Device,
     swapchain_images_size: usize,
-) -> vk::DescriptorPool {
+    dynamic_uniform_buffer: bool,
+) -> VkTutorialResult<vk::DescriptorPool> {
     let pool_sizes = [
         vk::DescriptorPoolSize {
             // transform descriptor pool
-            ty: vk::DescriptorType::UNIFORM_BUFFER,
+            ty: uniform_buffer_type(dynamic_uniform_buffer),
             descriptor_count: swapchain_images_size as u32,
         },
         vk::DescriptorPoolSize {
@@ -30,11 +40,7 @@ pub fn create_descriptor_pool(
         p_pool_sizes: pool_sizes.as_ptr(),
     };
 
-    unsafe {
-        device
-            .create_descriptor_pool(&descriptor_pool_create_info, None)
-            .expect("Failed to create Descriptor Pool!")
-    }
+    Ok(unsafe { device.create_descriptor_pool(&descriptor_pool_create_info, None)? })
 }
 
 pub fn create_descriptor_sets(
@@ -45,7 +51,8 @@ pub fn create_descriptor_sets(
     texture_image_view: vk::ImageView,
     texture_sampler: vk::Sampler,
     swapchain_images_size: usize,
-) -> Vec<vk::DescriptorSet> {
+    dynamic_uniform_buffer: bool,
+) -> VkTutorialResult<Vec<vk::DescriptorSet>> {
     let mut layouts: Vec<vk::DescriptorSetLayout> = vec![];
     for _ in 0..swapchain_images_size {
         layouts.push(descriptor_set_layout);
@@ -59,93 +66,108 @@ pub fn create_descriptor_sets(
         p_set_layouts: layouts.as_ptr(),
     };
 
-    let descriptor_sets = unsafe {
-        device
-            .allocate_descriptor_sets(&descriptor_set_allocate_info)
-            .expect("Failed to allocate descriptor sets!")
-    };
-
-    for (i, &descritptor_set) in descriptor_sets.iter().enumerate() {
-        let descriptor_buffer_infos = [vk::DescriptorBufferInfo {
-            buffer: uniforms_buffers[i],
-            offset: 0,
-            range: ::std::mem::size_of::<UniformBufferObject>() as u64,
-        }];
-
-        let descriptor_image_infos = [vk::DescriptorImageInfo {
-            sampler: texture_sampler,
-            image_view: texture_image_view,
-            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-        }];
-
-        let descriptor_write_sets = [
-            vk::WriteDescriptorSet {
-                // transform uniform
-                s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
-                p_next: ptr::null(),
-                dst_set: descritptor_set,
-                dst_binding: 0,
-                dst_array_element: 0,
-                descriptor_count: 1,
-                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
-                p_image_info: ptr::null(),
-                p_buffer_info: descriptor_buffer_infos.as_ptr(),
-                p_texel_buffer_view: ptr::null(),
-            },
-            vk::WriteDescriptorSet {
-                // sampler uniform
-                s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
-                p_next: ptr::null(),
-                dst_set: descritptor_set,
-                dst_binding: 1,
-                dst_array_element: 0,
-                descriptor_count: 1,
-                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                p_image_info: descriptor_image_infos.as_ptr(),
-                p_buffer_info: ptr::null(),
-                p_texel_buffer_view: ptr::null(),
-            },
-        ];
-
-        unsafe {
-            device.update_descriptor_sets(&descriptor_write_sets, &[]);
-        }
+    let descriptor_sets =
+        unsafe { device.allocate_descriptor_sets(&descriptor_set_allocate_info)? };
+
+    for (&descriptor_set, &uniform_buffer) in descriptor_sets.iter().zip(uniforms_buffers.iter()) {
+        update_descriptor_set(
+            device,
+            descriptor_set,
+            uniform_buffer,
+            texture_image_view,
+            texture_sampler,
+            dynamic_uniform_buffer,
+        );
     }
 
-    descriptor_sets
+    Ok(descriptor_sets)
 }
 
-pub fn create_descriptor_set_layout(device: &ash::Device) -> vk::DescriptorSetLayout {
-    let ubo_layout_bindings = [
-        vk::DescriptorSetLayoutBinding {
+/// Bind the transform `uniform_buffer` and the texture to `descriptor_set`.
+pub fn update_descriptor_set(
+    device: &ash::Device,
+    descriptor_set: vk::DescriptorSet,
+    uniform_buffer: vk::Buffer,
+    texture_image_view: vk::ImageView,
+    texture_sampler: vk::Sampler,
+    dynamic_uniform_buffer: bool,
+) {
+    let descriptor_buffer_infos = [vk::DescriptorBufferInfo {
+        buffer: uniform_buffer,
+        offset: 0,
+        range: ::std::mem::size_of::<UniformBufferObject>() as u64,
+    }];
+
+    let descriptor_image_infos = [vk::DescriptorImageInfo {
+        sampler: texture_sampler,
+        image_view: texture_image_view,
+        image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    }];
+
+    let descriptor_write_sets = [
+        vk::WriteDescriptorSet {
             // transform uniform
-            binding: 0,
-            descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+            s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+            p_next: ptr::null(),
+            dst_set: descriptor_set,
+            dst_binding: 0,
+            dst_array_element: 0,
             descriptor_count: 1,
-            stage_flags: vk::ShaderStageFlags::VERTEX,
-            p_immutable_samplers: ptr::null(),
+            descriptor_type: uniform_buffer_type(dynamic_uniform_buffer),
+            p_image_info: ptr::null(),
+            p_buffer_info: descriptor_buffer_infos.as_ptr(),
+            p_texel_buffer_view: ptr::null(),
         },
-        vk::DescriptorSetLayoutBinding {
+        vk::WriteDescriptorSet {
             // sampler uniform
-            binding: 1,
-            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+            p_next: ptr::null(),
+            dst_set: descriptor_set,
+            dst_binding: 1,
+            dst_array_element: 0,
             descriptor_count: 1,
-            stage_flags: vk::ShaderStageFlags::FRAGMENT,
-            p_immutable_samplers: ptr::null(),
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            p_image_info: descriptor_image_infos.as_ptr(),
+            p_buffer_info: ptr::null(),
+            p_texel_buffer_view: ptr::null(),
         },
     ];
 
-    let ubo_layout_create_info = vk::DescriptorSetLayoutCreateInfo {
-        s_type: vk::StructureType::DESCRIPTOR_SET_LAYOUT_CREATE_INFO,
-        p_next: ptr::null(),
-        flags: vk::DescriptorSetLayoutCreateFlags::empty(),
-        binding_count: ubo_layout_bindings.len() as u32,
-        p_bindings: ubo_layout_bindings.as_ptr(),
-    };
-
     unsafe {
-        device
-            .create_descriptor_set_layout(&ubo_layout_create_info, None)
-            .expect("Failed to create Descriptor Set Layout!")
+        device.update_descriptor_sets(&descriptor_write_sets, &[]);
+    }
+}
+
+/// The bindings declared in `26-shader-depth.vert` and `26-shader-depth.frag`, the transform
+/// uniform is bound with a dynamic offset when `dynamic_uniform_buffer` is set.
+pub fn descriptor_set_layout_bindings(
+    dynamic_uniform_buffer: bool,
+) -> VkTutorialResult<Vec<vk::DescriptorSetLayoutBinding>> {
+    let shaders = [
+        ShaderReflection::new(include_bytes!(
+            "../../../shaders/spv/26-shader-depth.vert.spv"
+        ))?,
+        ShaderReflection::new(include_bytes!(
+            "../../../shaders/spv/26-shader-depth.frag.spv"
+        ))?,
+    ];
+
+    let mut layout_bindings = reflection::descriptor_set_layout_bindings(&shaders, 0)?;
+    for layout_binding in layout_bindings.iter_mut().filter(|layout_binding| {
+        layout_binding.descriptor_type == vk::DescriptorType::UNIFORM_BUFFER
+    }) {
+        layout_binding.descriptor_type = uniform_buffer_type(dynamic_uniform_buffer);
     }
+
+    Ok(layout_bindings)
+}
+
+/// The layout of the bindings of `descriptor_set_layout_bindings`.
+pub fn create_descriptor_set_layout(
+    device: &ash::Device,
+    dynamic_uniform_buffer: bool,
+) -> VkTutorialResult<vk::DescriptorSetLayout> {
+    let layout_bindings = descriptor_set_layout_bindings(dynamic_uniform_buffer)?;
+
+    create_descriptor_set_layout_from_bindings(device, &layout_bindings)
 }
@@ -1,6 +1,8 @@
 use ash::vk;
 use cgmath::Matrix4;
 
+use crate::utility::render_target::RenderTarget;
+
 use memoffset::offset_of;
 
 pub struct DeviceExtension {
@@ -15,12 +17,28 @@ pub struct SurfaceStuff {
     pub screen_width: u32,
     pub screen_height: u32,
 }
+
+impl SurfaceStuff {
+    /// A headless surface has no `vk::SurfaceKHR` behind it, its size is used for the offscreen images.
+    pub fn is_headless(&self) -> bool {
+        self.surface == vk::SurfaceKHR::null()
+    }
+}
+
 pub struct SwapChainStuff {
     pub swapchain_loader: ash::extensions::khr::Swapchain,
     pub swapchain: vk::SwapchainKHR,
     pub swapchain_images: Vec<vk::Image>,
     pub swapchain_format: vk::Format,
     pub swapchain_extent: vk::Extent2D,
+    pub swapchain_image_usage: vk::ImageUsageFlags,
+}
+
+pub struct RenderTargetStuff {
+    pub render_target: RenderTarget,
+    pub swapchain_images: Vec<vk::Image>,
+    pub swapchain_format: vk::Format,
+    pub swapchain_extent: vk::Extent2D,
 }
 
 pub struct SwapChainSupportDetail {
@@ -32,6 +50,8 @@ pub struct SwapChainSupportDetail {
 pub struct QueueFamilyIndices {
     pub graphics_family: Option<u32>,
     pub present_family: Option<u32>,
+    /// A family without graphics for the uploads, transfer only if possible, `None` if the device has none.
+    pub transfer_family: Option<u32>,
 }
 
 impl QueueFamilyIndices {
@@ -39,6 +59,7 @@ impl QueueFamilyIndices {
         QueueFamilyIndices {
             graphics_family: None,
             present_family: None,
+            transfer_family: None,
         }
     }
 
@@ -134,6 +155,101 @@ impl VertexV3 {
     }
 }
 
+/// The vertex of the loaded models, the first three attributes match the ones of `VertexV3`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct VertexV4 {
+    pub pos: [f32; 3],
+    pub color: [f32; 4],
+    pub tex_coord: [f32; 2],
+    pub normal: [f32; 3],
+}
+impl VertexV4 {
+    pub fn get_binding_descriptions() -> [vk::VertexInputBindingDescription; 1] {
+        [vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: ::std::mem::size_of::<Self>() as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+        }]
+    }
+
+    pub fn get_attribute_descriptions() -> [vk::VertexInputAttributeDescription; 4] {
+        [
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 0,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: offset_of!(Self, pos) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 1,
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                offset: offset_of!(Self, color) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 2,
+                format: vk::Format::R32G32_SFLOAT,
+                offset: offset_of!(Self, tex_coord) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 3,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: offset_of!(Self, normal) as u32,
+            },
+        ]
+    }
+
+    /// Drop the normal, for the pipelines of the tutorial which use `VertexV3`.
+    pub fn to_vertex_v3(&self) -> VertexV3 {
+        VertexV3 {
+            pos: [self.pos[0], self.pos[1], self.pos[2], 1.0],
+            color: self.color,
+            tex_coord: self.tex_coord,
+        }
+    }
+}
+
+/// A particle of the compute sample, simulated in a storage buffer which is also its vertex buffer.
+///
+/// The layout matches the `std430` struct of `30-shader-particles.comp`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Particle {
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub color: [f32; 4],
+}
+impl Particle {
+    pub fn get_binding_descriptions() -> [vk::VertexInputBindingDescription; 1] {
+        [vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: ::std::mem::size_of::<Self>() as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+        }]
+    }
+
+    /// The velocity is only used by the simulation.
+    pub fn get_attribute_descriptions() -> [vk::VertexInputAttributeDescription; 2] {
+        [
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 0,
+                format: vk::Format::R32G32_SFLOAT,
+                offset: offset_of!(Self, position) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 1,
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                offset: offset_of!(Self, color) as u32,
+            },
+        ]
+    }
+}
+
 pub const RECT_VERTICES_DATA: [VertexV1; 4] = [
     VertexV1 {
         pos: [-0.5, -0.5],
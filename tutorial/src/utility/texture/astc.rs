@@ -0,0 +1,791 @@
+//! Decode the LDR ASTC blocks into their texels, row by row.
+//!
+//! The blocks using the HDR endpoint modes, and the invalid ones, are decoded to magenta as the
+//! LDR profile requires. 3D blocks are not supported.
+
+use std::convert::TryInto;
+
+const ERROR_COLOR: [u8; 4] = [255, 0, 255, 255];
+
+/// How the values of a range are encoded, with the count of bits stored along each trit or quint.
+#[derive(Clone, Copy)]
+enum Encoding {
+    Bits(u32),
+    Trits(u32),
+    Quints(u32),
+}
+
+/// The ranges of the integer sequences, from 0..=1 to 0..=255.
+///
+/// The weights use the first 12 ones, the color endpoints at least 0..=5.
+const RANGES: [Encoding; 21] = [
+    Encoding::Bits(1),
+    Encoding::Trits(0),
+    Encoding::Bits(2),
+    Encoding::Quints(0),
+    Encoding::Trits(1),
+    Encoding::Bits(3),
+    Encoding::Quints(1),
+    Encoding::Trits(2),
+    Encoding::Bits(4),
+    Encoding::Quints(2),
+    Encoding::Trits(3),
+    Encoding::Bits(5),
+    Encoding::Quints(3),
+    Encoding::Trits(4),
+    Encoding::Bits(6),
+    Encoding::Quints(4),
+    Encoding::Trits(5),
+    Encoding::Bits(7),
+    Encoding::Quints(5),
+    Encoding::Trits(6),
+    Encoding::Bits(8),
+];
+const MIN_COLOR_RANGE: usize = 4;
+
+/// The count of bits of `count` values of `range`.
+fn sequence_bits(count: u32, range: usize) -> u32 {
+    match RANGES[range] {
+        Encoding::Bits(bits) => count * bits,
+        Encoding::Trits(bits) => count * bits + (8 * count + 4) / 5,
+        Encoding::Quints(bits) => count * bits + (7 * count + 2) / 3,
+    }
+}
+
+/// Read `count` bits at `position` of a block, the bits after `end` being zeros.
+fn read_bits(bits: u128, position: u32, count: u32, end: u32) -> u32 {
+    if count == 0 || position >= end {
+        return 0;
+    }
+
+    let count = count.min(end - position);
+    (bits >> position) as u32 & ((1_u64 << count) - 1) as u32
+}
+
+fn bit(value: u32, position: u32) -> u32 {
+    (value >> position) & 1
+}
+
+/// Decode `count` values of `range` stored from `position`.
+fn decode_sequence(bits: u128, position: u32, count: u32, range: usize) -> Vec<u32> {
+    let end = position + sequence_bits(count, range);
+    let mut position = position;
+    let mut values = Vec::with_capacity(count as usize + 4);
+
+    while (values.len() as u32) < count {
+        match RANGES[range] {
+            Encoding::Bits(bit_count) => {
+                values.push(read_bits(bits, position, bit_count, end));
+                position += bit_count;
+            }
+            Encoding::Trits(bit_count) => {
+                // 5 values share 8 bits, split between their own bits.
+                let mut low_bits = [0; 5];
+                let mut packed = 0;
+                let mut packed_position = 0;
+                for (i, &packed_count) in [2, 2, 1, 2, 1].iter().enumerate() {
+                    low_bits[i] = read_bits(bits, position, bit_count, end);
+                    position += bit_count;
+                    packed |= read_bits(bits, position, packed_count, end) << packed_position;
+                    position += packed_count;
+                    packed_position += packed_count;
+                }
+
+                for (trit, low) in decode_trits(packed).iter().zip(low_bits.iter()) {
+                    values.push(trit << bit_count | low);
+                }
+            }
+            Encoding::Quints(bit_count) => {
+                // 3 values share 7 bits.
+                let mut low_bits = [0; 3];
+                let mut packed = 0;
+                let mut packed_position = 0;
+                for (i, &packed_count) in [3, 2, 2].iter().enumerate() {
+                    low_bits[i] = read_bits(bits, position, bit_count, end);
+                    position += bit_count;
+                    packed |= read_bits(bits, position, packed_count, end) << packed_position;
+                    position += packed_count;
+                    packed_position += packed_count;
+                }
+
+                for (quint, low) in decode_quints(packed).iter().zip(low_bits.iter()) {
+                    values.push(quint << bit_count | low);
+                }
+            }
+        }
+    }
+
+    values.truncate(count as usize);
+    values
+}
+
+fn decode_trits(packed: u32) -> [u32; 5] {
+    let (c, t4, t3) = if (packed >> 2) & 7 == 7 {
+        ((packed >> 5 & 7) << 2 | packed & 3, 2, 2)
+    } else if (packed >> 5) & 3 == 3 {
+        (packed & 0x1F, 2, bit(packed, 7))
+    } else {
+        (packed & 0x1F, bit(packed, 7), (packed >> 5) & 3)
+    };
+
+    let (t2, t1, t0) = if c & 3 == 3 {
+        (2, bit(c, 4), bit(c, 3) << 1 | (bit(c, 2) & !bit(c, 3) & 1))
+    } else if (c >> 2) & 3 == 3 {
+        (2, 2, c & 3)
+    } else {
+        (
+            bit(c, 4),
+            (c >> 2) & 3,
+            bit(c, 1) << 1 | (bit(c, 0) & !bit(c, 1) & 1),
+        )
+    };
+
+    [t0, t1, t2, t3, t4]
+}
+
+fn decode_quints(packed: u32) -> [u32; 3] {
+    if (packed >> 1) & 3 == 3 && (packed >> 5) & 3 == 0 {
+        let q2 = bit(packed, 0) << 2
+            | (bit(packed, 4) & !bit(packed, 0) & 1) << 1
+            | (bit(packed, 3) & !bit(packed, 0) & 1);
+        return [4, 4, q2];
+    }
+
+    let (q2, c) = if (packed >> 1) & 3 == 3 {
+        (
+            4,
+            (packed >> 3 & 3) << 3 | (!(packed >> 5) & 3) << 1 | bit(packed, 0),
+        )
+    } else {
+        ((packed >> 5) & 3, packed & 0x1F)
+    };
+    let (q1, q0) = if c & 7 == 5 {
+        (4, (c >> 3) & 3)
+    } else {
+        ((c >> 3) & 3, c & 7)
+    };
+
+    [q0, q1, q2]
+}
+
+/// Replicate the `from` bits of `value` to fill `to` bits.
+fn replicate(value: u32, from: u32, to: u32) -> u32 {
+    let mut result = 0;
+    let mut remaining = to as i32;
+    while remaining > 0 {
+        remaining -= from as i32;
+        result |= if remaining >= 0 {
+            value << remaining
+        } else {
+            value >> -remaining
+        };
+    }
+
+    result
+}
+
+/// Scale a color endpoint value of `range` to 0..=255.
+fn unquantize_color(value: u32, range: usize) -> u32 {
+    let (bit_count, multiplier, is_trit) = match RANGES[range] {
+        Encoding::Bits(bit_count) => return replicate(value, bit_count, 8),
+        Encoding::Trits(bit_count) => (
+            bit_count,
+            [0, 204, 93, 44, 22, 11, 5][bit_count as usize],
+            true,
+        ),
+        Encoding::Quints(bit_count) => (
+            bit_count,
+            [0, 113, 54, 26, 13, 6][bit_count as usize],
+            false,
+        ),
+    };
+
+    let low = value & ((1 << bit_count) - 1);
+    let digit = value >> bit_count;
+    let a = if low & 1 == 1 { 0x1FF } else { 0 };
+    let x = low >> 1;
+    let b = match (is_trit, bit_count) {
+        (true, 2) => x << 8 | x << 4 | x << 2 | x << 1,
+        (true, 3) => x << 7 | x << 2 | x,
+        (true, 4) => x << 6 | x,
+        (true, 5) => x << 5 | x >> 2,
+        (true, 6) => x << 4 | x >> 4,
+        (false, 2) => x << 8 | x << 3 | x << 2,
+        (false, 3) => x << 7 | x << 1 | x >> 1,
+        (false, 4) => x << 6 | x >> 1,
+        (false, 5) => x << 5 | x >> 3,
+        _ => 0,
+    };
+
+    let t = (digit * multiplier + b) ^ a;
+    (a & 0x80) | (t >> 2)
+}
+
+/// Scale a weight of `range` to 0..=64.
+fn unquantize_weight(value: u32, range: usize) -> u32 {
+    let weight = match RANGES[range] {
+        Encoding::Bits(bit_count) => replicate(value, bit_count, 6),
+        Encoding::Trits(0) => [0, 32, 63][value as usize],
+        Encoding::Quints(0) => [0, 16, 32, 47, 63][value as usize],
+        Encoding::Trits(bit_count) | Encoding::Quints(bit_count) => {
+            let low = value & ((1 << bit_count) - 1);
+            let digit = value >> bit_count;
+            let a = if low & 1 == 1 { 0x7F } else { 0 };
+            let x = low >> 1;
+            let (multiplier, b) = match (RANGES[range], bit_count) {
+                (Encoding::Trits(_), 1) => (50, 0),
+                (Encoding::Trits(_), 2) => (23, x << 6 | x << 2 | x),
+                (Encoding::Trits(_), _) => (11, x << 5 | x),
+                (_, 1) => (28, 0),
+                _ => (13, x << 6 | x << 1),
+            };
+
+            let t = (digit * multiplier + b) ^ a;
+            (a & 0x20) | (t >> 2)
+        }
+    };
+
+    if weight > 32 {
+        weight + 1
+    } else {
+        weight
+    }
+}
+
+/// The layout of the weights of a block mode.
+struct WeightGrid {
+    width: u32,
+    height: u32,
+    is_dual_plane: bool,
+    range: usize,
+}
+
+fn decode_block_mode(mode: u32) -> Option<WeightGrid> {
+    let a = (mode >> 5) & 3;
+    let b = (mode >> 7) & 3;
+    let mut is_dual_plane = bit(mode, 10) == 1;
+    let mut is_high_precision = bit(mode, 9) == 1;
+
+    let (range_bits, width, height) = if mode & 3 != 0 {
+        let range_bits = bit(mode, 4) | (mode & 3) << 1;
+        let (width, height) = match (mode >> 2) & 3 {
+            0 => (b + 4, a + 2),
+            1 => (b + 8, a + 2),
+            2 => (a + 2, b + 8),
+            _ if bit(mode, 8) == 0 => (a + 2, bit(mode, 7) + 6),
+            _ => (bit(mode, 7) + 2, a + 2),
+        };
+        (range_bits, width, height)
+    } else {
+        let range_bits = bit(mode, 4) | ((mode >> 2) & 3) << 1;
+        let (width, height) = match b {
+            0 => (12, a + 2),
+            1 => (a + 2, 12),
+            3 if a == 0 => (6, 10),
+            3 if a == 1 => (10, 6),
+            3 => return None,
+            _ => {
+                is_dual_plane = false;
+                is_high_precision = false;
+                (a + 6, ((mode >> 9) & 3) + 6)
+            }
+        };
+        (range_bits, width, height)
+    };
+
+    if range_bits < 2 {
+        return None;
+    }
+
+    Some(WeightGrid {
+        width,
+        height,
+        is_dual_plane,
+        range: (range_bits - 2) as usize + if is_high_precision { 6 } else { 0 },
+    })
+}
+
+/// The partition of the texel `(x, y)` among `partitions`, from the hash of the specification.
+fn select_partition(seed: u32, x: u32, y: u32, partitions: u32, is_small_block: bool) -> usize {
+    let (x, y) = if is_small_block {
+        (x << 1, y << 1)
+    } else {
+        (x, y)
+    };
+
+    let mut random = seed + (partitions - 1) * 1024;
+    random ^= random >> 15;
+    random = random.wrapping_sub(random << 17);
+    random = random.wrapping_add(random << 7);
+    random = random.wrapping_add(random << 4);
+    random ^= random >> 5;
+    random = random.wrapping_add(random << 16);
+    random ^= random >> 7;
+    random ^= random >> 3;
+    random ^= random << 6;
+    random ^= random >> 17;
+
+    let mut seeds = [0_u32; 8];
+    for (i, seed) in seeds.iter_mut().enumerate() {
+        let value = (random >> (i * 4)) & 0xF;
+        *seed = value * value;
+    }
+
+    let (shift1, shift2) = if seed & 1 == 1 {
+        (
+            if seed & 2 == 2 { 4 } else { 5 },
+            if partitions == 3 { 6 } else { 5 },
+        )
+    } else {
+        (
+            if partitions == 3 { 6 } else { 5 },
+            if seed & 2 == 2 { 4 } else { 5 },
+        )
+    };
+    for (i, seed) in seeds.iter_mut().enumerate() {
+        *seed >>= if i % 2 == 0 { shift1 } else { shift2 };
+    }
+
+    // the terms of z are left out as the blocks are 2D.
+    let a = (seeds[0] * x + seeds[1] * y + (random >> 14)) & 0x3F;
+    let b = (seeds[2] * x + seeds[3] * y + (random >> 10)) & 0x3F;
+    let mut c = (seeds[4] * x + seeds[5] * y + (random >> 6)) & 0x3F;
+    let mut d = (seeds[6] * x + seeds[7] * y + (random >> 2)) & 0x3F;
+    if partitions < 4 {
+        d = 0;
+    }
+    if partitions < 3 {
+        c = 0;
+    }
+
+    if a >= b && a >= c && a >= d {
+        0
+    } else if b >= c && b >= d {
+        1
+    } else if c >= d {
+        2
+    } else {
+        3
+    }
+}
+
+/// Move the bit after the 6 bits of `a` to the highest bit of `b`, and sign extend `a`.
+fn bit_transfer_signed(a: i32, b: i32) -> (i32, i32) {
+    let b = (b >> 1) | (a & 0x80);
+    let a = (a >> 1) & 0x3F;
+    let a = if a & 0x20 != 0 { a - 0x40 } else { a };
+
+    (a, b)
+}
+
+fn blue_contract(color: [i32; 4]) -> [i32; 4] {
+    [
+        (color[0] + color[2]) >> 1,
+        (color[1] + color[2]) >> 1,
+        color[2],
+        color[3],
+    ]
+}
+
+fn clamp_color(color: [i32; 4]) -> [i32; 4] {
+    let mut clamped = color;
+    for channel in clamped.iter_mut() {
+        *channel = (*channel).max(0).min(255);
+    }
+
+    clamped
+}
+
+/// The two endpoints of a color endpoint mode, `None` for the HDR modes.
+fn decode_endpoints(mode: u32, values: &[i32]) -> Option<[[i32; 4]; 2]> {
+    let v = values;
+    let endpoints = match mode {
+        0 => [[v[0], v[0], v[0], 255], [v[1], v[1], v[1], 255]],
+        1 => {
+            let l0 = (v[0] >> 2) | (v[1] & 0xC0);
+            let l1 = (l0 + (v[1] & 0x3F)).min(255);
+            [[l0, l0, l0, 255], [l1, l1, l1, 255]]
+        }
+        4 => [[v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]],
+        5 => {
+            let (d0, l0) = bit_transfer_signed(v[1], v[0]);
+            let (d1, a0) = bit_transfer_signed(v[3], v[2]);
+            clamp_endpoints([[l0, l0, l0, a0], [l0 + d0, l0 + d0, l0 + d0, a0 + d1]])
+        }
+        6 => [
+            [
+                (v[0] * v[3]) >> 8,
+                (v[1] * v[3]) >> 8,
+                (v[2] * v[3]) >> 8,
+                255,
+            ],
+            [v[0], v[1], v[2], 255],
+        ],
+        8 | 12 => {
+            let alphas = if mode == 12 { [v[6], v[7]] } else { [255, 255] };
+            let color0 = [v[0], v[2], v[4], alphas[0]];
+            let color1 = [v[1], v[3], v[5], alphas[1]];
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                [color0, color1]
+            } else {
+                [blue_contract(color1), blue_contract(color0)]
+            }
+        }
+        9 | 13 => {
+            let (d0, r) = bit_transfer_signed(v[1], v[0]);
+            let (d1, g) = bit_transfer_signed(v[3], v[2]);
+            let (d2, b) = bit_transfer_signed(v[5], v[4]);
+            let (d3, a) = if mode == 13 {
+                bit_transfer_signed(v[7], v[6])
+            } else {
+                (0, 255)
+            };
+            let base = [r, g, b, a];
+            let offset = [r + d0, g + d1, b + d2, a + d3];
+            if d0 + d1 + d2 >= 0 {
+                clamp_endpoints([base, offset])
+            } else {
+                clamp_endpoints([blue_contract(offset), blue_contract(base)])
+            }
+        }
+        10 => [
+            [
+                (v[0] * v[3]) >> 8,
+                (v[1] * v[3]) >> 8,
+                (v[2] * v[3]) >> 8,
+                v[4],
+            ],
+            [v[0], v[1], v[2], v[5]],
+        ],
+        _ => return None,
+    };
+
+    Some(endpoints)
+}
+
+fn clamp_endpoints(endpoints: [[i32; 4]; 2]) -> [[i32; 4]; 2] {
+    [clamp_color(endpoints[0]), clamp_color(endpoints[1])]
+}
+
+/// Decode the `block_width` x `block_height` texels of an ASTC block.
+pub(super) fn decode_astc_block(
+    block: &[u8],
+    block_width: u32,
+    block_height: u32,
+    is_srgb: bool,
+) -> Vec<[u8; 4]> {
+    let texel_count = (block_width * block_height) as usize;
+    decode_texels(block, block_width, block_height, is_srgb)
+        .unwrap_or_else(|| vec![ERROR_COLOR; texel_count])
+}
+
+fn decode_texels(
+    block: &[u8],
+    block_width: u32,
+    block_height: u32,
+    is_srgb: bool,
+) -> Option<Vec<[u8; 4]>> {
+    let bits = u128::from_le_bytes(block[..16].try_into().unwrap());
+    let texel_count = (block_width * block_height) as usize;
+    let block_mode = read_bits(bits, 0, 11, 128);
+
+    // the void-extent blocks are filled with a single color, LDR if the bit 9 is not set.
+    if block_mode & 0x1FF == 0x1FC {
+        if bit(block_mode, 9) == 1 {
+            return None;
+        }
+        let mut color = [0_u8; 4];
+        for (channel, value) in color.iter_mut().enumerate() {
+            *value = (read_bits(bits, 64 + channel as u32 * 16, 16, 128) >> 8) as u8;
+        }
+        return Some(vec![color; texel_count]);
+    }
+
+    let grid = decode_block_mode(block_mode)?;
+    let planes = if grid.is_dual_plane { 2 } else { 1 };
+    let weight_count = grid.width * grid.height * planes;
+    let weight_bits = sequence_bits(weight_count, grid.range);
+    if grid.width > block_width
+        || grid.height > block_height
+        || weight_count > 64
+        || weight_bits < 24
+        || weight_bits > 96
+    {
+        return None;
+    }
+
+    let partitions = read_bits(bits, 11, 2, 128) + 1;
+    if partitions == 4 && grid.is_dual_plane {
+        return None;
+    }
+
+    // the weights are stored from the highest bit of the block, below them are the bits
+    // of the endpoint modes which do not fit at their place, and the plane of the second weights.
+    let mut below_weights = 128 - weight_bits;
+    let mut endpoint_modes = [0_u32; 4];
+    let (partition_seed, endpoints_start) = if partitions == 1 {
+        endpoint_modes[0] = read_bits(bits, 13, 4, 128);
+        (0, 17)
+    } else {
+        let seed = read_bits(bits, 13, 10, 128);
+        let mut modes = read_bits(bits, 23, 6, 128);
+        if modes & 3 == 0 {
+            for mode in endpoint_modes.iter_mut() {
+                *mode = modes >> 2;
+            }
+            (seed, 29)
+        } else {
+            let extra_bits = 3 * partitions - 4;
+            below_weights -= extra_bits;
+            modes |= read_bits(bits, below_weights, extra_bits, 128) << 6;
+
+            let base_class = (modes & 3) - 1;
+            for (partition, mode) in endpoint_modes
+                .iter_mut()
+                .enumerate()
+                .take(partitions as usize)
+            {
+                let class = base_class + bit(modes, 2 + partition as u32);
+                let low_bits = (modes >> (2 + partitions + 2 * partition as u32)) & 3;
+                *mode = class << 2 | low_bits;
+            }
+            (seed, 25)
+        }
+    };
+
+    let second_plane_channel = if grid.is_dual_plane {
+        below_weights -= 2;
+        Some(read_bits(bits, below_weights, 2, 128) as usize)
+    } else {
+        None
+    };
+
+    // the color endpoints use the highest range fitting the bits left.
+    let value_count: u32 = endpoint_modes[..partitions as usize]
+        .iter()
+        .map(|mode| ((mode >> 2) + 1) * 2)
+        .sum();
+    if value_count > 18 || endpoints_start > below_weights {
+        return None;
+    }
+    let color_bits = below_weights - endpoints_start;
+    let color_range = (MIN_COLOR_RANGE..RANGES.len())
+        .rev()
+        .find(|&range| sequence_bits(value_count, range) <= color_bits)?;
+
+    let values: Vec<i32> = decode_sequence(bits, endpoints_start, value_count, color_range)
+        .into_iter()
+        .map(|value| unquantize_color(value, color_range) as i32)
+        .collect();
+    let mut endpoints = Vec::with_capacity(partitions as usize);
+    let mut value_offset = 0;
+    for &mode in endpoint_modes[..partitions as usize].iter() {
+        let mode_value_count = (((mode >> 2) + 1) * 2) as usize;
+        endpoints.push(decode_endpoints(
+            mode,
+            &values[value_offset..value_offset + mode_value_count],
+        )?);
+        value_offset += mode_value_count;
+    }
+
+    let weights: Vec<u32> = decode_sequence(bits.reverse_bits(), 0, weight_count, grid.range)
+        .into_iter()
+        .map(|weight| unquantize_weight(weight, grid.range))
+        .collect();
+
+    // the weights are interpolated from their grid to the texels.
+    let scale_x = (1024 + block_width / 2) / (block_width - 1);
+    let scale_y = (1024 + block_height / 2) / (block_height - 1);
+    let grid_weight = |x: u32, y: u32, plane: u32| {
+        let index = ((y * grid.width + x) * planes + plane) as usize;
+        weights.get(index).cloned().unwrap_or(0)
+    };
+
+    let mut texels = Vec::with_capacity(texel_count);
+    for y in 0..block_height {
+        for x in 0..block_width {
+            let grid_x = (scale_x * x * (grid.width - 1) + 32) >> 6;
+            let grid_y = (scale_y * y * (grid.height - 1) + 32) >> 6;
+            let (x0, fraction_x) = (grid_x >> 4, grid_x & 0xF);
+            let (y0, fraction_y) = (grid_y >> 4, grid_y & 0xF);
+            let weight11 = (fraction_x * fraction_y + 8) >> 4;
+            let weight10 = fraction_y - weight11;
+            let weight01 = fraction_x - weight11;
+            let weight00 = 16 - fraction_x - fraction_y + weight11;
+
+            let mut plane_weights = [0_u32; 2];
+            for (plane, weight) in plane_weights.iter_mut().enumerate().take(planes as usize) {
+                let plane = plane as u32;
+                *weight = (grid_weight(x0, y0, plane) * weight00
+                    + grid_weight(x0 + 1, y0, plane) * weight01
+                    + grid_weight(x0, y0 + 1, plane) * weight10
+                    + grid_weight(x0 + 1, y0 + 1, plane) * weight11
+                    + 8)
+                    >> 4;
+            }
+
+            let partition = if partitions > 1 {
+                select_partition(partition_seed, x, y, partitions, texel_count < 31)
+            } else {
+                0
+            };
+            let [endpoint0, endpoint1] = endpoints[partition];
+
+            let mut texel = [0_u8; 4];
+            for (channel, value) in texel.iter_mut().enumerate() {
+                let weight = match second_plane_channel {
+                    Some(second_channel) if second_channel == channel => plane_weights[1],
+                    _ => plane_weights[0],
+                } as i32;
+                // the endpoints are expanded to 16 bits, with a rounding bias for sRGB.
+                let expand = |value: i32| {
+                    if is_srgb {
+                        value << 8 | 0x80
+                    } else {
+                        value << 8 | value
+                    }
+                };
+                let color = (expand(endpoint0[channel]) * (64 - weight)
+                    + expand(endpoint1[channel]) * weight
+                    + 32)
+                    >> 6;
+                *value = (color >> 8) as u8;
+            }
+            texels.push(texel);
+        }
+    }
+
+    Some(texels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pack the `(value, count of bits)` fields from the least significant bit of a block.
+    fn pack_bits(fields: &[(u32, u32)]) -> [u8; 16] {
+        let mut bits = 0_u128;
+        let mut position = 0;
+        for &(value, count) in fields {
+            bits |= (value as u128) << position;
+            position += count;
+        }
+
+        bits.to_le_bytes()
+    }
+
+    #[test]
+    fn void_extent_block_is_a_single_color() {
+        let mut block = [0xFF_u8; 16];
+        block[0] = 0xFC;
+        block[1] = 0xFD;
+        block[8..16].copy_from_slice(&[0x00, 0xFF, 0x00, 0x80, 0x00, 0x40, 0xFF, 0xFF]);
+        let texels = decode_astc_block(&block, 6, 6, false);
+
+        assert_eq!(texels.len(), 36);
+        assert!(texels.iter().all(|texel| *texel == [255, 128, 64, 255]));
+    }
+
+    #[test]
+    fn reserved_block_mode_is_magenta() {
+        let texels = decode_astc_block(&[0; 16], 4, 4, false);
+        assert!(texels.iter().all(|texel| *texel == ERROR_COLOR));
+    }
+
+    #[test]
+    fn trits_and_quints_round_trip() {
+        // every packing of the trits and quints decodes to digits in their range.
+        for packed in 0..256 {
+            assert!(decode_trits(packed).iter().all(|&trit| trit < 3));
+        }
+        for packed in 0..128 {
+            assert!(decode_quints(packed).iter().all(|&quint| quint < 5));
+        }
+        // and every combination of digits has a packing.
+        let mut trits: Vec<[u32; 5]> = (0..256).map(decode_trits).collect();
+        trits.sort();
+        trits.dedup();
+        assert_eq!(trits.len(), 243);
+        let mut quints: Vec<[u32; 3]> = (0..128).map(decode_quints).collect();
+        quints.sort();
+        quints.dedup();
+        assert_eq!(quints.len(), 125);
+    }
+
+    #[test]
+    fn unquantized_ranges_span_the_full_range() {
+        for (range, &encoding) in RANGES.iter().enumerate() {
+            let count = match encoding {
+                Encoding::Bits(bits) => 1 << bits,
+                Encoding::Trits(bits) => 3 << bits,
+                Encoding::Quints(bits) => 5 << bits,
+            };
+
+            // the values of a range are not in the order of their unquantized values.
+            if range >= MIN_COLOR_RANGE {
+                let mut colors: Vec<u32> = (0..count)
+                    .map(|value| unquantize_color(value, range))
+                    .collect();
+                colors.sort();
+                assert_eq!(colors[0], 0);
+                assert_eq!(*colors.last().unwrap(), 255);
+            }
+            if range < 12 {
+                let mut weights: Vec<u32> = (0..count)
+                    .map(|value| unquantize_weight(value, range))
+                    .collect();
+                weights.sort();
+                assert_eq!(weights[0], 0);
+                assert_eq!(*weights.last().unwrap(), 64);
+            }
+        }
+    }
+
+    #[test]
+    fn single_partition_block_interpolates_the_endpoints() {
+        // a 4x4 grid of 2 bits weights: R = 4 and H = 0 with R1 and R2 in the bits 0 and 1, and R0 in the bit 4.
+        // the bits 2 and 3 are 0 and the grid is B + 4 by A + 2, with A = 2 in the bits 5 and 6 and B = 0.
+        let block_mode = 0b10 | 2 << 5;
+        // 1 partition, the endpoint mode 8 (RGB direct) with 6 values of 8 bits.
+        let mut block = pack_bits(&[
+            (block_mode, 11),
+            (0, 2),
+            (8, 4),
+            (0, 8),
+            (255, 8),
+            (64, 8),
+            (192, 8),
+            (128, 8),
+            (128, 8),
+        ]);
+        // the weights are stored from the highest bit, 3 for the odd texels.
+        for byte in block[12..16].iter_mut() {
+            *byte = 0x33;
+        }
+        let texels = decode_astc_block(&block, 4, 4, false);
+
+        assert_eq!(texels[0], [0, 64, 128, 255]);
+        assert_eq!(texels[1], [255, 192, 128, 255]);
+        assert_eq!(texels[2], [0, 64, 128, 255]);
+    }
+
+    #[test]
+    fn partitions_cover_the_block() {
+        // every texel belongs to one of the partitions, and a seed uses more than one of them.
+        for seed in 0..1024 {
+            let partitions: Vec<usize> = (0..36)
+                .map(|i| select_partition(seed, i % 6, i / 6, 2, false))
+                .collect();
+            assert!(partitions.iter().all(|&partition| partition < 2));
+        }
+        let seeds_using_3_partitions = (0..1024)
+            .filter(|&seed| {
+                let partitions: Vec<usize> = (0..144)
+                    .map(|i| select_partition(seed, i % 12, i / 12, 3, false))
+                    .collect();
+                (0..3).all(|partition| partitions.contains(&partition))
+            })
+            .count();
+        assert!(seeds_using_3_partitions > 512);
+    }
+}
@@ -0,0 +1,910 @@
+//! Decode the BC1 to BC7 blocks into their 4x4 texels, row by row.
+//!
+//! BC6H is decoded to half floats, the other formats to 8 bits channels.
+
+use std::convert::TryInto;
+
+/// The weights of the 2, 3 and 4 bits indices of BC6H and BC7, from the first endpoint to the second one.
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// The subset of each texel in the partitions of 2 subsets, shared by BC6H (the first 32) and BC7.
+#[rustfmt::skip]
+const PARTITIONS_2: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1], [0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1], [0, 0, 0, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 1, 1], [0, 0, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1], [0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 1], [0, 0, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 1, 1, 1, 1, 1, 1], [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 1, 1],
+    [0, 0, 0, 1, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1], [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1], [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1],
+    [0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 1, 0, 1, 1, 1, 1], [0, 1, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 1, 0], [0, 1, 1, 1, 0, 0, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0],
+    [0, 0, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0], [0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 0, 1, 1, 1, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 0], [0, 1, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 0, 1],
+    [0, 0, 1, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0], [0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 0],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0], [0, 0, 1, 1, 0, 1, 1, 0, 0, 1, 1, 0, 1, 1, 0, 0],
+    [0, 0, 0, 1, 0, 1, 1, 1, 1, 1, 1, 0, 1, 0, 0, 0], [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0],
+    [0, 1, 1, 1, 0, 0, 0, 1, 1, 0, 0, 0, 1, 1, 1, 0], [0, 0, 1, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 1, 0, 0],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1], [0, 0, 0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 1, 1, 1, 1],
+    [0, 1, 0, 1, 1, 0, 1, 0, 0, 1, 0, 1, 1, 0, 1, 0], [0, 0, 1, 1, 0, 0, 1, 1, 1, 1, 0, 0, 1, 1, 0, 0],
+    [0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 1, 1, 1, 1, 0, 0], [0, 1, 0, 1, 0, 1, 0, 1, 1, 0, 1, 0, 1, 0, 1, 0],
+    [0, 1, 1, 0, 1, 0, 0, 1, 0, 1, 1, 0, 1, 0, 0, 1], [0, 1, 0, 1, 1, 0, 1, 0, 1, 0, 1, 0, 0, 1, 0, 1],
+    [0, 1, 1, 1, 0, 0, 1, 1, 1, 1, 0, 0, 1, 1, 1, 0], [0, 0, 0, 1, 0, 0, 1, 1, 1, 1, 0, 0, 1, 0, 0, 0],
+    [0, 0, 1, 1, 0, 0, 1, 0, 0, 1, 0, 0, 1, 1, 0, 0], [0, 0, 1, 1, 1, 0, 1, 1, 1, 1, 0, 1, 1, 1, 0, 0],
+    [0, 1, 1, 0, 1, 0, 0, 1, 1, 0, 0, 1, 0, 1, 1, 0], [0, 0, 1, 1, 1, 1, 0, 0, 1, 1, 0, 0, 0, 0, 1, 1],
+    [0, 1, 1, 0, 0, 1, 1, 0, 1, 0, 0, 1, 1, 0, 0, 1], [0, 0, 0, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 0, 0, 1, 1, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0], [0, 0, 1, 0, 0, 1, 1, 1, 0, 0, 1, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 1, 1, 0, 0, 1, 0], [0, 0, 0, 0, 0, 1, 0, 0, 1, 1, 1, 0, 0, 1, 0, 0],
+    [0, 1, 1, 0, 1, 1, 0, 0, 1, 0, 0, 1, 0, 0, 1, 1], [0, 0, 1, 1, 0, 1, 1, 0, 1, 1, 0, 0, 1, 0, 0, 1],
+    [0, 1, 1, 0, 0, 0, 1, 1, 1, 0, 0, 1, 1, 1, 0, 0], [0, 0, 1, 1, 1, 0, 0, 1, 1, 1, 0, 0, 0, 1, 1, 0],
+    [0, 1, 1, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 0, 0, 1], [0, 1, 1, 0, 0, 0, 1, 1, 0, 0, 1, 1, 1, 0, 0, 1],
+    [0, 1, 1, 1, 1, 1, 1, 0, 1, 0, 0, 0, 0, 0, 0, 1], [0, 0, 0, 1, 1, 0, 0, 0, 1, 1, 1, 0, 0, 1, 1, 1],
+    [0, 0, 0, 0, 1, 1, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1], [0, 0, 1, 1, 0, 0, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 0, 0, 0, 1, 0, 1, 1, 1, 0, 1, 1, 1, 0], [0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 1, 1, 0, 1, 1, 1],
+];
+
+/// The subset of each texel in the partitions of 3 subsets of BC7.
+#[rustfmt::skip]
+const PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2], [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1], [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2], [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1], [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2], [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2], [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2], [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2], [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2], [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2], [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2], [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2], [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0], [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0], [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2], [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1], [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2], [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2], [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0], [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0], [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1], [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1], [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1], [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1], [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2], [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2], [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2], [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2], [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2], [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2], [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1], [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2], [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// The anchor texel of the second subset of the partitions of 2 subsets.
+#[rustfmt::skip]
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// The anchor texels of the second and third subsets of the partitions of 3 subsets.
+#[rustfmt::skip]
+const ANCHORS_3: [[u8; 64]; 2] = [
+    [
+        3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3,
+        3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15,
+        8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15,
+        3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+    ],
+    [
+        15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8,
+        15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8,
+        15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8,
+        15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+    ],
+];
+
+/// Read the fields of a BC6H or BC7 block, which are stored from its least significant bit.
+struct BitReader {
+    bits: u128,
+    position: u32,
+}
+
+impl BitReader {
+    fn new(block: &[u8]) -> BitReader {
+        BitReader {
+            bits: u128::from_le_bytes(block[..16].try_into().unwrap()),
+            position: 0,
+        }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        if count == 0 || self.position >= 128 {
+            return 0;
+        }
+
+        let value = (self.bits >> self.position) as u32 & ((1_u64 << count) - 1) as u32;
+        self.position += count;
+        value
+    }
+}
+
+/// Whether `texel` is the anchor of one of the subsets, whose index is stored without its highest bit.
+fn is_anchor(subsets: usize, partition: usize, texel: usize) -> bool {
+    match subsets {
+        1 => texel == 0,
+        2 => texel == 0 || texel == ANCHORS_2[partition] as usize,
+        _ => {
+            texel == 0
+                || texel == ANCHORS_3[0][partition] as usize
+                || texel == ANCHORS_3[1][partition] as usize
+        }
+    }
+}
+
+fn subset_of(subsets: usize, partition: usize, texel: usize) -> usize {
+    match subsets {
+        1 => 0,
+        2 => PARTITIONS_2[partition][texel] as usize,
+        _ => PARTITIONS_3[partition][texel] as usize,
+    }
+}
+
+fn index_weight(index: u32, index_bits: u32) -> u32 {
+    match index_bits {
+        2 => WEIGHTS_2[index as usize],
+        3 => WEIGHTS_3[index as usize],
+        _ => WEIGHTS_4[index as usize],
+    }
+}
+
+// BC1 to BC5 --------------------------------------------------------------------------------
+pub(super) fn decode_bc1_block(block: &[u8], has_alpha: bool) -> [[u8; 4]; 16] {
+    let mut texels = decode_color_block(block, true);
+    if !has_alpha {
+        // the transparent texels are black without the alpha channel.
+        for texel in texels.iter_mut() {
+            texel[3] = 255;
+        }
+    }
+
+    texels
+}
+
+pub(super) fn decode_bc2_block(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut texels = decode_color_block(&block[8..16], false);
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let alpha = (block[i / 2] >> ((i % 2) * 4)) & 0xF;
+        texel[3] = alpha * 17;
+    }
+
+    texels
+}
+
+pub(super) fn decode_bc3_block(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut texels = decode_color_block(&block[8..16], false);
+    let alphas = decode_channel_block(&block[0..8]);
+    for (texel, &alpha) in texels.iter_mut().zip(alphas.iter()) {
+        texel[3] = alpha;
+    }
+
+    texels
+}
+
+pub(super) fn decode_bc4_block(block: &[u8]) -> [[u8; 4]; 16] {
+    let reds = decode_channel_block(&block[0..8]);
+    let mut texels = [[0, 0, 0, 255]; 16];
+    for (texel, &red) in texels.iter_mut().zip(reds.iter()) {
+        texel[0] = red;
+    }
+
+    texels
+}
+
+pub(super) fn decode_bc5_block(block: &[u8]) -> [[u8; 4]; 16] {
+    let reds = decode_channel_block(&block[0..8]);
+    let greens = decode_channel_block(&block[8..16]);
+    let mut texels = [[0, 0, 0, 255]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[0] = reds[i];
+        texel[1] = greens[i];
+    }
+
+    texels
+}
+
+/// Decode the 16 texels of a BC1 color block, BC2 and BC3 always use the four colors mode.
+fn decode_color_block(block: &[u8], has_alpha_mode: bool) -> [[u8; 4]; 16] {
+    let color0 = u16::from_le_bytes([block[0], block[1]]);
+    let color1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+    let rgb0 = expand_rgb565(color0);
+    let rgb1 = expand_rgb565(color1);
+    let mix = |weight0: u32, weight1: u32| {
+        let mut color = [0, 0, 0, 255];
+        for channel in 0..3 {
+            color[channel] = ((rgb0[channel] as u32 * weight0 + rgb1[channel] as u32 * weight1)
+                / (weight0 + weight1)) as u8;
+        }
+        color
+    };
+
+    let palette = if color0 > color1 || !has_alpha_mode {
+        [
+            [rgb0[0], rgb0[1], rgb0[2], 255],
+            [rgb1[0], rgb1[1], rgb1[2], 255],
+            mix(2, 1),
+            mix(1, 2),
+        ]
+    } else {
+        [
+            [rgb0[0], rgb0[1], rgb0[2], 255],
+            [rgb1[0], rgb1[1], rgb1[2], 255],
+            mix(1, 1),
+            [0, 0, 0, 0],
+        ]
+    };
+
+    let mut texels = [[0; 4]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[((indices >> (i * 2)) & 0x3) as usize];
+    }
+
+    texels
+}
+
+/// Decode the 16 values of a BC3 alpha block, which is also a BC4 block and half a BC5 one.
+fn decode_channel_block(block: &[u8]) -> [u8; 16] {
+    let value0 = block[0] as u32;
+    let value1 = block[1] as u32;
+    let mut indices = 0_u64;
+    for (i, &byte) in block[2..8].iter().enumerate() {
+        indices |= (byte as u64) << (i * 8);
+    }
+
+    let mut palette = [0_u8; 8];
+    palette[0] = value0 as u8;
+    palette[1] = value1 as u8;
+    if value0 > value1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u32) * value0 + i as u32 * value1) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u32) * value0 + i as u32 * value1) / 5) as u8;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    let mut values = [0; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = palette[((indices >> (i * 3)) & 0x7) as usize];
+    }
+
+    values
+}
+
+fn expand_rgb565(color: u16) -> [u8; 3] {
+    let red = ((color >> 11) & 0x1F) as u8;
+    let green = ((color >> 5) & 0x3F) as u8;
+    let blue = (color & 0x1F) as u8;
+
+    [
+        (red << 3) | (red >> 2),
+        (green << 2) | (green >> 4),
+        (blue << 3) | (blue >> 2),
+    ]
+}
+
+// BC6H --------------------------------------------------------------------------------------
+// the endpoint fields of BC6H, the base endpoint `w` and the deltas `x`, `y` and `z`.
+const RW: usize = 0;
+const GW: usize = 1;
+const BW: usize = 2;
+const RX: usize = 3;
+const GX: usize = 4;
+const BX: usize = 5;
+const RY: usize = 6;
+const GY: usize = 7;
+const BY: usize = 8;
+const RZ: usize = 9;
+const GZ: usize = 10;
+const BZ: usize = 11;
+
+/// The layout of the endpoints of a BC6H mode, and their precision.
+struct Bc6hMode {
+    subsets: usize,
+    /// Whether the other endpoints are stored as deltas to the first one.
+    is_transformed: bool,
+    /// The bits of the first endpoint.
+    endpoint_bits: u32,
+    /// The bits of the red, green and blue channels of the other endpoints.
+    delta_bits: [u32; 3],
+    /// The runs of `(field, first bit, count of bits)` following the mode, in the order they are stored.
+    layout: &'static [(usize, u32, u32)],
+}
+
+#[rustfmt::skip]
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode { subsets: 2, is_transformed: true, endpoint_bits: 10, delta_bits: [5, 5, 5], layout: &[
+        (GY, 4, 1), (BY, 4, 1), (BZ, 4, 1), (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 5),
+        (GZ, 4, 1), (GY, 0, 4), (GX, 0, 5), (BZ, 0, 1), (GZ, 0, 4), (BX, 0, 5), (BZ, 1, 1),
+        (BY, 0, 4), (RY, 0, 5), (BZ, 2, 1), (RZ, 0, 5), (BZ, 3, 1),
+    ] },
+    Bc6hMode { subsets: 2, is_transformed: true, endpoint_bits: 7, delta_bits: [6, 6, 6], layout: &[
+        (GY, 5, 1), (GZ, 4, 1), (GZ, 5, 1), (RW, 0, 7), (BZ, 0, 1), (BZ, 1, 1), (BY, 4, 1),
+        (GW, 0, 7), (BY, 5, 1), (BZ, 2, 1), (GY, 4, 1), (BW, 0, 7), (BZ, 3, 1), (BZ, 5, 1),
+        (BZ, 4, 1), (RX, 0, 6), (GY, 0, 4), (GX, 0, 6), (GZ, 0, 4), (BX, 0, 6), (BY, 0, 4),
+        (RY, 0, 6), (RZ, 0, 6),
+    ] },
+    Bc6hMode { subsets: 2, is_transformed: true, endpoint_bits: 11, delta_bits: [5, 4, 4], layout: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 5), (RW, 10, 1), (GY, 0, 4), (GX, 0, 4),
+        (GW, 10, 1), (BZ, 0, 1), (GZ, 0, 4), (BX, 0, 4), (BW, 10, 1), (BZ, 1, 1), (BY, 0, 4),
+        (RY, 0, 5), (BZ, 2, 1), (RZ, 0, 5), (BZ, 3, 1),
+    ] },
+    Bc6hMode { subsets: 2, is_transformed: true, endpoint_bits: 11, delta_bits: [4, 5, 4], layout: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 4), (RW, 10, 1), (GZ, 4, 1), (GY, 0, 4),
+        (GX, 0, 5), (GW, 10, 1), (GZ, 0, 4), (BX, 0, 4), (BW, 10, 1), (BZ, 1, 1), (BY, 0, 4),
+        (RY, 0, 4), (BZ, 0, 1), (BZ, 2, 1), (RZ, 0, 4), (GY, 4, 1), (BZ, 3, 1),
+    ] },
+    Bc6hMode { subsets: 2, is_transformed: true, endpoint_bits: 11, delta_bits: [4, 4, 5], layout: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 4), (RW, 10, 1), (BY, 4, 1), (GY, 0, 4),
+        (GX, 0, 4), (GW, 10, 1), (BZ, 0, 1), (GZ, 0, 4), (BX, 0, 5), (BW, 10, 1), (BY, 0, 4),
+        (RY, 0, 4), (BZ, 1, 1), (BZ, 2, 1), (RZ, 0, 4), (BZ, 4, 1), (BZ, 3, 1),
+    ] },
+    Bc6hMode { subsets: 2, is_transformed: true, endpoint_bits: 9, delta_bits: [5, 5, 5], layout: &[
+        (RW, 0, 9), (BY, 4, 1), (GW, 0, 9), (GY, 4, 1), (BW, 0, 9), (BZ, 4, 1), (RX, 0, 5),
+        (GZ, 4, 1), (GY, 0, 4), (GX, 0, 5), (BZ, 0, 1), (GZ, 0, 4), (BX, 0, 5), (BZ, 1, 1),
+        (BY, 0, 4), (RY, 0, 5), (BZ, 2, 1), (RZ, 0, 5), (BZ, 3, 1),
+    ] },
+    Bc6hMode { subsets: 2, is_transformed: true, endpoint_bits: 8, delta_bits: [6, 5, 5], layout: &[
+        (RW, 0, 8), (GZ, 4, 1), (BY, 4, 1), (GW, 0, 8), (BZ, 2, 1), (GY, 4, 1), (BW, 0, 8),
+        (BZ, 3, 1), (BZ, 4, 1), (RX, 0, 6), (GY, 0, 4), (GX, 0, 5), (BZ, 0, 1), (GZ, 0, 4),
+        (BX, 0, 5), (BZ, 1, 1), (BY, 0, 4), (RY, 0, 6), (RZ, 0, 6),
+    ] },
+    Bc6hMode { subsets: 2, is_transformed: true, endpoint_bits: 8, delta_bits: [5, 6, 5], layout: &[
+        (RW, 0, 8), (BZ, 0, 1), (BY, 4, 1), (GW, 0, 8), (GY, 5, 1), (GY, 4, 1), (BW, 0, 8),
+        (GZ, 5, 1), (BZ, 4, 1), (RX, 0, 5), (GZ, 4, 1), (GY, 0, 4), (GX, 0, 6), (GZ, 0, 4),
+        (BX, 0, 5), (BZ, 1, 1), (BY, 0, 4), (RY, 0, 5), (BZ, 2, 1), (RZ, 0, 5), (BZ, 3, 1),
+    ] },
+    Bc6hMode { subsets: 2, is_transformed: true, endpoint_bits: 8, delta_bits: [5, 5, 6], layout: &[
+        (RW, 0, 8), (BZ, 1, 1), (BY, 4, 1), (GW, 0, 8), (BY, 5, 1), (GY, 4, 1), (BW, 0, 8),
+        (BZ, 5, 1), (BZ, 4, 1), (RX, 0, 5), (GZ, 4, 1), (GY, 0, 4), (GX, 0, 5), (BZ, 0, 1),
+        (GZ, 0, 4), (BX, 0, 6), (BY, 0, 4), (RY, 0, 5), (BZ, 2, 1), (RZ, 0, 5), (BZ, 3, 1),
+    ] },
+    Bc6hMode { subsets: 2, is_transformed: false, endpoint_bits: 6, delta_bits: [6, 6, 6], layout: &[
+        (RW, 0, 6), (GZ, 4, 1), (BZ, 0, 1), (BZ, 1, 1), (BY, 4, 1), (GW, 0, 6), (GY, 5, 1),
+        (BY, 5, 1), (BZ, 2, 1), (GY, 4, 1), (BW, 0, 6), (GZ, 5, 1), (BZ, 3, 1), (BZ, 5, 1),
+        (BZ, 4, 1), (RX, 0, 6), (GY, 0, 4), (GX, 0, 6), (GZ, 0, 4), (BX, 0, 6), (BY, 0, 4),
+        (RY, 0, 6), (RZ, 0, 6),
+    ] },
+    Bc6hMode { subsets: 1, is_transformed: false, endpoint_bits: 10, delta_bits: [10, 10, 10], layout: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 10), (GX, 0, 10), (BX, 0, 10),
+    ] },
+    Bc6hMode { subsets: 1, is_transformed: true, endpoint_bits: 11, delta_bits: [9, 9, 9], layout: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 9), (RW, 10, 1), (GX, 0, 9), (GW, 10, 1),
+        (BX, 0, 9), (BW, 10, 1),
+    ] },
+    // the highest bits of the first endpoint are reversed in the last two modes.
+    Bc6hMode { subsets: 1, is_transformed: true, endpoint_bits: 12, delta_bits: [8, 8, 8], layout: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 8), (RW, 11, 1), (RW, 10, 1), (GX, 0, 8),
+        (GW, 11, 1), (GW, 10, 1), (BX, 0, 8), (BW, 11, 1), (BW, 10, 1),
+    ] },
+    Bc6hMode { subsets: 1, is_transformed: true, endpoint_bits: 16, delta_bits: [4, 4, 4], layout: &[
+        (RW, 0, 10), (GW, 0, 10), (BW, 0, 10), (RX, 0, 4), (RW, 15, 1), (RW, 14, 1), (RW, 13, 1),
+        (RW, 12, 1), (RW, 11, 1), (RW, 10, 1), (GX, 0, 4), (GW, 15, 1), (GW, 14, 1), (GW, 13, 1),
+        (GW, 12, 1), (GW, 11, 1), (GW, 10, 1), (BX, 0, 4), (BW, 15, 1), (BW, 14, 1), (BW, 13, 1),
+        (BW, 12, 1), (BW, 11, 1), (BW, 10, 1),
+    ] },
+];
+
+/// The index in `BC6H_MODES` of the mode stored in the lowest 2 or 5 bits of a block.
+fn bc6h_mode(reader: &mut BitReader) -> Option<usize> {
+    let mode = reader.read(2);
+    if mode < 2 {
+        return Some(mode as usize);
+    }
+
+    let mode = mode | reader.read(3) << 2;
+    match mode {
+        0x02 => Some(2),
+        0x06 => Some(3),
+        0x0A => Some(4),
+        0x0E => Some(5),
+        0x12 => Some(6),
+        0x16 => Some(7),
+        0x1A => Some(8),
+        0x1E => Some(9),
+        0x03 => Some(10),
+        0x07 => Some(11),
+        0x0B => Some(12),
+        0x0F => Some(13),
+        // the reserved modes.
+        _ => None,
+    }
+}
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+/// Scale an endpoint channel of `bits` bits to the 16 bits range the weights are applied to.
+fn bc6h_unquantize(value: i32, bits: u32, is_signed: bool) -> i32 {
+    if !is_signed {
+        if bits >= 15 || value == 0 {
+            value
+        } else if value == (1 << bits) - 1 {
+            0xFFFF
+        } else {
+            ((value << 16) + 0x8000) >> bits
+        }
+    } else if bits >= 16 || value == 0 {
+        value
+    } else {
+        let magnitude = value.abs();
+        let unquantized = if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7FFF
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+
+        if value < 0 {
+            -unquantized
+        } else {
+            unquantized
+        }
+    }
+}
+
+/// Scale an interpolated channel to the bits of a half float.
+fn bc6h_to_half(value: i32, is_signed: bool) -> u16 {
+    if !is_signed {
+        ((value * 31) >> 6) as u16
+    } else if value < 0 {
+        0x8000 | (((-value) * 31) >> 5) as u16
+    } else {
+        ((value * 31) >> 5) as u16
+    }
+}
+
+/// Decode the 16 texels of a BC6H block into half floats, with an alpha of 1.0.
+///
+/// The reserved modes are decoded to black.
+pub(super) fn decode_bc6h_block(block: &[u8], is_signed: bool) -> [[u16; 4]; 16] {
+    let mut reader = BitReader::new(block);
+    let mode = match bc6h_mode(&mut reader) {
+        Some(mode) => &BC6H_MODES[mode],
+        None => return [[0, 0, 0, 0x3C00]; 16],
+    };
+
+    let mut fields = [0_i32; 12];
+    for &(field, first_bit, count) in mode.layout.iter() {
+        fields[field] |= (reader.read(count) << first_bit) as i32;
+    }
+
+    let subsets = mode.subsets;
+    let partition = if subsets == 2 {
+        reader.read(5) as usize
+    } else {
+        0
+    };
+
+    // the endpoints of each subset are [w, x] and [y, z], with the red, green and blue channels.
+    let mut endpoints = [[0_i32; 3]; 4];
+    for (endpoint, channels) in endpoints.iter_mut().enumerate().take(subsets * 2) {
+        for (channel, value) in channels.iter_mut().enumerate() {
+            let field = fields[endpoint * 3 + channel];
+            *value = if endpoint == 0 {
+                if is_signed {
+                    sign_extend(field, mode.endpoint_bits)
+                } else {
+                    field
+                }
+            } else if mode.is_transformed || is_signed {
+                sign_extend(field, mode.delta_bits[channel])
+            } else {
+                field
+            };
+        }
+    }
+
+    if mode.is_transformed {
+        let mask = (1 << mode.endpoint_bits) - 1;
+        let base = endpoints[0];
+        for channels in endpoints.iter_mut().skip(1).take(subsets * 2 - 1) {
+            for (channel, value) in channels.iter_mut().enumerate() {
+                *value = (base[channel] + *value) & mask;
+                if is_signed {
+                    *value = sign_extend(*value, mode.endpoint_bits);
+                }
+            }
+        }
+    }
+
+    for channels in endpoints.iter_mut() {
+        for value in channels.iter_mut() {
+            *value = bc6h_unquantize(*value, mode.endpoint_bits, is_signed);
+        }
+    }
+
+    let index_bits = if subsets == 2 { 3 } else { 4 };
+    let mut texels = [[0, 0, 0, 0x3C00]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let bits = index_bits - is_anchor(subsets, partition, i) as u32;
+        let weight = index_weight(reader.read(bits), index_bits) as i32;
+        let subset = subset_of(subsets, partition, i);
+        let (endpoint0, endpoint1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+
+        for channel in 0..3 {
+            let value =
+                (endpoint0[channel] * (64 - weight) + endpoint1[channel] * weight + 32) >> 6;
+            texel[channel] = bc6h_to_half(value, is_signed);
+        }
+    }
+
+    texels
+}
+
+// BC7 ---------------------------------------------------------------------------------------
+/// The fields of a BC7 mode, in the order they are stored.
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    /// Whether each endpoint has its own lowest bit.
+    has_endpoint_pbits: bool,
+    /// Whether the two endpoints of each subset share their lowest bit.
+    has_shared_pbits: bool,
+    index_bits: u32,
+    /// The bits of the second set of indices, used by the alpha channel or by the color ones.
+    secondary_index_bits: u32,
+}
+
+#[rustfmt::skip]
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0, has_endpoint_pbits: true, has_shared_pbits: false, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0, has_endpoint_pbits: false, has_shared_pbits: true, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0, has_endpoint_pbits: false, has_shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0, has_endpoint_pbits: true, has_shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6, has_endpoint_pbits: false, has_shared_pbits: false, index_bits: 2, secondary_index_bits: 3 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8, has_endpoint_pbits: false, has_shared_pbits: false, index_bits: 2, secondary_index_bits: 2 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7, has_endpoint_pbits: true, has_shared_pbits: false, index_bits: 4, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5, has_endpoint_pbits: true, has_shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+];
+
+/// Expand a channel of `bits` bits to 8 bits by replicating its highest bits.
+fn expand_channel(value: u32, bits: u32) -> u8 {
+    let value = value << (8 - bits);
+    (value | value >> bits) as u8
+}
+
+/// Decode the 16 texels of a BC7 block, the blocks without a valid mode are transparent black.
+pub(super) fn decode_bc7_block(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut reader = BitReader::new(block);
+    // the mode is the count of zeros before the first set bit.
+    let mode = match (0..8).find(|_| reader.read(1) == 1) {
+        Some(mode) => &BC7_MODES[mode],
+        None => return [[0; 4]; 16],
+    };
+
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let index_selection = reader.read(mode.index_selection_bits);
+
+    // the two endpoints of each subset, the red channel of all the endpoints first.
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0_u32; 4]; 6];
+    for channel in 0..4 {
+        let bits = if channel < 3 {
+            mode.color_bits
+        } else {
+            mode.alpha_bits
+        };
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = reader.read(bits);
+        }
+    }
+
+    let mut pbits = [0_u32; 6];
+    if mode.has_endpoint_pbits {
+        for pbit in pbits.iter_mut().take(endpoint_count) {
+            *pbit = reader.read(1);
+        }
+    } else if mode.has_shared_pbits {
+        for subset in 0..mode.subsets {
+            let pbit = reader.read(1);
+            pbits[subset * 2] = pbit;
+            pbits[subset * 2 + 1] = pbit;
+        }
+    }
+
+    let has_pbits = mode.has_endpoint_pbits || mode.has_shared_pbits;
+    let mut colors = [[0_u8; 4]; 6];
+    for (endpoint, color) in colors.iter_mut().enumerate().take(endpoint_count) {
+        for channel in 0..4 {
+            let bits = if channel < 3 {
+                mode.color_bits
+            } else {
+                mode.alpha_bits
+            };
+            color[channel] = if bits == 0 {
+                255
+            } else if has_pbits {
+                expand_channel(
+                    endpoints[endpoint][channel] << 1 | pbits[endpoint],
+                    bits + 1,
+                )
+            } else {
+                expand_channel(endpoints[endpoint][channel], bits)
+            };
+        }
+    }
+
+    let mut indices = [0_u32; 16];
+    for (i, index) in indices.iter_mut().enumerate() {
+        let bits = mode.index_bits - is_anchor(mode.subsets, partition, i) as u32;
+        *index = reader.read(bits);
+    }
+    let mut secondary_indices = [0_u32; 16];
+    if mode.secondary_index_bits > 0 {
+        for (i, index) in secondary_indices.iter_mut().enumerate() {
+            *index = reader.read(mode.secondary_index_bits - (i == 0) as u32);
+        }
+    }
+
+    let mut texels = [[0_u8; 4]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let subset = subset_of(mode.subsets, partition, i);
+        let (color0, color1) = (colors[subset * 2], colors[subset * 2 + 1]);
+
+        // the color and the alpha use their own indices in the modes with two sets of them.
+        let (color_weight, alpha_weight) = if mode.secondary_index_bits == 0 {
+            let weight = index_weight(indices[i], mode.index_bits);
+            (weight, weight)
+        } else {
+            let primary_weight = index_weight(indices[i], mode.index_bits);
+            let secondary_weight = index_weight(secondary_indices[i], mode.secondary_index_bits);
+            if index_selection == 0 {
+                (primary_weight, secondary_weight)
+            } else {
+                (secondary_weight, primary_weight)
+            }
+        };
+
+        for channel in 0..4 {
+            let weight = if channel < 3 {
+                color_weight
+            } else {
+                alpha_weight
+            };
+            texel[channel] =
+                ((color0[channel] as u32 * (64 - weight) + color1[channel] as u32 * weight + 32)
+                    >> 6) as u8;
+        }
+
+        // the rotation swaps the alpha channel with one of the color channels.
+        if rotation > 0 {
+            texel.swap(rotation as usize - 1, 3);
+        }
+    }
+
+    texels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pack the `(value, count of bits)` fields from the least significant bit of a block.
+    fn pack_bits(fields: &[(u32, u32)]) -> [u8; 16] {
+        let mut bits = 0_u128;
+        let mut position = 0;
+        for &(value, count) in fields {
+            bits |= (value as u128) << position;
+            position += count;
+        }
+        assert!(position <= 128);
+
+        bits.to_le_bytes()
+    }
+
+    #[test]
+    fn anchors_belong_to_their_subset() {
+        for partition in 0..64 {
+            assert_eq!(PARTITIONS_2[partition][0], 0);
+            assert_eq!(PARTITIONS_2[partition][ANCHORS_2[partition] as usize], 1);
+            assert_eq!(PARTITIONS_3[partition][0], 0);
+            assert_eq!(PARTITIONS_3[partition][ANCHORS_3[0][partition] as usize], 1);
+            assert_eq!(PARTITIONS_3[partition][ANCHORS_3[1][partition] as usize], 2);
+        }
+    }
+
+    #[test]
+    fn bc6h_layouts_store_every_endpoint_bit_once() {
+        for (index, mode) in BC6H_MODES.iter().enumerate() {
+            let field_bits = |field: usize| match field {
+                RW | GW | BW => mode.endpoint_bits,
+                _ => mode.delta_bits[field % 3],
+            };
+            let fields = mode.subsets * 6;
+
+            let mut stored = [0_u32; 12];
+            for &(field, first_bit, count) in mode.layout.iter() {
+                let bits = ((1_u32 << count) - 1) << first_bit;
+                assert_eq!(stored[field] & bits, 0);
+                stored[field] |= bits;
+            }
+            for field in 0..fields {
+                assert_eq!(stored[field], (1_u32 << field_bits(field)) - 1);
+            }
+
+            // the mode, the endpoints, the partition and the indices fill the block.
+            let layout_bits: u32 = mode.layout.iter().map(|&(_, _, count)| count).sum();
+            if fields == 12 {
+                let mode_bits = if index < 2 { 2 } else { 5 };
+                assert_eq!(mode_bits + layout_bits + 5 + 46, 128);
+            } else {
+                assert_eq!(5 + layout_bits + 63, 128);
+            }
+        }
+    }
+
+    #[test]
+    fn bc1_interpolates_the_four_colors() {
+        // red and blue endpoints, the indices 0, 1, 2 and 3 on each row.
+        let block = [0x00, 0xF8, 0x1F, 0x00, 0xE4, 0xE4, 0xE4, 0xE4];
+        let texels = decode_bc1_block(&block, true);
+
+        assert_eq!(texels[0], [255, 0, 0, 255]);
+        assert_eq!(texels[1], [0, 0, 255, 255]);
+        assert_eq!(texels[2], [170, 0, 85, 255]);
+        assert_eq!(texels[3], [85, 0, 170, 255]);
+        assert_eq!(texels[12], texels[0]);
+    }
+
+    #[test]
+    fn bc1_three_colors_mode_has_transparent_black() {
+        // the first endpoint is not above the second one.
+        let block = [0x1F, 0x00, 0x00, 0xF8, 0xE4, 0xE4, 0xE4, 0xE4];
+
+        let texels = decode_bc1_block(&block, true);
+        assert_eq!(texels[2], [127, 0, 127, 255]);
+        assert_eq!(texels[3], [0, 0, 0, 0]);
+
+        let texels = decode_bc1_block(&block, false);
+        assert_eq!(texels[3], [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc3_alpha_uses_eight_values() {
+        // 255 and 0 give 6 interpolated values, the indices 0 to 7 on the first row and half the second one.
+        let mut block = [0_u8; 16];
+        block[0..8].copy_from_slice(&[255, 0, 0x88, 0xC6, 0xFA, 0, 0, 0]);
+        block[8..16].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0]);
+        let texels = decode_bc3_block(&block);
+
+        let alphas: Vec<u8> = texels.iter().take(8).map(|texel| texel[3]).collect();
+        assert_eq!(alphas, vec![255, 0, 218, 182, 145, 109, 72, 36]);
+        assert_eq!(texels[0][0..3], [255, 255, 255]);
+    }
+
+    #[test]
+    fn bc5_decodes_red_and_green() {
+        let mut block = [0_u8; 16];
+        block[0] = 200;
+        block[8] = 100;
+        let texels = decode_bc5_block(&block);
+
+        assert!(texels.iter().all(|texel| *texel == [200, 100, 0, 255]));
+    }
+
+    #[test]
+    fn bc7_mode_6_interpolates_with_four_bits_indices() {
+        // mode 6, the endpoints (10, 20, 30, 254) and (100, 110, 120, 254) with 7 bits and a p-bit.
+        let mut fields = vec![(1 << 6, 7)];
+        for &(value0, value1) in &[(10, 100), (20, 110), (30, 120), (254, 254)] {
+            fields.push((value0 >> 1, 7));
+            fields.push((value1 >> 1, 7));
+        }
+        fields.push((0, 1));
+        fields.push((0, 1));
+        // the anchor index has 3 bits, the other ones 4.
+        fields.push((0, 3));
+        fields.push((15, 4));
+        fields.push((8, 4));
+        let texels = decode_bc7_block(&pack_bits(&fields));
+
+        assert_eq!(texels[0], [10, 20, 30, 254]);
+        assert_eq!(texels[1], [100, 110, 120, 254]);
+        // the weight of the index 8 is 34.
+        assert_eq!(texels[2], [58, 68, 78, 254]);
+        assert_eq!(texels[3], [10, 20, 30, 254]);
+    }
+
+    #[test]
+    fn bc7_mode_5_rotates_the_alpha_channel() {
+        // mode 5 with the rotation 1, the red channel is decoded from the alpha endpoints.
+        let fields = [
+            (1 << 5, 6),
+            (1, 2),
+            (0x7F, 7),
+            (0x7F, 7),
+            (0, 7),
+            (0, 7),
+            (0, 7),
+            (0, 7),
+            (64, 8),
+            (64, 8),
+        ];
+        let texels = decode_bc7_block(&pack_bits(&fields));
+
+        assert!(texels.iter().all(|texel| *texel == [64, 0, 0, 255]));
+    }
+
+    #[test]
+    fn bc7_without_mode_is_transparent_black() {
+        assert_eq!(decode_bc7_block(&[0; 16]), [[0; 4]; 16]);
+    }
+
+    #[test]
+    fn bc6h_one_subset_mode_interpolates_halves() {
+        // mode 11, from 0 to the highest 10 bits value, which is unquantized to 0xFFFF.
+        let fields = [
+            (0x03, 5),
+            (0, 10),
+            (0, 10),
+            (0, 10),
+            (0x3FF, 10),
+            (0x3FF, 10),
+            (0x3FF, 10),
+            // the anchor index has 3 bits, the other ones 4.
+            (0, 3),
+            (15, 4),
+            (8, 4),
+        ];
+        let texels = decode_bc6h_block(&pack_bits(&fields), false);
+
+        assert_eq!(texels[0], [0, 0, 0, 0x3C00]);
+        assert_eq!(texels[1], [0x7BFF, 0x7BFF, 0x7BFF, 0x3C00]);
+        // (0xFFFF * 34 + 32) >> 6 = 34815, scaled by 31 / 64.
+        assert_eq!(texels[2], [16863, 16863, 16863, 0x3C00]);
+    }
+
+    #[test]
+    fn bc6h_signed_endpoints_keep_their_sign() {
+        // mode 11 with the signed format, from -1 to the highest positive value of 10 bits.
+        let fields = [
+            (0x03, 5),
+            (0x3FF, 10),
+            (0x3FF, 10),
+            (0x3FF, 10),
+            (0x1FF, 10),
+            (0x1FF, 10),
+            (0x1FF, 10),
+            (0, 3),
+            (15, 4),
+        ];
+        let texels = decode_bc6h_block(&pack_bits(&fields), true);
+
+        // -1 is unquantized to -((1 << 15) + 0x4000) >> 9 = -96, then scaled by 31 / 32.
+        assert_eq!(texels[0], [0x8000 | 93, 0x8000 | 93, 0x8000 | 93, 0x3C00]);
+        assert_eq!(texels[1], [0x7BFF, 0x7BFF, 0x7BFF, 0x3C00]);
+    }
+
+    #[test]
+    fn bc6h_reserved_modes_are_black() {
+        let texels = decode_bc6h_block(&pack_bits(&[(0x13, 5)]), false);
+        assert!(texels.iter().all(|texel| *texel == [0, 0, 0, 0x3C00]));
+    }
+}
@@ -0,0 +1,351 @@
+//! Decode the ETC2 RGB, RGB with punch-through alpha and RGBA blocks into their 4x4 texels, row by row.
+//!
+//! The blocks are stored in big endian, and their texels column by column.
+
+use std::convert::TryInto;
+
+/// The modifiers of the individual and differential modes, for the indices 0 and 1.
+const MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+/// The distances between the paint colors of the T and H modes.
+const DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+/// The modifiers of the EAC alpha blocks, for the 3 bits indices.
+#[rustfmt::skip]
+const ALPHA_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14], [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12], [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11], [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10], [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9], [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9], [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9], [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8], [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+const TRANSPARENT_BLACK: [u8; 4] = [0, 0, 0, 0];
+
+fn bits(block: u64, first_bit: u32, count: u32) -> i32 {
+    ((block >> first_bit) & ((1 << count) - 1)) as i32
+}
+
+fn extend_4(value: i32) -> i32 {
+    value * 17
+}
+
+fn extend_5(value: i32) -> i32 {
+    (value << 3) | (value >> 2)
+}
+
+fn clamp(value: i32) -> u8 {
+    value.max(0).min(255) as u8
+}
+
+fn offset(color: [i32; 3], offset: i32) -> [u8; 4] {
+    [
+        clamp(color[0] + offset),
+        clamp(color[1] + offset),
+        clamp(color[2] + offset),
+        255,
+    ]
+}
+
+/// The 2 bits index of the texel `(x, y)`, its highest bit being stored in the upper half of the indices.
+fn texel_index(block: u64, x: usize, y: usize) -> usize {
+    let bit = x * 4 + y;
+    (((block >> (16 + bit)) & 1) << 1 | (block >> bit) & 1) as usize
+}
+
+/// Decode the 16 texels of an ETC2 color block.
+///
+/// With `has_punch_through_alpha`, the differential bit tells whether the block is opaque,
+/// and the transparent texels are black.
+pub(super) fn decode_etc2_block(block: &[u8], has_punch_through_alpha: bool) -> [[u8; 4]; 16] {
+    let block = u64::from_be_bytes(block[..8].try_into().unwrap());
+    let is_differential = bits(block, 33, 1) == 1;
+    let is_opaque = !has_punch_through_alpha || is_differential;
+
+    if !has_punch_through_alpha && !is_differential {
+        let colors = [
+            [
+                extend_4(bits(block, 60, 4)),
+                extend_4(bits(block, 52, 4)),
+                extend_4(bits(block, 44, 4)),
+            ],
+            [
+                extend_4(bits(block, 56, 4)),
+                extend_4(bits(block, 48, 4)),
+                extend_4(bits(block, 40, 4)),
+            ],
+        ];
+        return decode_subblocks(block, colors, true);
+    }
+
+    // the overflow of a channel of the second color selects the T, H and planar modes.
+    let red = bits(block, 59, 5);
+    let green = bits(block, 51, 5);
+    let blue = bits(block, 43, 5);
+    let red_delta = red + ((bits(block, 56, 3) << 29) >> 29);
+    let green_delta = green + ((bits(block, 48, 3) << 29) >> 29);
+    let blue_delta = blue + ((bits(block, 40, 3) << 29) >> 29);
+
+    if red_delta < 0 || red_delta > 31 {
+        decode_t_block(block, is_opaque)
+    } else if green_delta < 0 || green_delta > 31 {
+        decode_h_block(block, is_opaque)
+    } else if blue_delta < 0 || blue_delta > 31 {
+        decode_planar_block(block)
+    } else {
+        let colors = [
+            [extend_5(red), extend_5(green), extend_5(blue)],
+            [
+                extend_5(red_delta),
+                extend_5(green_delta),
+                extend_5(blue_delta),
+            ],
+        ];
+        decode_subblocks(block, colors, is_opaque)
+    }
+}
+
+/// Decode the two subblocks of the individual and differential modes.
+fn decode_subblocks(block: u64, colors: [[i32; 3]; 2], is_opaque: bool) -> [[u8; 4]; 16] {
+    let is_flipped = bits(block, 32, 1) == 1;
+    let tables = [bits(block, 37, 3) as usize, bits(block, 34, 3) as usize];
+
+    let mut texels = [[0; 4]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let (x, y) = (i % 4, i / 4);
+        // the subblocks are side by side, or one above the other when flipped.
+        let subblock = if is_flipped { y / 2 } else { x / 2 };
+        let modifiers = MODIFIERS[tables[subblock]];
+
+        *texel = match (texel_index(block, x, y), is_opaque) {
+            (0, true) => offset(colors[subblock], modifiers[0]),
+            (0, false) => offset(colors[subblock], 0),
+            (1, _) => offset(colors[subblock], modifiers[1]),
+            (2, true) => offset(colors[subblock], -modifiers[0]),
+            (2, false) => TRANSPARENT_BLACK,
+            _ => offset(colors[subblock], -modifiers[1]),
+        };
+    }
+
+    texels
+}
+
+/// Decode the texels of the T and H modes from their four paint colors.
+fn decode_paint_colors(block: u64, paint_colors: [[u8; 4]; 4], is_opaque: bool) -> [[u8; 4]; 16] {
+    let mut texels = [[0; 4]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let index = texel_index(block, i % 4, i / 4);
+        *texel = if index == 2 && !is_opaque {
+            TRANSPARENT_BLACK
+        } else {
+            paint_colors[index]
+        };
+    }
+
+    texels
+}
+
+fn decode_t_block(block: u64, is_opaque: bool) -> [[u8; 4]; 16] {
+    let color0 = [
+        extend_4(bits(block, 59, 2) << 2 | bits(block, 56, 2)),
+        extend_4(bits(block, 52, 4)),
+        extend_4(bits(block, 48, 4)),
+    ];
+    let color1 = [
+        extend_4(bits(block, 44, 4)),
+        extend_4(bits(block, 40, 4)),
+        extend_4(bits(block, 36, 4)),
+    ];
+    let distance = DISTANCES[(bits(block, 34, 2) << 1 | bits(block, 32, 1)) as usize];
+
+    let paint_colors = [
+        offset(color0, 0),
+        offset(color1, distance),
+        offset(color1, 0),
+        offset(color1, -distance),
+    ];
+    decode_paint_colors(block, paint_colors, is_opaque)
+}
+
+fn decode_h_block(block: u64, is_opaque: bool) -> [[u8; 4]; 16] {
+    let color0 = [
+        bits(block, 59, 4),
+        bits(block, 56, 3) << 1 | bits(block, 52, 1),
+        bits(block, 51, 1) << 3 | bits(block, 47, 3),
+    ];
+    let color1 = [bits(block, 43, 4), bits(block, 39, 4), bits(block, 35, 4)];
+
+    // the lowest bit of the distance is whether the first color is above the second one.
+    let value = |color: [i32; 3]| color[0] << 8 | color[1] << 4 | color[2];
+    let distance_index =
+        bits(block, 34, 1) << 2 | bits(block, 32, 1) << 1 | (value(color0) >= value(color1)) as i32;
+    let distance = DISTANCES[distance_index as usize];
+
+    let color0 = [
+        extend_4(color0[0]),
+        extend_4(color0[1]),
+        extend_4(color0[2]),
+    ];
+    let color1 = [
+        extend_4(color1[0]),
+        extend_4(color1[1]),
+        extend_4(color1[2]),
+    ];
+    let paint_colors = [
+        offset(color0, distance),
+        offset(color0, -distance),
+        offset(color1, distance),
+        offset(color1, -distance),
+    ];
+    decode_paint_colors(block, paint_colors, is_opaque)
+}
+
+/// Decode the planar mode, which is always opaque.
+fn decode_planar_block(block: u64) -> [[u8; 4]; 16] {
+    let extend_6 = |value: i32| (value << 2) | (value >> 4);
+    let extend_7 = |value: i32| (value << 1) | (value >> 6);
+
+    // the colors of the texels (0, 0), (4, 0) and (0, 4).
+    let origin = [
+        extend_6(bits(block, 57, 6)),
+        extend_7(bits(block, 56, 1) << 6 | bits(block, 49, 6)),
+        extend_6(bits(block, 48, 1) << 5 | bits(block, 43, 2) << 3 | bits(block, 39, 3)),
+    ];
+    let horizontal = [
+        extend_6(bits(block, 34, 5) << 1 | bits(block, 32, 1)),
+        extend_7(bits(block, 25, 7)),
+        extend_6(bits(block, 19, 6)),
+    ];
+    let vertical = [
+        extend_6(bits(block, 13, 6)),
+        extend_7(bits(block, 6, 7)),
+        extend_6(bits(block, 0, 6)),
+    ];
+
+    let mut texels = [[0; 4]; 16];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let (x, y) = ((i % 4) as i32, (i / 4) as i32);
+        for channel in 0..3 {
+            texel[channel] = clamp(
+                (x * (horizontal[channel] - origin[channel])
+                    + y * (vertical[channel] - origin[channel])
+                    + 4 * origin[channel]
+                    + 2)
+                    >> 2,
+            );
+        }
+        texel[3] = 255;
+    }
+
+    texels
+}
+
+/// Decode the 16 values of an EAC block, which is the alpha half of an ETC2 RGBA block.
+pub(super) fn decode_eac_block(block: &[u8]) -> [u8; 16] {
+    let block = u64::from_be_bytes(block[..8].try_into().unwrap());
+    let base = bits(block, 56, 8);
+    let multiplier = bits(block, 52, 4);
+    let modifiers = ALPHA_MODIFIERS[bits(block, 48, 4) as usize];
+
+    let mut values = [0; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        let (x, y) = (i % 4, i / 4);
+        let index = bits(block, 45 - 3 * (x * 4 + y) as u32, 3);
+        *value = clamp(base + modifiers[index as usize] * multiplier);
+    }
+
+    values
+}
+
+pub(super) fn decode_etc2_rgba_block(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut texels = decode_etc2_block(&block[8..16], false);
+    let alphas = decode_eac_block(&block[0..8]);
+    for (texel, &alpha) in texels.iter_mut().zip(alphas.iter()) {
+        texel[3] = alpha;
+    }
+
+    texels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn individual_mode_uses_a_color_per_subblock() {
+        // red and blue subblocks side by side, the table 0 and the index 0 (+2) everywhere.
+        let block = 0xF0_00_0F_00_00_00_00_00_u64.to_be_bytes();
+        let texels = decode_etc2_block(&block, false);
+
+        assert_eq!(texels[0], [255, 2, 2, 255]);
+        assert_eq!(texels[1], [255, 2, 2, 255]);
+        assert_eq!(texels[2], [2, 2, 255, 255]);
+        assert_eq!(texels[15], [2, 2, 255, 255]);
+    }
+
+    #[test]
+    fn differential_mode_applies_the_indices() {
+        // the color 16 on 5 bits with a delta of 0, the table 1, flipped subblocks.
+        let mut block = 0x80_80_80_02_00_00_00_00_u64 | 1 << 37 | 1 << 34 | 1 << 32;
+        // the texel (1, 0) uses the index 1 (+17), the texel (0, 1) the index 3 (-17).
+        block |= 1 << 4;
+        block |= 1 << 17 | 1 << 1;
+        let texels = decode_etc2_block(&block.to_be_bytes(), false);
+
+        assert_eq!(texels[0], [137, 137, 137, 255]);
+        assert_eq!(texels[1], [149, 149, 149, 255]);
+        assert_eq!(texels[4], [115, 115, 115, 255]);
+    }
+
+    #[test]
+    fn punch_through_alpha_has_transparent_texels() {
+        // not opaque, the texel (0, 0) uses the index 2.
+        let block = 0x80_80_80_00_00_01_00_00_u64;
+        let texels = decode_etc2_block(&block.to_be_bytes(), true);
+
+        assert_eq!(texels[0], [0, 0, 0, 0]);
+        // the index 0 has no modifier when the block is not opaque.
+        assert_eq!(texels[1], [132, 132, 132, 255]);
+    }
+
+    #[test]
+    fn planar_mode_interpolates_the_corners() {
+        // the blue channel overflows, the origin is black and the horizontal and vertical colors white.
+        let mut block = 0x00_00_04_02_00_00_00_00_u64;
+        block |= 0x1F << 34 | 1 << 32 | 0x7F << 25 | 0x3F << 19;
+        block |= 0x3F << 13 | 0x7F << 6 | 0x3F;
+        let texels = decode_etc2_block(&block.to_be_bytes(), false);
+
+        assert_eq!(texels[0], [0, 0, 0, 255]);
+        assert_eq!(texels[1], [64, 64, 64, 255]);
+        assert_eq!(texels[5], [128, 128, 128, 255]);
+        assert_eq!(texels[15], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn eac_applies_the_multiplier() {
+        // the base 128, the multiplier 2, the table 0, the indices 0 to 7 on the first two columns.
+        let mut block = 128_u64 << 56 | 2 << 52;
+        for i in 0..8 {
+            block |= (i as u64) << (45 - 3 * i);
+        }
+        let values = decode_eac_block(&block.to_be_bytes());
+
+        let first_column: Vec<u8> = (0..4).map(|y| values[y * 4]).collect();
+        let second_column: Vec<u8> = (0..4).map(|y| values[y * 4 + 1]).collect();
+        assert_eq!(first_column, vec![122, 116, 110, 98]);
+        assert_eq!(second_column, vec![132, 138, 144, 156]);
+    }
+}
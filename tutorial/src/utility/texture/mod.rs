@@ -0,0 +1,581 @@
+//! Load the textures stored in KTX2 and DDS files, usually compressed with their mip chain.
+//!
+//! The levels are kept as they are stored, so that they can be uploaded without any decoding.
+//! `TextureData::decompress` decodes them on the CPU for the devices which can not sample their format,
+//! to half floats for BC6H and to 8 bits channels for the other BCn, ETC2 and ASTC formats.
+//! Only the first layer and face of 2D textures are read, and KTX2 supercompression is not supported.
+
+mod astc;
+mod bcn;
+mod etc2;
+
+use ash::vk;
+
+use std::cmp::{
+    max,
+    min,
+};
+use std::convert::TryInto;
+use std::path::Path;
+
+use crate::utility::error::{
+    VkTutorialError,
+    VkTutorialResult,
+};
+use crate::utility::share::mipmap::mip_level_count;
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const KTX2_HEADER_SIZE: usize = 80;
+
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+const DDS_HEADER_SIZE: usize = 128;
+const DDS_DX10_HEADER_SIZE: usize = 20;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+
+/// The levels of a texture in the layout of `format`, the base level first.
+pub struct TextureData {
+    pub format: vk::Format,
+    pub width: u32,
+    pub height: u32,
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl TextureData {
+    pub fn mip_levels(&self) -> u32 {
+        self.levels.len() as u32
+    }
+
+    /// The size of the level `mip_level`, not rounded up to the block size.
+    pub fn level_extent(&self, mip_level: u32) -> (u32, u32) {
+        (
+            max(self.width >> mip_level, 1),
+            max(self.height >> mip_level, 1),
+        )
+    }
+
+    /// Decode the levels into R8G8B8A8 texels with the sRGB encoding of `format`,
+    /// or into R16G16B16A16_SFLOAT texels for BC6H.
+    pub fn decompress(&self) -> VkTutorialResult<TextureData> {
+        let format = decompressed_format(self.format).ok_or_else(|| {
+            VkTutorialError::TextureLoad(format!("{:?} can not be decoded on the CPU", self.format))
+        })?;
+
+        let levels = self
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(mip_level, level)| {
+                let (width, height) = self.level_extent(mip_level as u32);
+                decode_blocks(self.format, format, level, width, height)
+            })
+            .collect::<VkTutorialResult<Vec<_>>>()?;
+
+        Ok(TextureData {
+            format,
+            width: self.width,
+            height: self.height,
+            levels,
+        })
+    }
+}
+
+/// The size of a block in texels, and its size in bytes, for the formats the containers may hold.
+pub fn block_size(format: vk::Format) -> Option<(u32, u32, usize)> {
+    let block_size = match format {
+        vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::B8G8R8A8_SRGB => (1, 1, 4),
+        vk::Format::R16G16B16A16_SFLOAT => (1, 1, 8),
+        vk::Format::BC1_RGB_UNORM_BLOCK
+        | vk::Format::BC1_RGB_SRGB_BLOCK
+        | vk::Format::BC1_RGBA_UNORM_BLOCK
+        | vk::Format::BC1_RGBA_SRGB_BLOCK
+        | vk::Format::BC4_UNORM_BLOCK
+        | vk::Format::BC4_SNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8_SRGB_BLOCK
+        | vk::Format::ETC2_R8G8B8A1_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8A1_SRGB_BLOCK => (4, 4, 8),
+        vk::Format::BC2_UNORM_BLOCK
+        | vk::Format::BC2_SRGB_BLOCK
+        | vk::Format::BC3_UNORM_BLOCK
+        | vk::Format::BC3_SRGB_BLOCK
+        | vk::Format::BC5_UNORM_BLOCK
+        | vk::Format::BC5_SNORM_BLOCK
+        | vk::Format::BC6H_UFLOAT_BLOCK
+        | vk::Format::BC6H_SFLOAT_BLOCK
+        | vk::Format::BC7_UNORM_BLOCK
+        | vk::Format::BC7_SRGB_BLOCK
+        | vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK => (4, 4, 16),
+        vk::Format::ASTC_4X4_UNORM_BLOCK | vk::Format::ASTC_4X4_SRGB_BLOCK => (4, 4, 16),
+        vk::Format::ASTC_5X5_UNORM_BLOCK | vk::Format::ASTC_5X5_SRGB_BLOCK => (5, 5, 16),
+        vk::Format::ASTC_6X6_UNORM_BLOCK | vk::Format::ASTC_6X6_SRGB_BLOCK => (6, 6, 16),
+        vk::Format::ASTC_8X8_UNORM_BLOCK | vk::Format::ASTC_8X8_SRGB_BLOCK => (8, 8, 16),
+        vk::Format::ASTC_10X10_UNORM_BLOCK | vk::Format::ASTC_10X10_SRGB_BLOCK => (10, 10, 16),
+        vk::Format::ASTC_12X12_UNORM_BLOCK | vk::Format::ASTC_12X12_SRGB_BLOCK => (12, 12, 16),
+        _ => return None,
+    };
+
+    Some(block_size)
+}
+
+/// The size in bytes of a level of `width` x `height` texels, `None` if it does not fit in memory.
+pub fn level_size(format: vk::Format, width: u32, height: u32) -> Option<usize> {
+    let (block_width, block_height, block_bytes) = block_size(format)?;
+    let blocks_x = (width as usize + block_width as usize - 1) / block_width as usize;
+    let blocks_y = (height as usize + block_height as usize - 1) / block_height as usize;
+
+    blocks_x.checked_mul(blocks_y)?.checked_mul(block_bytes)
+}
+
+/// The format of the texels decoded on the CPU from `format`, `None` if it can not be decoded.
+fn decompressed_format(format: vk::Format) -> Option<vk::Format> {
+    let decompressed_format = match format {
+        vk::Format::BC1_RGB_SRGB_BLOCK
+        | vk::Format::BC1_RGBA_SRGB_BLOCK
+        | vk::Format::BC2_SRGB_BLOCK
+        | vk::Format::BC3_SRGB_BLOCK
+        | vk::Format::BC7_SRGB_BLOCK
+        | vk::Format::ETC2_R8G8B8_SRGB_BLOCK
+        | vk::Format::ETC2_R8G8B8A1_SRGB_BLOCK
+        | vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK
+        | vk::Format::ASTC_4X4_SRGB_BLOCK
+        | vk::Format::ASTC_5X5_SRGB_BLOCK
+        | vk::Format::ASTC_6X6_SRGB_BLOCK
+        | vk::Format::ASTC_8X8_SRGB_BLOCK
+        | vk::Format::ASTC_10X10_SRGB_BLOCK
+        | vk::Format::ASTC_12X12_SRGB_BLOCK => vk::Format::R8G8B8A8_SRGB,
+        vk::Format::BC1_RGB_UNORM_BLOCK
+        | vk::Format::BC1_RGBA_UNORM_BLOCK
+        | vk::Format::BC2_UNORM_BLOCK
+        | vk::Format::BC3_UNORM_BLOCK
+        | vk::Format::BC4_UNORM_BLOCK
+        | vk::Format::BC5_UNORM_BLOCK
+        | vk::Format::BC7_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8A1_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK
+        | vk::Format::ASTC_4X4_UNORM_BLOCK
+        | vk::Format::ASTC_5X5_UNORM_BLOCK
+        | vk::Format::ASTC_6X6_UNORM_BLOCK
+        | vk::Format::ASTC_8X8_UNORM_BLOCK
+        | vk::Format::ASTC_10X10_UNORM_BLOCK
+        | vk::Format::ASTC_12X12_UNORM_BLOCK => vk::Format::R8G8B8A8_UNORM,
+        vk::Format::BC6H_UFLOAT_BLOCK | vk::Format::BC6H_SFLOAT_BLOCK => {
+            vk::Format::R16G16B16A16_SFLOAT
+        }
+        _ => return None,
+    };
+
+    Some(decompressed_format)
+}
+
+/// Load a `.ktx2` or a `.dds` file.
+pub fn load_texture(texture_path: &Path) -> VkTutorialResult<TextureData> {
+    let bytes = std::fs::read(texture_path).map_err(|error| {
+        VkTutorialError::TextureLoad(format!("Failed to read {:?}: {}", texture_path, error))
+    })?;
+
+    if bytes.starts_with(&KTX2_IDENTIFIER) {
+        parse_ktx2(&bytes)
+    } else if bytes.starts_with(DDS_MAGIC) {
+        parse_dds(&bytes)
+    } else {
+        Err(VkTutorialError::TextureLoad(format!(
+            "{:?} is neither a KTX2 nor a DDS file",
+            texture_path
+        )))
+    }
+}
+
+pub fn parse_ktx2(bytes: &[u8]) -> VkTutorialResult<TextureData> {
+    if bytes.len() < KTX2_HEADER_SIZE || !bytes.starts_with(&KTX2_IDENTIFIER) {
+        return Err(malformed("KTX2 header"));
+    }
+
+    let format = vk::Format::from_raw(read_u32(bytes, 12)? as i32);
+    let width = read_u32(bytes, 20)?;
+    let height = max(read_u32(bytes, 24)?, 1);
+    let depth = read_u32(bytes, 28)?;
+    let supercompression_scheme = read_u32(bytes, 44)?;
+
+    if width == 0 {
+        return Err(malformed("KTX2 header"));
+    }
+    // the levels after a full mip chain are not read, whatever the file claims.
+    let level_count = min(max(read_u32(bytes, 40)?, 1), mip_level_count(width, height));
+
+    if depth > 1 {
+        return Err(VkTutorialError::TextureLoad(String::from(
+            "KTX2 3D textures are not supported",
+        )));
+    }
+    if supercompression_scheme != 0 {
+        return Err(VkTutorialError::TextureLoad(format!(
+            "KTX2 supercompression scheme {} is not supported",
+            supercompression_scheme
+        )));
+    }
+    if block_size(format).is_none() {
+        return Err(VkTutorialError::TextureLoad(format!(
+            "KTX2 format {:?} is not supported",
+            format
+        )));
+    }
+
+    let mut levels = Vec::with_capacity(level_count as usize);
+    for mip_level in 0..level_count {
+        // the level index follows the header, 3 u64 per level.
+        let index_offset = KTX2_HEADER_SIZE + mip_level as usize * 24;
+        let byte_offset = read_u64(bytes, index_offset)? as usize;
+        let (level_width, level_height) = (max(width >> mip_level, 1), max(height >> mip_level, 1));
+        // the layers and faces follow the first one in a level.
+        let level = level_size(format, level_width, level_height)
+            .and_then(|level_length| byte_offset.checked_add(level_length))
+            .and_then(|level_end| bytes.get(byte_offset..level_end))
+            .ok_or_else(|| malformed("KTX2 level"))?;
+        levels.push(level.to_vec());
+    }
+
+    Ok(TextureData {
+        format,
+        width,
+        height,
+        levels,
+    })
+}
+
+pub fn parse_dds(bytes: &[u8]) -> VkTutorialResult<TextureData> {
+    if bytes.len() < DDS_HEADER_SIZE || !bytes.starts_with(DDS_MAGIC) {
+        return Err(malformed("DDS header"));
+    }
+
+    let height = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 16)?;
+    if width == 0 || height == 0 {
+        return Err(malformed("DDS header"));
+    }
+    // the levels after a full mip chain are not read, whatever the file claims.
+    let level_count = min(max(read_u32(bytes, 28)?, 1), mip_level_count(width, height));
+    let pixel_format_flags = read_u32(bytes, 80)?;
+    let four_cc = &bytes[84..88];
+
+    let mut data_offset = DDS_HEADER_SIZE;
+    let format = if pixel_format_flags & DDPF_FOURCC != 0 {
+        match four_cc {
+            b"DXT1" => vk::Format::BC1_RGBA_UNORM_BLOCK,
+            b"DXT2" | b"DXT3" => vk::Format::BC2_UNORM_BLOCK,
+            b"DXT4" | b"DXT5" => vk::Format::BC3_UNORM_BLOCK,
+            b"ATI1" | b"BC4U" => vk::Format::BC4_UNORM_BLOCK,
+            b"BC4S" => vk::Format::BC4_SNORM_BLOCK,
+            b"ATI2" | b"BC5U" => vk::Format::BC5_UNORM_BLOCK,
+            b"BC5S" => vk::Format::BC5_SNORM_BLOCK,
+            b"DX10" => {
+                data_offset += DDS_DX10_HEADER_SIZE;
+                dxgi_format(read_u32(bytes, DDS_HEADER_SIZE)?)
+            }
+            _ => vk::Format::UNDEFINED,
+        }
+    } else if pixel_format_flags & DDPF_RGB != 0 && read_u32(bytes, 88)? == 32 {
+        // the channel masks tell the order of the bytes.
+        match (read_u32(bytes, 92)?, read_u32(bytes, 100)?) {
+            (0x0000_00FF, 0x00FF_0000) => vk::Format::R8G8B8A8_UNORM,
+            (0x00FF_0000, 0x0000_00FF) => vk::Format::B8G8R8A8_UNORM,
+            _ => vk::Format::UNDEFINED,
+        }
+    } else {
+        vk::Format::UNDEFINED
+    };
+
+    if block_size(format).is_none() {
+        return Err(VkTutorialError::TextureLoad(format!(
+            "DDS pixel format {:?} is not supported",
+            String::from_utf8_lossy(four_cc)
+        )));
+    }
+
+    // the levels of the first layer are stored one after the other.
+    let mut levels = Vec::with_capacity(level_count as usize);
+    for mip_level in 0..level_count {
+        let (level_width, level_height) = (max(width >> mip_level, 1), max(height >> mip_level, 1));
+        let level = level_size(format, level_width, level_height)
+            .and_then(|level_length| data_offset.checked_add(level_length))
+            .and_then(|level_end| bytes.get(data_offset..level_end))
+            .ok_or_else(|| malformed("DDS level"))?;
+        levels.push(level.to_vec());
+        data_offset += level.len();
+    }
+
+    Ok(TextureData {
+        format,
+        width,
+        height,
+        levels,
+    })
+}
+
+fn dxgi_format(dxgi_format: u32) -> vk::Format {
+    match dxgi_format {
+        28 => vk::Format::R8G8B8A8_UNORM,
+        29 => vk::Format::R8G8B8A8_SRGB,
+        71 => vk::Format::BC1_RGBA_UNORM_BLOCK,
+        72 => vk::Format::BC1_RGBA_SRGB_BLOCK,
+        74 => vk::Format::BC2_UNORM_BLOCK,
+        75 => vk::Format::BC2_SRGB_BLOCK,
+        77 => vk::Format::BC3_UNORM_BLOCK,
+        78 => vk::Format::BC3_SRGB_BLOCK,
+        80 => vk::Format::BC4_UNORM_BLOCK,
+        81 => vk::Format::BC4_SNORM_BLOCK,
+        83 => vk::Format::BC5_UNORM_BLOCK,
+        84 => vk::Format::BC5_SNORM_BLOCK,
+        87 => vk::Format::B8G8R8A8_UNORM,
+        91 => vk::Format::B8G8R8A8_SRGB,
+        95 => vk::Format::BC6H_UFLOAT_BLOCK,
+        96 => vk::Format::BC6H_SFLOAT_BLOCK,
+        98 => vk::Format::BC7_UNORM_BLOCK,
+        99 => vk::Format::BC7_SRGB_BLOCK,
+        _ => vk::Format::UNDEFINED,
+    }
+}
+
+fn malformed(part: &str) -> VkTutorialError {
+    VkTutorialError::TextureLoad(format!("Truncated or malformed {}", part))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> VkTutorialResult<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .ok_or_else(|| malformed("header"))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> VkTutorialResult<u64> {
+    bytes
+        .get(offset..offset + 8)
+        .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+        .ok_or_else(|| malformed("header"))
+}
+
+// CPU decoding ------------------------------------------------------------------------------
+fn decode_blocks(
+    format: vk::Format,
+    decompressed_format: vk::Format,
+    level: &[u8],
+    width: u32,
+    height: u32,
+) -> VkTutorialResult<Vec<u8>> {
+    let (block_width, block_height, block_bytes) = block_size(format).unwrap();
+    let (_, _, texel_bytes) = block_size(decompressed_format).unwrap();
+    let (block_width, block_height) = (block_width as usize, block_height as usize);
+    let blocks_x = (width as usize + block_width - 1) / block_width;
+    let blocks_y = (height as usize + block_height - 1) / block_height;
+    if level.len() < blocks_x * blocks_y * block_bytes {
+        return Err(malformed("compressed level"));
+    }
+
+    let mut texels = vec![0_u8; width as usize * height as usize * texel_bytes];
+    let mut block_texels = vec![0_u8; block_width * block_height * texel_bytes];
+    for block_y in 0..blocks_y {
+        for block_x in 0..blocks_x {
+            let offset = (block_y * blocks_x + block_x) * block_bytes;
+            decode_block(
+                format,
+                &level[offset..offset + block_bytes],
+                &mut block_texels,
+            );
+
+            // the blocks on the right and bottom edges may be partly outside of the level.
+            for (i, texel) in block_texels.chunks(texel_bytes).enumerate() {
+                let x = block_x * block_width + i % block_width;
+                let y = block_y * block_height + i / block_width;
+                if x < width as usize && y < height as usize {
+                    let texel_offset = (y * width as usize + x) * texel_bytes;
+                    texels[texel_offset..texel_offset + texel_bytes].copy_from_slice(texel);
+                }
+            }
+        }
+    }
+
+    Ok(texels)
+}
+
+/// Decode a block of `format` into the texels of its decompressed format, row by row.
+fn decode_block(format: vk::Format, block: &[u8], texels: &mut [u8]) {
+    let decoded = match format {
+        vk::Format::BC1_RGB_UNORM_BLOCK | vk::Format::BC1_RGB_SRGB_BLOCK => {
+            bcn::decode_bc1_block(block, false).to_vec()
+        }
+        vk::Format::BC1_RGBA_UNORM_BLOCK | vk::Format::BC1_RGBA_SRGB_BLOCK => {
+            bcn::decode_bc1_block(block, true).to_vec()
+        }
+        vk::Format::BC2_UNORM_BLOCK | vk::Format::BC2_SRGB_BLOCK => {
+            bcn::decode_bc2_block(block).to_vec()
+        }
+        vk::Format::BC3_UNORM_BLOCK | vk::Format::BC3_SRGB_BLOCK => {
+            bcn::decode_bc3_block(block).to_vec()
+        }
+        vk::Format::BC4_UNORM_BLOCK => bcn::decode_bc4_block(block).to_vec(),
+        vk::Format::BC5_UNORM_BLOCK => bcn::decode_bc5_block(block).to_vec(),
+        vk::Format::BC6H_UFLOAT_BLOCK | vk::Format::BC6H_SFLOAT_BLOCK => {
+            let is_signed = format == vk::Format::BC6H_SFLOAT_BLOCK;
+            let decoded = bcn::decode_bc6h_block(block, is_signed);
+            for (texel, channels) in texels.chunks_mut(8).zip(decoded.iter()) {
+                for (bytes, channel) in texel.chunks_mut(2).zip(channels.iter()) {
+                    bytes.copy_from_slice(&channel.to_le_bytes());
+                }
+            }
+            return;
+        }
+        vk::Format::BC7_UNORM_BLOCK | vk::Format::BC7_SRGB_BLOCK => {
+            bcn::decode_bc7_block(block).to_vec()
+        }
+        vk::Format::ETC2_R8G8B8_UNORM_BLOCK | vk::Format::ETC2_R8G8B8_SRGB_BLOCK => {
+            etc2::decode_etc2_block(block, false).to_vec()
+        }
+        vk::Format::ETC2_R8G8B8A1_UNORM_BLOCK | vk::Format::ETC2_R8G8B8A1_SRGB_BLOCK => {
+            etc2::decode_etc2_block(block, true).to_vec()
+        }
+        vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK | vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK => {
+            etc2::decode_etc2_rgba_block(block).to_vec()
+        }
+        // the ASTC formats.
+        _ => {
+            let (block_width, block_height, _) = block_size(format).unwrap();
+            let is_srgb = decompressed_format(format) == Some(vk::Format::R8G8B8A8_SRGB);
+            astc::decode_astc_block(block, block_width, block_height, is_srgb)
+        }
+    };
+
+    for (texel, channels) in texels.chunks_mut(4).zip(decoded.iter()) {
+        texel.copy_from_slice(channels);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A KTX2 file of one face and layer, with the levels stored after the level index.
+    fn ktx2_bytes(
+        format: vk::Format,
+        width: u32,
+        height: u32,
+        level_count: u32,
+        levels: &[&[u8]],
+    ) -> Vec<u8> {
+        let mut bytes = KTX2_IDENTIFIER.to_vec();
+        for value in &[
+            format.as_raw() as u32,
+            1,
+            width,
+            height,
+            0,
+            0,
+            1,
+            level_count,
+            0,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.resize(KTX2_HEADER_SIZE, 0);
+
+        let mut byte_offset = KTX2_HEADER_SIZE + levels.len() * 24;
+        for level in levels {
+            for value in &[byte_offset, level.len(), level.len()] {
+                bytes.extend_from_slice(&(*value as u64).to_le_bytes());
+            }
+            byte_offset += level.len();
+        }
+        for level in levels {
+            bytes.extend_from_slice(level);
+        }
+        bytes
+    }
+
+    #[test]
+    fn level_count_is_clamped_to_the_mip_chain() {
+        let (base_level, last_level) = ([1_u8; 8], [2_u8; 4]);
+        let bytes = ktx2_bytes(
+            vk::Format::R8G8B8A8_UNORM,
+            2,
+            1,
+            u32::max_value(),
+            &[&base_level, &last_level],
+        );
+
+        let texture_data = parse_ktx2(&bytes).unwrap();
+        assert_eq!(texture_data.mip_levels(), 2);
+        assert_eq!(texture_data.levels[1], last_level);
+    }
+
+    #[test]
+    fn malformed_files_are_rejected() {
+        let level = [0_u8; 4];
+        assert!(parse_ktx2(&ktx2_bytes(vk::Format::R8G8B8A8_UNORM, 0, 1, 1, &[&level])).is_err());
+        assert!(parse_ktx2(&ktx2_bytes(vk::Format::R8G8B8A8_UNORM, 2, 1, 1, &[&level])).is_err());
+        assert!(parse_ktx2(&KTX2_IDENTIFIER).is_err());
+        assert!(parse_dds(DDS_MAGIC).is_err());
+    }
+
+    #[test]
+    fn decompress_crops_the_edge_blocks() {
+        let mut level = [0_u8; 16];
+        level[..2].copy_from_slice(&[10, 10]);
+        level[8..10].copy_from_slice(&[200, 200]);
+        let texture_data = TextureData {
+            format: vk::Format::BC4_UNORM_BLOCK,
+            width: 5,
+            height: 1,
+            levels: vec![level.to_vec()],
+        };
+
+        let decompressed = texture_data.decompress().unwrap();
+        assert_eq!(decompressed.format, vk::Format::R8G8B8A8_UNORM);
+        let red: Vec<u8> = decompressed.levels[0]
+            .chunks(4)
+            .map(|texel| texel[0])
+            .collect();
+        assert_eq!(red, [10, 10, 10, 10, 200]);
+    }
+
+    #[test]
+    fn sample_texture_decodes_to_its_source_colors() {
+        let texture_data = load_texture(Path::new("assets/windows.ktx2")).unwrap();
+        assert_eq!(texture_data.format, vk::Format::BC3_SRGB_BLOCK);
+        assert_eq!((texture_data.width, texture_data.height), (128, 128));
+        assert_eq!(texture_data.mip_levels(), mip_level_count(128, 128));
+
+        let decompressed = texture_data.decompress().unwrap();
+        assert_eq!(decompressed.format, vk::Format::R8G8B8A8_SRGB);
+        assert_eq!(decompressed.levels[0].len(), 128 * 128 * 4);
+
+        // the base level was encoded from the png scaled down, so their average colors match.
+        let source = image::open("assets/windows.png").unwrap().to_rgba();
+        let average = |texels: &mut dyn Iterator<Item = &[u8]>| {
+            let mut sums = [0_f64; 4];
+            let mut count = 0_f64;
+            for texel in texels {
+                for (sum, &channel) in sums.iter_mut().zip(texel) {
+                    *sum += f64::from(channel);
+                }
+                count += 1.0;
+            }
+            sums.iter().map(|sum| sum / count).collect::<Vec<_>>()
+        };
+        let decoded_average = average(&mut decompressed.levels[0].chunks(4));
+        let source_average = average(&mut source.pixels().map(|pixel| &pixel.0[..]));
+        for (decoded, source) in decoded_average.iter().zip(&source_average) {
+            assert!(
+                (decoded - source).abs() < 4.0,
+                "{:?} {:?}",
+                decoded_average,
+                source_average
+            );
+        }
+    }
+}
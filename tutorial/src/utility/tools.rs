@@ -2,6 +2,11 @@ use std::ffi::CStr;
 use std::os::raw::c_char;
 use std::path::Path;
 
+use crate::utility::error::{
+    VkTutorialError,
+    VkTutorialResult,
+};
+
 /// Helper function to convert [c_char; SIZE] to string
 pub fn vk_to_string(raw_string_array: &[c_char]) -> String {
     // Implementation 1
@@ -34,13 +39,17 @@ pub fn vk_to_string(raw_string_array: &[c_char]) -> String {
         .to_owned()
 }
 
-pub fn read_shader_code(shader_path: &Path) -> Vec<u8> {
+pub fn read_shader_code(shader_path: &Path) -> VkTutorialResult<Vec<u8>> {
     use std::fs::File;
     use std::io::Read;
 
-    let spv_file =
-        File::open(shader_path).expect(&format!("Failed to find spv file at {:?}", shader_path));
+    let spv_file = File::open(shader_path).map_err(|error| {
+        VkTutorialError::ShaderLoad(format!(
+            "Failed to find spv file at {:?}: {}",
+            shader_path, error
+        ))
+    })?;
     let bytes_code: Vec<u8> = spv_file.bytes().filter_map(|byte| byte.ok()).collect();
 
-    bytes_code
+    Ok(bytes_code)
 }
@@ -10,7 +10,23 @@ use winit::event_loop::{
     EventLoop,
 };
 
+use std::path::PathBuf;
+use std::time::{
+    SystemTime,
+    UNIX_EPOCH,
+};
+
+use crate::utility::error::{
+    VkTutorialError,
+    VkTutorialResult,
+};
+use crate::utility::render_target::RenderTarget;
+
 const IS_PAINT_FPS_COUNTER: bool = true;
+/// Press it to save the next frame as `capture-<timestamp>.png` in the working directory.
+const CAPTURE_KEY: VirtualKeyCode = VirtualKeyCode::F12;
+/// Press it to switch to the next sample count in the apps rendering with multisampling.
+const MSAA_KEY: VirtualKeyCode = VirtualKeyCode::M;
 
 pub fn init_window(
     event_loop: &EventLoop<()>,
@@ -26,72 +42,230 @@ pub fn init_window(
 }
 
 pub trait VulkanApp {
-    fn draw_frame(&mut self, delta_time: f32);
-    fn recreate_swapchain(&mut self);
-    fn cleanup_swapchain(&self);
+    fn draw_frame(&mut self, delta_time: f32) -> VkTutorialResult<()>;
+    fn recreate_swapchain(&mut self) -> VkTutorialResult<()>;
+    fn cleanup_swapchain(&mut self);
     fn wait_device_idle(&self);
     fn resize_framebuffer(&mut self);
-    fn window_ref(&self) -> &winit::window::Window;
+    /// Return `None` when the app runs in headless mode.
+    fn window_ref(&self) -> Option<&winit::window::Window>;
+    /// The images the app renders into, which also copy the frames requested for capture.
+    fn render_target_mut(&mut self) -> &mut RenderTarget;
+    /// Switch to the next sample count and recreate the swapchain stuff,
+    /// only the apps rendering with multisampling do something.
+    fn cycle_msaa_samples(&mut self) -> VkTutorialResult<()> {
+        Ok(())
+    }
+}
+
+/// Settings of headless mode, which renders into offscreen images without any window.
+///
+/// Enable it by passing `--headless` to the program, e.g.
+/// `cargo run --bin 29 -- --headless --frames 10 --delta-time 0.016 --output frame.png`.
+#[derive(Debug, Clone)]
+pub struct HeadlessConfig {
+    pub frame_count: usize,
+    pub delta_time: f32,
+    /// Where to save the last frame.
+    pub output: Option<PathBuf>,
+}
+
+impl HeadlessConfig {
+    pub fn from_args() -> Option<HeadlessConfig> {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+
+        if !args.iter().any(|arg| arg == "--headless") {
+            return None;
+        }
+
+        let mut config = HeadlessConfig {
+            frame_count: 1,
+            delta_time: 1.0 / 60.0,
+            output: None,
+        };
+
+        for pair in args.windows(2) {
+            match pair[0].as_str() {
+                "--frames" => {
+                    config.frame_count = pair[1]
+                        .parse()
+                        .expect("Failed to parse the value of --frames.")
+                }
+                "--delta-time" => {
+                    config.delta_time = pair[1]
+                        .parse()
+                        .expect("Failed to parse the value of --delta-time.")
+                }
+                "--output" => config.output = Some(PathBuf::from(&pair[1])),
+                _ => {}
+            }
+        }
+
+        Some(config)
+    }
 }
 
 pub struct ProgramProc {
-    pub event_loop: EventLoop<()>,
+    /// `None` in headless mode, so that no display server is required.
+    pub event_loop: Option<EventLoop<()>>,
+    pub headless: Option<HeadlessConfig>,
 }
 
 impl ProgramProc {
     pub fn new() -> ProgramProc {
+        let headless = HeadlessConfig::from_args();
+
         // init window stuff
-        let event_loop = EventLoop::new();
+        let event_loop = if headless.is_some() {
+            None
+        } else {
+            Some(EventLoop::new())
+        };
 
-        ProgramProc { event_loop }
+        ProgramProc {
+            event_loop,
+            headless,
+        }
     }
 
-    pub fn main_loop<A: 'static + VulkanApp>(self, mut vulkan_app: A) {
-        let mut tick_counter = super::fps_limiter::FPSLimiter::new();
+    pub fn is_headless(&self) -> bool {
+        self.headless.is_some()
+    }
 
+    /// Create the window of the app, or return `None` in headless mode.
+    pub fn init_window(
+        &self,
+        title: &str,
+        width: u32,
+        height: u32,
+    ) -> Option<winit::window::Window> {
         self.event_loop
-            .run(move |event, _, control_flow| match event {
-                Event::WindowEvent { event, .. } => match event {
-                    WindowEvent::CloseRequested => {
-                        vulkan_app.wait_device_idle();
-                        *control_flow = ControlFlow::Exit
-                    }
-                    WindowEvent::KeyboardInput { input, .. } => match input {
-                        KeyboardInput {
-                            virtual_keycode,
-                            state,
-                            ..
-                        } => match (virtual_keycode, state) {
-                            (Some(VirtualKeyCode::Escape), ElementState::Pressed) => {
-                                vulkan_app.wait_device_idle();
-                                *control_flow = ControlFlow::Exit
+            .as_ref()
+            .map(|event_loop| init_window(event_loop, title, width, height))
+    }
+
+    pub fn main_loop<A: 'static + VulkanApp>(self, vulkan_app: A) {
+        match (self.event_loop, self.headless) {
+            (Some(event_loop), _) => ProgramProc::window_loop(event_loop, vulkan_app),
+            (None, Some(config)) => ProgramProc::headless_loop(config, vulkan_app),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    fn headless_loop<A: VulkanApp>(config: HeadlessConfig, mut vulkan_app: A) {
+        for frame_index in 0..config.frame_count {
+            if config.output.is_some() && frame_index + 1 == config.frame_count {
+                vulkan_app
+                    .render_target_mut()
+                    .request_capture()
+                    .expect("Failed to capture the last frame!");
+            }
+
+            vulkan_app
+                .draw_frame(config.delta_time)
+                .expect("Failed to draw frame!");
+        }
+
+        vulkan_app.wait_device_idle();
+
+        if let Some(output) = config.output {
+            let frame = vulkan_app
+                .render_target_mut()
+                .take_capture()
+                .and_then(|frame| frame.ok_or(VkTutorialError::CaptureUnsupported))
+                .expect("Failed to capture the last frame!");
+            frame.save(&output).expect("Failed to save the last frame!");
+        }
+    }
+
+    /// Save the frame captured by the latest `draw_frame`, if any.
+    fn save_capture<A: VulkanApp>(vulkan_app: &mut A) {
+        let frame = match vulkan_app.render_target_mut().take_capture() {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return,
+            Err(error) => {
+                eprintln!("Failed to capture frame: {}", error);
+                return;
+            }
+        };
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Failed to get system time!")
+            .as_millis();
+        let path = format!("capture-{}.png", timestamp);
+
+        match frame.save(&path) {
+            Ok(_) => println!("Captured frame to {}.", path),
+            Err(error) => eprintln!("Failed to save {}: {}", path, error),
+        }
+    }
+
+    fn window_loop<A: 'static + VulkanApp>(event_loop: EventLoop<()>, mut vulkan_app: A) {
+        let mut tick_counter = super::fps_limiter::FPSLimiter::new();
+
+        event_loop.run(move |event, _, control_flow| match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => {
+                    vulkan_app.wait_device_idle();
+                    *control_flow = ControlFlow::Exit
+                }
+                WindowEvent::KeyboardInput { input, .. } => match input {
+                    KeyboardInput {
+                        virtual_keycode,
+                        state,
+                        ..
+                    } => match (virtual_keycode, state) {
+                        (Some(VirtualKeyCode::Escape), ElementState::Pressed) => {
+                            vulkan_app.wait_device_idle();
+                            *control_flow = ControlFlow::Exit
+                        }
+                        (Some(CAPTURE_KEY), ElementState::Pressed) => {
+                            // the next frame is copied before it is presented.
+                            if let Err(error) = vulkan_app.render_target_mut().request_capture() {
+                                eprintln!("Failed to capture frame: {}", error);
                             }
-                            _ => {}
-                        },
+                        }
+                        (Some(MSAA_KEY), ElementState::Pressed) => {
+                            vulkan_app.wait_device_idle();
+                            if let Err(error) = vulkan_app.cycle_msaa_samples() {
+                                eprintln!("Failed to change the sample count: {}", error);
+                                *control_flow = ControlFlow::Exit;
+                            }
+                        }
+                        _ => {}
                     },
-                    WindowEvent::Resized(_new_size) => {
-                        vulkan_app.wait_device_idle();
-                        vulkan_app.resize_framebuffer();
-                    }
-                    _ => {}
                 },
-                Event::MainEventsCleared => {
-                    vulkan_app.window_ref().request_redraw();
+                WindowEvent::Resized(_new_size) => {
+                    vulkan_app.wait_device_idle();
+                    vulkan_app.resize_framebuffer();
                 }
-                Event::RedrawRequested(_window_id) => {
-                    let delta_time = tick_counter.delta_time();
-                    vulkan_app.draw_frame(delta_time);
-
-                    if IS_PAINT_FPS_COUNTER {
-                        print!("FPS: {}\r", tick_counter.fps());
-                    }
-
-                    tick_counter.tick_frame();
+                _ => {}
+            },
+            Event::MainEventsCleared => {
+                if let Some(window) = vulkan_app.window_ref() {
+                    window.request_redraw();
                 }
-                Event::LoopDestroyed => {
-                    vulkan_app.wait_device_idle();
+            }
+            Event::RedrawRequested(_window_id) => {
+                let delta_time = tick_counter.delta_time();
+                if let Err(error) = vulkan_app.draw_frame(delta_time) {
+                    eprintln!("Failed to draw frame: {}", error);
+                    *control_flow = ControlFlow::Exit;
+                    return;
                 }
-                _ => (),
-            })
+                ProgramProc::save_capture(&mut vulkan_app);
+
+                if IS_PAINT_FPS_COUNTER {
+                    print!("FPS: {}\r", tick_counter.fps());
+                }
+
+                tick_counter.tick_frame();
+            }
+            Event::LoopDestroyed => {
+                vulkan_app.wait_device_idle();
+            }
+            _ => (),
+        })
     }
 }
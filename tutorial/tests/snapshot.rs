@@ -0,0 +1,166 @@
+//! Golden-image tests, which render the tutorials in headless mode and compare their last frame
+//! with the images in `snapshot/golden`.
+//!
+//! A Vulkan driver is still required (a software one such as lavapipe is enough), so these tests
+//! are ignored by default:
+//!
+//! `cargo test --test snapshot -- --ignored`
+//!
+//! Set `UPDATE_SNAPSHOTS=1` to save the rendered frames as the new golden images.
+
+use image::{
+    Rgba,
+    RgbaImage,
+};
+
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::process::Command;
+
+const FRAME_COUNT: usize = 10;
+const DELTA_TIME: f32 = 0.016;
+/// The max difference allowed in each channel of a pixel.
+const PIXEL_TOLERANCE: u8 = 8;
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn output_dir() -> PathBuf {
+    let output_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("snapshot");
+    std::fs::create_dir_all(&output_dir).expect("Failed to create snapshot output directory!");
+    output_dir
+}
+
+fn render_frame(exe: &str, output: &Path) {
+    let status = Command::new(exe)
+        .current_dir(manifest_dir())
+        .arg("--headless")
+        .arg("--frames")
+        .arg(FRAME_COUNT.to_string())
+        .arg("--delta-time")
+        .arg(DELTA_TIME.to_string())
+        .arg("--output")
+        .arg(output)
+        .status()
+        .expect("Failed to launch tutorial!");
+
+    assert!(status.success(), "{} exited with {}", exe, status);
+}
+
+/// Return an image with the mismatched pixels painted red, and the number of them.
+fn diff_images(golden: &RgbaImage, actual: &RgbaImage) -> (RgbaImage, usize) {
+    let mut mismatched_count = 0;
+
+    let diff = RgbaImage::from_fn(golden.width(), golden.height(), |x, y| {
+        let expected = golden.get_pixel(x, y);
+        let found = actual.get_pixel(x, y);
+
+        let is_mismatched = expected
+            .0
+            .iter()
+            .zip(found.0.iter())
+            .any(|(&a, &b)| (a as i16 - b as i16).abs() > PIXEL_TOLERANCE as i16);
+
+        if is_mismatched {
+            mismatched_count += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            // dim the matched pixels, so the mismatched ones stand out.
+            let [r, g, b, _] = found.0;
+            Rgba([r / 4, g / 4, b / 4, 255])
+        }
+    });
+
+    (diff, mismatched_count)
+}
+
+fn check_snapshot(name: &str, exe: &str) {
+    let output_dir = output_dir();
+    let actual_path = output_dir.join(format!("{}.png", name));
+    let golden_path = manifest_dir()
+        .join("snapshot")
+        .join("golden")
+        .join(format!("{}.png", name));
+
+    render_frame(exe, &actual_path);
+
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::create_dir_all(golden_path.parent().unwrap())
+            .expect("Failed to create golden directory!");
+        std::fs::copy(&actual_path, &golden_path).expect("Failed to update golden image!");
+        return;
+    }
+
+    let golden = image::open(&golden_path)
+        .unwrap_or_else(|_| {
+            panic!(
+                "Failed to open {}, run with UPDATE_SNAPSHOTS=1 to create it.",
+                golden_path.display()
+            )
+        })
+        .to_rgba();
+    let actual = image::open(&actual_path)
+        .expect("Failed to open rendered frame!")
+        .to_rgba();
+
+    assert_eq!(
+        golden.dimensions(),
+        actual.dimensions(),
+        "The size of {} does not match its golden image.",
+        name
+    );
+
+    let (diff, mismatched_count) = diff_images(&golden, &actual);
+    if mismatched_count > 0 {
+        let diff_path = output_dir.join(format!("{}-diff.png", name));
+        diff.save(&diff_path).expect("Failed to save diff image!");
+
+        panic!(
+            "{} pixels of {} differ from {}, see {} and {}.",
+            mismatched_count,
+            name,
+            golden_path.display(),
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+#[ignore]
+fn snapshot_16_swap_chain_recreation() {
+    check_snapshot("16", env!("CARGO_BIN_EXE_16"));
+}
+
+#[test]
+#[ignore]
+fn snapshot_18_vertex_buffer() {
+    check_snapshot("18", env!("CARGO_BIN_EXE_18"));
+}
+
+#[test]
+#[ignore]
+fn snapshot_20_index_buffer() {
+    check_snapshot("20", env!("CARGO_BIN_EXE_20"));
+}
+
+#[test]
+#[ignore]
+fn snapshot_22_descriptor_sets() {
+    check_snapshot("22", env!("CARGO_BIN_EXE_22"));
+}
+
+#[test]
+#[ignore]
+fn snapshot_25_texture_mapping() {
+    check_snapshot("25", env!("CARGO_BIN_EXE_25"));
+}
+
+#[test]
+#[ignore]
+fn snapshot_29_multisampling() {
+    check_snapshot("29", env!("CARGO_BIN_EXE_29"));
+}